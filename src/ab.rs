@@ -0,0 +1,303 @@
+//! Running one workload against two targets to compare them
+//!
+//! For canary-vs-stable comparisons the same workload runs against target A
+//! (the one configured) and target B. Interleaved, the requests are split
+//! into rounds that alternate between the two, so drift over time (a noisy
+//! neighbour, a cache warming up) affects both alike; back to back, A runs in
+//! full and then B. Whether a difference is more than noise is judged with a
+//! Mann-Whitney U test on the latencies and a two-proportion z-test on the
+//! error rates.
+
+use crate::baseline::{self, MetricDelta};
+use crate::protocols::{calculate_stats, LiveResults, LoadTestConfig, LoadTestStats};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Rounds per target of an interleaved comparison
+pub const ROUNDS: u64 = 5;
+
+/// Differences with a lower p-value are reported as significant
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// How the runs against the two targets are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Order {
+    /// Rounds against A and B in turn
+    #[default]
+    Interleaved,
+    /// All of A, then all of B
+    BackToBack,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "interleaved" => Ok(Order::Interleaved),
+            "back-to-back" | "sequential" => Ok(Order::BackToBack),
+            _ => Err(format!(
+                "Invalid order '{}': expected interleaved or back-to-back",
+                s
+            )),
+        }
+    }
+}
+
+impl Order {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Order::Interleaved => "interleaved",
+            Order::BackToBack => "back-to-back",
+        }
+    }
+}
+
+/// The results against one of the targets
+#[derive(Debug, Serialize)]
+pub struct Side {
+    pub target: String,
+    pub stats: LoadTestStats,
+}
+
+/// Whether a difference between A and B is more than noise
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Significance {
+    /// Probability of a difference at least this large if A and B were the same
+    pub p_value: f64,
+    pub significant: bool,
+    /// Whether B has the higher values, i.e. is slower or fails more
+    pub b_higher: bool,
+}
+
+impl Significance {
+    fn new(p_value: f64, b_higher: bool) -> Self {
+        Self {
+            p_value,
+            significant: p_value < SIGNIFICANCE_LEVEL,
+            b_higher,
+        }
+    }
+}
+
+/// The outcome of an A/B comparison
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub order: &'static str,
+    /// Rounds per target
+    pub rounds: u64,
+    pub a: Side,
+    pub b: Side,
+    /// B's metrics relative to A's
+    pub deltas: Vec<MetricDelta>,
+    /// Mann-Whitney U test of the latencies, when both had successful requests
+    pub latency: Option<Significance>,
+    /// Two-proportion z-test of the error rates
+    pub error_rate: Option<Significance>,
+}
+
+/// Run the workload of `a` against both targets, calling `on_round` with
+/// "A" or "B", the round and the number of rounds before each run
+pub async fn run(
+    a: &LoadTestConfig,
+    b: &LoadTestConfig,
+    order: Order,
+    quiet: bool,
+    mut on_round: impl FnMut(&str, u64, u64),
+) -> Result<Comparison> {
+    let rounds = match order {
+        Order::BackToBack => 1,
+        Order::Interleaved if !a.stages.is_empty() => {
+            bail!("Stages can't be split into interleaved rounds; use --ab-order back-to-back")
+        }
+        Order::Interleaved => ROUNDS.min(a.num_requests).max(1),
+    };
+
+    let mut sides = [(a, Vec::new(), 0), (b, Vec::new(), 0)];
+    for round in 0..rounds {
+        for (index, (config, results, duration)) in sides.iter_mut().enumerate() {
+            on_round(["A", "B"][index], round + 1, rounds);
+            let mut config = (*config).clone();
+            if order == Order::Interleaved {
+                // Spread the remainder over the first rounds
+                config.num_requests =
+                    a.num_requests / rounds + u64::from(round < a.num_requests % rounds);
+            }
+
+            let live = LiveResults::default();
+            let mut recorded = live.subscribe();
+            let stats = config.run(quiet, &live).await?;
+            while let Ok(result) = recorded.try_recv() {
+                results.push(result);
+            }
+            *duration += stats.total_duration;
+        }
+    }
+
+    let [(a, a_results, a_duration), (b, b_results, b_duration)] = sides;
+    let side = |config: &LoadTestConfig, results: &[_], duration| {
+        let mut stats = calculate_stats(results, duration);
+        stats.sla = Some(config.sla(&stats));
+        Side {
+            target: config.display_target(),
+            stats,
+        }
+    };
+    let a = side(a, &a_results, a_duration);
+    let b = side(b, &b_results, b_duration);
+    Ok(Comparison {
+        order: order.as_str(),
+        rounds,
+        deltas: baseline::compare(&a.stats, &b.stats, &[]),
+        latency: mann_whitney(&a.stats.latency_histogram, &b.stats.latency_histogram),
+        error_rate: two_proportions(
+            a.stats.failed_requests,
+            a.stats.total_requests,
+            b.stats.failed_requests,
+            b.stats.total_requests,
+        ),
+        a,
+        b,
+    })
+}
+
+/// Mann-Whitney U test of two latency histograms, with the normal
+/// approximation and the correction for ties (latencies are whole ms)
+pub fn mann_whitney(a: &BTreeMap<u128, u64>, b: &BTreeMap<u128, u64>) -> Option<Significance> {
+    let n_a = a.values().sum::<u64>() as f64;
+    let n_b = b.values().sum::<u64>() as f64;
+    if n_a == 0.0 || n_b == 0.0 {
+        return None;
+    }
+
+    let mut latencies: Vec<&u128> = a.keys().chain(b.keys()).collect();
+    latencies.sort_unstable();
+    latencies.dedup();
+
+    // Equal latencies share the average of the ranks they span
+    let mut ranked = 0.0;
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    for latency in latencies {
+        let count_a = a.get(latency).copied().unwrap_or_default() as f64;
+        let count = count_a + b.get(latency).copied().unwrap_or_default() as f64;
+        rank_sum_a += count_a * (ranked + (count + 1.0) / 2.0);
+        ranked += count;
+        ties += count.powi(3) - count;
+    }
+
+    let n = n_a + n_b;
+    let u_a = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+    let mean = n_a * n_b / 2.0;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        // Every latency was the same
+        return Some(Significance::new(1.0, false));
+    }
+    let z = (u_a - mean) / variance.sqrt();
+    // A low U for A means A's latencies tend to be the lower ones
+    Some(Significance::new(two_sided(z), u_a < mean))
+}
+
+/// Two-proportion z-test of `failed_a` of `total_a` against `failed_b` of `total_b`
+pub fn two_proportions(
+    failed_a: u64,
+    total_a: u64,
+    failed_b: u64,
+    total_b: u64,
+) -> Option<Significance> {
+    if total_a == 0 || total_b == 0 {
+        return None;
+    }
+    let (n_a, n_b) = (total_a as f64, total_b as f64);
+    let (rate_a, rate_b) = (failed_a as f64 / n_a, failed_b as f64 / n_b);
+    let pooled = (failed_a + failed_b) as f64 / (n_a + n_b);
+    let error = (pooled * (1.0 - pooled) * (1.0 / n_a + 1.0 / n_b)).sqrt();
+    if error == 0.0 {
+        return Some(Significance::new(1.0, false));
+    }
+    Some(Significance::new(
+        two_sided((rate_b - rate_a) / error),
+        rate_b > rate_a,
+    ))
+}
+
+/// Two-sided p-value of a standard normal z score
+fn two_sided(z: f64) -> f64 {
+    erfc(z.abs() / std::f64::consts::SQRT_2).min(1.0)
+}
+
+/// Complementary error function, with a fractional error below 1.2e-7
+/// (Numerical Recipes' Chebyshev fit)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(latencies: &[(u128, u64)]) -> BTreeMap<u128, u64> {
+        latencies.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_two_sided() {
+        assert!((two_sided(0.0) - 1.0).abs() < 1e-6);
+        assert!((two_sided(1.96) - 0.05).abs() < 1e-3);
+        assert!((two_sided(-2.576) - 0.01).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_mann_whitney() {
+        let a = histogram(&[(10, 40), (11, 40), (12, 20)]);
+        let same = mann_whitney(&a, &a).unwrap();
+        assert!(!same.significant);
+        assert!((same.p_value - 1.0).abs() < 1e-6);
+
+        let slower = histogram(&[(11, 20), (12, 40), (13, 40)]);
+        let result = mann_whitney(&a, &slower).unwrap();
+        assert!(result.significant);
+        assert!(result.b_higher);
+        assert!(!mann_whitney(&slower, &a).unwrap().b_higher);
+
+        let constant = histogram(&[(5, 10)]);
+        assert_eq!(mann_whitney(&constant, &constant).unwrap().p_value, 1.0);
+        assert!(mann_whitney(&a, &BTreeMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_two_proportions() {
+        let result = two_proportions(10, 1000, 40, 1000).unwrap();
+        assert!(result.significant);
+        assert!(result.b_higher);
+        assert!(!two_proportions(10, 1000, 12, 1000).unwrap().significant);
+        assert_eq!(two_proportions(0, 100, 0, 100).unwrap().p_value, 1.0);
+        assert!(two_proportions(0, 0, 1, 10).is_none());
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(Order::from_str("Back-To-Back").unwrap(), Order::BackToBack);
+        assert_eq!(Order::from_str("interleaved").unwrap(), Order::Interleaved);
+        assert!(Order::from_str("random").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Baseline comparison and regression detection
+//!
+//! A baseline is the JSON written by a previous run in quiet mode. The current
+//! run is compared metric by metric and anything that got worse by more than
+//! the allowed tolerance is reported as a regression.
+
+use crate::output;
+use crate::protocols::LoadTestStats;
+use crate::thresholds::Metric;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Tolerance applied when none is configured (percent)
+pub const DEFAULT_TOLERANCE: f64 = 10.0;
+
+/// Metrics compared against the baseline
+const COMPARED_METRICS: [Metric; 6] = [
+    Metric::AvgLatency,
+    Metric::P50,
+    Metric::P95,
+    Metric::P99,
+    Metric::RequestsPerSecond,
+    Metric::ErrorRate,
+];
+
+/// Allowed regression, either for all metrics or for a single one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tolerance {
+    /// The metric this tolerance applies to (`None` for all metrics)
+    pub metric: Option<Metric>,
+    /// Allowed change in percent (percentage points for rate metrics)
+    pub percent: f64,
+}
+
+impl Tolerance {
+    /// Parse a tolerance such as `10`, `10%` or `p99=25%`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (metric, value) = match s.split_once('=') {
+            Some((metric, value)) => (Some(metric.parse::<Metric>()?), value),
+            None => (None, s),
+        };
+
+        let percent = value
+            .trim()
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid tolerance '{}': expected e.g. 10% or p99=25%", s))?;
+
+        Ok(Self { metric, percent })
+    }
+}
+
+/// Change of one metric relative to the baseline
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDelta {
+    pub metric: String,
+    pub baseline: f64,
+    pub current: f64,
+    /// Relative change in percent (percentage points for rate metrics);
+    /// `None` when the baseline value is zero
+    pub change: Option<f64>,
+    pub tolerance: f64,
+    pub regression: bool,
+}
+
+/// Load the stats of a previous run
+pub fn load(path: &Path) -> Result<LoadTestStats> {
+    let content = output::read_to_string(path)
+        .with_context(|| format!("Failed to read baseline {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse baseline {}", path.display()))
+}
+
+/// Compare the current run against the baseline
+pub fn compare(
+    baseline: &LoadTestStats,
+    current: &LoadTestStats,
+    tolerances: &[Tolerance],
+) -> Vec<MetricDelta> {
+    let default_tolerance = tolerances
+        .iter()
+        .find(|t| t.metric.is_none())
+        .map(|t| t.percent)
+        .unwrap_or(DEFAULT_TOLERANCE);
+
+    COMPARED_METRICS
+        .iter()
+        .map(|metric| {
+            let tolerance = tolerances
+                .iter()
+                .find(|t| t.metric == Some(*metric))
+                .map(|t| t.percent)
+                .unwrap_or(default_tolerance);
+
+            let baseline_value = metric.value(baseline);
+            let current_value = metric.value(current);
+
+            let change = if metric.is_rate() {
+                Some(current_value - baseline_value)
+            } else if baseline_value == 0.0 {
+                None
+            } else {
+                Some((current_value - baseline_value) / baseline_value * 100.0)
+            };
+
+            // Throughput regresses when it drops, everything else when it grows
+            let regression = match (metric, change) {
+                (Metric::RequestsPerSecond, Some(change)) => change < -tolerance,
+                (_, Some(change)) => change > tolerance,
+                (_, None) => false,
+            };
+
+            MetricDelta {
+                metric: metric.name().to_string(),
+                baseline: baseline_value,
+                current: current_value,
+                change,
+                tolerance,
+                regression,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(p99: u128, rps: f64, failed: u64) -> LoadTestStats {
+        LoadTestStats {
+            total_requests: 100,
+            successful_requests: 100 - failed,
+            failed_requests: failed,
+            avg_latency: 10.0,
+            p50: 10,
+            p95: 20,
+            p99,
+            requests_per_second: rps,
+            ..Default::default()
+        }
+    }
+
+    fn delta<'a>(deltas: &'a [MetricDelta], metric: &str) -> &'a MetricDelta {
+        deltas.iter().find(|d| d.metric == metric).unwrap()
+    }
+
+    #[test]
+    fn test_parse_tolerance() {
+        assert_eq!(
+            Tolerance::parse("15%").unwrap(),
+            Tolerance {
+                metric: None,
+                percent: 15.0
+            }
+        );
+        assert_eq!(
+            Tolerance::parse("p99=25").unwrap(),
+            Tolerance {
+                metric: Some(Metric::P99),
+                percent: 25.0
+            }
+        );
+        assert!(Tolerance::parse("p99=lots").is_err());
+        assert!(Tolerance::parse("p42=5").is_err());
+    }
+
+    #[test]
+    fn test_compare_detects_regressions() {
+        let baseline = stats(100, 1000.0, 0);
+        let current = stats(134, 880.0, 2);
+        let deltas = compare(&baseline, &current, &[]);
+
+        let p99 = delta(&deltas, "p99");
+        assert_eq!(p99.change, Some(34.0));
+        assert!(p99.regression);
+
+        let rps = delta(&deltas, "rps");
+        assert!((rps.change.unwrap() + 12.0).abs() < 1e-9);
+        assert!(rps.regression);
+
+        // Error rate is compared in percentage points
+        let errors = delta(&deltas, "error_rate");
+        assert_eq!(errors.change, Some(2.0));
+        assert!(!errors.regression);
+
+        assert!(!delta(&deltas, "p50").regression);
+    }
+
+    #[test]
+    fn test_compare_with_tolerances() {
+        let baseline = stats(100, 1000.0, 0);
+        let current = stats(134, 880.0, 0);
+        let tolerances = vec![
+            Tolerance::parse("50%").unwrap(),
+            Tolerance::parse("rps=5").unwrap(),
+        ];
+        let deltas = compare(&baseline, &current, &tolerances);
+
+        assert!(!delta(&deltas, "p99").regression);
+        assert!(delta(&deltas, "rps").regression);
+    }
+
+    #[test]
+    fn test_compare_zero_baseline() {
+        let mut baseline = stats(0, 1000.0, 0);
+        baseline.p99 = 0;
+        let current = stats(5, 1000.0, 0);
+        let deltas = compare(&baseline, &current, &[]);
+
+        let p99 = delta(&deltas, "p99");
+        assert_eq!(p99.change, None);
+        assert!(!p99.regression);
+    }
+}
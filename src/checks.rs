@@ -0,0 +1,352 @@
+//! Response checks
+//!
+//! A check is a condition every HTTP response must meet, such as
+//! `status == 200`, `body contains "ok"`, `body matches "^\{.*\}$"` or
+//! `json $.status == 'healthy'`. A response that fails a check counts as a
+//! failed request with the `assertion_failed` error kind, so a 200 with the
+//! wrong body isn't reported as a success. Body checks see the body as
+//! received (still compressed if the server compressed it).
+
+use crate::jsonpath::JsonPath;
+use regex::Regex;
+use serde_json::Value;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Comparison operators supported in status and JSON checks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    // Two-character operators must be tried before their one-character prefixes
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ];
+
+    fn symbol(&self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| comparison == self)
+            .map(|(symbol, _)| *symbol)
+            .unwrap_or_default()
+    }
+
+    /// Split `<op> <rest>` into the operator and what follows it
+    fn split(s: &str) -> Option<(Self, &str)> {
+        Self::OPERATORS.iter().find_map(|(symbol, comparison)| {
+            s.strip_prefix(symbol)
+                .map(|rest| (*comparison, rest.trim()))
+        })
+    }
+
+    /// Whether the operator holds for the ordering of actual vs expected
+    /// (`None` when the values can't be ordered, e.g. a string and a number)
+    fn holds(&self, ordering: Option<Ordering>) -> bool {
+        match (self, ordering) {
+            (Comparison::Equal, ordering) => ordering == Some(Ordering::Equal),
+            (Comparison::NotEqual, ordering) => ordering != Some(Ordering::Equal),
+            (_, None) => false,
+            (Comparison::Greater, Some(ordering)) => ordering.is_gt(),
+            (Comparison::GreaterOrEqual, Some(ordering)) => ordering.is_ge(),
+            (Comparison::Less, Some(ordering)) => ordering.is_lt(),
+            (Comparison::LessOrEqual, Some(ordering)) => ordering.is_le(),
+        }
+    }
+}
+
+/// Order two JSON values when they are both numbers or both strings, and
+/// otherwise only tell whether they are equal
+fn compare_json(actual: &Value, expected: &Value) -> Option<Ordering> {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if actual == expected => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// What a JSON check requires of the value at its path
+#[derive(Debug, Clone)]
+pub enum JsonCondition {
+    /// `json $.id exists`
+    Exists,
+    /// `json $.status == 'healthy'`, `json $.count > 0`, ...
+    Compare(Comparison, Value),
+}
+
+/// A condition every response must meet
+#[derive(Debug, Clone)]
+pub enum Check {
+    /// `status == 200`, `status < 500`, ...
+    Status { comparison: Comparison, code: u16 },
+    /// `body contains "text"`
+    BodyContains(String),
+    /// `body matches "regex"`
+    BodyMatches(Regex),
+    /// `json <path> exists` or `json <path> <op> <value>`
+    Json {
+        path: JsonPath,
+        condition: JsonCondition,
+    },
+}
+
+impl Check {
+    /// Parse a check such as `status == 200`, `body contains "ok"`,
+    /// `body matches "id\":\s*\d+"` or `json $.status == 'healthy'`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid check '{}': expected e.g. status == 200, body contains \"ok\", body matches \"^ok\" or json $.status == 'ok'",
+                s
+            )
+        };
+        let s = s.trim();
+
+        if let Some(rest) = s.strip_prefix("status") {
+            let (comparison, code) = Comparison::split(rest.trim_start()).ok_or_else(invalid)?;
+            return Ok(Check::Status {
+                comparison,
+                code: code.parse().map_err(|_| invalid())?,
+            });
+        }
+
+        if let Some(rest) = s.strip_prefix("json") {
+            let rest = rest.trim_start();
+            // The path ends at the operator, but quoted keys in [...] may hold spaces
+            let mut depth = 0;
+            let end = rest
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0 && (c.is_whitespace() || "=!<>".contains(c))
+                })
+                .map_or(rest.len(), |(i, _)| i);
+            let path = JsonPath::parse(&rest[..end])?;
+            let condition = rest[end..].trim();
+            let condition = if condition == "exists" {
+                JsonCondition::Exists
+            } else {
+                let (comparison, value) = Comparison::split(condition).ok_or_else(invalid)?;
+                JsonCondition::Compare(comparison, parse_json_literal(value).ok_or_else(invalid)?)
+            };
+            return Ok(Check::Json { path, condition });
+        }
+
+        let rest = s.strip_prefix("body").ok_or_else(invalid)?.trim_start();
+        if let Some(text) = rest.strip_prefix("contains") {
+            Ok(Check::BodyContains(unquote(text.trim())))
+        } else if let Some(pattern) = rest.strip_prefix("matches") {
+            let pattern = unquote(pattern.trim());
+            Regex::new(&pattern)
+                .map(Check::BodyMatches)
+                .map_err(|e| format!("Invalid regex in check '{}': {}", s, e))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    pub fn needs_body(&self) -> bool {
+        !matches!(self, Check::Status { .. })
+    }
+
+    /// Whether a response passes, with its body parsed as JSON on first use
+    fn passes(&self, status: u16, body: &str, json: &OnceCell<Option<Value>>) -> bool {
+        match self {
+            Check::Status { comparison, code } => comparison.holds(Some(status.cmp(code))),
+            Check::BodyContains(text) => body.contains(text.as_str()),
+            Check::BodyMatches(regex) => regex.is_match(body),
+            Check::Json { path, condition } => {
+                let json = json.get_or_init(|| serde_json::from_str(body).ok());
+                let Some(value) = json.as_ref().and_then(|json| path.find(json)) else {
+                    return false;
+                };
+                match condition {
+                    JsonCondition::Exists => true,
+                    JsonCondition::Compare(comparison, expected) => {
+                        comparison.holds(compare_json(value, expected))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Status { comparison, code } => {
+                write!(f, "status {} {}", comparison.symbol(), code)
+            }
+            Check::BodyContains(text) => write!(f, "body contains {}", quote(text)),
+            Check::BodyMatches(regex) => write!(f, "body matches {}", quote(regex.as_str())),
+            Check::Json {
+                path,
+                condition: JsonCondition::Exists,
+            } => write!(f, "json {} exists", path),
+            Check::Json {
+                path,
+                condition: JsonCondition::Compare(comparison, value),
+            } => match value {
+                Value::String(text) => write!(
+                    f,
+                    "json {} {} '{}'",
+                    path,
+                    comparison.symbol(),
+                    text.replace('\'', "\\'")
+                ),
+                value => write!(f, "json {} {} {}", path, comparison.symbol(), value),
+            },
+        }
+    }
+}
+
+/// Whether any check looks at the status; if so the checks decide which
+/// statuses are acceptable instead of the default 2xx rule
+pub fn covers_status(checks: &[Check]) -> bool {
+    checks.iter().any(|c| matches!(c, Check::Status { .. }))
+}
+
+/// The first check a response fails, if any
+pub fn first_failure<'a>(checks: &'a [Check], status: u16, body: &str) -> Option<&'a Check> {
+    let json = OnceCell::new();
+    checks
+        .iter()
+        .find(|check| !check.passes(status, body, &json))
+}
+
+/// Parse the expected value of a JSON check: a quoted string (`'healthy'` or
+/// `"healthy"`), or a JSON number, boolean or null
+fn parse_json_literal(s: &str) -> Option<Value> {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+            let escaped = format!("\\{}", quote);
+            return Some(Value::String(inner.replace(&escaped, &quote.to_string())));
+        }
+    }
+    serde_json::from_str(s)
+        .ok()
+        .filter(|value: &Value| !value.is_array() && !value.is_object())
+}
+
+/// Strip surrounding double quotes, undoing `\"` and `\\` escapes inside them
+/// (other backslashes are kept, so regex escapes can be written either way)
+fn unquote(s: &str) -> String {
+    let Some(inner) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
+        return s.to_string();
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == '"' || next == '\\' => {
+                unquoted.push(next);
+                chars.next();
+            }
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for check in [
+            "status == 200",
+            "status != 500",
+            "status < 400",
+            r#"body contains "ok""#,
+            r#"body contains "say \"hi\"""#,
+            r#"body matches "^\\{.*\\}$""#,
+            "json $.status == 'healthy'",
+            "json $.items[0].id >= 1",
+            "json $['odd key'] != null",
+            "json $.id exists",
+        ] {
+            assert_eq!(Check::parse(check).unwrap().to_string(), check);
+        }
+
+        assert!(matches!(
+            Check::parse("status>=200").unwrap(),
+            Check::Status {
+                comparison: Comparison::GreaterOrEqual,
+                code: 200
+            }
+        ));
+        assert!(Check::parse("status = 200").is_err());
+        assert!(Check::parse("status == ok").is_err());
+        assert!(Check::parse("header contains x").is_err());
+        assert!(Check::parse(r#"body matches "(""#).is_err());
+        assert!(Check::parse("json status == 'ok'").is_err());
+        assert!(Check::parse("json $.items == [1]").is_err());
+        assert!(Check::parse("json $.status").is_err());
+    }
+
+    #[test]
+    fn test_first_failure() {
+        let checks = vec![
+            Check::parse("status < 500").unwrap(),
+            Check::parse(r#"body contains "ok""#).unwrap(),
+            Check::parse(r#"body matches "id\":\s*\d+""#).unwrap(),
+        ];
+        assert!(covers_status(&checks));
+        assert!(first_failure(&checks, 200, r#"{"status": "ok", "id": 7}"#).is_none());
+        assert_eq!(
+            first_failure(&checks, 503, "ok").unwrap().to_string(),
+            "status < 500"
+        );
+        assert_eq!(
+            first_failure(&checks, 200, r#"{"status": "ok"}"#)
+                .unwrap()
+                .to_string(),
+            r#"body matches "id\":\\s*\\d+""#
+        );
+        assert!(!covers_status(&checks[1..]));
+    }
+
+    #[test]
+    fn test_json_checks() {
+        let checks = vec![
+            Check::parse("json $.status == \"healthy\"").unwrap(),
+            Check::parse("json $.items[-1].id > 1").unwrap(),
+            Check::parse("json $.ready == true").unwrap(),
+        ];
+        let body = r#"{"status": "healthy", "ready": true, "items": [{"id": 1}, {"id": 2.5}]}"#;
+        assert!(!covers_status(&checks));
+        assert!(first_failure(&checks, 200, body).is_none());
+        assert_eq!(
+            first_failure(&checks, 200, r#"{"status": "down"}"#)
+                .unwrap()
+                .to_string(),
+            "json $.status == 'healthy'"
+        );
+        // A missing value, a body that isn't JSON and a type mismatch all fail
+        assert!(first_failure(&checks[1..2], 200, r#"{"items": []}"#).is_some());
+        assert!(first_failure(&checks[..1], 200, "healthy").is_some());
+        assert!(first_failure(&checks[1..2], 200, r#"{"items": [{"id": "2"}]}"#).is_some());
+    }
+}
@@ -0,0 +1,371 @@
+//! Test definitions from a pasted curl command line
+//!
+//! `--from-curl` takes the request as copied from API docs or a browser's
+//! "Copy as cURL" and turns the options that describe the request (method,
+//! headers, body, credentials, compression) into test settings. Options that
+//! only affect curl's own output are ignored, and anything else is rejected
+//! rather than silently changing what is sent.
+
+use crate::config::TestFile;
+use crate::env;
+
+/// What `--compressed` asks for, in the order curl lists them
+const COMPRESSED: &str = "gzip,deflate,br,zstd";
+
+/// curl options that don't change the request and take no value
+const IGNORED_FLAGS: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "-i",
+    "--include",
+    "-v",
+    "--verbose",
+    "-f",
+    "--fail",
+    "-N",
+    "--no-buffer",
+    "-g",
+    "--globoff",
+    "--http1.1",
+    "--http2",
+];
+
+/// curl options that don't change the request but take a value
+const IGNORED_OPTIONS: &[&str] = &[
+    "-o",
+    "--output",
+    "-w",
+    "--write-out",
+    "-m",
+    "--max-time",
+    "--connect-timeout",
+];
+
+/// A request parsed from a curl command line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CurlCommand {
+    pub url: String,
+    pub method: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub basic_auth: Option<String>,
+    pub compressed: bool,
+}
+
+impl CurlCommand {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let words = split_words(s)?;
+        let mut words = words.iter().map(String::as_str).peekable();
+        if words.peek() == Some(&"curl") {
+            words.next();
+        }
+
+        let mut command = CurlCommand::default();
+        let mut url = None;
+        let mut data: Vec<String> = Vec::new();
+        let mut head = false;
+        let mut get = false;
+
+        while let Some(word) = words.next() {
+            if !word.starts_with('-') || word == "-" {
+                if url.replace(word.to_string()).is_some() {
+                    return Err("Only one URL is supported in a curl command".to_string());
+                }
+                continue;
+            }
+            if IGNORED_FLAGS.contains(&word) || is_ignored_flag_group(word) {
+                continue;
+            }
+
+            // Long options may be written --name=value, and short ones -Xvalue
+            let (option, attached) = match word.split_once('=') {
+                Some((option, value)) if word.starts_with("--") => (option, Some(value)),
+                _ if !word.starts_with("--") && word.len() > 2 && word.is_char_boundary(2) => {
+                    (&word[..2], Some(&word[2..]))
+                }
+                _ => (word, None),
+            };
+            let mut value = || {
+                attached
+                    .or_else(|| words.next())
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("Missing value for curl option {}", option))
+            };
+
+            match option {
+                "--url" => url = Some(value()?),
+                "-X" | "--request" => command.method = Some(value()?.to_uppercase()),
+                "-H" | "--header" => {
+                    let header = value()?;
+                    let (key, value) = header
+                        .split_once(':')
+                        .ok_or_else(|| format!("Invalid curl header '{}'", header))?;
+                    command
+                        .headers
+                        .push((key.trim().to_string(), value.trim().to_string()));
+                }
+                "-A" | "--user-agent" => {
+                    command.headers.push(("User-Agent".to_string(), value()?));
+                }
+                "-e" | "--referer" => command.headers.push(("Referer".to_string(), value()?)),
+                "-b" | "--cookie" => {
+                    let cookie = value()?;
+                    if !cookie.contains('=') {
+                        return Err(format!("Cookie files are not supported: {}", cookie));
+                    }
+                    command.headers.push(("Cookie".to_string(), cookie));
+                }
+                "-u" | "--user" => command.basic_auth = Some(value()?),
+                "-d" | "--data" | "--data-ascii" | "--data-binary" => {
+                    data.push(read_data(&value()?)?);
+                }
+                "--data-raw" | "--data-urlencode" => data.push(value()?),
+                "--json" => {
+                    data.push(read_data(&value()?)?);
+                    command.set_default_header("Content-Type", "application/json");
+                    command.set_default_header("Accept", "application/json");
+                }
+                "-I" | "--head" => head = true,
+                "-G" | "--get" => get = true,
+                "--compressed" => command.compressed = true,
+                option if IGNORED_OPTIONS.contains(&option) => {
+                    value()?;
+                }
+                _ => return Err(format!("Unsupported curl option {}", word)),
+            }
+        }
+
+        let mut url = url.ok_or_else(|| "No URL in curl command".to_string())?;
+        if !data.is_empty() {
+            let data = data.join("&");
+            if get {
+                // -G sends the data as the query string
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str(&data);
+            } else {
+                command.set_default_header("Content-Type", "application/x-www-form-urlencoded");
+                command.body = Some(data);
+            }
+        }
+        if command.method.is_none() {
+            command.method = match (head, &command.body) {
+                (true, _) => Some("HEAD".to_string()),
+                (false, Some(_)) => Some("POST".to_string()),
+                (false, None) => None,
+            };
+        }
+        command.url = url;
+        Ok(command)
+    }
+
+    /// Add a header unless the command already sets it
+    fn set_default_header(&mut self, key: &str, value: &str) {
+        if !self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(key))
+        {
+            self.headers.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    /// Use the command's request in a test definition, replacing its target
+    pub fn apply(&self, test_file: &mut TestFile) {
+        test_file.url = Some(self.url.clone());
+        test_file.url_file = None;
+        if let Some(method) = &self.method {
+            test_file.method = Some(method.clone());
+        }
+        for (key, value) in &self.headers {
+            test_file.headers.insert(key.clone(), value.clone());
+        }
+        if let Some(body) = &self.body {
+            test_file.body = Some(body.clone());
+        }
+        if let Some(credentials) = &self.basic_auth {
+            test_file.basic_auth = Some(credentials.clone());
+        }
+        if self.compressed {
+            test_file.compression = Some(COMPRESSED.to_string());
+        }
+    }
+}
+
+/// Whether a word such as `-sSL` combines short flags that are all ignored
+fn is_ignored_flag_group(word: &str) -> bool {
+    !word.starts_with("--")
+        && word.len() > 2
+        && word[1..]
+            .chars()
+            .all(|c| IGNORED_FLAGS.contains(&format!("-{}", c).as_str()))
+}
+
+/// The body of a `-d` option, which reads a file when it starts with `@`
+///
+/// A file's contents are sent as they are, without `${VAR}` expansion.
+fn read_data(value: &str) -> Result<String, String> {
+    match value.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|data| env::escape(&data))
+            .map_err(|e| format!("Failed to read curl data file {}: {}", path, e)),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, including
+/// bash's `$'...'` strings and backslash-newline continuations
+fn split_words(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    word.push(c);
+                    in_word = true;
+                }
+                None => return Err("Trailing backslash in curl command".to_string()),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated ' in curl command".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("Unterminated \" in curl command".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated \" in curl command".to_string()),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => word.push('\n'),
+                            Some('t') => word.push('\t'),
+                            Some('r') => word.push('\r'),
+                            Some('x') => {
+                                let hex: String = (0..2).filter_map(|_| chars.next()).collect();
+                                let byte = u8::from_str_radix(&hex, 16)
+                                    .map_err(|_| format!("Invalid escape \\x{}", hex))?;
+                                word.push(char::from(byte));
+                            }
+                            Some('u') => {
+                                let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+                                let c = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| format!("Invalid escape \\u{}", hex))?;
+                                word.push(c);
+                            }
+                            Some(c) => word.push(c),
+                            None => return Err("Unterminated $' in curl command".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated $' in curl command".to_string()),
+                    }
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words("curl 'a b' \"c \\\"d\\\"\" e\\ f \\\n  $'g\\nh\\u00e9'").unwrap(),
+            vec!["curl", "a b", "c \"d\"", "e f", "g\nhé"]
+        );
+        assert!(split_words("curl 'open").is_err());
+    }
+
+    #[test]
+    fn test_parse_curl() {
+        let command = CurlCommand::parse(
+            r#"curl 'https://api.example.com/orders' \
+              -H 'Authorization: Bearer abc' -H "content-type: application/json" \
+              --data-raw '{"item": 1}' --compressed -sSL"#,
+        )
+        .unwrap();
+        assert_eq!(command.url, "https://api.example.com/orders");
+        assert_eq!(command.method.as_deref(), Some("POST"));
+        assert_eq!(command.body.as_deref(), Some(r#"{"item": 1}"#));
+        assert_eq!(command.headers.len(), 2);
+        assert!(command.compressed);
+
+        let command =
+            CurlCommand::parse("curl -XPUT -u me:pw --url=http://x/a -d a=1 -d b=2").unwrap();
+        assert_eq!(command.method.as_deref(), Some("PUT"));
+        assert_eq!(command.basic_auth.as_deref(), Some("me:pw"));
+        assert_eq!(command.body.as_deref(), Some("a=1&b=2"));
+        assert_eq!(
+            command.headers,
+            vec![(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string()
+            )]
+        );
+
+        let command = CurlCommand::parse("curl -G http://x/search?q=1 -d page=2").unwrap();
+        assert_eq!(command.url, "http://x/search?q=1&page=2");
+        assert_eq!((command.method, command.body), (None, None));
+        assert_eq!(
+            CurlCommand::parse("curl -I http://x")
+                .unwrap()
+                .method
+                .as_deref(),
+            Some("HEAD")
+        );
+
+        assert!(CurlCommand::parse("curl -k https://x").is_err());
+        assert!(CurlCommand::parse("curl -H").is_err());
+        assert!(CurlCommand::parse("curl -s").is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Live web dashboard for runs
+//!
+//! The page is served by `rustyload serve` and by runs started with `--web`.
+//! It polls the same API as any other client and draws requests per second,
+//! latency percentiles and the error rate of each second on canvases, with
+//! no external assets, so it works on machines without internet access.
+
+use crate::protocols::{percentile, LiveResults, RequestResult};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+/// One second of a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Point {
+    /// Seconds from the start of the run to the start of this one
+    pub second: u64,
+    pub requests_per_second: f64,
+    /// Percentage of the requests completed in this second that failed
+    pub error_rate: f64,
+    pub p50: u128,
+    pub p95: u128,
+    pub p99: u128,
+}
+
+/// Per-second points of a run, recorded as its results come in
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    points: Arc<Mutex<Vec<Point>>>,
+}
+
+impl Timeline {
+    /// Record the results of `live` from now on, until every handle to them
+    /// has been dropped
+    pub fn start(live: &LiveResults) -> Self {
+        let timeline = Self::default();
+        let points = timeline.points.clone();
+        let mut results = live.subscribe();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(Duration::from_secs(1));
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticks.tick().await;
+
+            let mut second = Second::default();
+            loop {
+                tokio::select! {
+                    result = results.recv() => match result {
+                        Some(result) => second.add(&result),
+                        None => break,
+                    },
+                    _ = ticks.tick() => {
+                        let index = points.lock().unwrap().len() as u64;
+                        let point = std::mem::take(&mut second).point(index, 1.0);
+                        points.lock().unwrap().push(point);
+                    }
+                }
+            }
+
+            // The last, partial second
+            if second.requests > 0 {
+                let mut points = points.lock().unwrap();
+                let index = points.len() as u64;
+                let secs = second.start.elapsed().as_secs_f64().clamp(0.001, 1.0);
+                points.push(second.point(index, secs));
+            }
+        });
+        timeline
+    }
+
+    pub fn points(&self) -> Vec<Point> {
+        self.points.lock().unwrap().clone()
+    }
+}
+
+/// The results completed within one second
+struct Second {
+    start: Instant,
+    requests: u64,
+    failed: u64,
+    latencies: Vec<u128>,
+}
+
+impl Default for Second {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            requests: 0,
+            failed: 0,
+            latencies: Vec::new(),
+        }
+    }
+}
+
+impl Second {
+    fn add(&mut self, result: &RequestResult) {
+        self.requests += 1;
+        if result.success {
+            self.latencies.push(result.duration);
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    /// The point for these results, completed within `secs`
+    fn point(mut self, index: u64, secs: f64) -> Point {
+        self.latencies.sort_unstable();
+        Point {
+            second: index,
+            requests_per_second: self.requests as f64 / secs,
+            error_rate: if self.requests > 0 {
+                self.failed as f64 / self.requests as f64 * 100.0
+            } else {
+                0.0
+            },
+            p50: percentile(&self.latencies, 50.0),
+            p95: percentile(&self.latencies, 95.0),
+            p99: percentile(&self.latencies, 99.0),
+        }
+    }
+}
+
+/// The dashboard page, polling the API it is served next to
+pub const PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>RustyLoad dashboard</title>
+<style>
+body { font-family: -apple-system, 'Segoe UI', Roboto, sans-serif; margin: 2rem auto; max-width: 960px; color: #1f2328; }
+h1 { margin-bottom: 0; }
+h2 { font-size: 1.1rem; margin: 1.5rem 0 0.5rem; }
+.subtitle { color: #656d76; margin-top: 0.25rem; }
+.cards { display: flex; gap: 1rem; flex-wrap: wrap; margin: 1.5rem 0; }
+.card { flex: 1; min-width: 140px; border: 1px solid #d0d7de; border-radius: 8px; padding: 1rem; }
+.card .value { font-size: 1.6rem; font-weight: 600; }
+.card .label { color: #656d76; font-size: 0.85rem; }
+canvas { width: 100%; height: 180px; border: 1px solid #d0d7de; border-radius: 8px; }
+.legend span { margin-right: 1rem; font-size: 0.85rem; }
+.fail { color: #cf222e; font-weight: 600; }
+</style>
+</head>
+<body>
+<h1>RustyLoad dashboard</h1>
+<p class="subtitle"><select id="test"></select> <span id="target"></span></p>
+<p id="note" class="fail"></p>
+<div class="cards">
+<div class="card"><div class="value" id="status">-</div><div class="label">Status</div></div>
+<div class="card"><div class="value" id="progress">-</div><div class="label">Requests completed</div></div>
+<div class="card"><div class="value" id="rps">-</div><div class="label">Requests/sec</div></div>
+<div class="card"><div class="value" id="p99">-</div><div class="label">p99</div></div>
+<div class="card"><div class="value" id="errors">-</div><div class="label">Error rate</div></div>
+</div>
+<h2>Requests/sec</h2>
+<canvas id="rps-chart" height="180"></canvas>
+<h2>Latency (ms)</h2>
+<p class="legend"><span style="color:#1a7f37">&#9632; p50</span><span style="color:#9a6700">&#9632; p95</span><span style="color:#cf222e">&#9632; p99</span></p>
+<canvas id="latency-chart" height="180"></canvas>
+<h2>Error rate (%)</h2>
+<canvas id="errors-chart" height="180"></canvas>
+<script>
+const token = new URLSearchParams(location.search).get('token');
+const headers = token ? { Authorization: 'Bearer ' + token } : {};
+const select = document.getElementById('test');
+let selected = Number(location.hash.slice(1)) || null;
+select.onchange = () => { selected = Number(select.value); location.hash = selected; refresh(); };
+
+async function get(path) {
+  const response = await fetch(path, { headers });
+  const body = await response.json();
+  if (!response.ok) throw new Error(body.error || response.statusText);
+  return body;
+}
+
+function set(id, text) { document.getElementById(id).textContent = text; }
+
+function format(value) {
+  return value >= 100 ? Math.round(value).toString() : value.toFixed(1);
+}
+
+function draw(id, series, points) {
+  const canvas = document.getElementById(id);
+  const ctx = canvas.getContext('2d');
+  const width = canvas.width = canvas.clientWidth;
+  const height = canvas.height;
+  const left = 48, right = 8, top = 8, bottom = 20;
+  ctx.clearRect(0, 0, width, height);
+
+  const values = points.flatMap(p => series.map(s => s.value(p)));
+  const max = Math.max(1, ...values);
+  const x = i => left + (width - left - right) * (points.length > 1 ? i / (points.length - 1) : 0);
+  const y = v => height - bottom - (height - bottom - top) * v / max;
+
+  ctx.font = '11px sans-serif';
+  ctx.fillStyle = '#656d76';
+  ctx.strokeStyle = '#d0d7de';
+  for (const v of [0, max / 2, max]) {
+    ctx.beginPath();
+    ctx.moveTo(left, y(v));
+    ctx.lineTo(width - right, y(v));
+    ctx.stroke();
+    ctx.fillText(format(v), 4, y(v) + 4);
+  }
+  if (points.length) {
+    const last = points[points.length - 1].second + 's';
+    ctx.fillText(points[0].second + 's', left, height - 4);
+    ctx.fillText(last, width - right - ctx.measureText(last).width, height - 4);
+  }
+
+  ctx.lineWidth = 2;
+  for (const s of series) {
+    ctx.strokeStyle = s.color;
+    ctx.beginPath();
+    points.forEach((p, i) => i ? ctx.lineTo(x(i), y(s.value(p))) : ctx.moveTo(x(i), y(s.value(p))));
+    ctx.stroke();
+  }
+  ctx.lineWidth = 1;
+}
+
+function showTests(tests) {
+  if (selected === null || !tests.some(t => t.id === selected)) {
+    selected = tests.length ? tests[0].id : null;
+  }
+  select.replaceChildren(...tests.map(t => {
+    const option = document.createElement('option');
+    option.value = t.id;
+    option.textContent = `#${t.id} ${t.status}`;
+    option.selected = t.id === selected;
+    return option;
+  }));
+}
+
+async function refresh() {
+  try {
+    const tests = await get('tests');
+    showTests(tests);
+    const test = tests.find(t => t.id === selected);
+    if (test) {
+      const points = await get(`tests/${test.id}/timeline`);
+      const last = points[points.length - 1];
+      set('target', `${test.target} · ${test.protocol}`);
+      set('status', test.passed === false ? `${test.status} (thresholds failed)` : test.status);
+      set('progress', `${test.completed} / ${test.requests}`);
+      set('rps', last ? format(last.requests_per_second) : '-');
+      set('p99', last ? `${last.p99} ms` : '-');
+      set('errors', last ? `${format(last.error_rate)}%` : '-');
+      draw('rps-chart', [{ color: '#0969da', value: p => p.requests_per_second }], points);
+      draw('latency-chart', [
+        { color: '#1a7f37', value: p => p.p50 },
+        { color: '#9a6700', value: p => p.p95 },
+        { color: '#cf222e', value: p => p.p99 },
+      ], points);
+      draw('errors-chart', [{ color: '#cf222e', value: p => p.error_rate }], points);
+    } else {
+      set('target', 'No tests yet');
+    }
+    set('note', '');
+  } catch (e) {
+    set('note', `Not updating: ${e.message}`);
+  }
+}
+
+refresh();
+setInterval(refresh, 1000);
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeline() {
+        let live = LiveResults::default();
+        let timeline = Timeline::start(&live);
+        for duration in [10, 20, 30] {
+            live.record(RequestResult {
+                duration,
+                status: 200,
+                success: true,
+                ..Default::default()
+            });
+        }
+        live.record(RequestResult::default());
+
+        // Dropping the results ends the timeline with the partial second
+        drop(live);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let points = timeline.points();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].second, 0);
+        assert_eq!(points[0].error_rate, 25.0);
+        assert_eq!((points[0].p50, points[0].p99), (20, 29));
+    }
+}
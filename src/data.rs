@@ -0,0 +1,209 @@
+//! Data-driven requests from a CSV file
+//!
+//! The first line of the file names the columns, and every request takes one
+//! of the following rows, so `{{user_id}}` in the URL, header values or body
+//! becomes that row's `user_id` value.
+
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How requests pick their row
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DataOrder {
+    /// Rows in file order, starting over after the last one
+    #[default]
+    RoundRobin,
+    /// A random row per request
+    Random,
+}
+
+impl FromStr for DataOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "round-robin" => Ok(DataOrder::RoundRobin),
+            "random" => Ok(DataOrder::Random),
+            _ => Err(format!(
+                "Unsupported data order: {} (expected round-robin or random)",
+                s
+            )),
+        }
+    }
+}
+
+impl DataOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataOrder::RoundRobin => "round-robin",
+            DataOrder::Random => "random",
+        }
+    }
+
+    /// The index request number `seq` uses out of `len` entries
+    pub fn pick(&self, seq: u64, len: usize) -> usize {
+        match self {
+            DataOrder::RoundRobin => (seq % len as u64) as usize,
+            DataOrder::Random => rand::rng().random_range(0..len),
+        }
+    }
+}
+
+/// Rows loaded from a CSV file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataFeed {
+    path: PathBuf,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    order: DataOrder,
+}
+
+/// One row of a [`DataFeed`], looked up by column name
+#[derive(Debug, Clone, Copy)]
+pub struct DataRow<'a> {
+    columns: &'a [String],
+    values: &'a [String],
+}
+
+impl<'a> DataRow<'a> {
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let index = self.columns.iter().position(|name| name == column)?;
+        self.values.get(index).map(String::as_str)
+    }
+}
+
+impl DataFeed {
+    pub fn load(path: &Path, order: DataOrder) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read data file {}", path.display()))?;
+        let feed = Self::parse(&text, order)
+            .with_context(|| format!("In data file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..feed
+        })
+    }
+
+    fn parse(text: &str, order: DataOrder) -> Result<Self> {
+        let mut records = parse_csv(text)?.into_iter();
+        let Some(columns) = records.next() else {
+            bail!("The file is empty; expected a header line naming the columns");
+        };
+        let columns: Vec<String> = columns.iter().map(|c| c.trim().to_string()).collect();
+
+        let rows: Vec<Vec<String>> = records.collect();
+        if rows.is_empty() {
+            bail!("No rows after the header line");
+        }
+        if let Some(index) = rows.iter().position(|row| row.len() != columns.len()) {
+            bail!(
+                "Row {} has {} columns but the header has {}",
+                index + 1,
+                rows[index].len(),
+                columns.len()
+            );
+        }
+
+        Ok(Self {
+            path: PathBuf::new(),
+            columns,
+            rows,
+            order,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn order(&self) -> DataOrder {
+        self.order
+    }
+
+    /// The row request number `seq` uses
+    pub fn row(&self, seq: u64) -> DataRow<'_> {
+        DataRow {
+            columns: &self.columns,
+            values: &self.rows[self.order.pick(seq, self.rows.len())],
+        }
+    }
+}
+
+/// Split CSV text into records, honouring double-quoted fields (which may
+/// contain commas, newlines and `""` escapes) and skipping blank lines
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("Unterminated quoted field");
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_rows() {
+        let feed = DataFeed::parse(
+            "id,name,bio\r\n1,alice,\"likes \"\"tea\"\", cake\"\n\n2,bob,\"multi\nline\"\n",
+            DataOrder::RoundRobin,
+        )
+        .unwrap();
+        assert_eq!(feed.row_count(), 2);
+        assert_eq!(feed.row(0).get("name"), Some("alice"));
+        assert_eq!(feed.row(0).get("bio"), Some("likes \"tea\", cake"));
+        assert_eq!(feed.row(1).get("bio"), Some("multi\nline"));
+        assert_eq!(feed.row(2).get("id"), Some("1"));
+        assert_eq!(feed.row(0).get("missing"), None);
+
+        let random = DataFeed {
+            order: DataOrder::Random,
+            ..feed
+        };
+        for seq in 0..20 {
+            assert!(matches!(random.row(seq).get("id"), Some("1" | "2")));
+        }
+
+        assert!(DataFeed::parse("", DataOrder::RoundRobin).is_err());
+        assert!(DataFeed::parse("id,name\n", DataOrder::RoundRobin).is_err());
+        assert!(DataFeed::parse("id,name\n1\n", DataOrder::RoundRobin).is_err());
+        assert!(DataFeed::parse("id\n\"1\n", DataOrder::RoundRobin).is_err());
+    }
+}
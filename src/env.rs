@@ -0,0 +1,209 @@
+//! Environment variable interpolation
+//!
+//! `${VAR}` in URLs, headers, bodies and config files is replaced with the
+//! value of `VAR`, so secrets such as API tokens never have to be typed into
+//! the wizard or committed to a config file. Definitions are expanded once,
+//! as they are turned into a run configuration; payloads read from files or
+//! stdin are sent as they are.
+
+use anyhow::{bail, Result};
+use std::cell::RefCell;
+
+/// The variables a test definition's `${VAR}` references and credential
+/// fallbacks can read
+///
+/// Values substituted into the definition are remembered, so they can be
+/// hidden again wherever the target is shown or recorded.
+#[derive(Debug)]
+pub struct Env {
+    /// Whether the process environment can be read at all
+    allowed: bool,
+    /// Values expanded so far, with the reference each replaced
+    expanded: RefCell<Vec<(String, String)>>,
+}
+
+impl Env {
+    /// The process environment
+    pub fn process() -> Self {
+        Self {
+            allowed: true,
+            expanded: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// No environment, for definitions from someone who mustn't read it:
+    /// references without a default are an error and no credentials are
+    /// picked up from variables
+    pub fn denied() -> Self {
+        Self {
+            allowed: false,
+            expanded: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The value of a variable, if it is set and may be read
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.allowed.then(|| std::env::var(name).ok()).flatten()
+    }
+
+    /// Expand `${VAR}` and `${VAR:-default}` references in a string
+    ///
+    /// A missing variable without a default is an error rather than an empty
+    /// string, so a forgotten `export` doesn't silently send `Bearer `. Use
+    /// `$${` for a literal `${`.
+    pub fn expand(&self, input: &str) -> Result<String> {
+        let missing = if self.allowed {
+            "is not set"
+        } else {
+            "can't be read: environment variables are off for this test"
+        };
+        expand_with(
+            input,
+            |name| {
+                let value = self.var(name)?;
+                self.expanded
+                    .borrow_mut()
+                    .push((value.clone(), format!("${{{}}}", name)));
+                Some(value)
+            },
+            missing,
+        )
+    }
+
+    /// `text` with the values of expanded variables put back as the
+    /// references they came from
+    pub fn redact(&self, text: &str) -> String {
+        let mut expanded = self.expanded.borrow().clone();
+        // Longer values first, so one containing another is replaced whole
+        expanded.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+        expanded
+            .iter()
+            .filter(|(value, _)| !value.is_empty())
+            .fold(text.to_string(), |text, (value, reference)| {
+                text.replace(value.as_str(), reference)
+            })
+    }
+}
+
+/// Escape `text` so that expanding it gives it back unchanged, for payloads
+/// that must be sent as they are
+pub fn escape(text: &str) -> String {
+    text.replace("${", "$${")
+}
+
+fn expand_with(
+    input: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    missing: &str,
+) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start..];
+
+        if let Some(escaped) = after.strip_prefix("$${") {
+            output.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = after.strip_prefix("${") {
+            let Some(end) = reference.find('}') else {
+                bail!("Unterminated variable reference in '{}'", input);
+            };
+            let (name, default) = match reference[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&reference[..end], None),
+            };
+            if name.is_empty() {
+                bail!("Empty variable reference in '{}'", input);
+            }
+
+            match lookup(name).or_else(|| default.map(String::from)) {
+                Some(value) => output.push_str(&value),
+                None => bail!("Environment variable {} {}", name, missing),
+            }
+            rest = &reference[end + 1..];
+        } else {
+            output.push('$');
+            rest = &after[1..];
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "API_TOKEN" => Some("s3cret".to_string()),
+            "HOST" => Some("api.example.com".to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand(input: &str) -> Result<String> {
+        expand_with(input, lookup, "is not set")
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("Bearer ${API_TOKEN}").unwrap(), "Bearer s3cret");
+        assert_eq!(
+            expand("https://${HOST}/v1?a=$1").unwrap(),
+            "https://api.example.com/v1?a=$1"
+        );
+        assert_eq!(expand("no variables").unwrap(), "no variables");
+    }
+
+    #[test]
+    fn test_expand_default_and_escape() {
+        assert_eq!(expand("${PORT:-8080}").unwrap(), "8080");
+        assert_eq!(expand("${HOST:-localhost}").unwrap(), "api.example.com");
+        assert_eq!(expand("$${HOST}").unwrap(), "${HOST}");
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        assert!(expand("${MISSING}").is_err());
+        assert!(expand("${HOST").is_err());
+        assert!(expand("${}").is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        for text in ["plain", "${HOST}", "$${HOST}", "$$${HOST} costs $5", "${"] {
+            assert_eq!(expand(&escape(text)).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_denied() {
+        std::env::set_var("RUSTYLOAD_TEST_KEY", "k3y");
+        let denied = Env::denied();
+        assert_eq!(denied.var("RUSTYLOAD_TEST_KEY"), None);
+        let error = denied.expand("${RUSTYLOAD_TEST_KEY}").unwrap_err();
+        assert!(error.to_string().contains("are off"), "{}", error);
+        assert_eq!(
+            denied.expand("${RUSTYLOAD_TEST_KEY:-none}").unwrap(),
+            "none"
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        std::env::set_var("RUSTYLOAD_TEST_KEY", "k3y");
+        let env = Env::process();
+        let url = env
+            .expand("https://api.example.com/?key=${RUSTYLOAD_TEST_KEY}")
+            .unwrap();
+        assert_eq!(url, "https://api.example.com/?key=k3y");
+        assert_eq!(
+            env.redact(&url),
+            "https://api.example.com/?key=${RUSTYLOAD_TEST_KEY}"
+        );
+    }
+}
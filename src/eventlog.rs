@@ -0,0 +1,339 @@
+//! Per-request event log
+//!
+//! For analysis beyond the summary, every completed request can be written to
+//! a file as one JSON object per line (NDJSON) or as a CSV row, ready to be
+//! loaded into ClickHouse, Elasticsearch or a notebook. An error log holds the
+//! failed requests alone. Events are written as results come in, so the log
+//! of a long run grows with it rather than being held in memory until the
+//! end, and can be compressed on the fly like other exports.
+
+use crate::output;
+use crate::protocols::{ErrorKind, LiveResults, RequestResult};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// The format events are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    /// One JSON object per line
+    #[default]
+    Ndjson,
+    /// A header line, then one comma-separated row per event
+    Csv,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "ndjson" | "jsonl" => Ok(LogFormat::Ndjson),
+            "csv" => Ok(LogFormat::Csv),
+            _ => Err(format!(
+                "Invalid log format '{}': expected ndjson or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// The CSV columns, in the order of [`Event`]'s fields
+const CSV_HEADER: &str =
+    "timestamp,worker,latency_ms,status,success,error,message,operation,step,tag,backend";
+
+/// One completed request
+#[derive(Debug, PartialEq, Serialize)]
+struct Event<'a> {
+    /// Unix time the request completed, in milliseconds
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worker: Option<u64>,
+    latency_ms: u128,
+    status: u16,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    /// The message of an assertion failure or unclassified error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    /// The event of `result`, timed against the wall clock reading `now` taken at `then`
+    fn new(result: &'a RequestResult, now: SystemTime, then: Instant) -> Self {
+        let completed = match result.completed_at {
+            Some(at) => {
+                now - then.saturating_duration_since(at) + at.saturating_duration_since(then)
+            }
+            None => now,
+        };
+        let message = match &result.error {
+            Some(ErrorKind::AssertionFailed(message) | ErrorKind::Other(message)) => {
+                Some(message.as_str())
+            }
+            _ => None,
+        };
+        Self {
+            timestamp: completed
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            worker: result.worker,
+            latency_ms: result.duration,
+            status: result.status,
+            success: result.success,
+            error: result.error.as_ref().map(ErrorKind::name),
+            message,
+            operation: result.operation,
+            step: result.step.as_deref(),
+            tag: result.tag.as_deref(),
+            backend: result.backend.as_deref(),
+        }
+    }
+
+    /// The event as a CSV row, without the line ending
+    fn to_csv(&self) -> String {
+        let number = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_default();
+        [
+            self.timestamp.to_string(),
+            number(self.worker),
+            self.latency_ms.to_string(),
+            self.status.to_string(),
+            self.success.to_string(),
+            csv_field(self.error.unwrap_or_default()),
+            csv_field(self.message.unwrap_or_default()),
+            csv_field(self.operation.unwrap_or_default()),
+            csv_field(self.step.unwrap_or_default()),
+            csv_field(self.tag.unwrap_or_default()),
+            csv_field(self.backend.unwrap_or_default()),
+        ]
+        .join(",")
+    }
+}
+
+/// Quote a CSV field if it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes an event for every result of a run to a file from a blocking thread
+pub struct EventLog {
+    path: PathBuf,
+    done: oneshot::Sender<()>,
+    handle: JoinHandle<Result<u64>>,
+}
+
+impl EventLog {
+    /// Create the log file, zstd-compressed if `compress` is set, and start
+    /// writing the results of `live` to it, only the failed ones if
+    /// `errors_only` is set
+    pub fn start(
+        path: &Path,
+        format: LogFormat,
+        compress: bool,
+        errors_only: bool,
+        live: &LiveResults,
+    ) -> Result<Self> {
+        let mut writer = Writer {
+            out: output::create_writer(path, compress)?,
+            format,
+            errors_only,
+            written: 0,
+        };
+        if format == LogFormat::Csv {
+            writeln!(writer.out, "{}", CSV_HEADER)?;
+        }
+        let mut results = live.subscribe();
+        let (done, mut finished) = oneshot::channel();
+        let runtime = tokio::runtime::Handle::current();
+        // Results are awaited on the runtime but serialized, compressed and
+        // written on a blocking thread, so a slow disk can't stall its workers
+        let handle = tokio::task::spawn_blocking(move || {
+            while let Some(result) = runtime.block_on(async {
+                tokio::select! {
+                    result = results.recv() => result,
+                    _ = &mut finished => None,
+                }
+            }) {
+                writer.write(&result)?;
+            }
+            // Every result of the run has been sent by now
+            while let Ok(result) = results.try_recv() {
+                writer.write(&result)?;
+            }
+            writer.out.finish()?;
+            Ok(writer.written)
+        });
+        Ok(Self {
+            path: output::output_path(path, compress),
+            done,
+            handle,
+        })
+    }
+
+    /// The file the events are written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the remaining events once the run is over, returning how many
+    /// were written in all
+    pub async fn finish(self) -> Result<u64> {
+        let _ = self.done.send(());
+        self.handle
+            .await?
+            .with_context(|| format!("Failed to write event log {}", self.path.display()))
+    }
+}
+
+struct Writer {
+    out: output::OutputWriter,
+    format: LogFormat,
+    errors_only: bool,
+    written: u64,
+}
+
+impl Writer {
+    fn write(&mut self, result: &RequestResult) -> Result<()> {
+        if self.errors_only && result.success {
+            return Ok(());
+        }
+        let event = Event::new(result, SystemTime::now(), Instant::now());
+        match self.format {
+            LogFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, &event)?;
+                self.out.write_all(b"\n")?;
+            }
+            LogFormat::Csv => writeln!(self.out, "{}", event.to_csv())?,
+        }
+        self.written += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_event() {
+        let then = Instant::now();
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let result = RequestResult {
+            duration: 12,
+            status: 500,
+            worker: Some(3),
+            operation: Some("GET"),
+            tag: Some(Arc::from("checkout")),
+            completed_at: then.checked_sub(Duration::from_millis(250)),
+            ..Default::default()
+        };
+        let event = serde_json::to_value(Event::new(&result, now, then)).unwrap();
+        assert_eq!(
+            event,
+            serde_json::json!({
+                "timestamp": 1_699_999_999_750u64,
+                "worker": 3,
+                "latency_ms": 12,
+                "status": 500,
+                "success": false,
+                "operation": "GET",
+                "tag": "checkout",
+            })
+        );
+
+        let failed = RequestResult {
+            error: Some(ErrorKind::Other("broken pipe".to_string())),
+            ..Default::default()
+        };
+        let event = Event::new(&failed, now, then);
+        assert_eq!(event.error, Some("other"));
+        assert_eq!(event.message, Some("broken pipe"));
+    }
+
+    #[tokio::test]
+    async fn test_event_log() {
+        let path =
+            std::env::temp_dir().join(format!("rustyload-events-{}.ndjson", std::process::id()));
+        let live = LiveResults::default();
+        let log = EventLog::start(&path, LogFormat::Ndjson, false, false, &live).unwrap();
+        for status in [200, 200, 503] {
+            live.record(RequestResult {
+                status,
+                success: status == 200,
+                ..Default::default()
+            });
+        }
+        assert_eq!(log.finish().await.unwrap(), 3);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let statuses: Vec<u64> = contents
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["status"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(statuses, vec![200, 200, 503]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(LogFormat::from_str("NDJSON").unwrap(), LogFormat::Ndjson);
+        assert_eq!(LogFormat::from_str("jsonl").unwrap(), LogFormat::Ndjson);
+        assert_eq!(LogFormat::from_str("CSV").unwrap(), LogFormat::Csv);
+        assert!(LogFormat::from_str("xml").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_csv_error_log() {
+        let path =
+            std::env::temp_dir().join(format!("rustyload-errors-{}.csv", std::process::id()));
+        let live = LiveResults::default();
+        let log = EventLog::start(&path, LogFormat::Csv, true, true, &live).unwrap();
+        live.record(RequestResult {
+            status: 200,
+            success: true,
+            ..Default::default()
+        });
+        live.record(RequestResult {
+            status: 502,
+            error: Some(ErrorKind::Other("bad gateway, \"upstream\"".to_string())),
+            ..Default::default()
+        });
+        assert_eq!(log.finish().await.unwrap(), 1);
+
+        let compressed = output::output_path(&path, true);
+        let contents = output::read_to_string(&compressed).unwrap();
+        std::fs::remove_file(&compressed).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(
+            lines[1].ends_with(",0,502,false,other,\"bad gateway, \"\"upstream\"\"\",,,,"),
+            "{}",
+            lines[1]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Values extracted from responses for use in later requests
+//!
+//! `--extract token=$.data.token` stores the value at that JSON path from each
+//! successful response, and `{{token}}` in the URL, header values or body is
+//! replaced with the latest value before a request is sent. Placeholders are
+//! sent as-is until a response has provided a value.
+//!
+//! Values can also come from a response header (`id=header:Location`) or the
+//! first capture group of a regex over the body (`id=regex:"id":(\d+)`).
+
+use crate::jsonpath::JsonPath;
+use crate::template::{self, RequestContext};
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// A `NAME=SOURCE` rule saving a response value as a variable
+#[derive(Debug, Clone)]
+pub struct Extraction {
+    pub name: String,
+    pub source: Source,
+}
+
+/// Where in a response an extracted value comes from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// `$.path` into a JSON body
+    Json(JsonPath),
+    /// `header:Name`
+    Header(String),
+    /// `regex:PATTERN`, taking the first capture group or else the whole match
+    Regex(Regex),
+}
+
+impl Extraction {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, source) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid extraction '{}': expected NAME=$.path", s))?;
+        let source = source.trim();
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!(
+                "Invalid extraction '{}': names may only use letters, digits, _ and -",
+                s
+            ));
+        }
+        let source = if let Some(header) = source.strip_prefix("header:") {
+            let header = header.trim();
+            if header.is_empty() {
+                return Err(format!("Invalid extraction '{}': missing header name", s));
+            }
+            Source::Header(header.to_string())
+        } else if let Some(pattern) = source.strip_prefix("regex:") {
+            Source::Regex(
+                Regex::new(pattern)
+                    .map_err(|e| format!("Invalid regex in extraction '{}': {}", s, e))?,
+            )
+        } else {
+            Source::Json(JsonPath::parse(source)?)
+        };
+        Ok(Self {
+            name: name.to_string(),
+            source,
+        })
+    }
+
+    /// Whether the value comes from the response body
+    pub fn needs_body(&self) -> bool {
+        !matches!(self.source, Source::Header(_))
+    }
+}
+
+impl fmt::Display for Extraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Source::Json(path) => write!(f, "{}={}", self.name, path),
+            Source::Header(header) => write!(f, "{}=header:{}", self.name, header),
+            Source::Regex(regex) => write!(f, "{}=regex:{}", self.name, regex.as_str()),
+        }
+    }
+}
+
+/// Variables shared by every request of a run, or by the steps of one
+/// scenario iteration
+#[derive(Debug, Clone, Default)]
+pub struct Variables(Arc<RwLock<HashMap<String, String>>>);
+
+impl Variables {
+    /// Save the values the extractions find in a response; JSON strings are
+    /// stored as-is and other JSON values as JSON
+    pub fn extract(&self, extractions: &[Extraction], headers: &HeaderMap, body: &str) {
+        let mut json = None;
+        let mut variables = self.0.write().unwrap();
+        for extraction in extractions {
+            let value = match &extraction.source {
+                Source::Json(path) => {
+                    let json = json.get_or_insert_with(|| serde_json::from_str::<Value>(body).ok());
+                    json.as_ref()
+                        .and_then(|json| path.find(json))
+                        .map(|value| match value {
+                            Value::String(text) => text.clone(),
+                            value => value.to_string(),
+                        })
+                }
+                Source::Header(name) => headers
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string),
+                Source::Regex(regex) => regex.captures(body).and_then(|captures| {
+                    captures
+                        .get(1)
+                        .or_else(|| captures.get(0))
+                        .map(|m| m.as_str().to_string())
+                }),
+            };
+            if let Some(value) = value {
+                variables.insert(extraction.name.clone(), value);
+            }
+        }
+    }
+
+    /// Expand `{{name}}` placeholders for one request from its data row, the
+    /// saved values and the built-in generators, leaving unknown names untouched
+    pub fn substitute<'a>(&self, s: &'a str, context: &RequestContext) -> Cow<'a, str> {
+        template::expand(s, |name| {
+            if let Some(value) = context.row.and_then(|row| row.get(name)) {
+                return Some(value.to_string());
+            }
+            let variables = self.0.read().unwrap();
+            variables
+                .get(name)
+                .cloned()
+                .or_else(|| template::generate(name, context))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_and_substitute() {
+        let extractions = vec![
+            Extraction::parse("token=$.auth.token").unwrap(),
+            Extraction::parse("first_id = $.items[0].id").unwrap(),
+        ];
+        assert_eq!(extractions[1].to_string(), "first_id=$.items[0].id");
+        assert!(Extraction::parse("$.auth.token").is_err());
+        assert!(Extraction::parse("my token=$.token").is_err());
+        assert!(Extraction::parse("token=auth.token").is_err());
+
+        let variables = Variables::default();
+        assert_eq!(
+            variables.substitute("/items/{{first_id}}", &RequestContext::default()),
+            "/items/{{first_id}}"
+        );
+
+        variables.extract(
+            &extractions,
+            &HeaderMap::new(),
+            r#"{"auth": {"token": "abc"}, "items": [{"id": 7}]}"#,
+        );
+        assert_eq!(
+            variables.substitute(
+                "/items/{{first_id}}?t={{ token }}&n={{seq}}&x={{other}}",
+                &RequestContext {
+                    seq: 3,
+                    ..Default::default()
+                }
+            ),
+            "/items/7?t=abc&n=3&x={{other}}"
+        );
+
+        // Bodies that aren't JSON or lack a value keep what was saved before
+        variables.extract(&extractions, &HeaderMap::new(), "not json");
+        variables.extract(&extractions, &HeaderMap::new(), r#"{"items": []}"#);
+        assert_eq!(
+            variables.substitute("{{token}}-{{first_id}}", &RequestContext::default()),
+            "abc-7"
+        );
+    }
+
+    #[test]
+    fn test_header_and_regex_extractions() {
+        let extractions = vec![
+            Extraction::parse("location=header:Location").unwrap(),
+            Extraction::parse(r#"order=regex:"order":\s*(\d+)"#).unwrap(),
+            Extraction::parse("word=regex:[a-z]+").unwrap(),
+        ];
+        assert_eq!(extractions[0].to_string(), "location=header:Location");
+        assert_eq!(
+            extractions[1].to_string(),
+            r#"order=regex:"order":\s*(\d+)"#
+        );
+        assert!(!extractions[0].needs_body() && extractions[1].needs_body());
+        assert!(Extraction::parse("id=header:").is_err());
+        assert!(Extraction::parse("id=regex:(").is_err());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("location", "/orders/42".parse().unwrap());
+        let variables = Variables::default();
+        variables.extract(&extractions, &headers, r#"{"order": 42}"#);
+        assert_eq!(
+            variables.substitute(
+                "{{location}} {{order}} {{word}}",
+                &RequestContext::default()
+            ),
+            "/orders/42 42 order"
+        );
+    }
+}
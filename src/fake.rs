@@ -0,0 +1,186 @@
+//! Fake but realistic-looking values for request templates
+//!
+//! Good enough to give every POST a different payload; the word lists are
+//! short and nothing here is meant to be statistically representative.
+
+use rand::seq::IndexedRandom;
+use rand::Rng;
+
+const FIRST_NAMES: &[&str] = &[
+    "Alice", "Bob", "Carmen", "Dmitri", "Elena", "Farid", "Grace", "Hiro", "Ines", "Jamal",
+    "Katarina", "Liam", "Mei", "Nadia", "Oscar", "Priya", "Quentin", "Rosa", "Sven", "Tariq",
+    "Uma", "Victor", "Wen", "Ximena", "Yusuf", "Zoe",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Andersen",
+    "Brown",
+    "Chen",
+    "Dubois",
+    "Eriksen",
+    "Fernandez",
+    "Garcia",
+    "Hoffmann",
+    "Ito",
+    "Johnson",
+    "Kowalski",
+    "Lopez",
+    "Muller",
+    "Nakamura",
+    "O'Brien",
+    "Patel",
+    "Rossi",
+    "Silva",
+    "Tanaka",
+    "Usman",
+    "Varga",
+    "Williams",
+    "Yilmaz",
+    "Zhang",
+];
+
+const STREETS: &[&str] = &[
+    "Main St",
+    "Oak Ave",
+    "Maple Dr",
+    "Cedar Ln",
+    "Elm St",
+    "Park Rd",
+    "Hill St",
+    "Lake Ave",
+    "River Rd",
+    "Sunset Blvd",
+    "Station Rd",
+    "Church St",
+];
+
+const CITIES: &[&str] = &[
+    "Springfield",
+    "Riverside",
+    "Fairview",
+    "Kingston",
+    "Georgetown",
+    "Salem",
+    "Franklin",
+    "Madison",
+    "Clinton",
+    "Ashland",
+    "Burlington",
+    "Milton",
+];
+
+const EMAIL_DOMAINS: &[&str] = &["example.com", "example.org", "example.net", "mail.test"];
+
+const LOREM: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+];
+
+/// The default length of `{{lorem}}` text, in bytes
+pub const DEFAULT_LOREM_BYTES: usize = 64;
+
+fn pick(words: &[&'static str]) -> &'static str {
+    words.choose(&mut rand::rng()).copied().unwrap_or_default()
+}
+
+pub fn first_name() -> String {
+    pick(FIRST_NAMES).to_string()
+}
+
+pub fn last_name() -> String {
+    pick(LAST_NAMES).to_string()
+}
+
+pub fn name() -> String {
+    format!("{} {}", pick(FIRST_NAMES), pick(LAST_NAMES))
+}
+
+/// An address at one of the reserved example domains, with a random number so
+/// repeated names don't collide
+pub fn email() -> String {
+    format!(
+        "{}.{}{}@{}",
+        pick(FIRST_NAMES).to_lowercase(),
+        pick(LAST_NAMES).to_lowercase().replace('\'', ""),
+        rand::rng().random_range(1..10_000),
+        pick(EMAIL_DOMAINS)
+    )
+}
+
+/// A one-line street address, e.g. `742 Maple Dr, Springfield`
+pub fn address() -> String {
+    format!(
+        "{} {}, {}",
+        rand::rng().random_range(1..10_000),
+        pick(STREETS),
+        pick(CITIES)
+    )
+}
+
+/// Lorem ipsum words making up exactly `bytes` bytes of text
+pub fn lorem(bytes: usize) -> String {
+    let mut text = String::with_capacity(bytes + 16);
+    while text.len() < bytes {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(pick(LOREM));
+    }
+    text.truncate(bytes);
+    if text.ends_with(' ') {
+        text.pop();
+        text.push('.');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_values() {
+        assert_eq!(name().split(' ').count(), 2);
+        let email = email();
+        assert!(email.contains('@') && !email.contains(' ') && !email.contains('\''));
+        assert!(address().contains(", "));
+        for bytes in [0, 1, 5, 64, 1000] {
+            assert_eq!(lorem(bytes).len(), bytes);
+        }
+        for _ in 0..100 {
+            assert!(lorem(20).split(' ').all(|word| !word.is_empty()));
+        }
+    }
+}
@@ -0,0 +1,586 @@
+//! Post-run hints
+//!
+//! Looks at the results of a run and turns the patterns an experienced user
+//! would spot (a single dominant error, a long latency tail, one unhealthy
+//! backend, ...) into short, actionable suggestions.
+
+use crate::metrics::{CustomMetricSummary, CONNECTIONS_OPENED};
+use crate::protocols::http::{DnsCache, DNS_LOOKUP};
+use crate::protocols::{flashkv, LoadTestConfig, LoadTestStats, Protocol, STATUS_ERROR};
+
+/// A hit ratio falling this many percentage points over a run is reported
+const HIT_RATIO_DROP: f64 = 20.0;
+
+/// Below this many requests, percentiles are too noisy to rely on
+const MIN_RELIABLE_REQUESTS: u64 = 100;
+
+/// A p99 this many times the median is reported as a long tail
+const TAIL_RATIO: u128 = 10;
+
+/// DNS lookups taking this percentage of the total request time are reported
+const DNS_SHARE: f64 = 10.0;
+
+/// What to do about each error kind
+const KIND_ADVICE: [(&str, &str); 7] = [
+    ("dns", "the hostname could not be resolved, check the URL"),
+    (
+        "connect_refused",
+        "check that the server is running and the host/port are right",
+    ),
+    (
+        "connect_timeout",
+        "the server isn't accepting connections in time, check that it is reachable and its backlog",
+    ),
+    (
+        "tls",
+        "check the hostname and certificate chain, and that the port speaks TLS (http:// vs https://)",
+    ),
+    (
+        "response_timeout",
+        "the server can't keep up, lower --concurrency or raise the timeout",
+    ),
+    (
+        "reset",
+        "the server dropped connections, it may be hitting a connection or backlog limit",
+    ),
+    (
+        "protocol_parse",
+        "the replies couldn't be parsed, check that the target speaks the selected protocol",
+    ),
+];
+
+/// Known messages of otherwise unclassified errors and what to do about them
+const OTHER_PATTERNS: [(&str, &str); 3] = [
+    (
+        "too many open files",
+        "raise the file descriptor limit (ulimit -n) or lower --concurrency",
+    ),
+    (
+        "noauth",
+        "the server needs a password, pass it with --password (and --username for an ACL user)",
+    ),
+    (
+        "wrongpass",
+        "the server rejected the credentials, check --password and --username",
+    ),
+];
+
+/// Analyze a finished run and return actionable hints, most important first
+pub fn analyze(stats: &LoadTestStats, config: &LoadTestConfig) -> Vec<String> {
+    let mut hints = Vec::new();
+
+    error_hints(stats, &config.protocol, &mut hints);
+    client_hints(stats, &mut hints);
+    latency_hints(stats, config, &mut hints);
+    backend_hints(stats, &mut hints);
+    worker_hints(stats, &mut hints);
+    keyspace_hints(stats, &mut hints);
+    stream_hints(stats, &mut hints);
+
+    if stats.total_requests > 0 && stats.total_requests < MIN_RELIABLE_REQUESTS {
+        hints.push(format!(
+            "Only {} requests were sent: percentiles are unreliable at this size, use -n {} or more",
+            stats.total_requests, MIN_RELIABLE_REQUESTS
+        ));
+    }
+
+    hints
+}
+
+fn error_hints(stats: &LoadTestStats, protocol: &Protocol, hints: &mut Vec<String>) {
+    if stats.failed_requests == 0 {
+        return;
+    }
+
+    for error in &stats.errors {
+        // Only errors behind a meaningful share of the failures are worth a hint
+        let share = error.count as f64 / stats.failed_requests as f64 * 100.0;
+        if share < 10.0 {
+            continue;
+        }
+
+        let advice = match error.kind.as_str() {
+            STATUS_ERROR => status_advice(&error.message, protocol),
+            "other" => {
+                let message = error.message.to_lowercase();
+                OTHER_PATTERNS
+                    .iter()
+                    .find(|(pattern, _)| message.contains(pattern))
+                    .map(|(_, advice)| *advice)
+            }
+            kind => KIND_ADVICE
+                .iter()
+                .find(|(name, _)| *name == kind)
+                .map(|(_, advice)| *advice),
+        };
+
+        if let Some(advice) = advice {
+            let scope = if error.count == stats.failed_requests {
+                "All errors".to_string()
+            } else {
+                format!("{:.0}% of errors", share)
+            };
+            hints.push(format!(
+                "{} are '{}': {}",
+                scope,
+                short_error(&error.message),
+                advice
+            ));
+        }
+    }
+}
+
+/// Advice for failed responses that carry no error message, only a status
+fn status_advice(message: &str, protocol: &Protocol) -> Option<&'static str> {
+    if let Some(rcode) = message.strip_prefix("DNS ") {
+        return match rcode {
+            "SERVFAIL" => Some(
+                "the resolver couldn't complete the lookups, check its upstream servers and the \
+                 zone's name servers",
+            ),
+            "REFUSED" => Some(
+                "the resolver refused the queries, check that it allows recursion for this client",
+            ),
+            _ => None,
+        };
+    }
+    let status: u16 = message.strip_prefix("status ")?.parse().ok()?;
+    match status {
+        429 => Some("the target is rate limiting, lower --concurrency or raise its limits"),
+        401 | 403 => Some("requests are not authorized, check the credentials and headers"),
+        404 if *protocol == Protocol::S3 => Some(
+            "the objects don't exist, upload them first with --s3-op put over the same --s3-keys",
+        ),
+        404 => Some("the path doesn't exist on the target, check the URL"),
+        500..=599 => Some("the server is failing under load, check its logs"),
+        _ => None,
+    }
+}
+
+/// The most specific part of an error chain, e.g. "Connection refused (os error 111)"
+fn short_error(message: &str) -> &str {
+    message.rsplit(": ").next().unwrap_or(message)
+}
+
+fn client_hints(stats: &LoadTestStats, hints: &mut Vec<String>) {
+    let Some(client) = stats.client.as_ref().filter(|c| c.saturated) else {
+        return;
+    };
+    hints.push(format!(
+        "The load generator used {:.0}% of its {} cores at peak: it may have been the \
+         bottleneck, so the latency and throughput above understate the server. Lower \
+         --concurrency, or run from several machines and combine them with rustyload merge",
+        client.peak_cpu_share(),
+        client.cores
+    ));
+}
+
+fn latency_hints(stats: &LoadTestStats, config: &LoadTestConfig, hints: &mut Vec<String>) {
+    if stats.p50 > 0 && stats.p99 >= stats.p50 * TAIL_RATIO {
+        hints.push(format!(
+            "p99 ({} ms) is {}x the median ({} ms): a few requests are much slower, \
+             look for GC pauses, lock contention or queueing on the server",
+            stats.p99,
+            stats.p99 / stats.p50,
+            stats.p50
+        ));
+    }
+
+    if config.queues_requests() && stats.avg_queue_time > stats.avg_latency {
+        let fix = match config.protocol {
+            Protocol::FlashKV | Protocol::Redis => "lower --pipeline",
+            _ => "raise --max-connections",
+        };
+        hints.push(format!(
+            "Requests waited longer in the client queue ({:.2} ms) than on the server ({:.2} ms): {}",
+            stats.avg_queue_time, stats.avg_latency, fix
+        ));
+    }
+
+    let uncached = config
+        .http_config
+        .as_ref()
+        .is_some_and(|c| c.dns_cache == DnsCache::Off);
+    let dns_time = stats.custom_metrics.iter().find_map(|m| match m {
+        CustomMetricSummary::Timer {
+            name, count, avg, ..
+        } if name == DNS_LOOKUP => Some((*count, *count as f64 * avg)),
+        _ => None,
+    });
+    let request_time = stats.total_requests as f64 * stats.avg_latency;
+    if let Some((lookups, dns_time)) = dns_time.filter(|_| uncached && request_time > 0.0) {
+        let share = dns_time / request_time * 100.0;
+        if share >= DNS_SHARE {
+            hints.push(format!(
+                "DNS lookups took {:.0}% of the total request time ({} lookups): use \
+                 --dns-cache once to resolve the target once, unless DNS is part of what you test",
+                share, lookups
+            ));
+        }
+    }
+
+    if matches!(config.protocol, Protocol::FlashKV | Protocol::Redis) {
+        let pipelined = config
+            .flashkv_config
+            .as_ref()
+            .is_some_and(|c| c.pipeline_depth > 1);
+        let connections = stats.custom_metrics.iter().find_map(|m| match m {
+            CustomMetricSummary::Counter { name, total } if name == CONNECTIONS_OPENED => {
+                Some(*total)
+            }
+            _ => None,
+        });
+
+        let connect_time = stats.custom_metrics.iter().find_map(|m| match m {
+            CustomMetricSummary::Timer { name, avg, .. } if name == flashkv::CONNECT_TIME => {
+                Some(*avg)
+            }
+            _ => None,
+        });
+
+        if !pipelined && connections.is_some_and(|c| c >= stats.total_requests) {
+            let setup = match connect_time {
+                Some(avg) if avg > stats.avg_latency => format!(
+                    ", taking longer to connect ({:.2} ms) than to run the command ({:.2} ms)",
+                    avg, stats.avg_latency
+                ),
+                _ => String::new(),
+            };
+            hints.push(format!(
+                "Every command opened a new connection{}: use --pipeline to reuse connections",
+                setup
+            ));
+        }
+    }
+}
+
+fn backend_hints(stats: &LoadTestStats, hints: &mut Vec<String>) {
+    let Some(best) = stats
+        .by_backend
+        .iter()
+        .map(|b| b.error_rate())
+        .min_by(|a, b| a.total_cmp(b))
+    else {
+        return;
+    };
+
+    for backend in &stats.by_backend {
+        if backend.error_rate() - best >= 10.0 {
+            hints.push(format!(
+                "Backend {} failed {:.1}% of requests vs {:.1}% for the healthiest: \
+                 it is likely unhealthy behind DNS round-robin",
+                backend.name,
+                backend.error_rate(),
+                best
+            ));
+        }
+    }
+}
+
+fn worker_hints(stats: &LoadTestStats, hints: &mut Vec<String>) {
+    let Some(workers) = &stats.workers else {
+        return;
+    };
+    let Some(p99_without_slow) = workers.p99_without_slow else {
+        return;
+    };
+
+    let slowest = workers
+        .workers
+        .iter()
+        .find(|w| w.name == workers.slow[0])
+        .map_or(0, |w| w.p99);
+    let which = match workers.slow.len() {
+        1 => format!("Worker {} had", workers.slow[0]),
+        n => format!("{} workers had", n),
+    };
+    hints.push(format!(
+        "{} a p99 of up to {} ms against a median of {} ms across workers ({} ms without them): \
+         the slowness is confined to a few workers, so suspect the client (CPU, stalled \
+         connections) or the backend their connections reached rather than the whole server",
+        which, slowest, workers.median_p99, p99_without_slow
+    ));
+}
+
+fn keyspace_hints(stats: &LoadTestStats, hints: &mut Vec<String>) {
+    let Some(keyspace) = &stats.keyspace else {
+        return;
+    };
+    let (Some(first), Some(last)) = (keyspace.intervals.first(), keyspace.intervals.last()) else {
+        return;
+    };
+
+    if first.hit_ratio - last.hit_ratio >= HIT_RATIO_DROP {
+        hints.push(format!(
+            "The hit ratio fell from {:.1}% to {:.1}% during the run: keys are being evicted \
+             or expiring, check the server's memory limit and eviction policy",
+            first.hit_ratio, last.hit_ratio
+        ));
+    }
+}
+
+fn stream_hints(stats: &LoadTestStats, hints: &mut Vec<String>) {
+    let Some(stream) = &stats.stream else {
+        return;
+    };
+    if stream.dropped > 0 {
+        hints.push(format!(
+            "{} of {} event streams were closed before the subscriber was done: check the idle \
+             timeouts of proxies and load balancers in front of the server, and its connection limits",
+            stream.dropped, stream.subscribers
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{
+        ErrorCount, GroupStats, KeyspaceInterval, KeyspaceStats, StreamStats, WorkerStats,
+    };
+    use crate::resources::ClientStats;
+
+    fn http_config() -> LoadTestConfig {
+        LoadTestConfig::new_http("http://localhost".to_string(), 1000, 10)
+    }
+
+    fn stats() -> LoadTestStats {
+        LoadTestStats {
+            total_requests: 1000,
+            successful_requests: 1000,
+            p50: 10,
+            p99: 20,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_healthy_run_has_no_hints() {
+        assert!(analyze(&stats(), &http_config()).is_empty());
+    }
+
+    #[test]
+    fn test_error_hints() {
+        let stats = LoadTestStats {
+            successful_requests: 900,
+            failed_requests: 100,
+            errors: vec![
+                ErrorCount {
+                    kind: "other".to_string(),
+                    message: "error sending request: tcp connect error: Too many open files"
+                        .to_string(),
+                    count: 95,
+                },
+                ErrorCount {
+                    kind: STATUS_ERROR.to_string(),
+                    message: "status 429".to_string(),
+                    count: 5,
+                },
+            ],
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].starts_with("95% of errors are 'Too many open files'"));
+        assert!(hints[0].contains("ulimit"));
+    }
+
+    #[test]
+    fn test_status_hint() {
+        let stats = LoadTestStats {
+            failed_requests: 10,
+            errors: vec![ErrorCount {
+                kind: STATUS_ERROR.to_string(),
+                message: "status 429".to_string(),
+                count: 10,
+            }],
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert!(hints[0].starts_with("All errors are 'status 429'"));
+    }
+
+    #[test]
+    fn test_latency_and_backend_hints() {
+        let backend = |name: &str, failed| GroupStats {
+            name: name.to_string(),
+            total_requests: 100,
+            failed_requests: failed,
+            ..Default::default()
+        };
+        let stats = LoadTestStats {
+            p50: 5,
+            p99: 250,
+            by_backend: vec![backend("10.0.0.1", 0), backend("10.0.0.2", 40)],
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 2);
+        assert!(hints[0].contains("50x the median"));
+        assert!(hints[1].starts_with("Backend 10.0.0.2 failed 40.0%"));
+    }
+
+    #[test]
+    fn test_client_hint() {
+        let client = ClientStats {
+            cores: 4,
+            avg_cpu_percent: 300.0,
+            peak_cpu_percent: 380.0,
+            saturated: true,
+            ..Default::default()
+        };
+        let stats = LoadTestStats {
+            p50: 5,
+            p99: 250,
+            client: Some(client.clone()),
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 2);
+        assert!(hints[0].starts_with("The load generator used 95% of its 4 cores"));
+
+        let mut stats = stats;
+        stats.client = Some(ClientStats {
+            saturated: false,
+            ..client
+        });
+        assert_eq!(analyze(&stats, &http_config()).len(), 1);
+    }
+
+    #[test]
+    fn test_worker_hint() {
+        let worker = |name: &str, p99| GroupStats {
+            name: name.to_string(),
+            total_requests: 100,
+            successful_requests: 100,
+            p99,
+            ..Default::default()
+        };
+        let stats = LoadTestStats {
+            workers: Some(WorkerStats {
+                workers: vec![worker("0", 12), worker("1", 480), worker("2", 11)],
+                median_p99: 12,
+                slow: vec!["1".to_string()],
+                p99_without_slow: Some(12),
+            }),
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(
+            hints[0].starts_with("Worker 1 had a p99 of up to 480 ms against a median of 12 ms")
+        );
+    }
+
+    #[test]
+    fn test_flashkv_connection_hint() {
+        let config = LoadTestConfig::new_flashkv("localhost".to_string(), 6379, vec![], 1000, 10);
+        let stats = LoadTestStats {
+            custom_metrics: vec![CustomMetricSummary::Counter {
+                name: CONNECTIONS_OPENED.to_string(),
+                total: 1000,
+            }],
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &config);
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("--pipeline"));
+
+        let mut stats = stats;
+        stats.custom_metrics.push(CustomMetricSummary::Timer {
+            name: flashkv::CONNECT_TIME.to_string(),
+            count: 1000,
+            avg: 4.0,
+            p95: 6.0,
+            max: 9.0,
+        });
+        let hints = analyze(&stats, &config);
+        assert!(hints[0].contains("longer to connect (4.00 ms)"));
+    }
+
+    #[test]
+    fn test_dns_lookup_hint() {
+        let mut stats = LoadTestStats {
+            avg_latency: 10.0,
+            ..stats()
+        };
+        stats.custom_metrics.push(CustomMetricSummary::Timer {
+            name: DNS_LOOKUP.to_string(),
+            count: 1000,
+            avg: 2.0,
+            p95: 3.0,
+            max: 5.0,
+        });
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].starts_with("DNS lookups took 20% of the total request time"));
+
+        let mut config = http_config();
+        if let Some(http) = config.http_config.as_mut() {
+            http.dns_cache = DnsCache::Once;
+        }
+        assert!(analyze(&stats, &config).is_empty());
+    }
+
+    #[test]
+    fn test_keyspace_hint() {
+        let interval = |start_secs, hit_ratio| KeyspaceInterval {
+            start_secs,
+            hit_ratio,
+            ..Default::default()
+        };
+        let stats = LoadTestStats {
+            keyspace: Some(KeyspaceStats {
+                interval_secs: 10,
+                intervals: vec![interval(0, 95.0), interval(10, 80.0), interval(20, 40.0)],
+                ..Default::default()
+            }),
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].starts_with("The hit ratio fell from 95.0% to 40.0%"));
+    }
+
+    #[test]
+    fn test_dns_status_hint() {
+        let stats = LoadTestStats {
+            successful_requests: 900,
+            failed_requests: 100,
+            errors: vec![ErrorCount {
+                kind: STATUS_ERROR.to_string(),
+                message: "DNS SERVFAIL".to_string(),
+                count: 100,
+            }],
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].starts_with("All errors are 'DNS SERVFAIL': the resolver couldn't"));
+    }
+
+    #[test]
+    fn test_stream_hint() {
+        let stats = LoadTestStats {
+            stream: Some(StreamStats {
+                subscribers: 200,
+                dropped: 12,
+                ..Default::default()
+            }),
+            ..stats()
+        };
+
+        let hints = analyze(&stats, &http_config());
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].starts_with("12 of 200 event streams were closed"));
+    }
+}
@@ -0,0 +1,414 @@
+//! Run history stored in a local SQLite database
+//!
+//! Every run is recorded with its stats, a hash of its configuration and an
+//! optional label, so results can be browsed (`rustyload history`) and diffed
+//! (`rustyload compare <id> <id>`) over time.
+
+use crate::protocols::{LoadTestConfig, LoadTestStats, Startup};
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the default database location
+pub const HISTORY_ENV: &str = "RUSTYLOAD_HISTORY";
+
+/// Default database location: `$RUSTYLOAD_HISTORY`, else `~/.rustyload/history.db`
+pub fn default_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(HISTORY_ENV) {
+        return PathBuf::from(path);
+    }
+    data_dir().join("history.db")
+}
+
+/// `~/.rustyload`, where rustyload keeps its files
+pub(crate) fn data_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    home.join(".rustyload")
+}
+
+/// A stored run, without its full stats
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub id: i64,
+    /// UTC timestamp, `YYYY-MM-DD HH:MM:SS`
+    pub created_at: String,
+    pub label: Option<String>,
+    pub protocol: String,
+    pub target: String,
+    pub config_hash: String,
+    pub total_requests: u64,
+    pub p99: u128,
+    pub requests_per_second: f64,
+    pub error_rate: f64,
+}
+
+/// A stored run with its full stats
+#[derive(Debug)]
+pub struct RunRecord {
+    pub summary: RunSummary,
+    pub stats: LoadTestStats,
+}
+
+/// Handle to the history database
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open (and create if needed) the database at the given path
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    /// Open a throwaway in-memory database
+    #[cfg(test)]
+    fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS runs (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+                label       TEXT,
+                protocol    TEXT NOT NULL,
+                target      TEXT NOT NULL,
+                config_hash TEXT NOT NULL,
+                stats       TEXT NOT NULL
+            );",
+        )
+        .context("Failed to initialize history database")?;
+        Ok(Self { conn })
+    }
+
+    /// Record a finished run and return its id
+    pub fn record(
+        &self,
+        config: &LoadTestConfig,
+        stats: &LoadTestStats,
+        label: Option<&str>,
+    ) -> Result<i64> {
+        self.conn
+            .execute(
+                "INSERT INTO runs (label, protocol, target, config_hash, stats)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    label,
+                    config.protocol.display_name(),
+                    config.display_target(),
+                    config_hash(config),
+                    serde_json::to_string(stats)?,
+                ],
+            )
+            .context("Failed to record run in history")?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// List the most recent runs, newest first
+    pub fn list(&self, limit: usize) -> Result<Vec<RunSummary>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, created_at, label, protocol, target, config_hash, stats
+             FROM runs ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], read_row)?;
+
+        rows.map(|row| Ok(row?.summary))
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to read run history")
+    }
+
+    /// Fetch a single run by id
+    pub fn get(&self, id: i64) -> Result<RunRecord> {
+        let record = self
+            .conn
+            .query_row(
+                "SELECT id, created_at, label, protocol, target, config_hash, stats
+                 FROM runs WHERE id = ?1",
+                params![id],
+                read_row,
+            )
+            .optional()
+            .context("Failed to read run history")?;
+
+        match record {
+            Some(record) => Ok(record),
+            None => bail!("No run with id {} in history", id),
+        }
+    }
+}
+
+fn read_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RunRecord> {
+    let stats_json: String = row.get(6)?;
+    let stats: LoadTestStats = serde_json::from_str(&stats_json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+
+    let error_rate = if stats.total_requests > 0 {
+        stats.failed_requests as f64 / stats.total_requests as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    Ok(RunRecord {
+        summary: RunSummary {
+            id: row.get(0)?,
+            created_at: row.get(1)?,
+            label: row.get(2)?,
+            protocol: row.get(3)?,
+            target: row.get(4)?,
+            config_hash: row.get(5)?,
+            total_requests: stats.total_requests,
+            p99: stats.p99,
+            requests_per_second: stats.requests_per_second,
+            error_rate,
+        },
+        stats,
+    })
+}
+
+/// Stable hash of everything that shapes a run, so comparable runs can be spotted
+pub fn config_hash(config: &LoadTestConfig) -> String {
+    let mut canonical = format!(
+        "{:?}|{}|{}|{}",
+        config.protocol, config.num_requests, config.concurrency, config.timeout_secs
+    );
+    if !config.stages.is_empty() {
+        let _ = write!(canonical, "|{:?}", config.stages);
+    }
+    if config.startup != Startup::default() {
+        let _ = write!(canonical, "|startup{}", config.startup);
+    }
+    if let Some(hooks) = &config.hooks {
+        let _ = write!(canonical, "|hooks{}", hooks.name());
+    }
+    if let Some(http) = &config.http_config {
+        // Header order in a HashMap is random, so sort before hashing
+        let headers: BTreeMap<_, _> = http.headers.iter().collect();
+        let _ = write!(
+            canonical,
+            "|{}|{:?}|{:?}|{:?}",
+            http.url, http.method, headers, http.body
+        );
+        if http.spread_dns {
+            let _ = write!(canonical, "|spread{:?}", http.dns_weights);
+        }
+        // Only the user, so rotating a password doesn't start a new series
+        if let Some(user) = http.basic_auth_user() {
+            let _ = write!(canonical, "|basic{}", user);
+        }
+        if let Some(proxy) = &http.proxy {
+            let _ = write!(canonical, "|proxy{}", proxy);
+        }
+        for entry in &http.resolve {
+            let _ = write!(canonical, "|resolve{}", entry);
+        }
+        if let Some(max_connections) = http.max_connections {
+            let _ = write!(canonical, "|pool{}", max_connections);
+        }
+        if let Some(expect_status) = &http.expect_status {
+            let _ = write!(canonical, "|expect{}", expect_status);
+        }
+        for check in &http.checks {
+            let _ = write!(canonical, "|check{}", check);
+        }
+        for extraction in &http.extractions {
+            let _ = write!(canonical, "|extract{}", extraction);
+        }
+        for step in &http.steps {
+            let headers: BTreeMap<_, _> = step.headers.iter().collect();
+            let _ = write!(
+                canonical,
+                "|step{}|{:?}|{}|{:?}|{:?}",
+                step.name, step.method, step.url, headers, step.body
+            );
+            for check in &step.checks {
+                let _ = write!(canonical, "|check{}", check);
+            }
+            for extraction in &step.extractions {
+                let _ = write!(canonical, "|extract{}", extraction);
+            }
+        }
+        if let Some(list) = &http.url_list {
+            let _ = write!(
+                canonical,
+                "|urls{}|{}",
+                list.path.display(),
+                list.order.as_str()
+            );
+        }
+        if let Some(script) = &http.script {
+            let _ = write!(canonical, "|script{}", script.path.display());
+        }
+        if let Some(log) = &http.replay {
+            let _ = write!(canonical, "|replay{}|{:?}", log.path.display(), log.speed);
+        }
+        if let Some(data) = &http.data {
+            let _ = write!(
+                canonical,
+                "|data{}|{}",
+                data.path().display(),
+                data.order().as_str()
+            );
+        }
+        if let Some(accept_encoding) = &http.accept_encoding {
+            let _ = write!(canonical, "|enc{}", accept_encoding);
+        }
+        if !http.keepalive {
+            canonical.push_str("|nokeepalive");
+        }
+        if let Some(mode) = http.http2 {
+            let _ = write!(canonical, "|h2{}", mode.as_str());
+        }
+    }
+    if let Some(grpc) = &config.grpc_config {
+        let _ = write!(
+            canonical,
+            "|{}|{}|{:?}|{:?}",
+            grpc.url, grpc.method, grpc.message, grpc.metadata
+        );
+    }
+    if let Some(sse) = &config.sse_config {
+        let _ = write!(
+            canonical,
+            "|sse{}|{:?}|{:?}|{:?}",
+            sse.url, sse.headers, sse.hold, sse.max_events
+        );
+    }
+    if let Some(dns) = &config.dns_config {
+        let _ = write!(
+            canonical,
+            "|dns{}|{}|{}|{:?}",
+            dns.server, dns.name, dns.record_type, dns.transport
+        );
+    }
+    if let Some(udp) = &config.udp_config {
+        let _ = write!(
+            canonical,
+            "|udp{}|{:?}|{}|{:?}",
+            udp.target, udp.payload, udp.expect_reply, udp.packet_timeout
+        );
+    }
+    if let Some(nats) = &config.nats_config {
+        let _ = write!(
+            canonical,
+            "|nats{}|{}|{}|{}|{:?}",
+            nats.address(),
+            nats.subject,
+            nats.payload,
+            nats.mode.as_str(),
+            nats.user
+        );
+    }
+    if let Some(mysql) = &config.mysql_config {
+        let _ = write!(
+            canonical,
+            "|mysql{}|{:?}|{}|{}|{:?}|{}",
+            mysql.address(),
+            mysql.database,
+            mysql.user,
+            mysql.sql,
+            mysql.params,
+            mysql.reuse_statements
+        );
+    }
+    if let Some(s3) = &config.s3_config {
+        let _ = write!(
+            canonical,
+            "|s3{}|{}|{}|{}|{:?}|{:?}|{}|{}",
+            s3.endpoint,
+            s3.bucket,
+            s3.region,
+            s3.operations_label(),
+            s3.object_size,
+            s3.key_distribution,
+            s3.key_count,
+            s3.key_prefix
+        );
+    }
+    if let Some(ping) = &config.ping_config {
+        let _ = write!(canonical, "|ping{}|{}", ping.target, ping.mode.as_str());
+    }
+    if let Some(flashkv) = &config.flashkv_config {
+        let _ = write!(canonical, "|{:?}", flashkv);
+    }
+
+    format!("{:016x}", fnv1a(canonical.as_bytes()))
+}
+
+/// 64-bit FNV-1a, stable across Rust versions unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(p99: u128) -> LoadTestStats {
+        LoadTestStats {
+            total_requests: 100,
+            successful_requests: 98,
+            failed_requests: 2,
+            p99,
+            requests_per_second: 500.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_and_list() {
+        let history = History::open_in_memory().unwrap();
+        let config = LoadTestConfig::new_http("http://localhost".to_string(), 100, 10);
+
+        let first = history.record(&config, &stats(40), None).unwrap();
+        let second = history
+            .record(&config, &stats(55), Some("after cache"))
+            .unwrap();
+
+        let runs = history.list(10).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, second);
+        assert_eq!(runs[0].label.as_deref(), Some("after cache"));
+        assert_eq!(runs[1].id, first);
+        assert_eq!(runs[1].error_rate, 2.0);
+        assert_eq!(runs[0].config_hash, runs[1].config_hash);
+
+        assert_eq!(history.list(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get() {
+        let history = History::open_in_memory().unwrap();
+        let config = LoadTestConfig::new_http("http://localhost".to_string(), 100, 10);
+        let id = history.record(&config, &stats(40), None).unwrap();
+
+        let run = history.get(id).unwrap();
+        assert_eq!(run.stats.p99, 40);
+        assert_eq!(run.summary.target, "http://localhost");
+        assert!(history.get(id + 1).is_err());
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let config = LoadTestConfig::new_http("http://localhost".to_string(), 100, 10);
+        assert_eq!(config_hash(&config), config_hash(&config.clone()));
+
+        let other = LoadTestConfig::new_http("http://localhost".to_string(), 100, 20);
+        assert_ne!(config_hash(&config), config_hash(&other));
+    }
+}
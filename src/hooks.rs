@@ -0,0 +1,49 @@
+//! Callbacks around a run and its requests
+//!
+//! Embedding applications implement [`Hooks`] and set them with
+//! [`LoadTestConfig::with_hooks`]; on the command line `--hooks hooks.rhai`
+//! does the same with functions of a script (see [`ScriptHooks`](crate::script::ScriptHooks)).
+//! Hooks run on the request path, so they should return quickly.
+
+use crate::metrics::CustomMetrics;
+use crate::protocols::{LoadTestConfig, LoadTestStats, RequestResult};
+use std::fmt;
+
+/// A request about to be sent, which `before_request` may change
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HookRequest {
+    /// Position of the request's iteration in the run
+    pub seq: u64,
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Callbacks for a run; every one is optional
+pub trait Hooks: Send + Sync {
+    /// What the hooks are, for the config summary and run history
+    fn name(&self) -> String {
+        "hooks".to_string()
+    }
+
+    /// Before the first request, with the registry custom metrics can be
+    /// recorded in for the run's report
+    fn on_start(&self, _config: &LoadTestConfig, _metrics: &CustomMetrics) {}
+
+    /// Before every HTTP request is sent, e.g. to add headers
+    fn before_request(&self, _request: &mut HookRequest) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// After every request, with its result
+    fn after_response(&self, _result: &RequestResult) {}
+
+    /// After the last request, with the stats of the run
+    fn on_finish(&self, _stats: &LoadTestStats) {}
+}
+
+impl fmt::Debug for dyn Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hooks({})", self.name())
+    }
+}
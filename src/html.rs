@@ -0,0 +1,498 @@
+//! Self-contained HTML report
+//!
+//! A single file with inline styles and no external assets, so it can be
+//! attached to a ticket or archived as a CI artifact and still render.
+
+use crate::junit::escape;
+use crate::output;
+use crate::protocols::{format_bytes, GroupStats, LatencyWindow, LATENCY_WINDOW_SECS};
+use crate::report::Report;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Roboto, sans-serif; margin: 2rem auto; max-width: 960px; color: #1f2328; }
+h1 { margin-bottom: 0; }
+.subtitle { color: #656d76; margin-top: 0.25rem; }
+.cards { display: flex; gap: 1rem; flex-wrap: wrap; margin: 1.5rem 0; }
+.card { flex: 1; min-width: 140px; border: 1px solid #d0d7de; border-radius: 8px; padding: 1rem; }
+.card .value { font-size: 1.6rem; font-weight: 600; }
+.card .label { color: #656d76; font-size: 0.85rem; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 1.5rem; }
+th, td { text-align: left; padding: 0.4rem 0.75rem; border-bottom: 1px solid #d0d7de; }
+th { background: #f6f8fa; }
+.pass { color: #1a7f37; }
+.fail { color: #cf222e; font-weight: 600; }
+";
+
+/// Render the HTML report for a finished run
+pub fn render(report: &Report) -> String {
+    let stats = report.stats;
+    let success_rate = if stats.total_requests > 0 {
+        stats.successful_requests as f64 / stats.total_requests as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(
+        html,
+        "<title>RustyLoad report: {}</title>",
+        escape(report.target)
+    );
+    let _ = writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE);
+
+    let _ = writeln!(html, "<h1>RustyLoad report</h1>");
+    let _ = writeln!(
+        html,
+        "<p class=\"subtitle\">{} &middot; {}</p>",
+        escape(report.target),
+        escape(report.protocol)
+    );
+
+    // Headline numbers
+    html.push_str("<div class=\"cards\">\n");
+    let cards = [
+        ("Requests", stats.total_requests.to_string()),
+        ("Success rate", format!("{:.1}%", success_rate)),
+        ("Requests/sec", format!("{:.2}", stats.requests_per_second)),
+        ("p99", format!("{} ms", stats.p99)),
+    ];
+    for (label, value) in cards {
+        let _ = writeln!(
+            html,
+            "<div class=\"card\"><div class=\"value\">{}</div><div class=\"label\">{}</div></div>",
+            value, label
+        );
+    }
+    html.push_str("</div>\n");
+
+    // Latency
+    html.push_str("<h2>Latency</h2>\n<table>\n");
+    html.push_str(
+        "<tr><th>Min</th><th>Average</th><th>p50</th><th>p95</th><th>p99</th><th>Max</th></tr>\n",
+    );
+    let _ = writeln!(
+        html,
+        "<tr><td>{} ms</td><td>{:.2} ms</td><td>{} ms</td><td>{} ms</td><td>{} ms</td><td>{} ms</td></tr>",
+        stats.min_latency, stats.avg_latency, stats.p50, stats.p95, stats.p99, stats.max_latency
+    );
+    html.push_str("</table>\n");
+
+    if let Some(sla) = &stats.sla {
+        html.push_str("<h2>SLA</h2>\n");
+        if let Some(apdex) = &sla.apdex {
+            let _ = writeln!(
+                html,
+                "<p>Apdex (T = {} ms): <strong>{:.2}</strong> ({}), with {} satisfied, {} tolerating and {} frustrated requests.</p>",
+                apdex.threshold_ms,
+                apdex.score,
+                apdex.rating(),
+                apdex.satisfied,
+                apdex.tolerating,
+                apdex.frustrated
+            );
+        }
+        html.push_str("<table>\n<tr><th>Under</th><th>Requests</th></tr>\n");
+        for target in &sla.under {
+            let _ = writeln!(
+                html,
+                "<tr><td>{} ms</td><td>{:.2}%</td></tr>",
+                target.ms, target.percent
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if stats.latency_over_time.len() > 1 {
+        latency_chart(&mut html, &stats.latency_over_time);
+    }
+
+    let _ = writeln!(
+        html,
+        "<p>{} successful, {} failed, {} ms total.</p>",
+        stats.successful_requests, stats.failed_requests, stats.total_duration
+    );
+
+    if stats.peak_rps > 0.0 {
+        let _ = writeln!(
+            html,
+            "<p>Throughput: {:.2} req/s sustained, {:.0} req/s in the peak second, {:.0} req/s in the p5 second.</p>",
+            stats.requests_per_second, stats.peak_rps, stats.p5_rps
+        );
+    }
+
+    if !stats.http_versions.is_empty() {
+        let versions: Vec<String> = stats
+            .http_versions
+            .iter()
+            .map(|(version, count)| format!("{} ({})", version, count))
+            .collect();
+        let _ = writeln!(html, "<p>Served over: {}.</p>", versions.join(", "));
+    }
+
+    group_table(&mut html, "By step", &stats.by_step);
+    group_table(&mut html, "By tag", &stats.by_tag);
+    group_table(&mut html, "By operation", &stats.by_operation);
+    group_table(&mut html, "By backend", &stats.by_backend);
+    group_table(&mut html, "By HTTP version", &stats.by_http_version);
+    group_table(&mut html, "By gRPC status", &stats.by_grpc_status);
+
+    if let Some(transfer) = &stats.transfer {
+        let _ = write!(
+            html,
+            "<h2>Transfer</h2>\n<p>Received {} of response bodies ({} on average, {}/s)",
+            format_bytes(transfer.received_bytes),
+            format_bytes(transfer.avg_response_bytes.round() as u64),
+            format_bytes(transfer.bytes_per_second.round() as u64)
+        );
+        match transfer.compression_ratio() {
+            Some(ratio) => {
+                let _ = write!(
+                    html,
+                    ", {} decoded ({:.1}x)",
+                    format_bytes(transfer.decoded_bytes),
+                    ratio
+                );
+            }
+            None if transfer.decoded_bytes > 0 => {
+                let _ = write!(
+                    html,
+                    ", {} decoded where the size is known",
+                    format_bytes(transfer.decoded_bytes)
+                );
+            }
+            None => {}
+        }
+        let encodings: Vec<String> = transfer
+            .by_encoding
+            .iter()
+            .map(|(encoding, count)| format!("{} ({})", encoding, count))
+            .collect();
+        if !encodings.is_empty() {
+            let _ = write!(html, ". Encodings: {}", encodings.join(", "));
+        }
+        let _ = write!(html, ".");
+        if transfer.sent_bytes > 0 {
+            let _ = write!(
+                html,
+                " Uploaded {} of request bodies ({}/s).",
+                format_bytes(transfer.sent_bytes),
+                format_bytes(transfer.sent_bytes_per_second.round() as u64)
+            );
+        }
+        let _ = writeln!(html, "</p>");
+    }
+
+    if let Some(stream) = &stats.stream {
+        let _ = writeln!(
+            html,
+            "<p>Event stream: {} events ({:.1}/s, {:.2}/s per subscriber), {} of {} streams dropped, connected {:.1} s on average.</p>",
+            stream.events,
+            stream.events_per_second,
+            stream.events_per_subscriber_second,
+            stream.dropped,
+            stream.subscribers,
+            stream.avg_connected_secs
+        );
+    }
+
+    if let Some(dns) = &stats.dns {
+        let rcodes: Vec<String> = dns
+            .rcodes
+            .iter()
+            .map(|(rcode, count)| format!("{} {}", rcode, count))
+            .collect();
+        let _ = writeln!(
+            html,
+            "<p>DNS responses: {} ({:.1}% NXDOMAIN, {:.1}% SERVFAIL).</p>",
+            rcodes.join(", "),
+            dns.nxdomain_rate,
+            dns.servfail_rate
+        );
+    }
+
+    if let Some(datagrams) = &stats.datagrams {
+        let _ = writeln!(
+            html,
+            "<p>Datagrams: {} of {} got a reply, {} lost ({:.2}%).</p>",
+            datagrams.replies, datagrams.sent, datagrams.lost, datagrams.loss_rate
+        );
+    }
+
+    if let Some(client) = &stats.client {
+        let _ = writeln!(
+            html,
+            "<p>Client: {:.0}% CPU on average, {:.0}% at peak ({} cores), {} of memory, {} sockets at most{}.</p>",
+            client.avg_cpu_percent,
+            client.peak_cpu_percent,
+            client.cores,
+            format_bytes(client.peak_memory_bytes),
+            client.peak_sockets,
+            if client.saturated {
+                " <span class=\"fail\">(saturated)</span>"
+            } else {
+                ""
+            }
+        );
+    }
+
+    if let Some(keyspace) = &stats.keyspace {
+        let _ = writeln!(
+            html,
+            "<h2>Keyspace</h2>\n<p>Hit ratio {:.1}% ({} hits, {} misses).</p>",
+            keyspace.hit_ratio, keyspace.hits, keyspace.misses
+        );
+        if keyspace.intervals.len() > 1 {
+            html.push_str(
+                "<table>\n<tr><th>From</th><th>Hits</th><th>Misses</th><th>Hit ratio</th></tr>\n",
+            );
+            for interval in &keyspace.intervals {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{} s</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+                    interval.start_secs, interval.hits, interval.misses, interval.hit_ratio
+                );
+            }
+            html.push_str("</table>\n");
+        }
+    }
+
+    if !report.thresholds.is_empty() {
+        html.push_str("<h2>Thresholds</h2>\n<table>\n<tr><th>Threshold</th><th>Actual</th><th>Result</th></tr>\n");
+        for threshold in report.thresholds {
+            let (class, outcome) = if threshold.passed {
+                ("pass", "passed")
+            } else {
+                ("fail", "violated")
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{:.2}</td><td class=\"{}\">{}</td></tr>",
+                escape(&threshold.threshold),
+                threshold.actual,
+                class,
+                outcome
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.baseline.is_empty() {
+        html.push_str("<h2>Baseline comparison</h2>\n<table>\n<tr><th>Metric</th><th>Baseline</th><th>Current</th><th>Change</th></tr>\n");
+        for delta in report.baseline {
+            let change = delta
+                .change
+                .map(|c| format!("{:+.1}", c))
+                .unwrap_or_else(|| "n/a".to_string());
+            let class = if delta.regression { "fail" } else { "" };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{:.2}</td><td>{:.2}</td><td class=\"{}\">{}</td></tr>",
+                delta.metric, delta.baseline, delta.current, class, change
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !stats.custom_metrics.is_empty() {
+        html.push_str("<h2>Custom metrics</h2>\n<table>\n<tr><th>Metric</th><th>Value</th></tr>\n");
+        for metric in &stats.custom_metrics {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(metric.name()),
+                escape(&metric.display_value())
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !stats.errors.is_empty() {
+        html.push_str("<h2>Errors</h2>\n<table>\n<tr><th>Error</th><th>Count</th></tr>\n");
+        for error in &stats.errors {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(&error.message),
+                error.count
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.hints.is_empty() {
+        html.push_str("<h2>Hints</h2>\n<ul>\n");
+        for hint in report.hints {
+            let _ = writeln!(html, "<li>{}</li>", escape(hint));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// An inline SVG chart of p50/p95/p99 over the windows of the run
+fn latency_chart(html: &mut String, windows: &[LatencyWindow]) {
+    const WIDTH: f64 = 900.0;
+    const HEIGHT: f64 = 180.0;
+    const LEFT: f64 = 48.0;
+    const BOTTOM: f64 = 20.0;
+
+    let max = windows.iter().map(|w| w.p99).max().unwrap_or(0).max(1) as f64;
+    let last = (windows.len() - 1) as f64;
+    let x = |i: usize| LEFT + (WIDTH - LEFT - 8.0) * i as f64 / last;
+    let y = |ms: u128| 8.0 + (HEIGHT - BOTTOM - 8.0) * (1.0 - ms as f64 / max);
+
+    let _ = writeln!(
+        html,
+        "<h2>Latency over time</h2>\n<p>p50 (green), p95 (amber) and p99 (red) per {} s window.</p>",
+        LATENCY_WINDOW_SECS
+    );
+    let _ = writeln!(
+        html,
+        "<svg viewBox=\"0 0 {} {}\" width=\"100%\" role=\"img\" font-size=\"11\" fill=\"#656d76\">",
+        WIDTH, HEIGHT
+    );
+    for ms in [0, max as u128 / 2, max as u128] {
+        let _ = writeln!(
+            html,
+            "<line x1=\"{}\" x2=\"{}\" y1=\"{:.1}\" y2=\"{:.1}\" stroke=\"#d0d7de\"/><text x=\"4\" y=\"{:.1}\">{} ms</text>",
+            LEFT,
+            WIDTH - 8.0,
+            y(ms),
+            y(ms),
+            y(ms) + 4.0,
+            ms
+        );
+    }
+    let _ = writeln!(
+        html,
+        "<text x=\"{}\" y=\"{}\">{} s</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{} s</text>",
+        LEFT,
+        HEIGHT - 4.0,
+        windows[0].start_secs,
+        WIDTH - 8.0,
+        HEIGHT - 4.0,
+        windows[windows.len() - 1].start_secs
+    );
+    let series = [
+        ("#1a7f37", windows.iter().map(|w| w.p50).collect::<Vec<_>>()),
+        ("#9a6700", windows.iter().map(|w| w.p95).collect()),
+        ("#cf222e", windows.iter().map(|w| w.p99).collect()),
+    ];
+    for (color, values) in series {
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(i, &ms)| format!("{:.1},{:.1}", x(i), y(ms)))
+            .collect();
+        let _ = writeln!(
+            html,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            points.join(" ")
+        );
+    }
+    html.push_str("</svg>\n");
+}
+
+fn group_table(html: &mut String, title: &str, groups: &[GroupStats]) {
+    if groups.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(html, "<h2>{}</h2>", title);
+    html.push_str("<table>\n<tr><th>Name</th><th>Requests</th><th>Error rate</th><th>Average</th><th>p50</th><th>p95</th><th>p99</th></tr>\n");
+    for group in groups {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.1}%</td><td>{:.2} ms</td><td>{} ms</td><td>{} ms</td><td>{} ms</td></tr>",
+            escape(&group.name),
+            group.total_requests,
+            group.error_rate(),
+            group.avg_latency,
+            group.p50,
+            group.p95,
+            group.p99
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// Write the HTML report to a file, zstd-compressed if `compress` is set,
+/// returning the path written
+pub fn write_report(path: &Path, compress: bool, report: &Report) -> Result<PathBuf> {
+    output::write_file(path, compress, render(report))
+        .with_context(|| format!("Failed to write HTML report to {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{ErrorCount, LoadTestStats};
+    use crate::thresholds::ThresholdResult;
+
+    #[test]
+    fn test_render() {
+        let stats = LoadTestStats {
+            total_requests: 200,
+            successful_requests: 150,
+            failed_requests: 50,
+            p99: 321,
+            errors: vec![ErrorCount {
+                kind: "other".to_string(),
+                message: "status 503 <Service Unavailable>".to_string(),
+                count: 50,
+            }],
+            ..Default::default()
+        };
+        let thresholds = vec![ThresholdResult {
+            threshold: "error_rate>1%".to_string(),
+            actual: 25.0,
+            passed: false,
+        }];
+        let report = Report {
+            target: "https://example.com/?a=1&b=2",
+            protocol: "HTTP/HTTPS",
+            stats: &stats,
+            thresholds: &thresholds,
+            baseline: &[],
+            hints: &[],
+        };
+
+        let html = render(&report);
+        assert!(html.contains("<title>RustyLoad report: https://example.com/?a=1&amp;b=2</title>"));
+        assert!(html.contains("<div class=\"value\">75.0%</div>"));
+        assert!(html.contains("<td>321 ms</td>"));
+        assert!(html.contains("status 503 &lt;Service Unavailable&gt;"));
+        assert!(html.contains("<td class=\"fail\">violated</td>"));
+        assert!(!html.contains("<h2>Hints</h2>"));
+        assert!(!html.contains("<svg"));
+
+        let over_time = LoadTestStats {
+            latency_over_time: vec![
+                LatencyWindow {
+                    start_secs: 0,
+                    p99: 100,
+                    ..Default::default()
+                },
+                LatencyWindow {
+                    start_secs: 5,
+                    p99: 200,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let html = render(&Report {
+            stats: &over_time,
+            ..report
+        });
+        assert!(html.contains("<h2>Latency over time</h2>"));
+        assert_eq!(html.matches("<polyline").count(), 3);
+    }
+}
@@ -0,0 +1,120 @@
+//! A small JSONPath subset for checks and extraction
+//!
+//! Paths start at the root `$` and step through object keys (`.key` or
+//! `['key']`) and array indexes (`[0]`, or `[-1]` for the last element), e.g.
+//! `$.data.items[0].id`. Wildcards, slices and filters aren't supported since a
+//! check or extraction needs exactly one value.
+
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+/// A parsed path such as `$.data.items[0].id`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    expr: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid JSON path '{}': {}", s, reason);
+        let mut rest = s
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("must start with $"))?;
+
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid("empty key"));
+                }
+                segments.push(Segment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+                let inner = after[..end].trim();
+                let quoted = ['\'', '"'].iter().find_map(|quote| {
+                    inner
+                        .strip_prefix(*quote)
+                        .and_then(|key| key.strip_suffix(*quote))
+                });
+                segments.push(match quoted {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(
+                        inner
+                            .parse()
+                            .map_err(|_| invalid("expected an index or a quoted key in [...]"))?,
+                    ),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected . or [ after a step"));
+            }
+        }
+
+        Ok(Self {
+            expr: s.trim().to_string(),
+            segments,
+        })
+    }
+
+    /// The value at this path, if there is one
+    pub fn find<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+        self.segments
+            .iter()
+            .try_fold(root, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => {
+                    let array = value.as_array()?;
+                    let index = if *index < 0 {
+                        array.len().checked_sub(index.unsigned_abs() as usize)?
+                    } else {
+                        *index as usize
+                    };
+                    array.get(index)
+                }
+            })
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_find() {
+        let body = json!({
+            "status": "healthy",
+            "data": {"items": [{"id": 1}, {"id": 2}], "odd key": true}
+        });
+        let find = |path: &str| JsonPath::parse(path).unwrap().find(&body).cloned();
+
+        assert_eq!(find("$.status"), Some(json!("healthy")));
+        assert_eq!(find("$.data.items[1].id"), Some(json!(2)));
+        assert_eq!(find("$.data.items[-1].id"), Some(json!(2)));
+        assert_eq!(find("$['data']['odd key']"), Some(json!(true)));
+        assert_eq!(find("$"), Some(body.clone()));
+        assert_eq!(find("$.data.items[5]"), None);
+        assert_eq!(find("$.missing.key"), None);
+
+        assert!(JsonPath::parse("status").is_err());
+        assert!(JsonPath::parse("$.items[").is_err());
+        assert!(JsonPath::parse("$..id").is_err());
+        assert!(JsonPath::parse("$.items[*]").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! JUnit XML report output
+//!
+//! Produces a report that CI test reporters (Jenkins, GitLab, GitHub Actions)
+//! understand: one test case for the run itself plus one per threshold.
+
+use crate::output;
+use crate::protocols::LoadTestStats;
+use crate::thresholds::ThresholdResult;
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Render the JUnit XML document for a finished run
+pub fn render(target: &str, stats: &LoadTestStats, thresholds: &[ThresholdResult]) -> String {
+    let run_failed = stats.successful_requests == 0;
+    let tests = 1 + thresholds.len();
+    let failures = thresholds.iter().filter(|t| !t.passed).count() + usize::from(run_failed);
+    let time = stats.total_duration as f64 / 1000.0;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"rustyload\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        tests, failures, time
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">",
+        escape(target),
+        tests,
+        failures,
+        time
+    );
+
+    // Key stats as suite properties
+    xml.push_str("    <properties>\n");
+    let properties = [
+        ("total_requests", stats.total_requests.to_string()),
+        ("successful_requests", stats.successful_requests.to_string()),
+        ("failed_requests", stats.failed_requests.to_string()),
+        ("avg_latency_ms", format!("{:.2}", stats.avg_latency)),
+        ("p50_ms", stats.p50.to_string()),
+        ("p95_ms", stats.p95.to_string()),
+        ("p99_ms", stats.p99.to_string()),
+        (
+            "requests_per_second",
+            format!("{:.2}", stats.requests_per_second),
+        ),
+    ];
+    for (name, value) in properties {
+        let _ = writeln!(
+            xml,
+            "      <property name=\"{}\" value=\"{}\"/>",
+            name, value
+        );
+    }
+    for metric in &stats.custom_metrics {
+        let _ = writeln!(
+            xml,
+            "      <property name=\"custom.{}\" value=\"{}\"/>",
+            escape(metric.name()),
+            escape(&metric.display_value())
+        );
+    }
+    xml.push_str("    </properties>\n");
+
+    // The run itself fails only when nothing succeeded
+    let _ = write!(
+        xml,
+        "    <testcase classname=\"rustyload\" name=\"load test\" time=\"{:.3}\"",
+        time
+    );
+    if run_failed {
+        xml.push_str(">\n");
+        let _ = writeln!(
+            xml,
+            "      <failure type=\"run\" message=\"{}\"/>",
+            escape(&format!("All {} requests failed", stats.total_requests))
+        );
+        xml.push_str("    </testcase>\n");
+    } else {
+        xml.push_str("/>\n");
+    }
+
+    for threshold in thresholds {
+        let _ = write!(
+            xml,
+            "    <testcase classname=\"rustyload.thresholds\" name=\"{}\" time=\"0\"",
+            escape(&threshold.threshold)
+        );
+        if threshold.passed {
+            xml.push_str("/>\n");
+        } else {
+            xml.push_str(">\n");
+            let message = format!(
+                "{} violated (actual: {:.2})",
+                threshold.threshold, threshold.actual
+            );
+            let _ = writeln!(
+                xml,
+                "      <failure type=\"threshold\" message=\"{}\"/>",
+                escape(&message)
+            );
+            xml.push_str("    </testcase>\n");
+        }
+    }
+
+    xml.push_str("  </testsuite>\n");
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Write the JUnit XML report to a file, zstd-compressed if `compress` is
+/// set, returning the path written
+pub fn write_report(
+    path: &Path,
+    compress: bool,
+    target: &str,
+    stats: &LoadTestStats,
+    thresholds: &[ThresholdResult],
+) -> Result<PathBuf> {
+    output::write_file(path, compress, render(target, stats, thresholds))
+        .with_context(|| format!("Failed to write JUnit report to {}", path.display()))
+}
+
+/// Escape a string for use in an XML attribute or HTML text
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{calculate_stats, RequestResult};
+
+    fn stats(success: bool) -> LoadTestStats {
+        let results = vec![RequestResult {
+            duration: 120,
+            status: if success { 200 } else { 0 },
+            success,
+            error: None,
+            queue_time: 0,
+            operation: None,
+            backend: None,
+            completed_at: None,
+            cache_hit: None,
+            http_version: None,
+            body: None,
+            step: None,
+            tag: None,
+            grpc_status: None,
+            stream: None,
+            dns_rcode: None,
+            lost: None,
+            sent: None,
+            worker: None,
+        }];
+        calculate_stats(&results, 1500)
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("p99>500ms"), "p99&gt;500ms");
+        assert_eq!(escape("a & \"b\""), "a &amp; &quot;b&quot;");
+    }
+
+    #[test]
+    fn test_render_with_thresholds() {
+        let thresholds = vec![
+            ThresholdResult {
+                threshold: "p99>500ms".to_string(),
+                actual: 120.0,
+                passed: true,
+            },
+            ThresholdResult {
+                threshold: "p50>100ms".to_string(),
+                actual: 120.0,
+                passed: false,
+            },
+        ];
+        let xml = render("https://example.com?a=1&b=2", &stats(true), &thresholds);
+
+        assert!(xml.contains("tests=\"3\" failures=\"1\""));
+        assert!(xml.contains("name=\"https://example.com?a=1&amp;b=2\""));
+        assert!(xml.contains("name=\"p99&gt;500ms\" time=\"0\"/>"));
+        assert!(xml.contains("message=\"p50&gt;100ms violated (actual: 120.00)\""));
+        assert!(xml.contains("time=\"1.500\""));
+    }
+
+    #[test]
+    fn test_render_all_failed() {
+        let xml = render("localhost:6379", &stats(false), &[]);
+        assert!(xml.contains("tests=\"1\" failures=\"1\""));
+        assert!(xml.contains("All 1 requests failed"));
+    }
+}
@@ -0,0 +1,46 @@
+//! RustyLoad as a library
+//!
+//! The `rustyload` binary is a thin CLI over these modules, and applications
+//! can embed the same load tests. Build a [`protocols::LoadTestConfig`] and
+//! either `run` it to completion or `start` it in the background to receive
+//! each [`protocols::RequestResult`] as it completes and stop the test early.
+
+pub mod ab;
+pub mod baseline;
+pub mod checks;
+pub mod config;
+pub mod curl;
+pub mod dashboard;
+pub mod data;
+pub mod duration;
+pub mod env;
+pub mod eventlog;
+pub mod extract;
+mod fake;
+pub mod hints;
+pub mod history;
+pub mod hooks;
+pub mod html;
+mod jsonpath;
+pub mod junit;
+pub mod merge;
+pub mod metrics;
+pub mod multipart;
+pub mod output;
+pub mod profile;
+pub mod proto;
+pub mod protocols;
+pub mod reach;
+pub mod replay;
+pub mod report;
+pub mod resources;
+pub mod script;
+pub mod serve;
+pub mod size;
+pub mod sla;
+pub mod snapshot;
+pub mod sweep;
+mod template;
+pub mod thresholds;
+pub mod webhook;
+pub mod wrk;
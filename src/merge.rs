@@ -0,0 +1,678 @@
+//! Merging the results of several runs into one report
+//!
+//! Runs of the same workload from several machines (or several workers on
+//! one) are combined as if they had been a single run: counts are summed,
+//! latency percentiles come from the combined latency histograms, and
+//! throughput is summed on the assumption that the runs overlapped in time.
+//! Groups and latency windows are combined the same way, by name and by start
+//! time. Custom timers, and groups from results written before they carried a
+//! histogram, only have summaries to go on, so theirs are request-weighted
+//! averages rather than exact values.
+
+use crate::metrics::CustomMetricSummary;
+use crate::output;
+use crate::protocols::{
+    compare_workers, histogram_percentile, percentile, DatagramStats, DnsStats, ErrorCount,
+    GroupStats, LatencyWindow, LoadTestStats, Protocol, StreamStats, TransferStats, WorkerStats,
+    MAX_ERRORS,
+};
+use crate::resources::ClientStats;
+use crate::sla;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The JSON results of one run, as written by `--json` or quiet mode
+#[derive(Debug)]
+pub struct RunResults {
+    pub target: String,
+    pub protocol: Protocol,
+    pub stats: LoadTestStats,
+}
+
+/// The fields of a results file besides the stats
+#[derive(Deserialize)]
+struct Header {
+    target: String,
+    protocol: String,
+}
+
+/// Load the results of a run
+pub fn load(path: &Path) -> Result<RunResults> {
+    let content = output::read_to_string(path)
+        .with_context(|| format!("Failed to read results {}", path.display()))?;
+    let parse_error = || format!("Failed to parse results {}", path.display());
+    let header: Header = serde_json::from_str(&content).with_context(parse_error)?;
+    let stats: LoadTestStats = serde_json::from_str(&content).with_context(parse_error)?;
+
+    // Reports show the display name, e.g. "HTTP/HTTPS" or "Redis (RESP)"
+    let name = header.protocol.split(['/', ' ']).next().unwrap_or_default();
+    let protocol = name
+        .parse::<Protocol>()
+        .map_err(anyhow::Error::msg)
+        .with_context(parse_error)?;
+
+    if stats.successful_requests > 0 && stats.latency_histogram.is_empty() {
+        bail!(
+            "{} has no latency histogram to merge; it was written by an older version of rustyload",
+            path.display()
+        );
+    }
+    Ok(RunResults {
+        target: header.target,
+        protocol,
+        stats,
+    })
+}
+
+/// Combine the results of several runs of the same protocol
+pub fn merge(runs: &[RunResults]) -> Result<RunResults> {
+    let Some(first) = runs.first() else {
+        bail!("No results to merge");
+    };
+    if let Some(other) = runs.iter().find(|r| r.protocol != first.protocol) {
+        bail!(
+            "Can't merge {} results with {} results",
+            first.protocol.display_name(),
+            other.protocol.display_name()
+        );
+    }
+
+    let mut targets: Vec<&str> = Vec::new();
+    for run in runs {
+        if !targets.contains(&run.target.as_str()) {
+            targets.push(&run.target);
+        }
+    }
+    let stats: Vec<&LoadTestStats> = runs.iter().map(|r| &r.stats).collect();
+    Ok(RunResults {
+        target: targets.join(", "),
+        protocol: first.protocol.clone(),
+        stats: merge_stats(&stats),
+    })
+}
+
+/// Combine the stats of runs that overlapped in time
+pub fn merge_stats(runs: &[&LoadTestStats]) -> LoadTestStats {
+    let mut merged = LoadTestStats::default();
+    for run in runs {
+        merged.total_requests += run.total_requests;
+        merged.successful_requests += run.successful_requests;
+        merged.failed_requests += run.failed_requests;
+        merged.total_duration = merged.total_duration.max(run.total_duration);
+        merged.requests_per_second += run.requests_per_second;
+        merged.peak_rps += run.peak_rps;
+        merged.p5_rps += run.p5_rps;
+        merged.max_queue_time = merged.max_queue_time.max(run.max_queue_time);
+        add_counts(&mut merged.latency_histogram, &run.latency_histogram);
+        add_counts(&mut merged.http_versions, &run.http_versions);
+        add_counts(&mut merged.errors_by_kind, &run.errors_by_kind);
+        // Seconds counted from the start of each run
+        if merged.rps_series.len() < run.rps_series.len() {
+            merged.rps_series.resize(run.rps_series.len(), 0);
+        }
+        for (merged, count) in merged.rps_series.iter_mut().zip(&run.rps_series) {
+            *merged += count;
+        }
+    }
+    if !merged.rps_series.is_empty() {
+        let mut per_second = merged.rps_series.clone();
+        per_second.sort_unstable();
+        merged.peak_rps = per_second.last().copied().unwrap_or(0) as f64;
+        merged.p5_rps = percentile(&per_second, 5.0) as f64;
+    }
+
+    // Latencies are only recorded for successful requests
+    let answered = || runs.iter().filter(|r| r.successful_requests > 0);
+    merged.min_latency = answered().map(|r| r.min_latency).min().unwrap_or(0);
+    merged.max_latency = answered().map(|r| r.max_latency).max().unwrap_or(0);
+    merged.avg_latency = weighted(runs.iter().map(|r| (r.avg_latency, r.successful_requests)));
+    merged.avg_queue_time = weighted(
+        runs.iter()
+            .map(|r| (r.avg_queue_time, r.successful_requests)),
+    );
+    merged.p50 = histogram_percentile(&merged.latency_histogram, 50.0);
+    merged.p95 = histogram_percentile(&merged.latency_histogram, 95.0);
+    merged.p99 = histogram_percentile(&merged.latency_histogram, 99.0);
+
+    merged.custom_metrics = merge_metrics(runs);
+    merged.by_operation = merge_groups(runs, |r| &r.by_operation);
+    merged.by_backend = merge_groups(runs, |r| &r.by_backend);
+    merged.by_http_version = merge_groups(runs, |r| &r.by_http_version);
+    merged.by_tag = merge_groups(runs, |r| &r.by_tag);
+    merged.by_grpc_status = merge_groups(runs, |r| &r.by_grpc_status);
+    merged.by_step = merge_groups(runs, |r| &r.by_step);
+    merged.errors = merge_errors(runs);
+    merged.latency_over_time = merge_windows(runs);
+    merged.workers = merge_workers(runs);
+    merged.client = merge_each(
+        runs,
+        |r| r.client.as_ref(),
+        |m, c: &ClientStats| {
+            // The machines' usage side by side, as if they were one machine
+            m.cores += c.cores;
+            m.avg_cpu_percent += c.avg_cpu_percent;
+            m.peak_cpu_percent += c.peak_cpu_percent;
+            m.peak_memory_bytes += c.peak_memory_bytes;
+            m.peak_sockets += c.peak_sockets;
+            m.saturated |= c.saturated;
+        },
+    );
+    // Measured against the Apdex threshold the runs were given, if any
+    let apdex_threshold = runs
+        .iter()
+        .find_map(|r| Some(r.sla.as_ref()?.apdex.as_ref()?.threshold_ms));
+    merged.sla = runs
+        .iter()
+        .any(|r| r.sla.is_some())
+        .then(|| sla::summarize(&merged, apdex_threshold));
+
+    merged.keyspace = merge_each(
+        runs,
+        |r| r.keyspace.as_ref(),
+        |m, k| {
+            m.hits += k.hits;
+            m.misses += k.misses;
+        },
+    )
+    .map(|mut keyspace| {
+        // Intervals of different runs don't line up, so only the total is kept
+        keyspace.hit_ratio = percent(keyspace.hits, keyspace.hits + keyspace.misses);
+        keyspace
+    });
+    merged.transfer = merge_each(
+        runs,
+        |r| r.transfer.as_ref(),
+        |m, t| {
+            m.received_bytes += t.received_bytes;
+            m.decoded_bytes += t.decoded_bytes;
+            m.undecoded_responses += t.undecoded_responses;
+            add_counts(&mut m.by_encoding, &t.by_encoding);
+            m.bytes_per_second += t.bytes_per_second;
+            m.sent_bytes += t.sent_bytes;
+            m.sent_bytes_per_second += t.sent_bytes_per_second;
+        },
+    )
+    .map(|mut transfer: TransferStats| {
+        // Every response read is counted under its encoding
+        let responses: u64 = transfer.by_encoding.values().sum();
+        if responses > 0 {
+            transfer.avg_response_bytes = transfer.received_bytes as f64 / responses as f64;
+        }
+        transfer
+    });
+    merged.stream = merge_each(
+        runs,
+        |r| r.stream.as_ref(),
+        |m, s| {
+            m.subscribers += s.subscribers;
+            m.events += s.events;
+            m.events_per_second += s.events_per_second;
+            m.dropped += s.dropped;
+            // Summed per subscriber here, averaged below
+            m.events_per_subscriber_second += s.events_per_subscriber_second * s.subscribers as f64;
+            m.avg_connected_secs += s.avg_connected_secs * s.subscribers as f64;
+        },
+    )
+    .map(|mut stream: StreamStats| {
+        if stream.subscribers > 0 {
+            stream.events_per_subscriber_second /= stream.subscribers as f64;
+            stream.avg_connected_secs /= stream.subscribers as f64;
+        }
+        stream
+    });
+    merged.dns = merge_each(
+        runs,
+        |r| r.dns.as_ref(),
+        |m, d| {
+            m.answered += d.answered;
+            add_counts(&mut m.rcodes, &d.rcodes);
+        },
+    )
+    .map(|mut dns: DnsStats| {
+        let count = |rcode: &str| dns.rcodes.get(rcode).copied().unwrap_or_default();
+        dns.nxdomain_rate = percent(count("NXDOMAIN"), dns.answered);
+        dns.servfail_rate = percent(count("SERVFAIL"), dns.answered);
+        dns
+    });
+    merged.datagrams = merge_each(
+        runs,
+        |r| r.datagrams.as_ref(),
+        |m, d| {
+            m.sent += d.sent;
+            m.replies += d.replies;
+            m.lost += d.lost;
+        },
+    )
+    .map(|mut datagrams: DatagramStats| {
+        datagrams.loss_rate = percent(datagrams.lost, datagrams.sent);
+        datagrams
+    });
+    merged
+}
+
+/// Add the counts of `other` to `counts`
+fn add_counts<K: Ord + Clone>(counts: &mut BTreeMap<K, u64>, other: &BTreeMap<K, u64>) {
+    for (key, count) in other {
+        *counts.entry(key.clone()).or_default() += count;
+    }
+}
+
+/// `part` as a percentage of `total`
+fn percent(part: u64, total: u64) -> f64 {
+    if total > 0 {
+        part as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Average of `(value, weight)` pairs
+fn weighted(values: impl Iterator<Item = (f64, u64)>) -> f64 {
+    let (sum, weights) = values.fold((0.0, 0), |(sum, weights), (value, weight)| {
+        (sum + value * weight as f64, weights + weight)
+    });
+    if weights > 0 {
+        sum / weights as f64
+    } else {
+        0.0
+    }
+}
+
+/// Fold the stats some runs have into one, or `None` when no run has them
+fn merge_each<T: Default>(
+    runs: &[&LoadTestStats],
+    get: impl Fn(&LoadTestStats) -> Option<&T>,
+    add: impl Fn(&mut T, &T),
+) -> Option<T> {
+    let mut merged = None;
+    for stats in runs.iter().filter_map(|r| get(r)) {
+        add(merged.get_or_insert_with(T::default), stats);
+    }
+    merged
+}
+
+/// Combine breakdowns group by group
+fn merge_groups(
+    runs: &[&LoadTestStats],
+    groups: impl Fn(&LoadTestStats) -> &Vec<GroupStats>,
+) -> Vec<GroupStats> {
+    let mut by_name: BTreeMap<&str, Vec<&GroupStats>> = BTreeMap::new();
+    for group in runs.iter().flat_map(|r| groups(r)) {
+        by_name.entry(&group.name).or_default().push(group);
+    }
+
+    by_name
+        .into_iter()
+        .map(|(name, groups)| {
+            let average = |value: fn(&GroupStats) -> f64| {
+                weighted(groups.iter().map(|g| (value(g), g.successful_requests)))
+            };
+            let (latency_histogram, [p50, p95, p99]) = merge_percentiles(groups.iter().map(|g| {
+                (
+                    &g.latency_histogram,
+                    g.successful_requests,
+                    [g.p50, g.p95, g.p99],
+                )
+            }));
+            GroupStats {
+                name: name.to_string(),
+                total_requests: groups.iter().map(|g| g.total_requests).sum(),
+                successful_requests: groups.iter().map(|g| g.successful_requests).sum(),
+                failed_requests: groups.iter().map(|g| g.failed_requests).sum(),
+                avg_latency: average(|g| g.avg_latency),
+                p50,
+                p95,
+                p99,
+                latency_histogram,
+            }
+        })
+        .collect()
+}
+
+/// Combine the latency windows starting at the same second of each run
+fn merge_windows(runs: &[&LoadTestStats]) -> Vec<LatencyWindow> {
+    let mut by_start: BTreeMap<u64, Vec<&LatencyWindow>> = BTreeMap::new();
+    for window in runs.iter().flat_map(|r| &r.latency_over_time) {
+        by_start.entry(window.start_secs).or_default().push(window);
+    }
+
+    by_start
+        .into_iter()
+        .map(|(start_secs, windows)| {
+            let (latency_histogram, [p50, p95, p99]) = merge_percentiles(windows.iter().map(|w| {
+                (
+                    &w.latency_histogram,
+                    w.requests - w.failed,
+                    [w.p50, w.p95, w.p99],
+                )
+            }));
+            LatencyWindow {
+                start_secs,
+                requests: windows.iter().map(|w| w.requests).sum(),
+                failed: windows.iter().map(|w| w.failed).sum(),
+                p50,
+                p95,
+                p99,
+                latency_histogram,
+            }
+        })
+        .collect()
+}
+
+/// Put the workers of every run side by side, numbered by run when there
+/// are several, and compare them again
+fn merge_workers(runs: &[&LoadTestStats]) -> Option<WorkerStats> {
+    let with_workers: Vec<&WorkerStats> = runs.iter().filter_map(|r| r.workers.as_ref()).collect();
+    let workers = match with_workers.as_slice() {
+        [] => return None,
+        [only] => only.workers.clone(),
+        several => several
+            .iter()
+            .enumerate()
+            .flat_map(|(run, stats)| {
+                stats.workers.iter().map(move |worker| GroupStats {
+                    name: format!("{}.{}", run + 1, worker.name),
+                    ..worker.clone()
+                })
+            })
+            .collect(),
+    };
+    compare_workers(workers)
+}
+
+/// The combined histogram of several parts of a run and its p50, p95 and
+/// p99, from `(histogram, successful requests, percentiles)` of each part
+///
+/// Parts written without a histogram only have their percentiles, which are
+/// then averaged by request count instead.
+fn merge_percentiles<'a>(
+    parts: impl Iterator<Item = (&'a BTreeMap<u128, u64>, u64, [u128; 3])>,
+) -> (BTreeMap<u128, u64>, [u128; 3]) {
+    let parts: Vec<_> = parts.collect();
+    let mut histogram = BTreeMap::new();
+    for (part, _, _) in &parts {
+        add_counts(&mut histogram, part);
+    }
+    let complete = parts
+        .iter()
+        .all(|(part, successful, _)| part.values().sum::<u64>() == *successful);
+    let percentiles = if complete {
+        [50.0, 95.0, 99.0].map(|pct| histogram_percentile(&histogram, pct))
+    } else {
+        histogram.clear();
+        [0, 1, 2].map(|i| {
+            weighted(
+                parts
+                    .iter()
+                    .map(|(_, successful, p)| (p[i] as f64, *successful)),
+            )
+            .round() as u128
+        })
+    };
+    (histogram, percentiles)
+}
+
+/// Add up the most frequent errors of each run
+fn merge_errors(runs: &[&LoadTestStats]) -> Vec<ErrorCount> {
+    let mut errors: Vec<ErrorCount> = Vec::new();
+    for error in runs.iter().flat_map(|r| &r.errors) {
+        match errors
+            .iter_mut()
+            .find(|e| e.kind == error.kind && e.message == error.message)
+        {
+            Some(existing) => existing.count += error.count,
+            None => errors.push(error.clone()),
+        }
+    }
+    errors.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.message.cmp(&b.message))
+    });
+    errors.truncate(MAX_ERRORS);
+    errors
+}
+
+/// Combine custom metrics by name, keeping the first of a name used for
+/// metrics of different kinds
+fn merge_metrics(runs: &[&LoadTestStats]) -> Vec<CustomMetricSummary> {
+    let mut merged: Vec<CustomMetricSummary> = Vec::new();
+    for metric in runs.iter().flat_map(|r| &r.custom_metrics) {
+        let Some(existing) = merged.iter_mut().find(|m| m.name() == metric.name()) else {
+            merged.push(metric.clone());
+            continue;
+        };
+        match (existing, metric) {
+            (
+                CustomMetricSummary::Counter { total, .. },
+                CustomMetricSummary::Counter { total: other, .. },
+            ) => *total += other,
+            (
+                CustomMetricSummary::Gauge { last, min, max, .. },
+                CustomMetricSummary::Gauge {
+                    last: other_last,
+                    min: other_min,
+                    max: other_max,
+                    ..
+                },
+            ) => {
+                *last = *other_last;
+                *min = min.min(*other_min);
+                *max = max.max(*other_max);
+            }
+            (
+                CustomMetricSummary::Timer {
+                    count,
+                    avg,
+                    p95,
+                    max,
+                    ..
+                },
+                CustomMetricSummary::Timer {
+                    count: other_count,
+                    avg: other_avg,
+                    p95: other_p95,
+                    max: other_max,
+                    ..
+                },
+            ) => {
+                let pairs = |a: f64, b: f64| [(a, *count), (b, *other_count)].into_iter();
+                *avg = weighted(pairs(*avg, *other_avg));
+                *p95 = weighted(pairs(*p95, *other_p95));
+                *count += other_count;
+                *max = max.max(*other_max);
+            }
+            _ => {}
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{calculate_stats, ErrorKind, RequestResult};
+    use crate::report::Report;
+    use std::time::{Duration, Instant};
+
+    fn results(latencies: &[u128], failed: usize) -> Vec<RequestResult> {
+        let ok = latencies.iter().map(|&duration| RequestResult {
+            duration,
+            status: 200,
+            success: true,
+            ..Default::default()
+        });
+        let failed = (0..failed).map(|_| RequestResult {
+            error: Some(ErrorKind::ResponseTimeout),
+            ..Default::default()
+        });
+        ok.chain(failed).collect()
+    }
+
+    #[test]
+    fn test_merge_stats() {
+        let a = results(&[10, 12, 12, 15, 40], 1);
+        let b = results(&[8, 9, 30, 31, 32, 90, 200], 2);
+        let merged = merge_stats(&[&calculate_stats(&a, 1000), &calculate_stats(&b, 2000)]);
+
+        let all: Vec<RequestResult> = a.into_iter().chain(b).collect();
+        let single = calculate_stats(&all, 2000);
+        assert_eq!(merged.total_requests, 15);
+        assert_eq!(merged.failed_requests, 3);
+        assert_eq!(merged.total_duration, 2000);
+        assert_eq!(merged.requests_per_second, 6.0 + 4.5);
+        assert_eq!(merged.min_latency, single.min_latency);
+        assert_eq!(merged.max_latency, single.max_latency);
+        assert!((merged.avg_latency - single.avg_latency).abs() < 1e-9);
+        assert_eq!(
+            (merged.p50, merged.p95, merged.p99),
+            (single.p50, single.p95, single.p99)
+        );
+        assert_eq!(merged.errors, single.errors);
+        assert_eq!(merged.errors_by_kind, single.errors_by_kind);
+    }
+
+    #[test]
+    fn test_merge_stats_keeps_every_field() {
+        let origin = Instant::now();
+        // Requests of two workers in `step`, one completing per second
+        let run = |step: &str, latencies: &[u128]| -> Vec<RequestResult> {
+            latencies
+                .iter()
+                .enumerate()
+                .map(|(i, &duration)| RequestResult {
+                    duration,
+                    status: 200,
+                    success: true,
+                    completed_at: Some(
+                        origin + Duration::from_millis(duration as u64 + i as u64 * 1000),
+                    ),
+                    worker: Some(i as u64 % 2),
+                    step: Some(step.into()),
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let a: Vec<_> = run(
+            "login",
+            &[5, 8, 13, 21, 34, 55, 89, 144, 233, 377, 610, 987],
+        )
+        .into_iter()
+        .chain(run("order", &[10, 20, 30]))
+        .collect();
+        let b: Vec<_> = run("login", &[3, 6, 9, 12, 15, 18, 21, 24, 27, 30, 33, 36])
+            .into_iter()
+            .chain(run("order", &[40, 50]))
+            .collect();
+
+        let client = ClientStats {
+            cores: 4,
+            avg_cpu_percent: 50.0,
+            peak_cpu_percent: 100.0,
+            peak_memory_bytes: 1000,
+            peak_sockets: 10,
+            saturated: false,
+        };
+        let mut a_stats = calculate_stats(&a, 12000);
+        a_stats.client = Some(client.clone());
+        a_stats.sla = Some(sla::summarize(&a_stats, Some(20)));
+        let mut b_stats = calculate_stats(&b, 12000);
+        b_stats.client = Some(ClientStats {
+            saturated: true,
+            ..client
+        });
+        let merged = merge_stats(&[&a_stats, &b_stats]);
+
+        // The same requests measured as a single run
+        let all: Vec<RequestResult> = a.into_iter().chain(b).collect();
+        let single = calculate_stats(&all, 12000);
+        assert_eq!(merged.rps_series, single.rps_series);
+        assert_eq!(merged.peak_rps, single.peak_rps);
+        assert_eq!(merged.latency_over_time, single.latency_over_time);
+        assert_eq!(merged.by_step, single.by_step);
+        assert_eq!(merged.sla, Some(sla::summarize(&single, Some(20))));
+
+        let workers = merged.workers.unwrap();
+        let names: Vec<&str> = workers.workers.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, ["1.0", "1.1", "2.0", "2.1"]);
+        let first_of_b = &b_stats.workers.unwrap().workers[0];
+        assert_eq!(
+            workers.workers[2],
+            GroupStats {
+                name: "2.0".to_string(),
+                ..first_of_b.clone()
+            }
+        );
+
+        let client = merged.client.unwrap();
+        assert_eq!((client.cores, client.peak_sockets), (8, 20));
+        assert_eq!(client.avg_cpu_percent, 100.0);
+        assert!(client.saturated);
+    }
+
+    #[test]
+    fn test_merge_groups_without_histograms() {
+        // Results written before groups carried a histogram
+        let group = |p99, successful_requests| GroupStats {
+            name: "login".to_string(),
+            total_requests: successful_requests,
+            successful_requests,
+            p99,
+            ..Default::default()
+        };
+        let a = LoadTestStats {
+            by_step: vec![group(100, 1)],
+            ..Default::default()
+        };
+        let b = LoadTestStats {
+            by_step: vec![group(200, 3)],
+            ..Default::default()
+        };
+        let merged = merge_stats(&[&a, &b]).by_step;
+        assert_eq!((merged[0].p99, merged[0].total_requests), (175, 4));
+        assert!(merged[0].latency_histogram.is_empty());
+    }
+
+    #[test]
+    fn test_load_and_merge() {
+        let dir = std::env::temp_dir().join(format!("rustyload-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, target: &str, protocol: &Protocol, latencies: &[u128]| {
+            let path = dir.join(name);
+            let stats = calculate_stats(&results(latencies, 0), 1000);
+            let report = Report {
+                target,
+                protocol: protocol.display_name(),
+                stats: &stats,
+                thresholds: &[],
+                baseline: &[],
+                hints: &[],
+            };
+            report.write_json(&path, false).unwrap();
+            path
+        };
+
+        let a = write("a.json", "http://a", &Protocol::Http, &[5, 7]);
+        let b = write("b.json", "http://b", &Protocol::Http, &[9]);
+        let redis = write("redis.json", "localhost:6379", &Protocol::Redis, &[1]);
+
+        let runs = vec![load(&a).unwrap(), load(&b).unwrap(), load(&a).unwrap()];
+        assert_eq!(
+            runs[0].stats.latency_histogram,
+            BTreeMap::from([(5, 1), (7, 1)])
+        );
+        let merged = merge(&runs).unwrap();
+        assert_eq!(merged.target, "http://a, http://b");
+        assert_eq!(merged.protocol, Protocol::Http);
+        assert_eq!(merged.stats.successful_requests, 5);
+        assert_eq!(merged.stats.p50, 7);
+
+        let redis = load(&redis).unwrap();
+        assert_eq!(redis.protocol, Protocol::Redis);
+        assert!(merge(&[redis, load(&b).unwrap()]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+//! Custom metrics emitted during a run
+//!
+//! Protocol implementations (and later scripts and hooks) record named
+//! counters, gauges and timers through a cheap, cloneable [`CustomMetrics`]
+//! handle. At the end of the run the registry is summarized into
+//! [`LoadTestStats`](crate::protocols::LoadTestStats) so the values show up in
+//! every report and export alongside the built-in stats.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Counter of the connections opened during the run, recorded by every protocol
+pub const CONNECTIONS_OPENED: &str = "connections_opened";
+
+/// Raw values recorded for a single metric
+#[derive(Debug, Clone)]
+enum MetricValue {
+    Counter(u64),
+    Gauge { last: f64, min: f64, max: f64 },
+    Timer(Vec<f64>),
+}
+
+/// Thread-safe registry of custom metrics for one run
+#[derive(Debug, Clone, Default)]
+pub struct CustomMetrics {
+    values: Arc<Mutex<BTreeMap<String, MetricValue>>>,
+}
+
+impl CustomMetrics {
+    /// Add to a counter, creating it on first use
+    pub fn increment(&self, name: &str, by: u64) {
+        let mut values = self.values.lock().unwrap();
+        match values.get_mut(name) {
+            Some(MetricValue::Counter(total)) => *total += by,
+            // A name keeps the kind it was first recorded with
+            Some(_) => {}
+            None => {
+                values.insert(name.to_string(), MetricValue::Counter(by));
+            }
+        }
+    }
+
+    /// Set a gauge to its current value
+    pub fn gauge(&self, name: &str, value: f64) {
+        let mut values = self.values.lock().unwrap();
+        match values.get_mut(name) {
+            Some(MetricValue::Gauge { last, min, max }) => {
+                *last = value;
+                *min = min.min(value);
+                *max = max.max(value);
+            }
+            Some(_) => {}
+            None => {
+                values.insert(
+                    name.to_string(),
+                    MetricValue::Gauge {
+                        last: value,
+                        min: value,
+                        max: value,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Record a duration sample in milliseconds
+    pub fn time(&self, name: &str, millis: f64) {
+        let mut values = self.values.lock().unwrap();
+        match values.get_mut(name) {
+            Some(MetricValue::Timer(samples)) => samples.push(millis),
+            Some(_) => {}
+            None => {
+                values.insert(name.to_string(), MetricValue::Timer(vec![millis]));
+            }
+        }
+    }
+
+    /// Summarize every recorded metric, sorted by name
+    pub fn summarize(&self) -> Vec<CustomMetricSummary> {
+        let values = self.values.lock().unwrap();
+        values
+            .iter()
+            .map(|(name, value)| summarize_metric(name, value))
+            .collect()
+    }
+}
+
+fn summarize_metric(name: &str, value: &MetricValue) -> CustomMetricSummary {
+    let name = name.to_string();
+    match value {
+        MetricValue::Counter(total) => CustomMetricSummary::Counter {
+            name,
+            total: *total,
+        },
+        MetricValue::Gauge { last, min, max } => CustomMetricSummary::Gauge {
+            name,
+            last: *last,
+            min: *min,
+            max: *max,
+        },
+        MetricValue::Timer(samples) => {
+            let mut sorted = samples.clone();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let count = sorted.len();
+            let avg = sorted.iter().sum::<f64>() / count as f64;
+            let p95_index = ((count - 1) as f64 * 0.95).round() as usize;
+
+            CustomMetricSummary::Timer {
+                name,
+                count: count as u64,
+                avg,
+                p95: sorted[p95_index],
+                max: sorted[count - 1],
+            }
+        }
+    }
+}
+
+/// Aggregated value of a custom metric as it appears in reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CustomMetricSummary {
+    Counter {
+        name: String,
+        total: u64,
+    },
+    Gauge {
+        name: String,
+        last: f64,
+        min: f64,
+        max: f64,
+    },
+    Timer {
+        name: String,
+        count: u64,
+        avg: f64,
+        p95: f64,
+        max: f64,
+    },
+}
+
+impl CustomMetricSummary {
+    pub fn name(&self) -> &str {
+        match self {
+            CustomMetricSummary::Counter { name, .. }
+            | CustomMetricSummary::Gauge { name, .. }
+            | CustomMetricSummary::Timer { name, .. } => name,
+        }
+    }
+
+    /// Short human-readable value for terminal and report output
+    pub fn display_value(&self) -> String {
+        match self {
+            CustomMetricSummary::Counter { total, .. } => total.to_string(),
+            CustomMetricSummary::Gauge { last, min, max, .. } => {
+                format!("{:.2} (min {:.2}, max {:.2})", last, min, max)
+            }
+            CustomMetricSummary::Timer { avg, p95, .. } => {
+                format!("avg {:.2} ms, p95 {:.2} ms", avg, p95)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        let metrics = CustomMetrics::default();
+        metrics.increment("orders", 1);
+        metrics.clone().increment("orders", 2);
+
+        assert_eq!(
+            metrics.summarize(),
+            vec![CustomMetricSummary::Counter {
+                name: "orders".to_string(),
+                total: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_gauge_and_timer() {
+        let metrics = CustomMetrics::default();
+        metrics.gauge("queue", 5.0);
+        metrics.gauge("queue", 2.0);
+        metrics.gauge("queue", 8.0);
+        for ms in [10.0, 20.0, 30.0] {
+            metrics.time("checkout", ms);
+        }
+
+        let summary = metrics.summarize();
+        assert_eq!(
+            summary[0],
+            CustomMetricSummary::Timer {
+                name: "checkout".to_string(),
+                count: 3,
+                avg: 20.0,
+                p95: 30.0,
+                max: 30.0
+            }
+        );
+        assert_eq!(
+            summary[1],
+            CustomMetricSummary::Gauge {
+                name: "queue".to_string(),
+                last: 8.0,
+                min: 2.0,
+                max: 8.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_kind_mismatch_is_ignored() {
+        let metrics = CustomMetrics::default();
+        metrics.increment("items", 1);
+        metrics.gauge("items", 10.0);
+
+        assert_eq!(metrics.summarize()[0].display_value(), "1");
+    }
+}
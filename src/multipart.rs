@@ -0,0 +1,273 @@
+//! multipart/form-data request bodies
+//!
+//! Upload endpoints take form fields and files as multipart/form-data rather
+//! than a single payload. Parts are given curl-style: `name=value` for a
+//! field, `name=@path` for a file read from disk and `name=@blob:SIZE` for a
+//! file of SIZE random bytes, each file optionally followed by `;type=MIME`.
+//! The body is encoded once when the run starts and every request sends the
+//! same bytes, so large uploads don't cost a copy per request.
+
+use crate::size::parse_size;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use rand::Rng;
+use std::path::{Path, PathBuf};
+
+/// Prefix of a file part's value
+const FILE_PREFIX: char = '@';
+
+/// Prefix of a generated file's size, after [`FILE_PREFIX`]
+const BLOB_PREFIX: &str = "blob:";
+
+/// Separates a file from its content type
+const TYPE_SEPARATOR: &str = ";type=";
+
+/// Content type of files without one
+const DEFAULT_FILE_TYPE: &str = "application/octet-stream";
+
+/// One part of a form
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormPart {
+    pub name: String,
+    pub value: PartValue,
+}
+
+/// What a part holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartValue {
+    /// A plain form field
+    Text(String),
+    /// A file read from disk
+    File {
+        path: PathBuf,
+        content_type: Option<String>,
+    },
+    /// A file of random bytes
+    Blob {
+        size: u64,
+        content_type: Option<String>,
+    },
+}
+
+impl FormPart {
+    /// Parse `name=value`, `name=@path` or `name=@blob:SIZE`, the files
+    /// optionally followed by `;type=MIME`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, value) = s
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "Invalid form part '{}': expected NAME=VALUE, NAME=@FILE or NAME=@blob:SIZE",
+                    s
+                )
+            })?;
+        let name = name.trim().to_string();
+        let Some(file) = value.strip_prefix(FILE_PREFIX) else {
+            return Ok(Self {
+                name,
+                value: PartValue::Text(value.to_string()),
+            });
+        };
+        let (file, content_type) = match file.rsplit_once(TYPE_SEPARATOR) {
+            Some((file, content_type)) if !content_type.is_empty() => {
+                (file, Some(content_type.to_string()))
+            }
+            _ => (file, None),
+        };
+        if file.is_empty() {
+            return Err(format!("Invalid form part '{}': no file after @", s));
+        }
+        let value = match file.strip_prefix(BLOB_PREFIX) {
+            Some(size) => PartValue::Blob {
+                size: parse_size(size)?,
+                content_type,
+            },
+            None => PartValue::File {
+                path: PathBuf::from(file),
+                content_type,
+            },
+        };
+        Ok(Self { name, value })
+    }
+}
+
+impl std::fmt::Display for FormPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let content_type = match &self.value {
+            PartValue::Text(value) => return write!(f, "{}={}", self.name, value),
+            PartValue::File { path, content_type } => {
+                write!(f, "{}={}{}", self.name, FILE_PREFIX, path.display())?;
+                content_type
+            }
+            PartValue::Blob { size, content_type } => {
+                write!(f, "{}={}{}{}", self.name, FILE_PREFIX, BLOB_PREFIX, size)?;
+                content_type
+            }
+        };
+        match content_type {
+            Some(content_type) => write!(f, "{}{}", TYPE_SEPARATOR, content_type),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An encoded form, sent as the body of every request
+#[derive(Clone)]
+pub struct Multipart {
+    /// `multipart/form-data` with the boundary between the parts
+    pub content_type: String,
+    pub body: Bytes,
+}
+
+impl std::fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Multipart")
+            .field("content_type", &self.content_type)
+            .field("len", &self.body.len())
+            .finish()
+    }
+}
+
+impl Multipart {
+    /// Encode `parts`, reading their files and generating their blobs
+    pub fn encode(parts: &[FormPart]) -> Result<Self> {
+        let boundary = format!("rustyload-{:032x}", rand::rng().random::<u128>());
+        let mut body = Vec::new();
+        for part in parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            let name = quote(&part.name);
+            match &part.value {
+                PartValue::Text(value) => {
+                    body.extend_from_slice(
+                        format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name)
+                            .as_bytes(),
+                    );
+                    body.extend_from_slice(value.as_bytes());
+                }
+                PartValue::File { path, content_type } => {
+                    let data = std::fs::read(path).with_context(|| {
+                        format!(
+                            "Failed to read file {} for form part {}",
+                            path.display(),
+                            part.name
+                        )
+                    })?;
+                    file_header(&mut body, &name, &file_name(path), content_type);
+                    body.extend_from_slice(&data);
+                }
+                PartValue::Blob { size, content_type } => {
+                    file_header(&mut body, &name, "blob.bin", content_type);
+                    let start = body.len();
+                    body.resize(start + *size as usize, 0);
+                    rand::rng().fill(&mut body[start..]);
+                }
+            }
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        Ok(Self {
+            content_type: format!("multipart/form-data; boundary={}", boundary),
+            body: Bytes::from(body),
+        })
+    }
+}
+
+fn file_header(body: &mut Vec<u8>, name: &str, file_name: &str, content_type: &Option<String>) {
+    body.extend_from_slice(
+        format!(
+            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            name,
+            quote(file_name),
+            content_type.as_deref().unwrap_or(DEFAULT_FILE_TYPE)
+        )
+        .as_bytes(),
+    );
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || "file".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Escape a name for a quoted header parameter the way browsers do
+fn quote(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let field = FormPart::parse("title=Quarterly report").unwrap();
+        assert_eq!(field.value, PartValue::Text("Quarterly report".to_string()));
+
+        let file = FormPart::parse("upload=@./report.pdf;type=application/pdf").unwrap();
+        assert_eq!(
+            file.value,
+            PartValue::File {
+                path: PathBuf::from("./report.pdf"),
+                content_type: Some("application/pdf".to_string()),
+            }
+        );
+        let blob = FormPart::parse("upload=@blob:1MiB").unwrap();
+        assert_eq!(
+            blob.value,
+            PartValue::Blob {
+                size: 1 << 20,
+                content_type: None,
+            }
+        );
+
+        for part in [field, file, blob] {
+            assert_eq!(FormPart::parse(&part.to_string()).unwrap(), part);
+        }
+        assert!(FormPart::parse("no-equals").is_err());
+        assert!(FormPart::parse("=value").is_err());
+        assert!(FormPart::parse("upload=@").is_err());
+        assert!(FormPart::parse("upload=@blob:lots").is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        let path =
+            std::env::temp_dir().join(format!("rustyload-upload-{}.txt", std::process::id()));
+        std::fs::write(&path, "hello").unwrap();
+        let parts = [
+            FormPart::parse("title=Report").unwrap(),
+            FormPart::parse(&format!("doc=@{};type=text/plain", path.display())).unwrap(),
+            FormPart::parse("blob=@blob:16").unwrap(),
+        ];
+        let form = Multipart::encode(&parts).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let boundary = form
+            .content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        let body = &form.body[..];
+        let text = String::from_utf8_lossy(body);
+        assert!(text.starts_with(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nReport\r\n",
+            boundary
+        )));
+        assert!(text.contains(&format!(
+            "name=\"doc\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n",
+            path.file_name().unwrap().to_string_lossy()
+        )));
+        assert!(text.contains(
+            "name=\"blob\"; filename=\"blob.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        ));
+        assert!(text.ends_with(&format!("\r\n--{}--\r\n", boundary)));
+        assert_eq!(text.matches(&format!("--{}\r\n", boundary)).count(), 3);
+
+        let missing = [FormPart::parse("doc=@/nonexistent/rustyload.txt").unwrap()];
+        assert!(Multipart::encode(&missing).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Output file helpers shared by the result exporters
+//!
+//! Raw exports (the per-request event and error logs) can grow to many
+//! gigabytes on long runs, so every exporter opens its file through
+//! [`create_writer`], which compresses the stream on the fly with zstd under
+//! `--compress-output`. Results files read back (baselines, merges) are
+//! decompressed by [`read_to_string`] when their name ends in `.zst`.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// File extension appended to compressed outputs
+pub const COMPRESSED_EXTENSION: &str = "zst";
+
+/// zstd level used for on-the-fly compression (fast enough to keep up with the run)
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Resolve the final path of an output file, adding `.zst` when compressing
+pub fn output_path(path: &Path, compress: bool) -> PathBuf {
+    let already_compressed = path
+        .extension()
+        .is_some_and(|ext| ext == COMPRESSED_EXTENSION);
+
+    if compress && !already_compressed {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(COMPRESSED_EXTENSION);
+        PathBuf::from(name)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// A buffered export file, zstd-compressed or not
+///
+/// Call [`finish`](Self::finish) once everything is written: it flushes the
+/// buffer and ends the zstd frame, and an error there means the file is
+/// incomplete.
+pub struct OutputWriter(Inner);
+
+enum Inner {
+    Plain(BufWriter<File>),
+    Compressed(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputWriter {
+    /// Flush everything written and, when compressing, end the zstd frame
+    pub fn finish(self) -> Result<()> {
+        let mut file = match self.0 {
+            Inner::Plain(writer) => writer,
+            Inner::Compressed(encoder) => encoder
+                .finish()
+                .context("Failed to finish zstd compression")?,
+        };
+        file.flush().context("Failed to flush output file")
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Inner::Plain(writer) => writer.write(buf),
+            Inner::Compressed(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Inner::Plain(writer) => writer.flush(),
+            Inner::Compressed(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Create a buffered writer for an export file, optionally zstd-compressed
+pub fn create_writer(path: &Path, compress: bool) -> Result<OutputWriter> {
+    let path = output_path(path, compress);
+    let file = File::create(&path)
+        .with_context(|| format!("Failed to create output file {}", path.display()))?;
+    let writer = BufWriter::new(file);
+
+    if compress {
+        let encoder = zstd::Encoder::new(writer, COMPRESSION_LEVEL)
+            .context("Failed to initialize zstd compression")?;
+        Ok(OutputWriter(Inner::Compressed(encoder)))
+    } else {
+        Ok(OutputWriter(Inner::Plain(writer)))
+    }
+}
+
+/// Write a whole export through [`create_writer`], returning the path it
+/// ended up at
+pub fn write_file(path: &Path, compress: bool, contents: impl AsRef<[u8]>) -> Result<PathBuf> {
+    let mut writer = create_writer(path, compress)?;
+    writer.write_all(contents.as_ref())?;
+    writer.finish()?;
+    Ok(output_path(path, compress))
+}
+
+/// Read an export back, decompressing it if its name ends in `.zst`
+pub fn read_to_string(path: &Path) -> Result<String> {
+    let data = std::fs::read(path)?;
+    let data = if path
+        .extension()
+        .is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+    {
+        zstd::decode_all(data.as_slice()).context("Failed to decompress")?
+    } else {
+        data
+    };
+    String::from_utf8(data).context("Not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        let path = Path::new("run.ndjson");
+        assert_eq!(output_path(path, false), PathBuf::from("run.ndjson"));
+        assert_eq!(output_path(path, true), PathBuf::from("run.ndjson.zst"));
+        assert_eq!(
+            output_path(Path::new("run.ndjson.zst"), true),
+            PathBuf::from("run.ndjson.zst")
+        );
+    }
+
+    #[test]
+    fn test_compressed_writer_roundtrip() {
+        let path = std::env::temp_dir().join(format!("rustyload-{}.log", std::process::id()));
+
+        let mut writer = create_writer(&path, true).unwrap();
+        writeln!(writer, "first line").unwrap();
+        writeln!(writer, "second line").unwrap();
+        writer.finish().unwrap();
+
+        let compressed_path = output_path(&path, true);
+        let data = std::fs::read(&compressed_path).unwrap();
+        let decoded = zstd::decode_all(data.as_slice()).unwrap();
+        assert_eq!(decoded, b"first line\nsecond line\n");
+        assert_eq!(
+            read_to_string(&compressed_path).unwrap(),
+            "first line\nsecond line\n"
+        );
+        std::fs::remove_file(&compressed_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_file() {
+        let path = std::env::temp_dir().join(format!("rustyload-{}.json", std::process::id()));
+        for compress in [false, true] {
+            let written = write_file(&path, compress, "{}").unwrap();
+            assert_eq!(written, output_path(&path, compress));
+            assert_eq!(read_to_string(&written).unwrap(), "{}");
+            std::fs::remove_file(&written).unwrap();
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Test definitions saved from the interactive mode, and built-in templates
+//!
+//! Profiles are ordinary TOML test files kept in `~/.rustyload/profiles`, so a
+//! profile can also be run with `--config` or edited by hand, and any test file
+//! copied there is listed by the interactive mode. Templates are test files for
+//! common workloads that ship with rustyload, offered next to the profiles.
+
+use crate::config::{RandomKeys, TestFile};
+use crate::history;
+use crate::protocols::Stage;
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Environment variable overriding the default profile directory
+pub const PROFILES_ENV: &str = "RUSTYLOAD_PROFILES";
+
+/// Default profile directory: `$RUSTYLOAD_PROFILES`, else `~/.rustyload/profiles`
+pub fn default_dir() -> PathBuf {
+    match std::env::var_os(PROFILES_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => history::data_dir().join("profiles"),
+    }
+}
+
+/// A saved test definition
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// The file name without its extension
+    pub name: String,
+    pub path: PathBuf,
+    /// When the profile was last saved
+    pub modified: SystemTime,
+}
+
+impl Profile {
+    pub fn load(&self) -> Result<TestFile> {
+        TestFile::load(&self.path)
+    }
+}
+
+/// The profiles in `dir`, most recently saved first; none when the directory
+/// doesn't exist yet
+pub fn list(dir: &Path) -> Result<Vec<Profile>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read profiles in {}", dir.display()))
+        }
+    };
+
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let test_file = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("toml" | "yaml" | "yml")
+        );
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if !test_file || !path.is_file() {
+            continue;
+        }
+        profiles.push(Profile {
+            name: name.to_string(),
+            modified: path.metadata()?.modified()?,
+            path,
+        });
+    }
+    profiles.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.name.cmp(&b.name)));
+    Ok(profiles)
+}
+
+/// Check a profile name can be used as a file name
+pub fn check_name(name: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ');
+    if name.trim().is_empty() {
+        Err("The name can't be empty".to_string())
+    } else if name.starts_with('.') || !name.chars().all(allowed) {
+        Err("Use letters, digits, spaces, '-', '_' and '.' (not first)".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Save a test as profile `name` in `dir`, replacing any of the same name
+pub fn save(dir: &Path, name: &str, test: &TestFile) -> Result<PathBuf> {
+    check_name(name).map_err(|e| anyhow!(e))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.toml", name.trim()));
+    test.save(&path)?;
+    Ok(path)
+}
+
+/// A built-in starting point for a common workload
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub test: TestFile,
+}
+
+/// The built-in templates, pointed at a local server until changed
+pub fn templates() -> Vec<Template> {
+    let cache = |commands: Vec<&str>, value_size: &str| TestFile {
+        protocol: Some("redis".to_string()),
+        url: Some("localhost:6379".to_string()),
+        requests: Some(10_000),
+        concurrency: Some(50),
+        timeout: Some(5),
+        commands: commands.into_iter().map(str::to_string).collect(),
+        random_keys: Some(RandomKeys {
+            prefix: Some("key".to_string()),
+            range: Some(10_000),
+        }),
+        value_size: Some(value_size.to_string()),
+        thresholds: vec!["error_rate>1%".to_string()],
+        ..Default::default()
+    };
+
+    vec![
+        Template {
+            name: "REST API smoke",
+            description: "a few GETs to check an endpoint holds up",
+            test: TestFile {
+                protocol: Some("http".to_string()),
+                url: Some("http://localhost:8080/health".to_string()),
+                requests: Some(50),
+                concurrency: Some(5),
+                timeout: Some(10),
+                thresholds: vec!["error_rate>0%".to_string(), "p95>500ms".to_string()],
+                ..Default::default()
+            },
+        },
+        Template {
+            name: "Cache read-heavy 90/10",
+            description: "9 GETs for every SET over 10k preloaded keys",
+            test: TestFile {
+                preload: Some(10_000),
+                ..cache([vec!["GET key"; 9], vec!["SET key value"]].concat(), "256")
+            },
+        },
+        Template {
+            name: "Write-heavy ingestion",
+            description: "pipelined SETs of 512B-4KiB values",
+            test: TestFile {
+                pipeline: Some(8),
+                ..cache(vec!["SET key value"], "512-4KiB")
+            },
+        },
+        Template {
+            name: "Spike test",
+            description: "a quiet stage, a burst at 20x the concurrency, then quiet again",
+            test: TestFile {
+                protocol: Some("http".to_string()),
+                url: Some("http://localhost:8080/".to_string()),
+                timeout: Some(10),
+                stages: vec![
+                    Stage {
+                        requests: 500,
+                        concurrency: 5,
+                    },
+                    Stage {
+                        requests: 5_000,
+                        concurrency: 100,
+                    },
+                    Stage {
+                        requests: 500,
+                        concurrency: 5,
+                    },
+                ],
+                thresholds: vec!["error_rate>5%".to_string()],
+                ..Default::default()
+            },
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_save_and_list() {
+        let dir = std::env::temp_dir().join(format!("rustyload-profiles-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(list(&dir).unwrap().is_empty());
+
+        let test = TestFile {
+            protocol: Some("http".to_string()),
+            url: Some("https://api.example.com/orders".to_string()),
+            requests: Some(500),
+            method: Some("POST".to_string()),
+            headers: HashMap::from([("Accept".to_string(), "application/json".to_string())]),
+            body: Some(r#"{"id": 7}"#.to_string()),
+            ..Default::default()
+        };
+        let path = save(&dir, "orders api", &test).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a profile").unwrap();
+
+        let profiles = list(&dir).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "orders api");
+        assert_eq!(profiles[0].path, path);
+
+        let (config, _) = profiles[0].load().unwrap().into_config().unwrap();
+        assert_eq!(config.num_requests, 500);
+        let http = config.http_config.unwrap();
+        assert_eq!(http.url, "https://api.example.com/orders");
+        assert_eq!(http.body.as_deref(), Some(r#"{"id": 7}"#));
+        assert_eq!(http.headers["Accept"], "application/json");
+
+        assert!(save(&dir, "../escape", &test).is_err());
+        assert!(save(&dir, " ", &test).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_templates() {
+        for template in templates() {
+            let (config, thresholds) = template.test.into_config().unwrap();
+            assert!(config.num_requests > 0, "{}", template.name);
+            assert!(!thresholds.is_empty(), "{}", template.name);
+        }
+    }
+}
@@ -0,0 +1,955 @@
+//! Protocol Buffers schemas read from `.proto` files
+//!
+//! Parses the messages, enums and services of proto2 and proto3 files (and the
+//! local files they import), so gRPC requests can be written as JSON and sent
+//! as protobuf without generated code. JSON follows the proto3 JSON mapping:
+//! fields by name or lowerCamelCase name, 64-bit integers as numbers or
+//! strings, enums by name or number, bytes as base64 and maps as objects.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Scalar field types
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "double" => Scalar::Double,
+            "float" => Scalar::Float,
+            "int32" => Scalar::Int32,
+            "int64" => Scalar::Int64,
+            "uint32" => Scalar::Uint32,
+            "uint64" => Scalar::Uint64,
+            "sint32" => Scalar::Sint32,
+            "sint64" => Scalar::Sint64,
+            "fixed32" => Scalar::Fixed32,
+            "fixed64" => Scalar::Fixed64,
+            "sfixed32" => Scalar::Sfixed32,
+            "sfixed64" => Scalar::Sfixed64,
+            "bool" => Scalar::Bool,
+            "string" => Scalar::String,
+            "bytes" => Scalar::Bytes,
+            _ => return None,
+        })
+    }
+
+    fn wire_type(self) -> u8 {
+        match self {
+            Scalar::Double | Scalar::Fixed64 | Scalar::Sfixed64 => FIXED64,
+            Scalar::Float | Scalar::Fixed32 | Scalar::Sfixed32 => FIXED32,
+            Scalar::String | Scalar::Bytes => LEN,
+            _ => VARINT,
+        }
+    }
+}
+
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LEN: u8 = 2;
+const FIXED32: u8 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+enum FieldType {
+    Scalar(Scalar),
+    /// A message, by full name
+    Message(String),
+    /// An enum, by full name
+    Enum(String),
+    Map(Scalar, Box<FieldType>),
+    /// A type name not resolved yet, or not defined in the loaded files
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: String,
+    json_name: String,
+    number: u32,
+    repeated: bool,
+    kind: FieldType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Message {
+    fields: Vec<Field>,
+}
+
+/// A service method
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    /// `package.Service/Method`, as sent in the request path
+    pub path: String,
+    /// Full name of the request message
+    pub input: String,
+    pub output: String,
+    /// Whether the client or server sends a stream of messages
+    pub streaming: bool,
+}
+
+/// The messages, enums and services of a set of `.proto` files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProtoSchema {
+    pub path: PathBuf,
+    messages: HashMap<String, Message>,
+    /// Value names and numbers per enum
+    enums: HashMap<String, Vec<(String, i32)>>,
+    pub methods: Vec<Method>,
+}
+
+impl ProtoSchema {
+    /// Load a file and the files it imports, looked up next to it
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut schema = Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut pending = vec![path.to_path_buf()];
+        let mut loaded: Vec<PathBuf> = Vec::new();
+        while let Some(file) = pending.pop() {
+            if loaded.contains(&file) {
+                continue;
+            }
+            let text = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read proto file {}", file.display()))?;
+            let imports = schema
+                .parse_file(&text)
+                .with_context(|| format!("In proto file {}", file.display()))?;
+            loaded.push(file);
+            // Well-known types aren't bundled, so their imports are skipped and
+            // using them fails when a request is encoded
+            pending.extend(
+                imports
+                    .iter()
+                    .filter(|import| !import.starts_with("google/protobuf/"))
+                    .map(|import| root.join(import)),
+            );
+        }
+        schema.resolve_types();
+        Ok(schema)
+    }
+
+    /// Parse one file's definitions into the schema, returning its imports
+    fn parse_file(&mut self, text: &str) -> Result<Vec<String>> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let mut package = String::new();
+        let mut imports = Vec::new();
+        while let Some(token) = parser.next() {
+            match token.as_str() {
+                "syntax" | "edition" | "option" => parser.skip_statement()?,
+                "package" => {
+                    package = parser.ident()?;
+                    parser.expect(";")?;
+                }
+                "import" => {
+                    if matches!(parser.peek(), Some("public" | "weak")) {
+                        parser.next();
+                    }
+                    imports.push(parser.string()?);
+                    parser.expect(";")?;
+                }
+                "message" => self.parse_message(&mut parser, &package)?,
+                "enum" => self.parse_enum(&mut parser, &package)?,
+                "service" => self.parse_service(&mut parser, &package)?,
+                "extend" => parser.skip_statement()?,
+                ";" => {}
+                other => bail!("Unexpected '{}'", other),
+            }
+        }
+        Ok(imports)
+    }
+
+    fn parse_message(&mut self, parser: &mut Parser, scope: &str) -> Result<()> {
+        let name = qualify(scope, &parser.ident()?);
+        parser.expect("{")?;
+        let mut fields = Vec::new();
+        loop {
+            let token = parser.next().context("Unterminated message")?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "message" => self.parse_message(parser, &name)?,
+                "enum" => self.parse_enum(parser, &name)?,
+                "option" | "reserved" | "extensions" | "extend" => parser.skip_statement()?,
+                "oneof" => {
+                    parser.ident()?;
+                    parser.expect("{")?;
+                    loop {
+                        match parser.next().context("Unterminated oneof")?.as_str() {
+                            "}" => break,
+                            "option" => parser.skip_statement()?,
+                            kind => fields.push(parse_field(parser, kind, false)?),
+                        }
+                    }
+                }
+                "map" => {
+                    parser.expect("<")?;
+                    let key = parser.ident()?;
+                    let key = Scalar::from_name(&key)
+                        .filter(|key| {
+                            !matches!(key, Scalar::Double | Scalar::Float | Scalar::Bytes)
+                        })
+                        .ok_or_else(|| anyhow!("Invalid map key type '{}'", key))?;
+                    parser.expect(",")?;
+                    let value = field_type(&parser.ident()?);
+                    parser.expect(">")?;
+                    let mut field = parse_field(parser, "bytes", false)?;
+                    field.kind = FieldType::Map(key, Box::new(value));
+                    fields.push(field);
+                }
+                "repeated" => {
+                    let kind = parser.ident()?;
+                    fields.push(parse_field(parser, &kind, true)?);
+                }
+                "optional" | "required" => {
+                    let kind = parser.ident()?;
+                    fields.push(parse_field(parser, &kind, false)?);
+                }
+                "group" => bail!("Groups are not supported"),
+                kind => fields.push(parse_field(parser, kind, false)?),
+            }
+        }
+        self.messages.insert(name, Message { fields });
+        Ok(())
+    }
+
+    fn parse_enum(&mut self, parser: &mut Parser, scope: &str) -> Result<()> {
+        let name = qualify(scope, &parser.ident()?);
+        parser.expect("{")?;
+        let mut values = Vec::new();
+        loop {
+            let token = parser.next().context("Unterminated enum")?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" | "reserved" => parser.skip_statement()?,
+                value => {
+                    parser.expect("=")?;
+                    let number = parser.integer()?;
+                    let number = i32::try_from(number)
+                        .map_err(|_| anyhow!("Enum value {} is out of range", value))?;
+                    values.push((value.to_string(), number));
+                    parser.skip_statement()?;
+                }
+            }
+        }
+        self.enums.insert(name, values);
+        Ok(())
+    }
+
+    fn parse_service(&mut self, parser: &mut Parser, scope: &str) -> Result<()> {
+        let service = qualify(scope, &parser.ident()?);
+        parser.expect("{")?;
+        loop {
+            let token = parser.next().context("Unterminated service")?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" => parser.skip_statement()?,
+                "rpc" => {
+                    let name = parser.ident()?;
+                    let mut streaming = false;
+                    let mut message_type = |parser: &mut Parser| -> Result<String> {
+                        parser.expect("(")?;
+                        if parser.peek() == Some("stream") {
+                            parser.next();
+                            streaming = true;
+                        }
+                        let name = parser.ident()?;
+                        parser.expect(")")?;
+                        Ok(name)
+                    };
+                    let input = message_type(parser)?;
+                    parser.expect("returns")?;
+                    let output = message_type(parser)?;
+                    if parser.peek() == Some("{") {
+                        parser.skip_block()?;
+                    } else {
+                        parser.expect(";")?;
+                    }
+                    // Resolved along with the message fields
+                    self.methods.push(Method {
+                        path: format!("{}/{}", service, name),
+                        input: format!("{}|{}", scope, input),
+                        output: format!("{}|{}", scope, output),
+                        streaming,
+                    });
+                }
+                other => bail!("Unexpected '{}' in service {}", other, service),
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the type names fields and methods use with the full names of
+    /// the messages and enums they refer to
+    fn resolve_types(&mut self) {
+        let names: Vec<String> = self.messages.keys().cloned().collect();
+        for name in names {
+            let mut fields = self.messages[&name].fields.clone();
+            for field in &mut fields {
+                match &mut field.kind {
+                    FieldType::Map(_, value) => **value = self.resolve(&name, value),
+                    kind => *kind = self.resolve(&name, kind),
+                }
+            }
+            self.messages.get_mut(&name).unwrap().fields = fields;
+        }
+
+        let mut methods = std::mem::take(&mut self.methods);
+        for method in &mut methods {
+            for name in [&mut method.input, &mut method.output] {
+                let (scope, type_name) = name.split_once('|').unwrap_or(("", name.as_str()));
+                *name = match self.resolve(scope, &FieldType::Named(type_name.to_string())) {
+                    FieldType::Message(full) => full,
+                    _ => type_name.trim_start_matches('.').to_string(),
+                };
+            }
+        }
+        self.methods = methods;
+    }
+
+    fn resolve(&self, scope: &str, kind: &FieldType) -> FieldType {
+        let FieldType::Named(name) = kind else {
+            return kind.clone();
+        };
+        let candidates: Vec<String> = match name.strip_prefix('.') {
+            Some(absolute) => vec![absolute.to_string()],
+            // The innermost scope that defines the name wins
+            None => {
+                let parts: Vec<&str> = scope.split('.').filter(|p| !p.is_empty()).collect();
+                (0..=parts.len())
+                    .rev()
+                    .map(|depth| qualify(&parts[..depth].join("."), name))
+                    .collect()
+            }
+        };
+        for candidate in candidates {
+            if self.messages.contains_key(&candidate) {
+                return FieldType::Message(candidate);
+            }
+            if self.enums.contains_key(&candidate) {
+                return FieldType::Enum(candidate);
+            }
+        }
+        kind.clone()
+    }
+
+    /// Look up a method by `package.Service/Method`, or `Service/Method` when
+    /// the name is unambiguous
+    pub fn method(&self, name: &str) -> Result<&Method> {
+        let name = name.trim_start_matches('/');
+        let mut matches = self
+            .methods
+            .iter()
+            .filter(|method| method.path == name || method.path.ends_with(&format!(".{}", name)));
+        let Some(method) = matches.next() else {
+            let known: Vec<_> = self.methods.iter().map(|m| m.path.as_str()).collect();
+            bail!(
+                "No method '{}' in {}; it defines {}",
+                name,
+                self.path.display(),
+                if known.is_empty() {
+                    "no services".to_string()
+                } else {
+                    known.join(", ")
+                }
+            );
+        };
+        if matches.next().is_some() {
+            bail!("Method '{}' is ambiguous, include the package", name);
+        }
+        Ok(method)
+    }
+
+    /// Encode a JSON value as the protobuf message `message`
+    pub fn encode(&self, message: &str, value: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.encode_message(message, value, &mut out)?;
+        Ok(out)
+    }
+
+    fn encode_message(&self, name: &str, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        let message = self
+            .messages
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown message {}", name))?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Expected a JSON object for message {}", name))?;
+        let mut fields: Vec<(&Field, &str, &Value)> = Vec::with_capacity(object.len());
+        for (key, value) in object {
+            let field = message
+                .fields
+                .iter()
+                .find(|field| field.name == *key || field.json_name == *key)
+                .ok_or_else(|| anyhow!("Message {} has no field '{}'", name, key))?;
+            if !value.is_null() {
+                fields.push((field, key, value));
+            }
+        }
+        // In field number order, like other encoders
+        fields.sort_by_key(|(field, _, _)| field.number);
+        for (field, key, value) in fields {
+            self.encode_field(field, value, out)
+                .with_context(|| format!("In field {}.{}", name, key))?;
+        }
+        Ok(())
+    }
+
+    fn encode_field(&self, field: &Field, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        if let FieldType::Map(key_type, value_type) = &field.kind {
+            let object = value
+                .as_object()
+                .ok_or_else(|| anyhow!("Expected a JSON object for a map"))?;
+            for (key, value) in object {
+                let mut entry = Vec::new();
+                let key = match key_type {
+                    Scalar::String => Value::String(key.clone()),
+                    Scalar::Bool => Value::Bool(key == "true"),
+                    _ => Value::String(key.clone()),
+                };
+                self.encode_value(&FieldType::Scalar(*key_type), 1, &key, &mut entry)?;
+                self.encode_value(value_type, 2, value, &mut entry)?;
+                write_len(field.number, &entry, out);
+            }
+            return Ok(());
+        }
+        if !field.repeated {
+            return self.encode_value(&field.kind, field.number, value, out);
+        }
+
+        let items = value
+            .as_array()
+            .ok_or_else(|| anyhow!("Expected a JSON array for a repeated field"))?;
+        match &field.kind {
+            // Repeated numbers are packed
+            FieldType::Scalar(scalar) if scalar.wire_type() != LEN => {
+                let mut packed = Vec::new();
+                for item in items {
+                    write_scalar(*scalar, item, &mut packed)?;
+                }
+                write_len(field.number, &packed, out);
+            }
+            FieldType::Enum(name) => {
+                let mut packed = Vec::new();
+                for item in items {
+                    write_varint(self.enum_number(name, item)? as i64 as u64, &mut packed);
+                }
+                write_len(field.number, &packed, out);
+            }
+            kind => {
+                for item in items {
+                    self.encode_value(kind, field.number, item, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode one value with its tag
+    fn encode_value(
+        &self,
+        kind: &FieldType,
+        number: u32,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        match kind {
+            FieldType::Scalar(scalar) => {
+                write_tag(number, scalar.wire_type(), out);
+                write_scalar(*scalar, value, out)
+            }
+            FieldType::Enum(name) => {
+                write_tag(number, VARINT, out);
+                write_varint(self.enum_number(name, value)? as i64 as u64, out);
+                Ok(())
+            }
+            FieldType::Message(name) => {
+                let mut nested = Vec::new();
+                self.encode_message(name, value, &mut nested)?;
+                write_len(number, &nested, out);
+                Ok(())
+            }
+            FieldType::Map(..) => bail!("Maps can't be nested in maps"),
+            FieldType::Named(name) => bail!(
+                "Type {} isn't defined in the loaded proto files (well-known google.protobuf types aren't supported)",
+                name
+            ),
+        }
+    }
+
+    fn enum_number(&self, name: &str, value: &Value) -> Result<i32> {
+        if let Some(number) = value.as_i64() {
+            return i32::try_from(number).map_err(|_| anyhow!("{} is out of range", number));
+        }
+        let label = value
+            .as_str()
+            .ok_or_else(|| anyhow!("Expected a name or number for enum {}", name))?;
+        self.enums[name]
+            .iter()
+            .find(|(value, _)| value == label)
+            .map(|(_, number)| *number)
+            .ok_or_else(|| anyhow!("Enum {} has no value {}", name, label))
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn field_type(name: &str) -> FieldType {
+    match Scalar::from_name(name) {
+        Some(scalar) => FieldType::Scalar(scalar),
+        None => FieldType::Named(name.to_string()),
+    }
+}
+
+/// Parse `name = number [options];` after a field's type
+fn parse_field(parser: &mut Parser, kind: &str, repeated: bool) -> Result<Field> {
+    let name = parser.ident()?;
+    parser.expect("=")?;
+    let number = parser.integer()?;
+    let number = u32::try_from(number)
+        .ok()
+        .filter(|n| (1..1 << 29).contains(n))
+        .ok_or_else(|| anyhow!("Invalid field number {} for {}", number, name))?;
+    parser.skip_statement()?;
+    Ok(Field {
+        json_name: json_name(&name),
+        name,
+        number,
+        repeated,
+        kind: field_type(kind),
+    })
+}
+
+/// The lowerCamelCase name a field has in JSON
+fn json_name(name: &str) -> String {
+    let mut json = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            json.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            json.push(c);
+        }
+    }
+    json
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_tag(number: u32, wire_type: u8, out: &mut Vec<u8>) {
+    write_varint(((number as u64) << 3) | wire_type as u64, out);
+}
+
+fn write_len(number: u32, bytes: &[u8], out: &mut Vec<u8>) {
+    write_tag(number, LEN, out);
+    write_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+/// Write a scalar's value, without its tag
+fn write_scalar(scalar: Scalar, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match scalar {
+        Scalar::Int32 => write_varint(integer::<i32>(value)? as i64 as u64, out),
+        Scalar::Int64 => write_varint(integer::<i64>(value)? as u64, out),
+        Scalar::Uint32 => write_varint(integer::<u32>(value)? as u64, out),
+        Scalar::Uint64 => write_varint(integer::<u64>(value)?, out),
+        Scalar::Sint32 => {
+            let n = integer::<i32>(value)?;
+            write_varint(((n << 1) ^ (n >> 31)) as u32 as u64, out);
+        }
+        Scalar::Sint64 => {
+            let n = integer::<i64>(value)?;
+            write_varint(((n << 1) ^ (n >> 63)) as u64, out);
+        }
+        Scalar::Fixed32 => out.extend_from_slice(&integer::<u32>(value)?.to_le_bytes()),
+        Scalar::Fixed64 => out.extend_from_slice(&integer::<u64>(value)?.to_le_bytes()),
+        Scalar::Sfixed32 => out.extend_from_slice(&integer::<i32>(value)?.to_le_bytes()),
+        Scalar::Sfixed64 => out.extend_from_slice(&integer::<i64>(value)?.to_le_bytes()),
+        Scalar::Double => out.extend_from_slice(&float(value)?.to_le_bytes()),
+        Scalar::Float => out.extend_from_slice(&(float(value)? as f32).to_le_bytes()),
+        Scalar::Bool => {
+            let b = value
+                .as_bool()
+                .ok_or_else(|| anyhow!("Expected true or false, got {}", value))?;
+            out.push(b as u8);
+        }
+        Scalar::String => {
+            let s = value
+                .as_str()
+                .ok_or_else(|| anyhow!("Expected a string, got {}", value))?;
+            write_varint(s.len() as u64, out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Scalar::Bytes => {
+            let s = value
+                .as_str()
+                .ok_or_else(|| anyhow!("Expected a base64 string, got {}", value))?;
+            let bytes = base64_decode(s)?;
+            write_varint(bytes.len() as u64, out);
+            out.extend_from_slice(&bytes);
+        }
+    }
+    Ok(())
+}
+
+/// An integer given as a JSON number or string
+fn integer<T>(value: &Value) -> Result<T>
+where
+    T: TryFrom<i64> + TryFrom<u64> + std::str::FromStr,
+{
+    let parsed = match value {
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => T::try_from(i).ok(),
+            (None, Some(u)) => T::try_from(u).ok(),
+            (None, None) => None,
+        },
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| anyhow!("Expected an integer in range, got {}", value))
+}
+
+fn float(value: &Value) -> Result<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        },
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("Expected a number, got {}", value))
+}
+
+/// Decode standard or URL-safe base64, with or without padding
+fn base64_decode(s: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => bail!("Invalid base64 character '{}'", c),
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Split a `.proto` file into identifiers, numbers, strings and symbols
+fn tokenize(text: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => last = c,
+                        None => bail!("Unterminated comment"),
+                    }
+                }
+            }
+            '"' | '\'' => {
+                // Strings keep their quote so they can't be mistaken for identifiers
+                let mut s = String::from('"');
+                loop {
+                    match chars.next() {
+                        Some('\\') => s.extend(chars.next()),
+                        Some(q) if q == c => break,
+                        Some(c) => s.push(c),
+                        None => bail!("Unterminated string"),
+                    }
+                }
+                tokens.push(s);
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    let exponent_sign = (c == '-' || c == '+')
+                        && word.starts_with(|c: char| c.is_ascii_digit())
+                        && word.ends_with(['e', 'E']);
+                    if c.is_alphanumeric() || c == '_' || c == '.' || exponent_sign {
+                        word.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(word);
+            }
+            c => tokens.push(c.to_string()),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected '{}' but found '{}'", expected, token),
+            None => bail!("Expected '{}' but the file ended", expected),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            Some(token)
+                if token.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '.') =>
+            {
+                Ok(token)
+            }
+            Some(token) => bail!("Expected a name but found '{}'", token),
+            None => bail!("Expected a name but the file ended"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.next() {
+            Some(token) if token.starts_with('"') => Ok(token[1..].to_string()),
+            Some(token) => bail!("Expected a string but found '{}'", token),
+            None => bail!("Expected a string but the file ended"),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        let negative = self.peek() == Some("-");
+        if negative {
+            self.next();
+        }
+        let token = self
+            .next()
+            .context("Expected a number but the file ended")?;
+        let value = match token
+            .strip_prefix("0x")
+            .or_else(|| token.strip_prefix("0X"))
+        {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None if token.len() > 1 && token.starts_with('0') => {
+                i64::from_str_radix(&token[1..], 8)
+            }
+            None => token.parse(),
+        }
+        .map_err(|_| anyhow!("Expected a number but found '{}'", token))?;
+        Ok(if negative { -value } else { value })
+    }
+
+    /// Skip to the end of the current statement: past its `;`, or past a
+    /// `{ ... }` block ending it
+    fn skip_statement(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.next().context("Unterminated statement")?.as_str() {
+                ";" if depth == 0 => return Ok(()),
+                "[" | "(" => depth += 1,
+                "]" | ")" => depth -= 1,
+                "{" if depth == 0 => {
+                    self.pos -= 1;
+                    return self.skip_block();
+                }
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn skip_block(&mut self) -> Result<()> {
+        self.expect("{")?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next().context("Unterminated block")?.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PROTO: &str = r#"
+        syntax = "proto3";
+        package shop.v1;
+        import "google/protobuf/timestamp.proto";
+        option go_package = "example.com/shop;shop";
+
+        /* Orders */
+        message Order {
+            enum Status { UNKNOWN = 0; PAID = 1; SHIPPED = 2 [deprecated = true]; }
+            message Line { string sku = 1; int32 quantity = 2; }
+            int64 id = 1;
+            repeated Line lines = 2;
+            Status status = 3;
+            map<string, int32> counts = 4;
+            oneof payment { string card = 5; bytes token = 6; }
+            repeated sint32 deltas = 7 [packed = true];
+            double total = 8;
+            bool gift = 9;
+            google.protobuf.Timestamp placed_at = 10;
+        }
+
+        service Orders {
+            option (google.api.default_host) = "shop.example.com";
+            rpc Create (Order) returns (Order) { option (google.api.http) = { post: "/v1/orders" }; }
+            rpc Watch (Order) returns (stream Order);
+        }
+    "#;
+
+    fn schema() -> ProtoSchema {
+        let mut schema = ProtoSchema::default();
+        schema.parse_file(PROTO).unwrap();
+        schema.resolve_types();
+        schema
+    }
+
+    #[test]
+    fn test_parse_proto() {
+        let schema = schema();
+        let method = schema.method("Orders/Create").unwrap();
+        assert_eq!(method.path, "shop.v1.Orders/Create");
+        assert_eq!(method.input, "shop.v1.Order");
+        assert!(!method.streaming);
+        assert!(schema.method("shop.v1.Orders/Watch").unwrap().streaming);
+        assert!(schema.method("Orders/Delete").is_err());
+
+        let order = &schema.messages["shop.v1.Order"];
+        assert_eq!(order.fields.len(), 10);
+        assert_eq!(
+            order.fields[1].kind,
+            FieldType::Message("shop.v1.Order.Line".to_string())
+        );
+        assert_eq!(order.fields[9].json_name, "placedAt");
+        assert_eq!(
+            schema.enums["shop.v1.Order.Status"][2],
+            ("SHIPPED".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let schema = schema();
+        let bytes = schema
+            .encode(
+                "shop.v1.Order",
+                &json!({
+                    "id": "300",
+                    "lines": [{"sku": "a", "quantity": -1}],
+                    "status": "PAID",
+                    "counts": {"x": 2},
+                    "token": "AQI=",
+                    "deltas": [1, -2],
+                    "total": 1.5,
+                    "gift": true,
+                    "card": null
+                }),
+            )
+            .unwrap();
+        let mut expected = vec![0x08, 0xac, 0x02];
+        expected.extend([0x12, 0x0e, 0x0a, 0x01, b'a', 0x10]);
+        expected.extend([0xff; 9]);
+        expected.push(0x01);
+        expected.extend([0x18, 0x01]);
+        expected.extend([0x22, 0x05, 0x0a, 0x01, b'x', 0x10, 0x02]);
+        expected.extend([0x32, 0x02, 0x01, 0x02]);
+        expected.extend([0x3a, 0x02, 0x02, 0x03]);
+        expected.push(0x41);
+        expected.extend(1.5f64.to_le_bytes());
+        expected.extend([0x48, 0x01]);
+        assert_eq!(bytes, expected);
+
+        assert!(schema.encode("shop.v1.Order", &json!({"nope": 1})).is_err());
+        assert!(schema
+            .encode("shop.v1.Order", &json!({"status": "LOST"}))
+            .is_err());
+        assert!(schema
+            .encode("shop.v1.Order", &json!({"placedAt": {}}))
+            .is_err());
+        assert!(schema
+            .encode("shop.v1.Order", &json!({"lines": [{"quantity": 1e10}]}))
+            .is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! DNS protocol implementation for load testing
+//!
+//! Every request is one query for the same name and record type, sent to the
+//! target resolver over UDP (from a fresh socket, so a new source port, per
+//! query) or over TCP (a new connection per query). Queries are classified by
+//! the response code the resolver answers with.
+
+use crate::hooks::Hooks;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::{ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup};
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// Port used when the resolver address has none
+pub const DEFAULT_DNS_PORT: u16 = 53;
+
+/// Response codes by number
+pub const RCODE_NAMES: [&str; 11] = [
+    "NOERROR", "FORMERR", "SERVFAIL", "NXDOMAIN", "NOTIMP", "REFUSED", "YXDOMAIN", "YXRRSET",
+    "NXRRSET", "NOTAUTH", "NOTZONE",
+];
+
+/// Record types that can be queried by name
+const RECORD_TYPES: [(&str, u16); 14] = [
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+    ("DS", 43),
+    ("DNSKEY", 48),
+    ("HTTPS", 65),
+    ("ANY", 255),
+    ("CAA", 257),
+];
+
+/// Parse a record type name such as `AAAA`, or `TYPE65` for any other type
+pub fn parse_record_type(s: &str) -> Result<u16, String> {
+    let upper = s.trim().to_uppercase();
+    if let Some((_, code)) = RECORD_TYPES.iter().find(|(name, _)| *name == upper) {
+        return Ok(*code);
+    }
+    upper
+        .strip_prefix("TYPE")
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| format!("Unknown DNS record type: {}", s))
+}
+
+/// Name of a record type, `TYPE<n>` for types without one
+pub fn record_type_name(code: u16) -> String {
+    match RECORD_TYPES.iter().find(|(_, c)| *c == code) {
+        Some((name, _)) => name.to_string(),
+        None => format!("TYPE{}", code),
+    }
+}
+
+/// How queries reach the resolver
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DnsTransport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+impl FromStr for DnsTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "udp" => Ok(DnsTransport::Udp),
+            "tcp" => Ok(DnsTransport::Tcp),
+            _ => Err(format!("Unsupported DNS transport: {} (use udp or tcp)", s)),
+        }
+    }
+}
+
+impl DnsTransport {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DnsTransport::Udp => "UDP",
+            DnsTransport::Tcp => "TCP",
+        }
+    }
+}
+
+/// Configuration for DNS load testing
+#[derive(Debug, Clone)]
+pub struct DnsConfig {
+    /// Resolver address as `host[:port]`
+    pub server: String,
+    /// The name to look up
+    pub name: String,
+    /// Record type code, e.g. 1 for A
+    pub record_type: u16,
+    pub transport: DnsTransport,
+}
+
+impl DnsConfig {
+    /// Query `name` for A records over UDP
+    pub fn new(server: String, name: String) -> Result<Self> {
+        // Fail on a name that can't be encoded before the run starts
+        encode_query(&name, 1)?;
+        Ok(Self {
+            server,
+            name,
+            record_type: 1,
+            transport: DnsTransport::Udp,
+        })
+    }
+
+    pub fn with_record_type(mut self, record_type: u16) -> Self {
+        self.record_type = record_type;
+        self
+    }
+
+    pub fn with_transport(mut self, transport: DnsTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// The resolver address with the default port filled in
+    fn server_address(&self) -> String {
+        let server = self.server.trim_start_matches("dns://");
+        let has_port = match server.rsplit_once(':') {
+            // A bare IPv6 address has colons but no port
+            Some((host, _)) => !host.contains(':') || host.ends_with(']'),
+            None => false,
+        };
+        if has_port {
+            server.to_string()
+        } else if server.contains(':') && !server.starts_with('[') {
+            format!("[{}]:{}", server, DEFAULT_DNS_PORT)
+        } else {
+            format!("{}:{}", server, DEFAULT_DNS_PORT)
+        }
+    }
+}
+
+/// Encode a recursive query for `name`, with an ID of 0 to fill in per query
+fn encode_query(name: &str, record_type: u16) -> Result<Vec<u8>> {
+    // ID, flags with recursion desired, one question, no other records
+    let mut query = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    let name = name.trim_end_matches('.');
+    let mut encoded_len = 1;
+    for label in name.split('.').filter(|_| !name.is_empty()) {
+        if label.is_empty() || label.len() > 63 {
+            bail!("Invalid DNS name {:?}: labels must be 1 to 63 bytes", name);
+        }
+        encoded_len += label.len() + 1;
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    if encoded_len > 255 {
+        bail!("Invalid DNS name {:?}: longer than 255 bytes", name);
+    }
+    query.push(0);
+    query.extend(record_type.to_be_bytes());
+    // Class IN
+    query.extend([0, 1]);
+    Ok(query)
+}
+
+/// The response code of a reply to the query with `id`
+fn parse_response(id: u16, response: &[u8]) -> Result<u8, ErrorKind> {
+    if response.len() < 12 || response[..2] != id.to_be_bytes() || response[2] & 0x80 == 0 {
+        return Err(ErrorKind::ProtocolParse);
+    }
+    Ok(response[3] & 0x0f)
+}
+
+/// Send one query and wait for its reply
+async fn exchange(
+    server: SocketAddr,
+    transport: DnsTransport,
+    query: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    match transport {
+        DnsTransport::Udp => {
+            let local: SocketAddr = if server.is_ipv4() {
+                ([0, 0, 0, 0], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            let socket = UdpSocket::bind(local).await?;
+            socket.connect(server).await?;
+            socket.send(query).await?;
+            let mut buf = vec![0; 65535];
+            let len = socket.recv(&mut buf).await?;
+            buf.truncate(len);
+            Ok(buf)
+        }
+        DnsTransport::Tcp => {
+            // Messages are prefixed with their length over TCP
+            let mut stream = TcpStream::connect(server).await?;
+            let mut message = (query.len() as u16).to_be_bytes().to_vec();
+            message.extend(query);
+            stream.write_all(&message).await?;
+            let len = stream.read_u16().await?;
+            let mut buf = vec![0; len as usize];
+            stream.read_exact(&mut buf).await?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Make one query
+async fn fire_single_request(
+    server: SocketAddr,
+    transport: DnsTransport,
+    template: &[u8],
+    timeout_secs: u64,
+) -> RequestResult {
+    let id: u16 = rand::rng().random();
+    let mut query = template.to_vec();
+    query[..2].copy_from_slice(&id.to_be_bytes());
+
+    let start = Instant::now();
+    let reply = tokio::time::timeout(
+        Duration::from_secs(timeout_secs),
+        exchange(server, transport, &query),
+    )
+    .await;
+    let duration = start.elapsed().as_millis();
+
+    let rcode = match reply {
+        Ok(Ok(response)) => parse_response(id, &response),
+        Ok(Err(e)) => Err(ErrorKind::from_error(&e)),
+        Err(_) => Err(ErrorKind::ResponseTimeout),
+    };
+    match rcode {
+        Ok(rcode) => {
+            let name = RCODE_NAMES
+                .get(rcode as usize)
+                .copied()
+                .unwrap_or("UNKNOWN");
+            RequestResult {
+                duration,
+                status: rcode as u16,
+                // A name that doesn't exist is still an answer
+                success: name == "NOERROR" || name == "NXDOMAIN",
+                dns_rcode: Some(name),
+                ..Default::default()
+            }
+        }
+        Err(error) => RequestResult {
+            duration,
+            error: Some(error),
+            ..Default::default()
+        },
+    }
+}
+
+/// Makes one query per iteration
+struct DnsExecutor {
+    server: SocketAddr,
+    config: DnsConfig,
+    query: Vec<u8>,
+    timeout_secs: u64,
+}
+
+impl RequestExecutor for DnsExecutor {
+    fn progress_message(&self) -> String {
+        format!(
+            "Querying {} {} over {}...",
+            self.config.name,
+            record_type_name(self.config.record_type),
+            self.config.transport.name()
+        )
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "blue"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = fire_single_request(
+            self.server,
+            self.config.transport,
+            &self.query,
+            self.timeout_secs,
+        )
+        .await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run a DNS load test with the given configuration
+pub async fn run_load_test(
+    config: &DnsConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    // Resolve the resolver's own address once, up front
+    let address = config.server_address();
+    let server = tokio::net::lookup_host(&address)
+        .await
+        .with_context(|| format!("Failed to resolve DNS server {}", address))?
+        .next()
+        .with_context(|| format!("No address found for DNS server {}", address))?;
+    let executor = DnsExecutor {
+        server,
+        query: encode_query(&config.name, config.record_type)?,
+        config: config.clone(),
+        timeout_secs,
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_query() {
+        let query = encode_query("example.com.", 28).unwrap();
+        assert_eq!(&query[..12], &[0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..], b"\x07example\x03com\x00\x00\x1c\x00\x01");
+        assert_eq!(&encode_query(".", 2).unwrap()[12..], &[0, 0, 2, 0, 1]);
+        assert!(encode_query("a..b", 1).is_err());
+        assert!(encode_query(&"x".repeat(64), 1).is_err());
+
+        assert_eq!(parse_record_type("aaaa"), Ok(28));
+        assert_eq!(parse_record_type("TYPE99"), Ok(99));
+        assert!(parse_record_type("BOGUS").is_err());
+        assert_eq!(record_type_name(99), "TYPE99");
+    }
+
+    #[test]
+    fn test_parse_response_and_server() {
+        let mut reply = vec![0x12, 0x34, 0x81, 0x83, 0, 1, 0, 0, 0, 0, 0, 0];
+        assert_eq!(parse_response(0x1234, &reply), Ok(3));
+        assert_eq!(
+            parse_response(0x4321, &reply),
+            Err(ErrorKind::ProtocolParse)
+        );
+        // Not a response
+        reply[2] = 0x01;
+        assert_eq!(
+            parse_response(0x1234, &reply),
+            Err(ErrorKind::ProtocolParse)
+        );
+        assert_eq!(
+            parse_response(0x1234, &reply[..4]),
+            Err(ErrorKind::ProtocolParse)
+        );
+
+        let server = |server: &str| {
+            DnsConfig::new(server.to_string(), "example.com".to_string())
+                .unwrap()
+                .server_address()
+        };
+        assert_eq!(server("1.1.1.1"), "1.1.1.1:53");
+        assert_eq!(server("dns://127.0.0.1:5353"), "127.0.0.1:5353");
+        assert_eq!(server("::1"), "[::1]:53");
+        assert_eq!(server("[::1]:5353"), "[::1]:5353");
+        assert_eq!(server("dns.google"), "dns.google:53");
+    }
+}
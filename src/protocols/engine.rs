@@ -0,0 +1,307 @@
+//! The load generator shared by all protocols
+//!
+//! A protocol implements [`RequestExecutor`], i.e. how one iteration is sent,
+//! and [`run`] does everything else: stages, worker startup, concurrency,
+//! progress and stats. Scheduling features added here apply to every protocol.
+
+use crate::hooks::Hooks;
+use crate::protocols::{
+    calculate_stats, LiveResults, LoadTestStats, RequestResult, Stage, Startup, StopHandle,
+};
+use crate::resources::Sampler;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A whole stage run by an executor itself, see [`RequestExecutor::run_stage`]
+pub type StageFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// How a protocol sends its requests
+pub trait RequestExecutor: Send + Sync + 'static {
+    /// Shown next to the progress bar
+    fn progress_message(&self) -> String;
+
+    /// Colour of the progress bar
+    fn progress_color(&self) -> &'static str {
+        "cyan"
+    }
+
+    /// Requests one iteration sends, e.g. the steps of a scenario
+    fn requests_per_iteration(&self) -> u64 {
+        1
+    }
+
+    /// How long after the start of the run iteration `seq` is due, for
+    /// executors that pace their own requests
+    fn due(&self, _seq: u64) -> Option<Duration> {
+        None
+    }
+
+    /// Send one iteration, recording its results
+    fn execute(&self, iteration: Iteration) -> impl Future<Output = ()> + Send;
+
+    /// Run a whole stage on the executor's own workers instead of one task
+    /// per iteration, e.g. to keep a persistent connection per worker
+    fn run_stage(self: &Arc<Self>, _stage: &StageRun) -> Option<StageFuture> {
+        None
+    }
+}
+
+/// One iteration, running on a worker of the current stage
+pub struct Iteration {
+    /// Position of the iteration in the whole run
+    pub seq: u64,
+    pub worker_id: u64,
+    /// How many iterations this worker started before this one
+    pub iteration: u64,
+    pub recorder: Recorder,
+}
+
+/// A stage handed to [`RequestExecutor::run_stage`]
+pub struct StageRun {
+    /// The iterations of the stage
+    pub seqs: Range<u64>,
+    pub concurrency: u64,
+    pub startup: Startup,
+    pub recorder: Recorder,
+    /// When the run started, which [`RequestExecutor::due`] counts from
+    pub start: Instant,
+}
+
+/// Records results and moves the progress bar on
+#[derive(Clone)]
+pub struct Recorder {
+    live: LiveResults,
+    pb: ProgressBar,
+    stop: StopHandle,
+    hooks: Option<Arc<dyn Hooks>>,
+    /// The worker whose results these are, stamped on each of them
+    worker: Option<u64>,
+}
+
+impl Recorder {
+    /// A recorder for the results of one worker, for per-worker stats
+    pub fn for_worker(&self, worker: u64) -> Self {
+        Self {
+            worker: Some(worker),
+            ..self.clone()
+        }
+    }
+
+    pub fn record(&self, mut result: RequestResult) {
+        result.worker = result.worker.or(self.worker);
+        if let Some(hooks) = &self.hooks {
+            hooks.after_response(&result);
+        }
+        self.live.record(result);
+        self.pb.inc(1);
+    }
+
+    pub fn extend(&self, mut results: Vec<RequestResult>) {
+        if let Some(worker) = self.worker {
+            for result in &mut results {
+                result.worker.get_or_insert(worker);
+            }
+        }
+        if let Some(hooks) = &self.hooks {
+            results
+                .iter()
+                .for_each(|result| hooks.after_response(result));
+        }
+        let count = results.len() as u64;
+        self.live.extend(results);
+        self.pb.inc(count);
+    }
+
+    /// Count requests that were not sent, or were recorded elsewhere
+    pub fn advance(&self, requests: u64) {
+        self.pb.inc(requests);
+    }
+
+    /// Whether the run was stopped, so no more requests should be sent
+    pub fn is_stopped(&self) -> bool {
+        self.stop.is_stopped()
+    }
+
+    /// Wait until the run is stopped, e.g. to end a long-lived request early
+    pub async fn stopped(&self) {
+        self.stop.stopped().await
+    }
+
+    /// Print a warning without garbling the progress bar
+    pub fn warn(&self, message: &str) {
+        self.pb.suspend(|| eprintln!("Warning: {}", message));
+    }
+}
+
+/// Run the stages back to back, each with its own concurrency
+pub async fn run<E: RequestExecutor>(
+    executor: E,
+    stages: &[Stage],
+    startup: Startup,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> LoadTestStats {
+    let executor = Arc::new(executor);
+    let num_iterations: u64 = stages.iter().map(|s| s.requests).sum();
+
+    // Create progress bar
+    let pb = ProgressBar::new(num_iterations * executor.requests_per_iteration());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(&format!(
+                "{{spinner:.green}} [{{elapsed_precise}}] [{{bar:40.{}/blue}}] {{pos}}/{{len}} ({{percent}}%) {{msg}}",
+                executor.progress_color()
+            ))
+            .unwrap()
+            .progress_chars("█▓▒░  "),
+    );
+    pb.set_message(executor.progress_message());
+    if quiet {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    let stop = live.stop_handle();
+    let recorder = Recorder {
+        live: live.clone(),
+        pb: pb.clone(),
+        stop: stop.clone(),
+        hooks,
+        worker: None,
+    };
+
+    let sampler = Sampler::start();
+    let overall_start = Instant::now();
+
+    let mut first = 0;
+    for stage in stages {
+        if stop.is_stopped() {
+            break;
+        }
+        let stage = StageRun {
+            seqs: first..first + stage.requests,
+            concurrency: stage.concurrency,
+            startup,
+            recorder: recorder.clone(),
+            start: overall_start,
+        };
+        first = stage.seqs.end;
+
+        if let Some(run_stage) = executor.run_stage(&stage) {
+            run_stage.await;
+            continue;
+        }
+
+        let workers = startup.worker_pool(stage.concurrency);
+        let mut handles = Vec::with_capacity((stage.seqs.end - stage.seqs.start) as usize);
+        for seq in stage.seqs {
+            let executor = Arc::clone(&executor);
+            let workers = Arc::clone(&workers);
+            let recorder = recorder.clone();
+            let stop = stop.clone();
+
+            handles.push(tokio::spawn(async move {
+                if let Some(due) = executor.due(seq) {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until((overall_start + due).into()) => {}
+                        _ = stop.stopped() => return,
+                    }
+                }
+                let worker = workers.acquire().await;
+                if stop.is_stopped() {
+                    return;
+                }
+                let iteration = Iteration {
+                    seq,
+                    worker_id: worker.id,
+                    iteration: worker.iteration,
+                    recorder: recorder.for_worker(worker.id),
+                };
+                executor.execute(iteration).await;
+            }));
+        }
+
+        // Wait for the stage to finish before starting the next one
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+    let results = live.take();
+
+    let total_duration = overall_start.elapsed().as_millis();
+
+    pb.finish_with_message("Complete!");
+
+    // Calculate statistics
+    let mut stats = calculate_stats(&results, total_duration);
+    stats.custom_metrics = live.metrics().summarize();
+    stats.client = sampler.finish();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Records a successful result per iteration and stops the run after
+    /// `stop_after` of them
+    struct Counting {
+        sent: AtomicU64,
+        stop_after: u64,
+        stop: StopHandle,
+    }
+
+    impl RequestExecutor for Counting {
+        fn progress_message(&self) -> String {
+            String::new()
+        }
+
+        async fn execute(&self, iteration: Iteration) {
+            iteration.recorder.record(RequestResult {
+                success: true,
+                status: 200,
+                ..Default::default()
+            });
+            if self.sent.fetch_add(1, Ordering::SeqCst) + 1 == self.stop_after {
+                self.stop.stop();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_streams_results_and_stops() {
+        let live = LiveResults::default();
+        let mut received = live.subscribe();
+        let executor = Counting {
+            sent: AtomicU64::new(0),
+            stop_after: 5,
+            stop: live.stop_handle(),
+        };
+        let stages = [
+            Stage {
+                requests: 100,
+                concurrency: 1,
+            },
+            Stage {
+                requests: 100,
+                concurrency: 4,
+            },
+        ];
+
+        let stats = run(executor, &stages, Startup::default(), true, &live, None).await;
+        assert_eq!(stats.total_requests, 5);
+
+        drop(live);
+        let mut streamed = 0;
+        while let Some(result) = received.recv().await {
+            assert_eq!(result.status, 200);
+            assert!(result.completed_at.is_some());
+            streamed += 1;
+        }
+        assert_eq!(streamed, 5);
+    }
+}
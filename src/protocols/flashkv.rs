@@ -1,19 +1,58 @@
 //! FlashKV TCP protocol implementation for load testing
 //!
 //! FlashKV is a Redis-like in-memory key-value database that communicates over TCP.
-//! This module provides load testing capabilities for FlashKV servers.
+//! This module provides load testing capabilities for FlashKV servers, and for
+//! Redis/Valkey servers by speaking RESP instead of FlashKV's inline format.
 
-use crate::protocols::{calculate_stats, LoadTestStats, RequestResult};
-use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::hooks::Hooks;
+use crate::metrics::{CustomMetrics, CONNECTIONS_OPENED};
+use crate::protocols::engine::{self, Iteration, Recorder, RequestExecutor, StageFuture, StageRun};
+use crate::protocols::resp::{self, Reply};
+use crate::protocols::s3::ObjectSize;
+use crate::protocols::{
+    check_local_addrs, ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup,
+};
+use crate::replay::CommandLog;
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use rand::Rng;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::sync::Semaphore;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::net::{TcpSocket, TcpStream};
 use tokio::time::{timeout, Duration};
 
+/// Scheme of servers listening on a Unix domain socket
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// Custom metric with the time taken to open each connection, AUTH included,
+/// apart from the command latency
+pub const CONNECT_TIME: &str = "connect";
+
+/// Counter of the dropped persistent connections that were reopened
+pub const RECONNECTS: &str = "reconnects";
+
+/// Wait before the first attempt to reopen a dropped connection, doubling
+/// after every failed one up to [`DEFAULT_RECONNECT_MAX_BACKOFF`]
+pub const DEFAULT_RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+pub const DEFAULT_RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Attempts to reopen a dropped connection before failing the batch waiting on it
+const RECONNECT_ATTEMPTS: u32 = 8;
+
+/// Size of preloaded values unless configured
+pub const DEFAULT_PRELOAD_VALUE_SIZE: usize = 64;
+
+/// Connections, and SETs in flight on each, while preloading
+const PRELOAD_CONNECTIONS: u64 = 8;
+const PRELOAD_BATCH: u64 = 64;
+
 /// Supported FlashKV commands
 #[derive(Debug, Clone, PartialEq)]
 pub enum FlashKVCommand {
@@ -22,7 +61,7 @@ pub enum FlashKVCommand {
     /// GET <key> - Retrieve a value
     Get { key: String },
     /// SET <key> <value> - Store a value
-    Set { key: String, value: String },
+    Set { key: String, value: Vec<u8> },
     /// DEL <key> - Delete a key
     Del { key: String },
     /// INCR <key> - Increment a numeric value
@@ -30,7 +69,7 @@ pub enum FlashKVCommand {
     /// DECR <key> - Decrement a numeric value
     Decr { key: String },
     /// LPUSH <key> <value> - Push to list
-    LPush { key: String, value: String },
+    LPush { key: String, value: Vec<u8> },
     /// LPOP <key> - Pop from list
     LPop { key: String },
     /// EXISTS <key> - Check if key exists
@@ -43,14 +82,54 @@ pub enum FlashKVCommand {
     Keys { pattern: String },
     /// FLUSHDB - Clear all keys (use with caution!)
     FlushDb,
+    /// SETEX <key> <seconds> <value> - Store a value that expires
+    SetEx {
+        key: String,
+        seconds: u64,
+        value: Vec<u8>,
+    },
+    /// MGET <key> [key ...] - Retrieve several values
+    MGet { keys: Vec<String> },
+    /// MSET <key> <value> [key value ...] - Store several values
+    MSet { pairs: Vec<(String, Vec<u8>)> },
+    /// HSET <key> <field> <value> - Store a hash field
+    HSet {
+        key: String,
+        field: String,
+        value: Vec<u8>,
+    },
+    /// HGET <key> <field> - Retrieve a hash field
+    HGet { key: String, field: String },
+    /// SADD <key> <member> [member ...] - Add to a set
+    SAdd { key: String, members: Vec<String> },
+    /// SMEMBERS <key> - List a set's members
+    SMembers { key: String },
+    /// ZADD <key> <score> <member> - Add to a sorted set
+    ZAdd {
+        key: String,
+        score: f64,
+        member: String,
+    },
+    /// ZRANGE <key> <start> <stop> - Members of a sorted set by rank
+    ZRange { key: String, start: i64, stop: i64 },
     /// Custom raw command
     Raw { command: String },
 }
 
-impl FlashKVCommand {
+impl FromStr for FlashKVCommand {
+    type Err = String;
+
     /// Parse a command string into a FlashKVCommand
-    pub fn from_str(s: &str) -> Result<Self, String> {
-        let parts: Vec<&str> = s.trim().split_whitespace().collect();
+    ///
+    /// Arguments are split on whitespace, or quoted as with redis-cli, e.g.
+    /// `SET key "two\nlines"` or `SET key "\x00\xff"` for binary values.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let args = split_args(s)?;
+        let words: Vec<String> = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        let parts: Vec<&str> = words.iter().map(String::as_str).collect();
         if parts.is_empty() {
             return Err("Empty command".to_string());
         }
@@ -72,7 +151,7 @@ impl FlashKVCommand {
                 } else {
                     Ok(FlashKVCommand::Set {
                         key: parts[1].to_string(),
-                        value: parts[2..].join(" "),
+                        value: args[2..].join(&b' '),
                     })
                 }
             }
@@ -109,7 +188,7 @@ impl FlashKVCommand {
                 } else {
                     Ok(FlashKVCommand::LPush {
                         key: parts[1].to_string(),
-                        value: parts[2..].join(" "),
+                        value: args[2..].join(&b' '),
                     })
                 }
             }
@@ -162,44 +241,226 @@ impl FlashKVCommand {
                 Ok(FlashKVCommand::Keys { pattern })
             }
             "FLUSHDB" => Ok(FlashKVCommand::FlushDb),
+            "SETEX" => {
+                if parts.len() < 4 {
+                    Err("SETEX requires a key, seconds and value".to_string())
+                } else {
+                    let seconds = parts[2]
+                        .parse::<u64>()
+                        .map_err(|_| "Invalid seconds value")?;
+                    Ok(FlashKVCommand::SetEx {
+                        key: parts[1].to_string(),
+                        seconds,
+                        value: args[3..].join(&b' '),
+                    })
+                }
+            }
+            "MGET" => {
+                if parts.len() < 2 {
+                    Err("MGET requires at least one key".to_string())
+                } else {
+                    Ok(FlashKVCommand::MGet {
+                        keys: parts[1..].iter().map(|k| k.to_string()).collect(),
+                    })
+                }
+            }
+            "MSET" => {
+                if parts.len() < 3 || parts.len().is_multiple_of(2) {
+                    Err("MSET requires key and value pairs".to_string())
+                } else {
+                    Ok(FlashKVCommand::MSet {
+                        pairs: args[1..]
+                            .chunks(2)
+                            .map(|pair| {
+                                (
+                                    String::from_utf8_lossy(&pair[0]).into_owned(),
+                                    pair[1].clone(),
+                                )
+                            })
+                            .collect(),
+                    })
+                }
+            }
+            "HSET" => {
+                if parts.len() < 4 {
+                    Err("HSET requires a key, field and value".to_string())
+                } else {
+                    Ok(FlashKVCommand::HSet {
+                        key: parts[1].to_string(),
+                        field: parts[2].to_string(),
+                        value: args[3..].join(&b' '),
+                    })
+                }
+            }
+            "HGET" => {
+                if parts.len() < 3 {
+                    Err("HGET requires a key and field".to_string())
+                } else {
+                    Ok(FlashKVCommand::HGet {
+                        key: parts[1].to_string(),
+                        field: parts[2].to_string(),
+                    })
+                }
+            }
+            "SADD" => {
+                if parts.len() < 3 {
+                    Err("SADD requires a key and member".to_string())
+                } else {
+                    Ok(FlashKVCommand::SAdd {
+                        key: parts[1].to_string(),
+                        members: parts[2..].iter().map(|m| m.to_string()).collect(),
+                    })
+                }
+            }
+            "SMEMBERS" => {
+                if parts.len() < 2 {
+                    Err("SMEMBERS requires a key".to_string())
+                } else {
+                    Ok(FlashKVCommand::SMembers {
+                        key: parts[1].to_string(),
+                    })
+                }
+            }
+            "ZADD" => {
+                if parts.len() < 4 {
+                    Err("ZADD requires a key, score and member".to_string())
+                } else {
+                    let score = parts[2].parse::<f64>().map_err(|_| "Invalid score value")?;
+                    Ok(FlashKVCommand::ZAdd {
+                        key: parts[1].to_string(),
+                        score,
+                        member: parts[3].to_string(),
+                    })
+                }
+            }
+            "ZRANGE" => {
+                if parts.len() < 4 {
+                    Err("ZRANGE requires a key, start and stop".to_string())
+                } else {
+                    let rank = |s: &str| s.parse::<i64>().map_err(|_| "Invalid rank value");
+                    Ok(FlashKVCommand::ZRange {
+                        key: parts[1].to_string(),
+                        start: rank(parts[2])?,
+                        stop: rank(parts[3])?,
+                    })
+                }
+            }
             _ => Ok(FlashKVCommand::Raw {
                 command: s.to_string(),
             }),
         }
     }
+}
 
-    /// Convert the command to a wire format string
-    pub fn to_wire_format(&self) -> String {
-        match self {
-            FlashKVCommand::Ping => "PING\r\n".to_string(),
-            FlashKVCommand::Get { key } => format!("GET {}\r\n", key),
-            FlashKVCommand::Set { key, value } => format!("SET {} {}\r\n", key, value),
-            FlashKVCommand::Del { key } => format!("DEL {}\r\n", key),
-            FlashKVCommand::Incr { key } => format!("INCR {}\r\n", key),
-            FlashKVCommand::Decr { key } => format!("DECR {}\r\n", key),
-            FlashKVCommand::LPush { key, value } => format!("LPUSH {} {}\r\n", key, value),
-            FlashKVCommand::LPop { key } => format!("LPOP {}\r\n", key),
-            FlashKVCommand::Exists { key } => format!("EXISTS {}\r\n", key),
-            FlashKVCommand::Expire { key, seconds } => format!("EXPIRE {} {}\r\n", key, seconds),
-            FlashKVCommand::Ttl { key } => format!("TTL {}\r\n", key),
-            FlashKVCommand::Keys { pattern } => format!("KEYS {}\r\n", pattern),
-            FlashKVCommand::FlushDb => "FLUSHDB\r\n".to_string(),
-            FlashKVCommand::Raw { command } => {
-                if command.ends_with("\r\n") {
-                    command.clone()
-                } else if command.ends_with('\n') {
-                    format!("{}\r\n", command.trim_end())
-                } else {
-                    format!("{}\r\n", command)
-                }
+impl FlashKVCommand {
+    /// Encode the command as an inline command line
+    ///
+    /// Arguments with whitespace, quotes or bytes that aren't printable ASCII
+    /// are quoted and escaped; raw commands are sent as they were written.
+    pub fn to_wire_format(&self) -> Vec<u8> {
+        if let FlashKVCommand::Raw { command } = self {
+            let line = command.trim_end_matches(['\r', '\n']);
+            return format!("{}\r\n", line).into_bytes();
+        }
+        let mut line = self
+            .args()
+            .iter()
+            .map(|arg| quote_inline(arg))
+            .collect::<Vec<_>>()
+            .join(&b' ');
+        line.extend(b"\r\n");
+        line
+    }
+
+    /// The command's name and arguments, as sent in a RESP array
+    pub fn args(&self) -> Vec<Vec<u8>> {
+        let args: Vec<&[u8]> = match self {
+            FlashKVCommand::Get { key }
+            | FlashKVCommand::Del { key }
+            | FlashKVCommand::Incr { key }
+            | FlashKVCommand::Decr { key }
+            | FlashKVCommand::LPop { key }
+            | FlashKVCommand::Exists { key }
+            | FlashKVCommand::Ttl { key }
+            | FlashKVCommand::SMembers { key } => {
+                vec![self.display_name().as_bytes(), key.as_bytes()]
+            }
+            FlashKVCommand::Set { key, value } | FlashKVCommand::LPush { key, value } => {
+                vec![self.display_name().as_bytes(), key.as_bytes(), value]
+            }
+            FlashKVCommand::Expire { key, seconds } => {
+                return vec![
+                    b"EXPIRE".to_vec(),
+                    key.clone().into_bytes(),
+                    seconds.to_string().into_bytes(),
+                ]
+            }
+            FlashKVCommand::Keys { pattern } => vec![b"KEYS", pattern.as_bytes()],
+            FlashKVCommand::SetEx {
+                key,
+                seconds,
+                value,
+            } => {
+                return vec![
+                    b"SETEX".to_vec(),
+                    key.clone().into_bytes(),
+                    seconds.to_string().into_bytes(),
+                    value.clone(),
+                ]
             }
+            FlashKVCommand::MGet { keys } => std::iter::once(&b"MGET"[..])
+                .chain(keys.iter().map(String::as_bytes))
+                .collect(),
+            FlashKVCommand::MSet { pairs } => std::iter::once(&b"MSET"[..])
+                .chain(pairs.iter().flat_map(|(k, v)| [k.as_bytes(), v.as_slice()]))
+                .collect(),
+            FlashKVCommand::HSet { key, field, value } => {
+                vec![b"HSET", key.as_bytes(), field.as_bytes(), value]
+            }
+            FlashKVCommand::HGet { key, field } => {
+                vec![b"HGET", key.as_bytes(), field.as_bytes()]
+            }
+            FlashKVCommand::SAdd { key, members } => [&b"SADD"[..], key.as_bytes()]
+                .into_iter()
+                .chain(members.iter().map(String::as_bytes))
+                .collect(),
+            FlashKVCommand::ZAdd { key, score, member } => {
+                return vec![
+                    b"ZADD".to_vec(),
+                    key.clone().into_bytes(),
+                    score.to_string().into_bytes(),
+                    member.clone().into_bytes(),
+                ]
+            }
+            FlashKVCommand::ZRange { key, start, stop } => {
+                return vec![
+                    b"ZRANGE".to_vec(),
+                    key.clone().into_bytes(),
+                    start.to_string().into_bytes(),
+                    stop.to_string().into_bytes(),
+                ]
+            }
+            FlashKVCommand::Ping | FlashKVCommand::FlushDb => {
+                vec![self.display_name().as_bytes()]
+            }
+            // Checked to split when parsed
+            FlashKVCommand::Raw { command } => return split_args(command).unwrap_or_default(),
+        };
+        args.into_iter().map(<[u8]>::to_vec).collect()
+    }
+
+    /// Encode the command in the given wire format
+    pub fn encode(&self, wire: WireFormat) -> Vec<u8> {
+        match wire {
+            WireFormat::Inline => self.to_wire_format(),
+            WireFormat::Resp2 | WireFormat::Resp3 => resp::encode(&self.args()),
         }
     }
 
     /// Create a command with a randomized key based on config
     pub fn with_random_key(&self, prefix: &str, range: u64) -> Self {
-        let random_suffix: u64 = rand::rng().random_range(0..range);
-        let random_key = format!("{}:{}", prefix, random_suffix);
+        let random = || format!("{}:{}", prefix, rand::rng().random_range(0..range));
+        let random_key = random();
 
         match self {
             FlashKVCommand::Get { .. } => FlashKVCommand::Get { key: random_key },
@@ -221,11 +482,141 @@ impl FlashKVCommand {
                 seconds: *seconds,
             },
             FlashKVCommand::Ttl { .. } => FlashKVCommand::Ttl { key: random_key },
+            FlashKVCommand::SetEx { seconds, value, .. } => FlashKVCommand::SetEx {
+                key: random_key,
+                seconds: *seconds,
+                value: value.clone(),
+            },
+            // Every key of a multi-key command is drawn separately
+            FlashKVCommand::MGet { keys } => FlashKVCommand::MGet {
+                keys: keys.iter().map(|_| random()).collect(),
+            },
+            FlashKVCommand::MSet { pairs } => FlashKVCommand::MSet {
+                pairs: pairs.iter().map(|(_, v)| (random(), v.clone())).collect(),
+            },
+            FlashKVCommand::HSet { field, value, .. } => FlashKVCommand::HSet {
+                key: random_key,
+                field: field.clone(),
+                value: value.clone(),
+            },
+            FlashKVCommand::HGet { field, .. } => FlashKVCommand::HGet {
+                key: random_key,
+                field: field.clone(),
+            },
+            FlashKVCommand::SAdd { members, .. } => FlashKVCommand::SAdd {
+                key: random_key,
+                members: members.clone(),
+            },
+            FlashKVCommand::SMembers { .. } => FlashKVCommand::SMembers { key: random_key },
+            FlashKVCommand::ZAdd { score, member, .. } => FlashKVCommand::ZAdd {
+                key: random_key,
+                score: *score,
+                member: member.clone(),
+            },
+            FlashKVCommand::ZRange { start, stop, .. } => FlashKVCommand::ZRange {
+                key: random_key,
+                start: *start,
+                stop: *stop,
+            },
             // Commands that don't use keys
             _ => self.clone(),
         }
     }
 
+    /// The key the command operates on, the first one for multi-key commands
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            FlashKVCommand::Get { key }
+            | FlashKVCommand::Set { key, .. }
+            | FlashKVCommand::Del { key }
+            | FlashKVCommand::Incr { key }
+            | FlashKVCommand::Decr { key }
+            | FlashKVCommand::LPush { key, .. }
+            | FlashKVCommand::LPop { key }
+            | FlashKVCommand::Exists { key }
+            | FlashKVCommand::Expire { key, .. }
+            | FlashKVCommand::Ttl { key }
+            | FlashKVCommand::SetEx { key, .. }
+            | FlashKVCommand::HSet { key, .. }
+            | FlashKVCommand::HGet { key, .. }
+            | FlashKVCommand::SAdd { key, .. }
+            | FlashKVCommand::SMembers { key }
+            | FlashKVCommand::ZAdd { key, .. }
+            | FlashKVCommand::ZRange { key, .. } => Some(key),
+            FlashKVCommand::MGet { keys } => keys.first().map(String::as_str),
+            FlashKVCommand::MSet { pairs } => pairs.first().map(|(key, _)| key.as_str()),
+            // Raw commands are assumed to name their key first
+            FlashKVCommand::Raw { command } => command.split_whitespace().nth(1),
+            FlashKVCommand::Ping | FlashKVCommand::Keys { .. } | FlashKVCommand::FlushDb => None,
+        }
+    }
+
+    /// Why the command could wipe data or stall the server, for the commands
+    /// refused unless destructive workloads are allowed
+    pub fn danger(&self) -> Option<&'static str> {
+        let is_pattern = |s: &str| s.contains(['*', '?', '[']);
+        match self {
+            FlashKVCommand::FlushDb => Some("FLUSHDB deletes every key"),
+            FlashKVCommand::Keys { pattern } if is_pattern(pattern) => {
+                Some("KEYS with a pattern walks the whole keyspace, blocking the server")
+            }
+            FlashKVCommand::Del { key } if is_pattern(key) => {
+                Some("DEL with a pattern can delete every matching key")
+            }
+            FlashKVCommand::Raw { command } => {
+                match command.split_whitespace().next().map(str::to_uppercase) {
+                    Some(name) if name == "FLUSHALL" || name == "FLUSHDB" => {
+                        Some("FLUSHALL and FLUSHDB delete every key")
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the command writes values, see [`FlashKVCommand::with_values`]
+    pub fn writes_value(&self) -> bool {
+        matches!(
+            self,
+            FlashKVCommand::Set { .. }
+                | FlashKVCommand::LPush { .. }
+                | FlashKVCommand::SetEx { .. }
+                | FlashKVCommand::MSet { .. }
+                | FlashKVCommand::HSet { .. }
+        )
+    }
+
+    /// Replace the values written by SET, LPUSH, SETEX, MSET and HSET with
+    /// ones from `value`
+    pub fn with_values(self, mut value: impl FnMut() -> Vec<u8>) -> Self {
+        match self {
+            FlashKVCommand::Set { key, .. } => FlashKVCommand::Set {
+                key,
+                value: value(),
+            },
+            FlashKVCommand::LPush { key, .. } => FlashKVCommand::LPush {
+                key,
+                value: value(),
+            },
+            FlashKVCommand::SetEx { key, seconds, .. } => FlashKVCommand::SetEx {
+                key,
+                seconds,
+                value: value(),
+            },
+            FlashKVCommand::MSet { pairs } => FlashKVCommand::MSet {
+                pairs: pairs.into_iter().map(|(k, _)| (k, value())).collect(),
+            },
+            FlashKVCommand::HSet { key, field, .. } => FlashKVCommand::HSet {
+                key,
+                field,
+                value: value(),
+            },
+            // Commands that don't write values
+            other => other,
+        }
+    }
+
     /// Get the display name of the command
     pub fn display_name(&self) -> &'static str {
         match self {
@@ -242,11 +633,208 @@ impl FlashKVCommand {
             FlashKVCommand::Ttl { .. } => "TTL",
             FlashKVCommand::Keys { .. } => "KEYS",
             FlashKVCommand::FlushDb => "FLUSHDB",
+            FlashKVCommand::SetEx { .. } => "SETEX",
+            FlashKVCommand::MGet { .. } => "MGET",
+            FlashKVCommand::MSet { .. } => "MSET",
+            FlashKVCommand::HSet { .. } => "HSET",
+            FlashKVCommand::HGet { .. } => "HGET",
+            FlashKVCommand::SAdd { .. } => "SADD",
+            FlashKVCommand::SMembers { .. } => "SMEMBERS",
+            FlashKVCommand::ZAdd { .. } => "ZADD",
+            FlashKVCommand::ZRange { .. } => "ZRANGE",
             FlashKVCommand::Raw { .. } => "RAW",
         }
     }
 }
 
+/// Split a command line into arguments the way redis-cli does
+///
+/// Arguments are separated by whitespace. Double-quoted ones may contain
+/// whitespace and the escapes `\n`, `\r`, `\t`, `\b`, `\a`, `\"`, `\\` and
+/// `\xHH` for any byte; single-quoted ones are taken literally but for `\'`.
+pub fn split_args(line: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut args = Vec::new();
+    let mut bytes = line.bytes().peekable();
+    loop {
+        while bytes.next_if(u8::is_ascii_whitespace).is_some() {}
+        let Some(&first) = bytes.peek() else {
+            return Ok(args);
+        };
+
+        let mut arg = Vec::new();
+        if first == b'"' || first == b'\'' {
+            bytes.next();
+            loop {
+                match (bytes.next(), first) {
+                    (None, _) => return Err(format!("Unbalanced quotes in {}", line)),
+                    (Some(quote), _) if quote == first => break,
+                    (Some(b'\\'), b'"') => match bytes.next() {
+                        Some(b'n') => arg.push(b'\n'),
+                        Some(b'r') => arg.push(b'\r'),
+                        Some(b't') => arg.push(b'\t'),
+                        Some(b'b') => arg.push(0x08),
+                        Some(b'a') => arg.push(0x07),
+                        Some(b'x') => {
+                            let hex = [bytes.next(), bytes.next()];
+                            let byte = match hex {
+                                [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                                    .ok()
+                                    .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                                _ => None,
+                            };
+                            arg.push(
+                                byte.ok_or_else(|| format!("Invalid \\x escape in {}", line))?,
+                            );
+                        }
+                        Some(other) => arg.push(other),
+                        None => return Err(format!("Unbalanced quotes in {}", line)),
+                    },
+                    (Some(b'\\'), _) if bytes.peek() == Some(&b'\'') => {
+                        arg.push(b'\'');
+                        bytes.next();
+                    }
+                    (Some(byte), _) => arg.push(byte),
+                }
+            }
+            // A closing quote must end the argument
+            if bytes.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+                return Err(format!(
+                    "Closing quote must be followed by a space in {}",
+                    line
+                ));
+            }
+        } else {
+            while let Some(byte) = bytes.next_if(|b| !b.is_ascii_whitespace()) {
+                arg.push(byte);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Quote an argument for an inline command when it has whitespace, quotes or
+/// bytes that aren't printable ASCII, so the server reads it back as one
+pub fn quote_inline(arg: &[u8]) -> Vec<u8> {
+    let plain = |b: &u8| b.is_ascii_graphic() && *b != b'"' && *b != b'\'' && *b != b'\\';
+    if !arg.is_empty() && arg.iter().all(plain) {
+        return arg.to_vec();
+    }
+
+    let mut quoted = vec![b'"'];
+    for &byte in arg {
+        match byte {
+            b'"' => quoted.extend(b"\\\""),
+            b'\\' => quoted.extend(b"\\\\"),
+            b'\n' => quoted.extend(b"\\n"),
+            b'\r' => quoted.extend(b"\\r"),
+            b'\t' => quoted.extend(b"\\t"),
+            b' ' => quoted.push(b' '),
+            _ if plain(&byte) => quoted.push(byte),
+            _ => quoted.extend(format!("\\x{:02x}", byte).as_bytes()),
+        }
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+/// Number of hash slots keys are spread over, as in Redis Cluster
+const HASH_SLOTS: u16 = 16384;
+
+/// The Redis Cluster hash slot of a key: CRC16 of the key, or of its `{tag}`
+/// when it has a non-empty one, so related keys can share a node
+pub fn hash_slot(key: &str) -> u16 {
+    let key = key.as_bytes();
+    let hashed = match key.iter().position(|&b| b == b'{') {
+        Some(open) => match key[open + 1..].iter().position(|&b| b == b'}') {
+            Some(len) if len > 0 => &key[open + 1..open + 1 + len],
+            _ => key,
+        },
+        None => key,
+    };
+
+    // CRC16-CCITT (XModem)
+    let mut crc: u16 = 0;
+    for &byte in hashed {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc % HASH_SLOTS
+}
+
+/// How commands are spread across several nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NodePlacement {
+    /// By the hash slot of the command's key, so a key always hits one node
+    #[default]
+    KeyHash,
+    /// Each node in turn
+    RoundRobin,
+}
+
+impl FromStr for NodePlacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "key-hash" | "hash" => Ok(NodePlacement::KeyHash),
+            "round-robin" | "rr" => Ok(NodePlacement::RoundRobin),
+            _ => Err(format!(
+                "Unsupported node placement: {} (use key-hash or round-robin)",
+                s
+            )),
+        }
+    }
+}
+
+impl NodePlacement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodePlacement::KeyHash => "key-hash",
+            NodePlacement::RoundRobin => "round-robin",
+        }
+    }
+}
+
+/// How commands and replies are encoded on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WireFormat {
+    /// FlashKV's inline commands, with a one-line reply each
+    #[default]
+    Inline,
+    /// RESP, as spoken by Redis and Valkey
+    Resp2,
+    /// RESP, switched to version 3 with `HELLO 3` on every new connection
+    Resp3,
+}
+
+impl WireFormat {
+    /// The RESP version for `--resp`
+    pub fn resp(version: u8) -> Result<Self, String> {
+        match version {
+            2 => Ok(WireFormat::Resp2),
+            3 => Ok(WireFormat::Resp3),
+            _ => Err(format!(
+                "Unsupported RESP version: {} (use 2 or 3)",
+                version
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WireFormat::Inline => "inline",
+            WireFormat::Resp2 => "RESP2",
+            WireFormat::Resp3 => "RESP3",
+        }
+    }
+}
+
 /// FlashKV-specific configuration
 #[derive(Debug, Clone)]
 pub struct FlashKVConfig {
@@ -262,6 +850,40 @@ pub struct FlashKVConfig {
     pub key_prefix: String,
     /// Range for random key generation (0 to key_range-1)
     pub key_range: u64,
+    /// Commands in flight per connection (1 = no pipelining)
+    pub pipeline_depth: usize,
+    /// How commands and replies are encoded
+    pub wire: WireFormat,
+    /// Keep one connection per worker; when off every command opens a new one
+    pub keepalive: bool,
+    /// ACL user to authenticate as, the default user when unset
+    pub username: Option<String>,
+    /// Password sent with AUTH on every new connection
+    pub password: Option<String>,
+    /// Keys to SET before the measured run, named as random keys are
+    pub preload: u64,
+    /// Size in bytes of every preloaded value
+    pub preload_value_size: usize,
+    /// Random values of this size (or range of sizes) replace the written
+    /// values, when set
+    pub value_size: Option<ObjectSize>,
+    /// Addresses of further nodes of a sharded deployment, after host:port
+    pub nodes: Vec<String>,
+    /// How commands are spread across the nodes
+    pub placement: NodePlacement,
+    /// Unix domain socket to connect to instead of host:port
+    pub socket: Option<PathBuf>,
+    /// Wait before reopening a dropped connection, doubled after every
+    /// failed attempt
+    pub reconnect_backoff: Duration,
+    /// Longest wait between attempts to reopen a dropped connection
+    pub reconnect_max_backoff: Duration,
+    /// Random values are made of any bytes rather than alphanumeric characters
+    pub binary_values: bool,
+    /// Command log whose commands are sent instead of `commands`
+    pub replay: Option<Arc<CommandLog>>,
+    /// Source addresses to bind connections to, taken in turn
+    pub local_addrs: Vec<IpAddr>,
 }
 
 impl FlashKVConfig {
@@ -273,6 +895,22 @@ impl FlashKVConfig {
             use_random_keys: false,
             key_prefix: "key".to_string(),
             key_range: 1000,
+            pipeline_depth: 1,
+            wire: WireFormat::Inline,
+            keepalive: true,
+            username: None,
+            password: None,
+            preload: 0,
+            preload_value_size: DEFAULT_PRELOAD_VALUE_SIZE,
+            value_size: None,
+            nodes: Vec::new(),
+            placement: NodePlacement::KeyHash,
+            socket: None,
+            reconnect_backoff: DEFAULT_RECONNECT_BACKOFF,
+            reconnect_max_backoff: DEFAULT_RECONNECT_MAX_BACKOFF,
+            binary_values: false,
+            replay: None,
+            local_addrs: Vec::new(),
         }
     }
 
@@ -288,9 +926,160 @@ impl FlashKVConfig {
         self
     }
 
-    /// Get the server address
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        self.pipeline_depth = depth.max(1);
+        self
+    }
+
+    pub fn with_wire(mut self, wire: WireFormat) -> Self {
+        self.wire = wire;
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: bool) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    pub fn with_auth(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.username = username;
+        self.password = password;
+        self
+    }
+
+    pub fn with_preload(mut self, keys: u64, value_size: usize) -> Self {
+        self.preload = keys;
+        self.preload_value_size = value_size;
+        self
+    }
+
+    pub fn with_value_size(mut self, size: Option<ObjectSize>) -> Self {
+        self.value_size = size;
+        self
+    }
+
+    pub fn with_binary_values(mut self, binary: bool) -> Self {
+        self.binary_values = binary;
+        self
+    }
+
+    pub fn with_nodes(mut self, nodes: Vec<String>, placement: NodePlacement) -> Self {
+        self.nodes = nodes;
+        self.placement = placement;
+        self
+    }
+
+    /// Connect over a Unix domain socket rather than TCP
+    pub fn with_socket(mut self, socket: Option<PathBuf>) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    pub fn with_local_addrs(mut self, local_addrs: Vec<IpAddr>) -> Self {
+        self.local_addrs = local_addrs;
+        self
+    }
+
+    /// Back off from `backoff`, doubling up to `max_backoff`, between attempts to
+    /// reopen a dropped connection
+    pub fn with_reconnect_backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.reconnect_backoff = backoff;
+        self.reconnect_max_backoff = max_backoff.max(backoff);
+        self
+    }
+
+    /// Replay the commands of a command log, in order, instead of `commands`
+    pub fn with_replay(mut self, replay: Option<CommandLog>) -> Self {
+        self.replay = replay.map(Arc::new);
+        self
+    }
+
+    /// Get the server address, `unix:///path` for a Unix domain socket
     pub fn address(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+        match &self.socket {
+            Some(socket) => format!("{}{}", UNIX_SCHEME, socket.display()),
+            None => format!("{}:{}", self.host, self.port),
+        }
+    }
+
+    /// Number of nodes commands are spread across
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() + 1
+    }
+
+    /// Address of a node, host:port (or the socket) being the first
+    fn node_address(&self, node: usize) -> String {
+        match node {
+            0 => self.address(),
+            _ => self.nodes[node - 1].clone(),
+        }
+    }
+
+    /// The node request `index` is sent to
+    fn node_for(&self, index: u64, command: &FlashKVCommand) -> usize {
+        let count = self.node_count();
+        match (self.placement, command.key()) {
+            _ if count == 1 => 0,
+            // Nodes own equal, consecutive ranges of slots, as when a cluster
+            // is created with its slots split evenly
+            (NodePlacement::KeyHash, Some(key)) => {
+                hash_slot(key) as usize * count / HASH_SLOTS as usize
+            }
+            // Commands without a key go round-robin
+            _ => (index % count as u64) as usize,
+        }
+    }
+
+    /// Tag results with the node that served them, for the per-node breakdown
+    fn label_node(&self, results: &mut [RequestResult], node: usize) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let address = self.node_address(node);
+        for result in results {
+            result.backend = Some(address.clone());
+        }
+    }
+
+    /// Why the workload could wipe data or stall the server, see
+    /// [`FlashKVCommand::danger`]
+    pub fn danger(&self) -> Option<&'static str> {
+        let logged = self.replay.iter().flat_map(|log| &log.entries);
+        let mut commands = self
+            .commands
+            .iter()
+            .chain(logged.map(|entry| &entry.command));
+        commands.find_map(|command| match command {
+            FlashKVCommand::Del { .. } if self.use_random_keys => {
+                Some("DEL on random keys deletes keys across the whole key range")
+            }
+            command => command.danger(),
+        })
+    }
+
+    /// The key preloaded as number `index`, the same a random key draws
+    fn preload_key(&self, index: u64) -> String {
+        format!("{}:{}", self.key_prefix, index)
+    }
+
+    /// Build the command for the given request index (cycling through commands)
+    fn command_for(&self, index: usize) -> FlashKVCommand {
+        let base_command = match &self.replay {
+            Some(log) => &log.entry(index as u64).command,
+            None => &self.commands[index % self.commands.len()],
+        };
+
+        let command = if self.use_random_keys {
+            base_command.with_random_key(&self.key_prefix, self.key_range)
+        } else {
+            base_command.clone()
+        };
+        match self.value_size {
+            Some(size) if command.writes_value() => {
+                command.with_values(|| random_value(size.pick() as usize, self.binary_values))
+            }
+            _ => command,
+        }
     }
 }
 
@@ -313,48 +1102,44 @@ pub async fn fire_single_request(
     config: &FlashKVConfig,
     command_index: usize,
     timeout_secs: u64,
+    metrics: &CustomMetrics,
 ) -> RequestResult {
-    let start = Instant::now();
+    fire_with_reply(config, command_index, timeout_secs, metrics)
+        .await
+        .0
+}
 
-    // Get the command to execute (cycle through commands)
-    let base_command = &config.commands[command_index % config.commands.len()];
+/// Send the first command of the workload once, outside of any run, returning
+/// its result and the server's reply
+pub async fn preview(config: &FlashKVConfig, timeout_secs: u64) -> (RequestResult, Option<String>) {
+    let (result, reply) = fire_with_reply(config, 0, timeout_secs, &CustomMetrics::default()).await;
+    (result, reply.map(|reply| reply.to_string()))
+}
 
-    // Apply random key if configured
-    let command = if config.use_random_keys {
-        base_command.with_random_key(&config.key_prefix, config.key_range)
-    } else {
-        base_command.clone()
-    };
+/// Fire a single FlashKV request, keeping the reply when there was one
+async fn fire_with_reply(
+    config: &FlashKVConfig,
+    command_index: usize,
+    timeout_secs: u64,
+    metrics: &CustomMetrics,
+) -> (RequestResult, Option<Reply>) {
+    let start = Instant::now();
 
-    let wire_command = command.to_wire_format();
+    let command = config.command_for(command_index);
+    let node = config.node_for(command_index as u64, &command);
 
     // Try to connect and send the command
-    match timeout(
+    let mut reply = None;
+    let mut result = match timeout(
         Duration::from_secs(timeout_secs),
-        execute_command(&config.address(), &wire_command),
+        execute_command(config, node, &command, metrics),
     )
     .await
     {
-        Ok(Ok((response, is_error))) => {
-            let duration = start.elapsed().as_millis();
-            let (status, success) = if is_error {
-                (status::ERROR, false)
-            } else if response.to_uppercase().contains("NIL")
-                || response.to_uppercase().contains("NOT FOUND")
-                || response.to_uppercase().contains("(nil)")
-            {
-                // Key not found is still a successful operation
-                (status::NOT_FOUND, true)
-            } else {
-                (status::OK, true)
-            };
-
-            RequestResult {
-                duration,
-                status,
-                success,
-                error: if is_error { Some(response) } else { None },
-            }
+        // The connect time is recorded apart, leaving the command round trip
+        Ok(Ok((response, round_trip))) => {
+            reply = Some(response.clone());
+            response_result(&command, response, round_trip.as_millis(), 0)
         }
         Ok(Err(e)) => {
             let duration = start.elapsed().as_millis();
@@ -362,7 +1147,22 @@ pub async fn fire_single_request(
                 duration,
                 status: status::CONNECTION_ERROR,
                 success: false,
-                error: Some(e.to_string()),
+                error: Some(ErrorKind::from_error(e.as_ref())),
+                queue_time: 0,
+                operation: Some(command.display_name()),
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
             }
         }
         Err(_) => {
@@ -371,110 +1171,805 @@ pub async fn fire_single_request(
                 duration,
                 status: status::TIMEOUT,
                 success: false,
-                error: Some("Request timed out".to_string()),
+                error: Some(ErrorKind::ResponseTimeout),
+                queue_time: 0,
+                operation: Some(command.display_name()),
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
             }
         }
+    };
+    config.label_node(std::slice::from_mut(&mut result), node);
+    (result, reply)
+}
+
+/// Interpret a one-line inline reply the way RESP would have typed it
+fn inline_reply(response: &str) -> Reply {
+    if is_error_response(response) {
+        return Reply::Error(response.to_string());
+    }
+    let upper = response.to_uppercase();
+    if upper.contains("NIL") || upper.contains("NOT FOUND") {
+        return Reply::Null;
+    }
+    // Integers come as e.g. ":1" or "(integer) 1"
+    match response
+        .trim_start_matches(':')
+        .trim_start_matches("(integer)")
+        .trim()
+        .parse()
+    {
+        Ok(n) => Reply::Integer(n),
+        Err(_) => Reply::Simple(response.to_string()),
     }
 }
 
-/// Execute a command on the FlashKV server
-async fn execute_command(address: &str, command: &str) -> Result<(String, bool)> {
-    // Connect to the server
-    let stream = TcpStream::connect(address)
+/// Read the reply to one command
+async fn read_response<R: AsyncBufRead + Unpin + Send>(
+    reader: &mut R,
+    wire: WireFormat,
+) -> Result<Reply> {
+    if wire != WireFormat::Inline {
+        return resp::read_reply(reader)
+            .await
+            .context("Failed to read response");
+    }
+    // Replies may carry binary values, which aren't valid UTF-8
+    let mut response = Vec::new();
+    let bytes_read = reader
+        .read_until(b'\n', &mut response)
         .await
-        .context("Failed to connect to FlashKV server")?;
+        .context("Failed to read response")?;
+    if bytes_read == 0 {
+        bail!("Connection closed by server");
+    }
+    Ok(inline_reply(String::from_utf8_lossy(&response).trim()))
+}
+
+/// The arguments of the AUTH command for the configured credentials
+fn auth_args(config: &FlashKVConfig) -> Option<Vec<String>> {
+    let password = config.password.clone()?;
+    let mut args = vec!["AUTH".to_string()];
+    args.extend(config.username.clone());
+    args.push(password);
+    Some(args)
+}
+
+/// Whether an error reply is the server rejecting our credentials (or lack of them)
+fn is_auth_error(message: &str) -> bool {
+    let message = message.trim_start_matches('-');
+    message.starts_with("WRONGPASS")
+        || message.starts_with("NOAUTH")
+        || message.to_lowercase().contains("invalid password")
+}
+
+/// Prepare a new connection: authenticate and switch to RESP3 when asked for
+async fn handshake<R: AsyncBufRead + Unpin + Send>(
+    reader: &mut R,
+    writer: &mut Writer,
+    config: &FlashKVConfig,
+) -> Result<()> {
+    if config.wire == WireFormat::Resp3 {
+        // HELLO takes the credentials too, saving a round trip
+        let mut hello = vec!["HELLO".to_string(), "3".to_string()];
+        if let Some(password) = &config.password {
+            let username = config.username.as_deref().unwrap_or("default");
+            hello.extend(["AUTH".to_string(), username.to_string(), password.clone()]);
+        }
+        writer
+            .write_all(&resp::encode(&hello))
+            .await
+            .context("Failed to send HELLO")?;
+        return match read_response(reader, config.wire).await? {
+            Reply::Error(e) if is_auth_error(&e) => bail!("Authentication failed: {}", e),
+            Reply::Error(e) => bail!("The server doesn't support RESP3: {}", e),
+            _ => Ok(()),
+        };
+    }
+
+    let Some(auth) = auth_args(config) else {
+        return Ok(());
+    };
+    let encoded = match config.wire {
+        WireFormat::Inline => format!("{}\r\n", auth.join(" ")).into_bytes(),
+        WireFormat::Resp2 | WireFormat::Resp3 => resp::encode(&auth),
+    };
+    writer
+        .write_all(&encoded)
+        .await
+        .context("Failed to send AUTH")?;
+    match read_response(reader, config.wire).await? {
+        Reply::Error(e) => bail!("Authentication failed: {}", e),
+        _ => Ok(()),
+    }
+}
+
+/// Classify a server reply into a request result
+fn response_result(
+    command: &FlashKVCommand,
+    reply: Reply,
+    duration: u128,
+    queue_time: u128,
+) -> RequestResult {
+    let is_error = reply.is_error();
+
+    let (status, success) = if is_error {
+        (status::ERROR, false)
+    } else if reply == Reply::Null {
+        // Key not found is still a successful operation
+        (status::NOT_FOUND, true)
+    } else {
+        (status::OK, true)
+    };
 
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader);
+    let cache_hit = match command {
+        _ if is_error => None,
+        FlashKVCommand::Get { .. } | FlashKVCommand::HGet { .. } => {
+            Some(status != status::NOT_FOUND)
+        }
+        // EXISTS replies with the number of keys found
+        FlashKVCommand::Exists { .. } => Some(matches!(reply, Reply::Integer(found) if found > 0)),
+        _ => None,
+    };
+
+    let error = match reply {
+        Reply::Error(message) => Some(ErrorKind::Other(message)),
+        _ => None,
+    };
+    RequestResult {
+        duration,
+        status,
+        success,
+        error,
+        queue_time,
+        // For the per-command breakdown of mixed workloads
+        operation: Some(command.display_name()),
+        backend: None,
+        completed_at: None,
+        cache_hit,
+        http_version: None,
+        body: None,
+        step: None,
+        tag: None,
+        grpc_status: None,
+        stream: None,
+        dns_rcode: None,
+        lost: None,
+        sent: None,
+        worker: None,
+    }
+}
+
+/// Check if a server reply indicates an error
+pub fn is_error_response(response: &str) -> bool {
+    response.starts_with("-ERR")
+        || response.starts_with("ERROR")
+        || response.starts_with("-")
+        || response.to_uppercase().starts_with("ERR")
+}
+
+/// Send a single command to the server and return its reply
+///
+/// Used outside of the measured run, e.g. to validate commands while configuring.
+pub async fn send_command(
+    config: &FlashKVConfig,
+    command: &FlashKVCommand,
+    timeout_secs: u64,
+) -> Result<Reply> {
+    timeout(
+        Duration::from_secs(timeout_secs),
+        execute_command(config, 0, command, &CustomMetrics::default()),
+    )
+    .await
+    .context("Request timed out")?
+    .map(|(reply, _)| reply)
+}
+
+/// Execute a command on a new connection to the given node, returning the
+/// reply and the command's round trip, without the connect
+async fn execute_command(
+    config: &FlashKVConfig,
+    node: usize,
+    command: &FlashKVCommand,
+    metrics: &CustomMetrics,
+) -> Result<(Reply, Duration)> {
+    let (mut reader, mut writer) = connect(config, node, metrics).await?;
+    let start = Instant::now();
 
     // Send the command
     writer
-        .write_all(command.as_bytes())
+        .write_all(&command.encode(config.wire))
         .await
         .context("Failed to send command")?;
     writer.flush().await.context("Failed to flush")?;
 
-    // Read the response (assuming line-based protocol)
-    let mut response = String::new();
-    reader
-        .read_line(&mut response)
-        .await
-        .context("Failed to read response")?;
+    let reply = read_response(&mut reader, config.wire).await?;
+    Ok((reply, start.elapsed()))
+}
 
-    let response = response.trim().to_string();
+/// Send a batch of commands over a persistent connection and read their replies
+///
+/// The server answers a pipeline in order, so a command's service time runs
+/// from when it was written, or the reply before it arrived if that was later,
+/// to its own reply. When pipelining, everything before that since the batch
+/// was issued at `issued_at` (waiting for the connection, for other nodes'
+/// batches and behind earlier commands) is reported as queue time instead of
+/// inflating the server round trip. A lone command records none.
+async fn execute_pipeline_batch(
+    config: &FlashKVConfig,
+    reader: &mut Reader,
+    writer: &mut Writer,
+    commands: Vec<FlashKVCommand>,
+    issued_at: Instant,
+) -> Result<Vec<RequestResult>> {
+    let mut written = Vec::with_capacity(commands.len());
+    for command in &commands {
+        writer
+            .write_all(&command.encode(config.wire))
+            .await
+            .context("Failed to send command")?;
+        written.push(Instant::now());
+    }
+    writer.flush().await.context("Failed to flush")?;
 
-    // Check if response indicates an error
-    let is_error = response.starts_with("-ERR")
-        || response.starts_with("ERROR")
-        || response.starts_with("-")
-        || response.to_uppercase().starts_with("ERR");
+    let mut results = Vec::with_capacity(commands.len());
+    let mut previous_reply = issued_at;
+    for (command, written_at) in commands.iter().zip(written) {
+        let reply = read_response(reader, config.wire).await?;
+        let replied_at = Instant::now();
+        let served_from = written_at.max(previous_reply);
+        previous_reply = replied_at;
+
+        let duration = (replied_at - served_from).as_millis();
+        let queue_time = if config.pipeline_depth > 1 {
+            (served_from - issued_at).as_micros()
+        } else {
+            0
+        };
+        results.push(response_result(command, reply, duration, queue_time));
+    }
 
-    Ok((response, is_error))
+    Ok(results)
 }
 
-/// Run a FlashKV load test with the given configuration
-pub async fn run_load_test(
+/// Build a failed result for every command of a batch, named by `operations`
+fn failed_batch(
+    operations: &[&'static str],
+    status: u16,
+    error: ErrorKind,
+    duration: u128,
+) -> Vec<RequestResult> {
+    operations
+        .iter()
+        .map(|&operation| RequestResult {
+            duration,
+            status,
+            success: false,
+            error: Some(error.clone()),
+            queue_time: 0,
+            operation: Some(operation),
+            backend: None,
+            completed_at: None,
+            cache_hit: None,
+            http_version: None,
+            body: None,
+            step: None,
+            tag: None,
+            grpc_status: None,
+            stream: None,
+            dns_rcode: None,
+            lost: None,
+            sent: None,
+            worker: None,
+        })
+        .collect()
+}
+
+/// Read half of a connection, over TCP or a Unix domain socket
+type Reader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+/// Write half of a connection, over TCP or a Unix domain socket
+type Writer = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// A persistent connection, ready for commands
+type Connection = (Reader, Writer);
+
+/// The next of the source addresses, which connections take in turn
+static NEXT_LOCAL_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// Open a stream to a node address, a `unix://` one being a socket path
+async fn open_stream(address: &str, local_addrs: &[IpAddr]) -> Result<(Reader, Writer)> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix(UNIX_SCHEME) {
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("Failed to connect to FlashKV socket {}", path))?;
+        let (reader, writer) = stream.into_split();
+        return Ok((BufReader::new(Box::new(reader)), Box::new(writer)));
+    }
+
+    let stream = match local_addrs {
+        [] => TcpStream::connect(address).await,
+        _ => {
+            let next = NEXT_LOCAL_ADDR.fetch_add(1, Ordering::Relaxed);
+            connect_from(address, local_addrs[next % local_addrs.len()]).await
+        }
+    }
+    .context("Failed to connect to FlashKV server")?;
+    let (reader, writer) = stream.into_split();
+    Ok((BufReader::new(Box::new(reader)), Box::new(writer)))
+}
+
+/// Connect to a `host:port` address from the given source address
+async fn connect_from(address: &str, local_addr: IpAddr) -> std::io::Result<TcpStream> {
+    let target = tokio::net::lookup_host(address)
+        .await?
+        .find(|target| target.is_ipv4() == local_addr.is_ipv4())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                format!("{} has no address reachable from {}", address, local_addr),
+            )
+        })?;
+    let socket = if local_addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.bind(SocketAddr::new(local_addr, 0))?;
+    socket.connect(target).await
+}
+
+/// Open a persistent connection to the given node, timing it as [`CONNECT_TIME`]
+async fn connect(
     config: &FlashKVConfig,
-    num_requests: u64,
-    concurrency: u64,
+    node: usize,
+    metrics: &CustomMetrics,
+) -> Result<Connection> {
+    let start = Instant::now();
+    let (mut reader, mut writer) =
+        open_stream(&config.node_address(node), &config.local_addrs).await?;
+    metrics.increment(CONNECTIONS_OPENED, 1);
+    handshake(&mut reader, &mut writer, config).await?;
+    metrics.time(CONNECT_TIME, start.elapsed().as_secs_f64() * 1000.0);
+    Ok((reader, writer))
+}
+
+/// A worker's persistent connection to one node
+#[derive(Default)]
+struct NodeConnection {
+    connection: Option<Connection>,
+    /// Set when an open connection failed, so the next one is a reconnect
+    dropped: bool,
+}
+
+/// Connect to a node within the request timeout
+async fn connect_within(
+    config: &FlashKVConfig,
+    node: usize,
+    request_timeout: Duration,
+    metrics: &CustomMetrics,
+) -> Result<Connection> {
+    match timeout(request_timeout, connect(config, node, metrics)).await {
+        Ok(connected) => connected,
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "Timed out connecting to FlashKV server",
+        )
+        .into()),
+    }
+}
+
+/// Reopen a dropped connection, backing off exponentially between attempts
+///
+/// Gives up after [`RECONNECT_ATTEMPTS`] failed attempts, or when the run is
+/// stopped while waiting.
+async fn reconnect(
+    config: &FlashKVConfig,
+    node: usize,
+    request_timeout: Duration,
+    metrics: &CustomMetrics,
+    results: &Recorder,
+) -> Result<Connection> {
+    let mut backoff = config.reconnect_backoff;
+    let mut attempt = 1;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = results.stopped() => bail!("Stopped while reconnecting"),
+        }
+        match connect_within(config, node, request_timeout, metrics).await {
+            Ok(connection) => {
+                metrics.increment(RECONNECTS, 1);
+                return Ok(connection);
+            }
+            Err(e) if attempt >= RECONNECT_ATTEMPTS => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                backoff = (backoff * 2).min(config.reconnect_max_backoff);
+            }
+        }
+    }
+}
+
+/// Pipeline commands issued at `issued_at` to one node over its persistent
+/// connection, opening it first if needed
+///
+/// On an error the connection is dropped and every command of the batch
+/// fails. The next batch waits for it to be reopened with backoff, and fails
+/// only when that doesn't succeed either, so a server restart doesn't fail
+/// every request in the meantime.
+#[allow(clippy::too_many_arguments)]
+async fn send_to_node(
+    config: &FlashKVConfig,
+    node: usize,
+    state: &mut NodeConnection,
+    commands: Vec<FlashKVCommand>,
+    issued_at: Instant,
+    request_timeout: Duration,
+    metrics: &CustomMetrics,
+    results: &Recorder,
+) -> Vec<RequestResult> {
+    let operations: Vec<&'static str> = commands.iter().map(|c| c.display_name()).collect();
+    let start = Instant::now();
+
+    if state.connection.is_none() {
+        let connected = if state.dropped {
+            // Until the next drop, connect failures fail their batch right away
+            state.dropped = false;
+            reconnect(config, node, request_timeout, metrics, results).await
+        } else {
+            connect_within(config, node, request_timeout, metrics).await
+        };
+        match connected {
+            Ok(opened) => state.connection = Some(opened),
+            Err(e) => {
+                let error = ErrorKind::from_error(e.as_ref());
+                let status = match error {
+                    ErrorKind::ConnectTimeout => status::TIMEOUT,
+                    _ => status::CONNECTION_ERROR,
+                };
+                return failed_batch(&operations, status, error, start.elapsed().as_millis());
+            }
+        }
+    }
+
+    let (reader, writer) = state
+        .connection
+        .as_mut()
+        .expect("connection established above");
+    match timeout(
+        request_timeout,
+        execute_pipeline_batch(config, reader, writer, commands, issued_at),
+    )
+    .await
+    {
+        Ok(Ok(results)) => results,
+        Ok(Err(e)) => {
+            // The connection is in an unknown state, reconnect for the next batch
+            state.connection = None;
+            state.dropped = true;
+            failed_batch(
+                &operations,
+                status::CONNECTION_ERROR,
+                ErrorKind::from_error(e.as_ref()),
+                start.elapsed().as_millis(),
+            )
+        }
+        Err(_) => {
+            state.connection = None;
+            state.dropped = true;
+            failed_batch(
+                &operations,
+                status::TIMEOUT,
+                ErrorKind::ResponseTimeout,
+                start.elapsed().as_millis(),
+            )
+        }
+    }
+}
+
+/// Worker that keeps one persistent connection per node and sends batches of
+/// commands over them, reconnecting after an error
+///
+/// Claims batches of `pipeline_depth` request indices from `next_index` until
+/// `end` is reached. The commands of a batch are pipelined to their nodes one
+/// node after the other. A replay keeping the logged timing holds each batch
+/// until its first command is due, counted from `start`.
+async fn connection_worker(
+    config: Arc<FlashKVConfig>,
+    next_index: Arc<AtomicU64>,
+    start: Instant,
+    end: u64,
     timeout_secs: u64,
-) -> Result<LoadTestStats> {
-    let config = Arc::new(config.clone());
-    let semaphore = Arc::new(Semaphore::new(concurrency as usize));
+    results: Recorder,
+    metrics: CustomMetrics,
+) {
+    let depth = config.pipeline_depth as u64;
+    let request_timeout = Duration::from_secs(timeout_secs);
+    let mut connections: Vec<NodeConnection> = (0..config.node_count())
+        .map(|_| NodeConnection::default())
+        .collect();
+
+    while !results.is_stopped() {
+        let first = next_index.fetch_add(depth, Ordering::Relaxed);
+        if first >= end {
+            break;
+        }
+        if let Some(due) = config.replay.as_ref().and_then(|log| log.due(first)) {
+            tokio::select! {
+                _ = tokio::time::sleep_until((start + due).into()) => {}
+                _ = results.stopped() => break,
+            }
+        }
 
-    // Create progress bar
-    let pb = ProgressBar::new(num_requests);
+        let issued_at = Instant::now();
+        let mut by_node = vec![Vec::new(); config.node_count()];
+        for index in first..(first + depth).min(end) {
+            let command = config.command_for(index as usize);
+            by_node[config.node_for(index, &command)].push(command);
+        }
+
+        for (node, commands) in by_node.into_iter().enumerate() {
+            if commands.is_empty() {
+                continue;
+            }
+            let mut node_results = send_to_node(
+                &config,
+                node,
+                &mut connections[node],
+                commands,
+                issued_at,
+                request_timeout,
+                &metrics,
+                &results,
+            )
+            .await;
+            config.label_node(&mut node_results, node);
+            results.extend(node_results);
+        }
+    }
+}
+
+/// A random value of alphanumeric characters, or of any bytes when `binary`
+fn random_value(size: usize, binary: bool) -> Vec<u8> {
+    let mut rng = rand::rng();
+    if binary {
+        let mut value = vec![0; size];
+        rng.fill(&mut value[..]);
+        return value;
+    }
+    rng.sample_iter(rand::distr::Alphanumeric)
+        .take(size)
+        .collect()
+}
+
+/// SET preloaded keys, claiming batches from `next` until all are written
+async fn preload_worker(
+    config: Arc<FlashKVConfig>,
+    next: Arc<AtomicU64>,
+    request_timeout: Duration,
+    pb: ProgressBar,
+) -> Result<()> {
+    let mut connections: Vec<Option<Connection>> = (0..config.node_count()).map(|_| None).collect();
+
+    loop {
+        let first = next.fetch_add(PRELOAD_BATCH, Ordering::Relaxed);
+        if first >= config.preload {
+            return Ok(());
+        }
+
+        let mut by_node = vec![Vec::new(); config.node_count()];
+        for index in first..(first + PRELOAD_BATCH).min(config.preload) {
+            let set = FlashKVCommand::Set {
+                key: config.preload_key(index),
+                value: random_value(config.preload_value_size, config.binary_values),
+            };
+            by_node[config.node_for(index, &set)].push(set);
+        }
+
+        for (node, sets) in by_node.into_iter().enumerate() {
+            if sets.is_empty() {
+                continue;
+            }
+            let (reader, writer) = match &mut connections[node] {
+                Some(connection) => connection,
+                empty => empty.insert(
+                    timeout(
+                        request_timeout,
+                        connect(&config, node, &CustomMetrics::default()),
+                    )
+                    .await
+                    .context("Timed out connecting")??,
+                ),
+            };
+
+            let write_batch = async {
+                for set in &sets {
+                    writer
+                        .write_all(&set.encode(config.wire))
+                        .await
+                        .context("Failed to send SET")?;
+                }
+                writer.flush().await.context("Failed to flush")?;
+                for _ in &sets {
+                    if let Reply::Error(e) = read_response(reader, config.wire).await? {
+                        bail!("SET failed: {}", e);
+                    }
+                }
+                Ok(())
+            };
+            timeout(request_timeout, write_batch)
+                .await
+                .context("Timed out waiting for SET replies")??;
+            pb.inc(sets.len() as u64);
+        }
+    }
+}
+
+/// SET `config.preload` keys before the measured run, so reads find data
+async fn preload(config: &FlashKVConfig, timeout_secs: u64, quiet: bool) -> Result<()> {
+    let pb = ProgressBar::new(config.preload);
     pb.set_style(
         ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.magenta/blue}] {pos}/{len} ({percent}%) {msg}")
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.magenta/blue}] {pos}/{len} {msg}",
+            )
             .unwrap()
             .progress_chars("█▓▒░  "),
     );
+    pb.set_message("Preloading keys...");
+    if quiet {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
 
-    let commands_desc = config
-        .commands
-        .iter()
-        .map(|c| c.display_name())
-        .collect::<Vec<_>>()
-        .join(", ");
-    pb.set_message(format!("Sending FlashKV commands: {}...", commands_desc));
-
-    let overall_start = Instant::now();
-
-    // Spawn all tasks
-    let mut handles = Vec::with_capacity(num_requests as usize);
-
-    for i in 0..num_requests {
-        let config = Arc::clone(&config);
-        let semaphore = Arc::clone(&semaphore);
-        let pb = pb.clone();
-
-        let handle = tokio::spawn(async move {
-            let _permit = semaphore.acquire().await.unwrap();
-            let result = fire_single_request(&config, i as usize, timeout_secs).await;
-            pb.inc(1);
-            result
-        });
+    let config = Arc::new(config.clone());
+    let next = Arc::new(AtomicU64::new(0));
+    let workers: Vec<_> = (0..PRELOAD_CONNECTIONS.min(config.preload))
+        .map(|_| {
+            tokio::spawn(preload_worker(
+                Arc::clone(&config),
+                Arc::clone(&next),
+                Duration::from_secs(timeout_secs),
+                pb.clone(),
+            ))
+        })
+        .collect();
+    for worker in workers {
+        worker.await.context("Preload worker panicked")??;
+    }
 
-        handles.push(handle);
+    pb.finish_and_clear();
+    if !quiet {
+        println!(
+            "Preloaded {} keys of {} bytes in {:.1?}",
+            config.preload,
+            config.preload_value_size,
+            pb.elapsed()
+        );
     }
+    Ok(())
+}
+
+/// Sends FlashKV commands over one persistent connection per worker, or over a
+/// new connection per command without keepalive
+struct FlashKVExecutor {
+    config: Arc<FlashKVConfig>,
+    timeout_secs: u64,
+    metrics: CustomMetrics,
+}
 
-    // Collect results
-    let mut results = Vec::with_capacity(num_requests as usize);
-    for handle in handles {
-        if let Ok(result) = handle.await {
-            results.push(result);
+impl RequestExecutor for FlashKVExecutor {
+    fn progress_message(&self) -> String {
+        let server = match self.config.wire {
+            WireFormat::Inline => "FlashKV",
+            WireFormat::Resp2 | WireFormat::Resp3 => "Redis",
+        };
+        if let Some(log) = &self.config.replay {
+            return format!(
+                "Replaying {} logged {} commands...",
+                log.entries.len(),
+                server
+            );
         }
+        let commands_desc = self
+            .config
+            .commands
+            .iter()
+            .map(|c| c.display_name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Sending {} commands: {}...", server, commands_desc)
     }
 
-    let total_duration = overall_start.elapsed().as_millis();
+    fn progress_color(&self) -> &'static str {
+        "magenta"
+    }
 
-    pb.finish_with_message("Complete!");
+    fn due(&self, seq: u64) -> Option<Duration> {
+        // A replay keeping the logged timing holds each command until it's due
+        self.config.replay.as_ref().and_then(|log| log.due(seq))
+    }
 
-    // Calculate statistics
-    let stats = calculate_stats(&results, total_duration);
+    async fn execute(&self, iteration: Iteration) {
+        let result = fire_single_request(
+            &self.config,
+            iteration.seq as usize,
+            self.timeout_secs,
+            &self.metrics,
+        )
+        .await;
+        iteration.recorder.record(result);
+    }
 
-    Ok(stats)
+    fn run_stage(self: &Arc<Self>, stage: &StageRun) -> Option<StageFuture> {
+        if !self.config.keepalive {
+            return None;
+        }
+
+        // One persistent connection per concurrent worker
+        let next_index = Arc::new(AtomicU64::new(stage.seqs.start));
+        let workers: Vec<_> = (0..stage.concurrency)
+            .map(|worker| {
+                let delay = stage.startup.delay(worker, stage.concurrency);
+                let worker = connection_worker(
+                    Arc::clone(&self.config),
+                    Arc::clone(&next_index),
+                    stage.start,
+                    stage.seqs.end,
+                    self.timeout_secs,
+                    stage.recorder.for_worker(worker),
+                    self.metrics.clone(),
+                );
+                async move {
+                    tokio::time::sleep(delay).await;
+                    worker.await
+                }
+            })
+            .collect();
+
+        Some(Box::pin(async move {
+            let handles: Vec<_> = workers.into_iter().map(tokio::spawn).collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        }))
+    }
+}
+
+/// Run a FlashKV load test with the given configuration
+pub async fn run_load_test(
+    config: &FlashKVConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    check_local_addrs(&config.local_addrs)?;
+    if config.preload > 0 {
+        preload(config, timeout_secs, quiet)
+            .await
+            .context("Failed to preload keys")?;
+    }
+
+    let executor = FlashKVExecutor {
+        config: Arc::new(config.clone()),
+        timeout_secs,
+        metrics: live.metrics().clone(),
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
 }
 
 #[cfg(test)]
@@ -505,7 +2000,7 @@ mod tests {
             cmd,
             FlashKVCommand::Set {
                 key: "mykey".to_string(),
-                value: "myvalue".to_string()
+                value: b"myvalue".to_vec()
             }
         );
     }
@@ -517,7 +2012,7 @@ mod tests {
             cmd,
             FlashKVCommand::Set {
                 key: "mykey".to_string(),
-                value: "hello world".to_string()
+                value: b"hello world".to_vec()
             }
         );
     }
@@ -578,22 +2073,65 @@ mod tests {
 
     #[test]
     fn test_wire_format() {
-        assert_eq!(FlashKVCommand::Ping.to_wire_format(), "PING\r\n");
+        assert_eq!(FlashKVCommand::Ping.to_wire_format(), b"PING\r\n");
         assert_eq!(
             FlashKVCommand::Get {
                 key: "test".to_string()
             }
             .to_wire_format(),
-            "GET test\r\n"
+            b"GET test\r\n"
         );
         assert_eq!(
             FlashKVCommand::Set {
                 key: "test".to_string(),
-                value: "value".to_string()
+                value: b"value".to_vec()
             }
             .to_wire_format(),
-            "SET test value\r\n"
+            b"SET test value\r\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_safe_values() {
+        let set = |value: &[u8]| FlashKVCommand::Set {
+            key: "k".to_string(),
+            value: value.to_vec(),
+        };
+        assert_eq!(
+            set(b"two words").to_wire_format(),
+            b"SET k \"two words\"\r\n"
+        );
+        assert_eq!(
+            set(b"a\"b\\c\r\n").to_wire_format(),
+            b"SET k \"a\\\"b\\\\c\\r\\n\"\r\n"
+        );
+        assert_eq!(
+            set(&[0x00, 0xff]).to_wire_format(),
+            b"SET k \"\\x00\\xff\"\r\n"
+        );
+        assert_eq!(set(b"").to_wire_format(), b"SET k \"\"\r\n");
+        assert_eq!(
+            set(&[0x00, b'\n']).encode(WireFormat::Resp2),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$2\r\n\x00\n\r\n"
+        );
+
+        // Quoted values parse back to the same bytes
+        for value in [&b"two words"[..], b"a\"b\\c\r\n", &[0x00, 0xff], b""] {
+            let line = String::from_utf8(set(value).to_wire_format()).unwrap();
+            assert_eq!(FlashKVCommand::from_str(&line).unwrap(), set(value));
+        }
+        assert_eq!(
+            split_args("SET k 'it\\'s' \"\\x41\\t\"").unwrap(),
+            vec![
+                b"SET".to_vec(),
+                b"k".to_vec(),
+                b"it's".to_vec(),
+                b"A\t".to_vec()
+            ]
         );
+        assert!(FlashKVCommand::from_str("SET k \"open").is_err());
+        assert!(FlashKVCommand::from_str("SET k \"a\"b").is_err());
+        assert!(FlashKVCommand::from_str("SET k \"\\xzz\"").is_err());
     }
 
     #[test]
@@ -613,12 +2151,338 @@ mod tests {
         assert!(config.use_random_keys);
         assert_eq!(config.key_prefix, "prefix");
         assert_eq!(config.key_range, 500);
+        assert_eq!(config.pipeline_depth, 1);
+    }
+
+    #[test]
+    fn test_value_size() {
+        let config = FlashKVConfig::new("localhost".to_string(), 6379)
+            .with_commands(vec![
+                FlashKVCommand::from_str("SET user testvalue").unwrap(),
+                FlashKVCommand::from_str("GET user").unwrap(),
+            ])
+            .with_value_size(Some(ObjectSize::parse("16-32").unwrap()));
+
+        match config.command_for(0) {
+            FlashKVCommand::Set { key, value } => {
+                assert_eq!(key, "user");
+                assert!((16..=32).contains(&value.len()));
+            }
+            other => panic!("Expected SET, got {:?}", other),
+        }
+        assert_eq!(
+            config.command_for(1),
+            FlashKVCommand::Get {
+                key: "user".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_preload_keys() {
+        let config = FlashKVConfig::new("localhost".to_string(), 6379)
+            .with_random_keys(true, "user".to_string(), 100)
+            .with_preload(100, 16);
+        // Preloaded keys are the ones random keys draw from
+        assert_eq!(config.preload_key(7), "user:7");
+        let value = random_value(config.preload_value_size, false);
+        assert_eq!(value.len(), 16);
+        assert!(value.iter().all(u8::is_ascii_alphanumeric));
+        assert_eq!(random_value(300, true).len(), 300);
+    }
+
+    #[test]
+    fn test_node_placement() {
+        // Reference slots from the Redis Cluster specification
+        assert_eq!(hash_slot("123456789"), 0x31c3);
+        assert_eq!(hash_slot("{user1000}.following"), hash_slot("user1000"));
+        assert_eq!(hash_slot("foo{}{bar}"), hash_slot("foo{}{bar}"));
+        assert_ne!(hash_slot("foo{}{bar}"), hash_slot("bar"));
+
+        let get = FlashKVCommand::from_str("GET user:1").unwrap();
+        let config = FlashKVConfig::new("10.0.0.1".to_string(), 7000);
+        assert_eq!(config.node_for(5, &get), 0);
+
+        let config = config.with_nodes(
+            vec!["10.0.0.2:7000".to_string(), "10.0.0.3:7000".to_string()],
+            NodePlacement::KeyHash,
+        );
+        assert_eq!(config.node_count(), 3);
+        assert_eq!(config.node_address(2), "10.0.0.3:7000");
+        let node = config.node_for(0, &get);
+        assert!((0..3).all(|index| config.node_for(index, &get) == node));
+        // PING has no key and goes round-robin
+        assert_eq!(config.node_for(4, &FlashKVCommand::Ping), 1);
+
+        let nodes = config.nodes.clone();
+        let config = config.with_nodes(nodes, NodePlacement::RoundRobin);
+        assert_eq!(config.node_for(0, &get), 0);
+        assert_eq!(config.node_for(5, &get), 2);
+
+        assert_eq!(
+            NodePlacement::from_str("rr").unwrap(),
+            NodePlacement::RoundRobin
+        );
+        assert!(NodePlacement::from_str("random").is_err());
+    }
+
+    #[test]
+    fn test_destructive_commands() {
+        let danger = |s: &str| FlashKVCommand::from_str(s).unwrap().danger();
+        assert!(danger("FLUSHDB").is_some());
+        assert!(danger("flushall async").is_some());
+        assert!(danger("KEYS *").is_some());
+        assert!(danger("DEL user:*").is_some());
+        assert_eq!(danger("KEYS user:1"), None);
+        assert_eq!(danger("DEL user:1"), None);
+        assert_eq!(danger("GET *"), None);
+
+        let config = FlashKVConfig::new("localhost".to_string(), 6379)
+            .with_commands(vec![FlashKVCommand::from_str("DEL user").unwrap()]);
+        assert_eq!(config.danger(), None);
+        let config = config.with_random_keys(true, "user".to_string(), 100);
+        assert!(config.danger().unwrap().contains("random keys"));
+    }
+
+    #[test]
+    fn test_pipeline_depth_minimum() {
+        let config = FlashKVConfig::new("localhost".to_string(), 6379).with_pipeline_depth(0);
+        assert_eq!(config.pipeline_depth, 1);
+
+        let config = config.with_pipeline_depth(16);
+        assert_eq!(config.pipeline_depth, 16);
+    }
+
+    #[test]
+    fn test_auth_args() {
+        let config = FlashKVConfig::new("localhost".to_string(), 6379);
+        assert_eq!(auth_args(&config), None);
+
+        let config = config.with_auth(None, Some("secret".to_string()));
+        assert_eq!(auth_args(&config).unwrap(), vec!["AUTH", "secret"]);
+
+        let config = config.with_auth(Some("app".to_string()), Some("secret".to_string()));
+        assert_eq!(auth_args(&config).unwrap(), vec!["AUTH", "app", "secret"]);
+
+        assert!(is_auth_error("WRONGPASS invalid username-password pair"));
+        assert!(is_auth_error("-NOAUTH Authentication required."));
+        assert!(!is_auth_error("NOPROTO unsupported protocol version"));
+    }
+
+    #[test]
+    fn test_is_error_response() {
+        assert!(is_error_response("-ERR unknown command 'FOO'"));
+        assert!(is_error_response("ERROR wrong number of arguments"));
+        assert!(!is_error_response("+OK"));
+        assert!(!is_error_response("PONG"));
+    }
+
+    #[test]
+    fn test_response_result_classification() {
+        let ok = response_result(&FlashKVCommand::Ping, inline_reply("+OK"), 1, 0);
+        assert!(ok.success);
+        assert_eq!(ok.status, status::OK);
+        assert_eq!(ok.cache_hit, None);
+
+        let get = FlashKVCommand::from_str("GET key").unwrap();
+        let miss = response_result(&get, inline_reply("(nil)"), 1, 0);
+        assert!(miss.success);
+        assert_eq!(miss.status, status::NOT_FOUND);
+        assert_eq!(miss.cache_hit, Some(false));
+        assert_eq!(
+            response_result(&get, inline_reply("value"), 1, 0).cache_hit,
+            Some(true)
+        );
+
+        let exists = FlashKVCommand::from_str("EXISTS key").unwrap();
+        assert_eq!(
+            response_result(&exists, inline_reply(":1"), 1, 0).cache_hit,
+            Some(true)
+        );
+        assert_eq!(
+            response_result(&exists, inline_reply("(integer) 0"), 1, 0).cache_hit,
+            Some(false)
+        );
+
+        let err = response_result(&get, inline_reply("-ERR unknown command"), 1, 250);
+        assert!(!err.success);
+        assert_eq!(err.status, status::ERROR);
+        assert_eq!(err.queue_time, 250);
+        assert_eq!(err.cache_hit, None);
+    }
+
+    #[test]
+    fn test_resp_commands_and_replies() {
+        let set = FlashKVCommand::from_str("SET greeting hello world").unwrap();
+        assert_eq!(
+            set.encode(WireFormat::Resp2),
+            b"*3\r\n$3\r\nSET\r\n$8\r\ngreeting\r\n$11\r\nhello world\r\n"
+        );
+        assert_eq!(
+            set.encode(WireFormat::Inline),
+            b"SET greeting \"hello world\"\r\n"
+        );
+        let expire = FlashKVCommand::from_str("EXPIRE key 60").unwrap();
+        assert_eq!(expire.args(), [&b"EXPIRE"[..], b"key", b"60"]);
+
+        // Replies are classified by type, not by their text
+        let get = FlashKVCommand::from_str("GET key").unwrap();
+        let hit = response_result(&get, Reply::Bulk(b"vanilla".to_vec()), 1, 0);
+        assert_eq!((hit.status, hit.cache_hit), (status::OK, Some(true)));
+        assert_eq!(hit.operation, Some("GET"));
+        let miss = response_result(&get, Reply::Null, 1, 0);
+        assert_eq!(
+            (miss.status, miss.cache_hit),
+            (status::NOT_FOUND, Some(false))
+        );
+        let err = response_result(&get, Reply::Error("WRONGTYPE".to_string()), 1, 0);
+        assert_eq!(err.error, Some(ErrorKind::Other("WRONGTYPE".to_string())));
+
+        // Failed batches keep the command of every request, for the per-command breakdown
+        let failed = failed_batch(
+            &["SET", "GET"],
+            status::TIMEOUT,
+            ErrorKind::ResponseTimeout,
+            5,
+        );
+        assert_eq!(failed[1].operation, Some("GET"));
+        assert!(!failed[0].success);
     }
 
     #[test]
     fn test_address() {
         let config = FlashKVConfig::new("127.0.0.1".to_string(), 6379);
         assert_eq!(config.address(), "127.0.0.1:6379");
+
+        let config = config.with_socket(Some(PathBuf::from("/var/run/flashkv.sock")));
+        assert_eq!(config.address(), "unix:///var/run/flashkv.sock");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let path = std::env::temp_dir().join(format!("rustyload-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            assert_eq!(line, "PING\r\n");
+            writer.write_all(b"PONG\r\n").await.unwrap();
+        });
+
+        let config = FlashKVConfig::new(String::new(), 0).with_socket(Some(path.clone()));
+        let reply = send_command(&config, &FlashKVCommand::Ping, 5)
+            .await
+            .unwrap();
+        assert_eq!(reply, Reply::Simple("PONG".to_string()));
+        server.await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_preview() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            assert_eq!(line, "GET user\r\n");
+            writer.write_all(b"alice\r\n").await.unwrap();
+        });
+
+        let config = FlashKVConfig::new("127.0.0.1".to_string(), port)
+            .with_commands(vec![FlashKVCommand::from_str("GET user").unwrap()]);
+        let (result, reply) = preview(&config, 5).await;
+        assert!(result.success);
+        assert_eq!(result.operation, Some("GET"));
+        assert_eq!(reply.as_deref(), Some("alice"));
+        server.await.unwrap();
+
+        // Nothing listens there any more
+        let (result, reply) = preview(&config, 5).await;
+        assert!(!result.success);
+        assert_eq!(reply, None);
+    }
+
+    #[tokio::test]
+    async fn test_unpipelined_run_has_no_queue_time() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(_)) = lines.next_line().await {
+                writer.write_all(b"PONG\r\n").await.unwrap();
+            }
+        });
+
+        let config = crate::protocols::LoadTestConfig::new_flashkv(
+            "127.0.0.1".to_string(),
+            port,
+            vec![FlashKVCommand::Ping],
+            50,
+            1,
+        );
+        assert!(!config.queues_requests());
+        let stats = config.run(true, &LiveResults::default()).await.unwrap();
+        assert_eq!(stats.successful_requests, 50);
+        assert_eq!(stats.max_queue_time, 0.0);
+        assert!(!crate::hints::analyze(&stats, &config)
+            .iter()
+            .any(|hint| hint.contains("client queue")));
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_queue_time_and_duration_dont_overlap() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            // Take a while over each command, one after the other
+            while let Ok(Some(_)) = lines.next_line().await {
+                tokio::time::sleep(Duration::from_millis(40)).await;
+                writer.write_all(b"PONG\r\n").await.unwrap();
+            }
+        });
+
+        let config = FlashKVConfig::new("127.0.0.1".to_string(), port).with_pipeline_depth(3);
+        let issued_at = Instant::now();
+        // The batch waits for its connection before it is written
+        let (mut reader, mut writer) = connect(&config, 0, &CustomMetrics::default())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let results = execute_pipeline_batch(
+            &config,
+            &mut reader,
+            &mut writer,
+            vec![FlashKVCommand::Ping; 3],
+            issued_at,
+        )
+        .await
+        .unwrap();
+        let elapsed = issued_at.elapsed();
+
+        assert!(results.iter().all(|r| r.success));
+        assert!(results[0].queue_time >= 20_000, "{:?}", results[0]);
+        // The last command waited behind both earlier replies
+        assert!(results[2].queue_time >= 100_000, "{:?}", results[2]);
+        for result in &results {
+            let total = Duration::from_micros(result.queue_time as u64)
+                + Duration::from_millis(result.duration as u64);
+            assert!(total <= elapsed, "{:?} vs {:?}", result, elapsed);
+        }
+        // Each command is served after the one before it, not from the write
+        let served: u128 = results.iter().map(|r| r.duration).sum();
+        assert!(served <= elapsed.as_millis(), "{:?}", results);
     }
 
     #[test]
@@ -635,6 +2499,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_data_structure_commands() {
+        let args = |s: &str| {
+            String::from_utf8(FlashKVCommand::from_str(s).unwrap().args().join(&b' ')).unwrap()
+        };
+        assert_eq!(
+            args("hset user:1 name Ada Lovelace"),
+            "HSET user:1 name Ada Lovelace"
+        );
+        assert_eq!(args("HGET user:1 name"), "HGET user:1 name");
+        assert_eq!(args("SADD tags a b"), "SADD tags a b");
+        assert_eq!(args("SMEMBERS tags"), "SMEMBERS tags");
+        assert_eq!(args("ZADD board 1.5 ada"), "ZADD board 1.5 ada");
+        assert_eq!(args("ZRANGE board 0 -1"), "ZRANGE board 0 -1");
+        assert_eq!(args("MGET a b c"), "MGET a b c");
+        assert_eq!(args("MSET a 1 b 2"), "MSET a 1 b 2");
+        assert_eq!(args("SETEX session 60 token"), "SETEX session 60 token");
+        assert_eq!(
+            FlashKVCommand::from_str("HGET user:1 name")
+                .unwrap()
+                .to_wire_format(),
+            b"HGET user:1 name\r\n"
+        );
+
+        assert!(FlashKVCommand::from_str("MSET a 1 b").is_err());
+        assert!(FlashKVCommand::from_str("ZADD board high ada").is_err());
+        assert!(FlashKVCommand::from_str("SETEX session soon token").is_err());
+
+        // Multi-key commands draw every key, and keep their values
+        let mset = FlashKVCommand::from_str("MSET a 1 b 2").unwrap();
+        match mset.with_random_key("k", 1000) {
+            FlashKVCommand::MSet { pairs } => {
+                assert!(pairs.iter().all(|(k, _)| k.starts_with("k:")));
+                assert_eq!(pairs[1].1, b"2");
+            }
+            other => panic!("Expected MSET, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_ping_no_random_key() {
         let cmd = FlashKVCommand::Ping;
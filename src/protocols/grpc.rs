@@ -0,0 +1,294 @@
+//! gRPC protocol implementation for load testing
+//!
+//! Requests are unary calls described by a `.proto` file: the JSON request is
+//! encoded once with the method's input message and sent over HTTP/2 with every
+//! call. Calls are classified by the `grpc-status` the server returns.
+
+use crate::hooks::Hooks;
+use crate::proto::ProtoSchema;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::http::{before_request, error_kind, version_name};
+use crate::protocols::{
+    BodySize, ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup,
+};
+use anyhow::{bail, Context, Result};
+use http_body_util::BodyExt;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, TE};
+use reqwest::Client;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// gRPC status codes by number
+pub const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// Configuration for gRPC load testing
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    /// Server URL, e.g. `http://localhost:50051` (`https://` for TLS)
+    pub url: String,
+    /// The `.proto` file the method was read from
+    pub proto: PathBuf,
+    /// `package.Service/Method`
+    pub method: String,
+    /// The request message, encoded as protobuf
+    pub message: Vec<u8>,
+    /// Metadata sent with every call
+    pub metadata: Vec<(String, String)>,
+}
+
+impl GrpcConfig {
+    /// Look up `method` in the `.proto` file and encode `request` as its input
+    pub fn new(
+        url: String,
+        proto: &Path,
+        method: &str,
+        request: &serde_json::Value,
+    ) -> Result<Self> {
+        let schema = ProtoSchema::load(proto)?;
+        let method = schema.method(method)?;
+        if method.streaming {
+            bail!(
+                "{} is a streaming method, only unary calls are supported",
+                method.path
+            );
+        }
+        let message = schema
+            .encode(&method.input, request)
+            .with_context(|| format!("Failed to encode the request as {}", method.input))?;
+        Ok(Self {
+            url,
+            proto: proto.to_path_buf(),
+            method: method.path.clone(),
+            message,
+            metadata: Vec::new(),
+        })
+    }
+
+    pub fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// The request message in a gRPC frame: uncompressed flag, length, message
+    fn frame(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(self.message.len() + 5);
+        frame.push(0);
+        frame.extend((self.message.len() as u32).to_be_bytes());
+        frame.extend(&self.message);
+        frame
+    }
+}
+
+/// Name of the `grpc-status` in headers or trailers
+fn grpc_status(headers: &HeaderMap) -> Option<&'static str> {
+    let code: usize = headers
+        .get("grpc-status")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(STATUS_NAMES.get(code).copied().unwrap_or("UNKNOWN"))
+}
+
+/// Make one unary call
+async fn fire_single_request(
+    client: &Client,
+    config: &GrpcConfig,
+    frame: &[u8],
+    seq: u64,
+    hooks: Option<&dyn Hooks>,
+) -> RequestResult {
+    let start = Instant::now();
+    let mut request_builder = client
+        .post(format!(
+            "{}/{}",
+            config.url.trim_end_matches('/'),
+            config.method
+        ))
+        .header(CONTENT_TYPE, "application/grpc")
+        .header(TE, "trailers")
+        .body(frame.to_vec());
+    for (key, value) in &config.metadata {
+        request_builder = request_builder.header(key, value);
+    }
+
+    let request = request_builder
+        .build()
+        .map_err(|e| error_kind(&e))
+        .and_then(|mut request| match hooks {
+            Some(hooks) => before_request(hooks, seq, &mut request)
+                .map(|()| request)
+                .map_err(|e| ErrorKind::Other(format!("before_request hook: {}", e))),
+            None => Ok(request),
+        });
+    let sent = match request {
+        Ok(request) => client.execute(request).await.map_err(|e| error_kind(&e)),
+        Err(error) => Err(error),
+    };
+    let response = match sent {
+        Ok(response) => response,
+        Err(error) => {
+            return RequestResult {
+                duration: start.elapsed().as_millis(),
+                error: Some(error),
+                ..Default::default()
+            }
+        }
+    };
+
+    let status = response.status().as_u16();
+    let http_version = Some(version_name(response.version()));
+    // A call that fails before any message has a "trailers-only" response,
+    // with the status in the headers
+    let header_status = grpc_status(response.headers());
+    let body = reqwest::Body::from(response).collect().await;
+    let duration = start.elapsed().as_millis();
+
+    let (grpc_status, body, error) = match body {
+        Ok(collected) => {
+            let grpc_status = collected.trailers().and_then(grpc_status).or(header_status);
+            let received = collected.to_bytes().len() as u64;
+            let body = BodySize {
+                received,
+                decoded: Some(received),
+                encoding: "identity",
+            };
+            let error =
+                (grpc_status.is_none() && status == 200).then_some(ErrorKind::ProtocolParse);
+            (grpc_status, Some(body), error)
+        }
+        Err(e) => (header_status, None, Some(error_kind(&e))),
+    };
+
+    RequestResult {
+        duration,
+        status,
+        success: status == 200 && grpc_status == Some("OK") && error.is_none(),
+        error,
+        http_version,
+        body,
+        grpc_status,
+        ..Default::default()
+    }
+}
+
+/// Makes one unary call per iteration over a shared HTTP/2 client
+struct GrpcExecutor {
+    client: Client,
+    config: GrpcConfig,
+    frame: Vec<u8>,
+    hooks: Option<Arc<dyn Hooks>>,
+}
+
+impl RequestExecutor for GrpcExecutor {
+    fn progress_message(&self) -> String {
+        format!("Calling {}...", self.config.method)
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "yellow"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = fire_single_request(
+            &self.client,
+            &self.config,
+            &self.frame,
+            iteration.seq,
+            self.hooks.as_deref(),
+        )
+        .await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run a gRPC load test with the given configuration
+pub async fn run_load_test(
+    config: &GrpcConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    // gRPC always runs over HTTP/2: plaintext targets speak it from the first
+    // byte, while over TLS the server picks it with ALPN
+    let mut builder = Client::builder()
+        .user_agent("rustyload/0.2")
+        .timeout(Duration::from_secs(timeout_secs));
+    if !config.url.starts_with("https://") {
+        builder = builder.http2_prior_knowledge();
+    }
+    let client = builder.build().context("Failed to build gRPC client")?;
+    let executor = GrpcExecutor {
+        client,
+        frame: config.frame(),
+        config: config.clone(),
+        hooks: hooks.clone(),
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_config_frames_request() {
+        let dir = std::env::temp_dir().join(format!("rustyload-grpc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let proto = dir.join("echo.proto");
+        std::fs::write(
+            &proto,
+            "syntax = \"proto3\"; package echo;\n\
+             message Msg { string text = 1; }\n\
+             service Echo { rpc Say (Msg) returns (Msg); rpc Chat (stream Msg) returns (stream Msg); }",
+        )
+        .unwrap();
+
+        let request = serde_json::json!({"text": "hi"});
+        let config = GrpcConfig::new(
+            "http://localhost:50051".to_string(),
+            &proto,
+            "Echo/Say",
+            &request,
+        )
+        .unwrap();
+        assert_eq!(config.method, "echo.Echo/Say");
+        assert_eq!(config.frame(), vec![0, 0, 0, 0, 4, 0x0a, 0x02, b'h', b'i']);
+        assert!(GrpcConfig::new(String::new(), &proto, "Echo/Chat", &request).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_grpc_status() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(grpc_status(&headers), None);
+        headers.insert("grpc-status", HeaderValue::from_static("14"));
+        assert_eq!(grpc_status(&headers), Some("UNAVAILABLE"));
+        headers.insert("grpc-status", HeaderValue::from_static("99"));
+        assert_eq!(grpc_status(&headers), Some("UNKNOWN"));
+    }
+}
@@ -1,16 +1,40 @@
 //! HTTP protocol implementation for load testing
 
-use crate::protocols::{calculate_stats, LoadTestStats, RequestResult};
-use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::{Client, Method};
+use crate::checks::{self, Check};
+use crate::data::{DataFeed, DataOrder};
+use crate::extract::{Extraction, Variables};
+use crate::hooks::{HookRequest, Hooks};
+use crate::metrics::{CustomMetrics, CONNECTIONS_OPENED};
+use crate::multipart::Multipart;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::{
+    check_local_addrs, BodySize, ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage,
+    Startup,
+};
+use crate::replay::AccessLog;
+use crate::script::{Script, ScriptRequest, ScriptResponse, ScriptRunner, Sender, SCRIPT_ERRORS};
+use crate::template::RequestContext;
+use anyhow::{bail, Context, Result};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, HOST,
+    USER_AGENT,
+};
+use reqwest::{Certificate, Client, Method, Proxy};
 use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::task::Poll;
+use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
 
 /// Supported HTTP methods for load testing
 #[derive(Debug, Clone, Default, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum HttpMethod {
     #[default]
     GET,
@@ -21,9 +45,11 @@ pub enum HttpMethod {
     HEAD,
 }
 
-impl HttpMethod {
+impl FromStr for HttpMethod {
+    type Err = String;
+
     /// Parse a string into an HttpMethod
-    pub fn from_str(s: &str) -> Result<Self, String> {
+    fn from_str(s: &str) -> Result<Self, String> {
         match s.to_uppercase().as_str() {
             "GET" => Ok(HttpMethod::GET),
             "POST" => Ok(HttpMethod::POST),
@@ -34,6 +60,20 @@ impl HttpMethod {
             _ => Err(format!("Unsupported HTTP method: {}", s)),
         }
     }
+}
+
+impl HttpMethod {
+    /// Get the method name as sent on the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::POST => "POST",
+            HttpMethod::PUT => "PUT",
+            HttpMethod::DELETE => "DELETE",
+            HttpMethod::PATCH => "PATCH",
+            HttpMethod::HEAD => "HEAD",
+        }
+    }
 
     /// Convert to reqwest::Method
     fn to_reqwest_method(&self) -> Method {
@@ -48,6 +88,412 @@ impl HttpMethod {
     }
 }
 
+/// How HTTP/2 is negotiated when it is enabled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Http2Mode {
+    /// Offer h2 during the TLS handshake and fall back to HTTP/1.1 if the
+    /// server doesn't pick it (plain http:// URLs stay on HTTP/1.1)
+    Alpn,
+    /// Speak HTTP/2 from the first byte, including over plain http:// (h2c)
+    PriorKnowledge,
+}
+
+impl FromStr for Http2Mode {
+    type Err = String;
+
+    /// Parse `alpn` or `prior-knowledge`
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "alpn" => Ok(Http2Mode::Alpn),
+            "prior-knowledge" => Ok(Http2Mode::PriorKnowledge),
+            _ => Err(format!(
+                "Unsupported HTTP/2 mode: {} (expected alpn or prior-knowledge)",
+                s
+            )),
+        }
+    }
+}
+
+impl Http2Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Http2Mode::Alpn => "alpn",
+            Http2Mode::PriorKnowledge => "prior-knowledge",
+        }
+    }
+}
+
+/// How often the target host is looked up in DNS
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DnsCache {
+    /// For every new connection, as without a cache in the client
+    #[default]
+    Off,
+    /// Once, reusing the addresses for the rest of the run
+    Once,
+    /// Again for new connections once this many requests were sent since the
+    /// last lookup
+    Every(u64),
+}
+
+impl FromStr for DnsCache {
+    type Err = String;
+
+    /// Parse `off`, `once` or a number of requests
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(DnsCache::Off),
+            "once" => Ok(DnsCache::Once),
+            requests => match requests.parse() {
+                Ok(0) | Err(_) => Err(format!(
+                    "Invalid DNS cache mode: {} (expected off, once or a number of requests)",
+                    s
+                )),
+                Ok(requests) => Ok(DnsCache::Every(requests)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for DnsCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsCache::Off => write!(f, "off"),
+            DnsCache::Once => write!(f, "once"),
+            DnsCache::Every(requests) => write!(f, "{}", requests),
+        }
+    }
+}
+
+/// The protocol version that served a response, as shown in reports
+pub(super) fn version_name(version: reqwest::Version) -> &'static str {
+    match version {
+        reqwest::Version::HTTP_09 => "HTTP/0.9",
+        reqwest::Version::HTTP_10 => "HTTP/1.0",
+        reqwest::Version::HTTP_11 => "HTTP/1.1",
+        reqwest::Version::HTTP_2 => "HTTP/2",
+        reqwest::Version::HTTP_3 => "HTTP/3",
+        _ => "unknown",
+    }
+}
+
+/// Largest DNS weight, which keeps the request schedule built from the
+/// weights small while still allowing shares as fine as 0.01%
+pub const MAX_DNS_WEIGHT: u32 = 10_000;
+
+/// Relative share of requests sent to one resolved address
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsWeight {
+    pub ip: IpAddr,
+    pub weight: u32,
+}
+
+impl DnsWeight {
+    /// Parse an `IP=WEIGHT` pair, e.g. `10.0.0.1=3` (a weight of 0 skips the address)
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid DNS weight '{}': expected IP=WEIGHT, e.g. 10.0.0.1=3",
+                s
+            )
+        };
+        let (ip, weight) = s.split_once('=').ok_or_else(invalid)?;
+        let weight: u32 = weight.trim().parse().map_err(|_| invalid())?;
+        if weight > MAX_DNS_WEIGHT {
+            return Err(format!(
+                "Invalid DNS weight '{}': weights go up to {}",
+                s, MAX_DNS_WEIGHT
+            ));
+        }
+
+        Ok(Self {
+            ip: ip.trim().parse().map_err(|_| invalid())?,
+            weight,
+        })
+    }
+}
+
+/// Scheme of targets served over a Unix domain socket
+const UNIX_SCHEME: &str = "unix://";
+
+/// Whether a target looks like something the HTTP protocol can load test
+pub fn is_http_target(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with(UNIX_SCHEME)
+}
+
+/// Split a `unix:///path/to.sock[:/request/path]` target into the socket path
+/// and the URL to request over it (the request path defaults to `/`)
+fn unix_target(url: &str) -> Option<(PathBuf, String)> {
+    let rest = url.strip_prefix(UNIX_SCHEME)?;
+    let (socket, path) = match rest.rfind(":/") {
+        Some(index) => (&rest[..index], &rest[index + 1..]),
+        None => (rest, "/"),
+    };
+    Some((PathBuf::from(socket), format!("http://localhost{}", path)))
+}
+
+/// One request of a scenario, sent in order with the other steps by every
+/// virtual user
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub name: String,
+    pub method: HttpMethod,
+    pub url: String,
+    /// Sent on top of the shared headers
+    pub headers: HashMap<String, String>,
+    /// Headers taking their values from files, on top of the shared ones
+    pub header_values: Vec<HeaderValues>,
+    pub body: Option<String>,
+    /// Checked on top of the shared checks
+    pub checks: Vec<Check>,
+    /// Response values saved for the later steps of the same iteration
+    pub extractions: Vec<Extraction>,
+    /// Groups the step's requests with others sharing the tag in the stats
+    pub tag: Option<String>,
+}
+
+/// Target URLs loaded from a file, e.g. exported from access logs
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlList {
+    pub path: PathBuf,
+    pub urls: Vec<String>,
+    pub order: DataOrder,
+}
+
+impl UrlList {
+    /// Load one URL per line, skipping blank lines and `#` comments
+    pub fn load(path: &Path, order: DataOrder) -> Result<Self> {
+        let urls = read_list(path, "URL")?;
+        if let Some(url) = urls
+            .iter()
+            .find(|url| url.starts_with(UNIX_SCHEME) || !is_http_target(url))
+        {
+            bail!(
+                "Unsupported URL in {}: {} (expected http:// or https://)",
+                path.display(),
+                url
+            );
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            urls,
+            order,
+        })
+    }
+
+    /// The URL request number `seq` uses
+    fn url(&self, seq: u64) -> &str {
+        &self.urls[self.order.pick(seq, self.urls.len())]
+    }
+}
+
+/// User-Agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = "rustyload/0.2";
+
+/// User-Agent strings loaded from a file, one picked at random per request
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAgentList {
+    pub path: PathBuf,
+    pub agents: Vec<String>,
+}
+
+impl UserAgentList {
+    /// Load one User-Agent per line, skipping blank lines and `#` comments
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            agents: read_header_values(path, "User-Agent")?,
+        })
+    }
+
+    fn pick(&self) -> &str {
+        &self.agents[DataOrder::Random.pick(0, self.agents.len())]
+    }
+}
+
+/// The file a header value of `@path` names, if it names one; a value starting
+/// with `@@` is a literal starting with `@` instead
+pub fn header_value_file(value: &str) -> Option<&Path> {
+    value
+        .strip_prefix('@')
+        .filter(|path| !path.starts_with('@'))
+        .map(Path::new)
+}
+
+/// Values for a header loaded from the file its `@path` value names, taken
+/// in turn by consecutive requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderValues {
+    pub name: String,
+    pub path: PathBuf,
+    pub values: Vec<String>,
+}
+
+impl HeaderValues {
+    /// Load one value per line, skipping blank lines and `#` comments
+    pub fn load(name: &str, path: &Path) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            values: read_header_values(path, &format!("{} header value", name))?,
+        })
+    }
+
+    /// The value request number `seq` sends
+    fn value(&self, seq: u64) -> &str {
+        &self.values[DataOrder::RoundRobin.pick(seq, self.values.len())]
+    }
+}
+
+/// The non-blank lines of a list file that aren't `#` comments, trimmed
+fn read_list(path: &Path, what: &str) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {} file {}", what, path.display()))?;
+    let lines: Vec<String> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        bail!("No {}s in {}", what, path.display());
+    }
+    Ok(lines)
+}
+
+/// A list file of values that must each be valid in a header
+fn read_header_values(path: &Path, what: &str) -> Result<Vec<String>> {
+    let values = read_list(path, what)?;
+    if let Some(value) = values
+        .iter()
+        .find(|value| HeaderValue::from_str(value).is_err())
+    {
+        bail!("Invalid {} in {}: {}", what, path.display(), value);
+    }
+    Ok(values)
+}
+
+/// A curl-style `HOST:PORT:ADDR` override sending a host's traffic to a fixed address
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u16,
+    pub addr: IpAddr,
+}
+
+impl ResolveOverride {
+    /// Parse `HOST:PORT:ADDR`, e.g. `example.com:443:10.0.0.5` or `example.com:443:[::1]`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid resolve override '{}': expected HOST:PORT:ADDR, e.g. example.com:443:10.0.0.5",
+                s
+            )
+        };
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let addr = addr.trim_start_matches('[').trim_end_matches(']');
+
+        Ok(Self {
+            host: host.to_lowercase(),
+            port: port.parse().map_err(|_| invalid())?,
+            addr: addr.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for ResolveOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.addr {
+            IpAddr::V6(addr) => write!(f, "{}:{}:[{}]", self.host, self.port, addr),
+            IpAddr::V4(addr) => write!(f, "{}:{}:{}", self.host, self.port, addr),
+        }
+    }
+}
+
+/// Status codes that count as success in place of the default 2xx rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedStatus(Vec<(u16, u16)>);
+
+impl ExpectedStatus {
+    /// Parse a comma-separated list of codes and classes, e.g. `200,201,404` or `2xx,404`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = |item: &str| {
+            format!(
+                "Invalid expected status '{}': expected codes or classes such as 200,404 or 2xx",
+                item
+            )
+        };
+        let ranges = s
+            .split(',')
+            .map(str::trim)
+            .map(|item| {
+                let range = match item.to_lowercase().strip_suffix("xx") {
+                    Some(class) => {
+                        let class: u16 = class.parse().map_err(|_| invalid(item))?;
+                        (class * 100, class * 100 + 99)
+                    }
+                    None => {
+                        let code = item.parse().map_err(|_| invalid(item))?;
+                        (code, code)
+                    }
+                };
+                if (100..=599).contains(&range.0) {
+                    Ok(range)
+                } else {
+                    Err(invalid(item))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(ranges))
+    }
+
+    pub fn contains(&self, status: u16) -> bool {
+        self.0
+            .iter()
+            .any(|&(low, high)| (low..=high).contains(&status))
+    }
+}
+
+impl std::fmt::Display for ExpectedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let items: Vec<String> = self
+            .0
+            .iter()
+            .map(|&(low, high)| match low == high {
+                true => low.to_string(),
+                false => format!("{}xx", low / 100),
+            })
+            .collect();
+        write!(f, "{}", items.join(","))
+    }
+}
+
+/// Custom metric with the time taken to open each connection through the proxy
+pub const PROXY_CONNECT: &str = "proxy_connect";
+
+/// Custom metric with the time taken by each DNS lookup of a host
+pub const DNS_LOOKUP: &str = "dns_lookup";
+
+/// Parse a `Key: Value` header, e.g. `Authorization: Bearer token`
+pub fn parse_header(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid header '{}': expected \"Key: Value\", e.g. \"Accept: application/json\"",
+            s
+        )),
+    }
+}
+
 /// HTTP-specific configuration
 #[derive(Debug, Clone)]
 pub struct HttpConfig {
@@ -55,6 +501,70 @@ pub struct HttpConfig {
     pub method: HttpMethod,
     pub headers: HashMap<String, String>,
     pub body: Option<String>,
+    /// multipart/form-data body sent instead of `body`
+    pub multipart: Option<Multipart>,
+    /// `user:password` credentials sent as a Basic Authorization header
+    pub basic_auth: Option<String>,
+    /// Spread requests across every address the host resolves to instead of
+    /// letting the OS pick one
+    pub spread_dns: bool,
+    /// Relative weights per address when spreading (unlisted addresses get 1)
+    pub dns_weights: Vec<DnsWeight>,
+    /// HTTP proxy to send every request through
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates to trust
+    pub ca_cert: Option<PathBuf>,
+    /// TLS server name to send instead of the URL host, which connections
+    /// still go to; certificates are checked against this name
+    pub sni: Option<String>,
+    /// Allow HTTP/2, negotiated this way (HTTP/1.1 only when unset)
+    pub http2: Option<Http2Mode>,
+    /// Reuse pooled connections; when off every request opens a new one
+    pub keepalive: bool,
+    /// Cap on requests in flight per backend, and so on HTTP/1.1 connections
+    /// open to it; requests beyond it wait in the client queue
+    pub max_connections: Option<usize>,
+    /// Fixed addresses for the target host, bypassing DNS but keeping the
+    /// hostname for the Host header and SNI
+    pub resolve: Vec<ResolveOverride>,
+    /// Source addresses to bind connections to, each client taking one of
+    /// them in turn
+    pub local_addrs: Vec<IpAddr>,
+    /// How often the target host is looked up
+    pub dns_cache: DnsCache,
+    /// Unix domain socket to connect to, split out of a `unix://` target
+    /// when the run starts
+    pub unix_socket: Option<PathBuf>,
+    /// `Accept-Encoding` to send
+    pub accept_encoding: Option<String>,
+    /// Status codes that count as success (2xx when unset)
+    pub expect_status: Option<ExpectedStatus>,
+    /// Conditions every response must meet to count as a success
+    pub checks: Vec<Check>,
+    /// Response values saved as variables for `{{name}}` placeholders
+    pub extractions: Vec<Extraction>,
+    /// CSV rows whose columns fill `{{name}}` placeholders, one row per request
+    pub data: Option<Arc<DataFeed>>,
+    /// URLs to spread requests over instead of `url`, which is then the first
+    /// of them
+    pub url_list: Option<UrlList>,
+    /// User-Agent to send instead of [`DEFAULT_USER_AGENT`]
+    pub user_agent: Option<String>,
+    /// User-Agents to pick one from for every request
+    pub user_agents: Option<UserAgentList>,
+    /// Headers whose value changes from one request to the next
+    pub header_values: Vec<HeaderValues>,
+    /// Requests every iteration sends in order instead of a single request
+    /// to `url` (which still sets up the connections)
+    pub steps: Vec<Step>,
+    /// Logged requests whose method and path are sent to `url` instead of
+    /// the configured method and URL
+    pub replay: Option<Arc<AccessLog>>,
+    /// Script run by every iteration instead of sending the configured request
+    pub script: Option<Script>,
+    /// Callbacks around every request, set from the run's
+    /// [`LoadTestConfig::hooks`](crate::protocols::LoadTestConfig::hooks)
+    pub hooks: Option<Arc<dyn Hooks>>,
 }
 
 impl HttpConfig {
@@ -64,6 +574,33 @@ impl HttpConfig {
             method: HttpMethod::GET,
             headers: HashMap::new(),
             body: None,
+            multipart: None,
+            basic_auth: None,
+            spread_dns: false,
+            dns_weights: Vec::new(),
+            proxy: None,
+            ca_cert: None,
+            sni: None,
+            http2: None,
+            keepalive: true,
+            max_connections: None,
+            resolve: Vec::new(),
+            local_addrs: Vec::new(),
+            dns_cache: DnsCache::Off,
+            unix_socket: None,
+            accept_encoding: None,
+            expect_status: None,
+            checks: Vec::new(),
+            extractions: Vec::new(),
+            data: None,
+            url_list: None,
+            user_agent: None,
+            user_agents: None,
+            header_values: Vec::new(),
+            steps: Vec::new(),
+            replay: None,
+            script: None,
+            hooks: None,
         }
     }
 
@@ -81,113 +618,1285 @@ impl HttpConfig {
         self.body = body;
         self
     }
+
+    pub fn with_multipart(mut self, multipart: Option<Multipart>) -> Self {
+        self.multipart = multipart;
+        self
+    }
+
+    pub fn with_basic_auth(mut self, credentials: Option<String>) -> Self {
+        self.basic_auth = credentials;
+        self
+    }
+
+    /// The user name of the basic auth credentials, if any
+    pub fn basic_auth_user(&self) -> Option<&str> {
+        let credentials = self.basic_auth.as_deref()?;
+        Some(
+            credentials
+                .split_once(':')
+                .map_or(credentials, |(user, _)| user),
+        )
+    }
+
+    pub fn with_proxy(mut self, proxy: Option<String>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn with_ca_cert(mut self, path: Option<PathBuf>) -> Self {
+        self.ca_cert = path;
+        self
+    }
+
+    pub fn with_sni(mut self, sni: Option<String>) -> Self {
+        self.sni = sni;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    pub fn with_user_agents(mut self, user_agents: Option<UserAgentList>) -> Self {
+        self.user_agents = user_agents;
+        self
+    }
+
+    pub fn with_header_values(mut self, header_values: Vec<HeaderValues>) -> Self {
+        self.header_values = header_values;
+        self
+    }
+
+    pub fn with_http2(mut self, mode: Option<Http2Mode>) -> Self {
+        self.http2 = mode;
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: bool) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: Option<usize>) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn with_resolve(mut self, resolve: Vec<ResolveOverride>) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    pub fn with_local_addrs(mut self, local_addrs: Vec<IpAddr>) -> Self {
+        self.local_addrs = local_addrs;
+        self
+    }
+
+    pub fn with_dns_cache(mut self, dns_cache: DnsCache) -> Self {
+        self.dns_cache = dns_cache;
+        self
+    }
+
+    pub fn with_accept_encoding(mut self, accept_encoding: Option<String>) -> Self {
+        self.accept_encoding = accept_encoding;
+        self
+    }
+
+    pub fn with_expect_status(mut self, expect_status: Option<ExpectedStatus>) -> Self {
+        self.expect_status = expect_status;
+        self
+    }
+
+    pub fn with_checks(mut self, checks: Vec<Check>) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    pub fn with_extractions(mut self, extractions: Vec<Extraction>) -> Self {
+        self.extractions = extractions;
+        self
+    }
+
+    pub fn with_data(mut self, data: Option<DataFeed>) -> Self {
+        self.data = data.map(Arc::new);
+        self
+    }
+
+    pub fn with_replay(mut self, replay: Option<AccessLog>) -> Self {
+        self.replay = replay.map(Arc::new);
+        self
+    }
+
+    pub fn with_script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn with_steps(mut self, steps: Vec<Step>) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// The configuration for sending one step of the scenario
+    fn for_step(&self, step: &Step) -> HttpConfig {
+        let mut config = self.clone();
+        config.steps = Vec::new();
+        config.method = step.method.clone();
+        config.url = step.url.clone();
+        // A step's header replaces a shared one of the same name, whether
+        // either takes its values from a file or not
+        let replaced = |name: &str| {
+            step.headers
+                .keys()
+                .chain(step.header_values.iter().map(|header| &header.name))
+                .any(|key| key.eq_ignore_ascii_case(name))
+        };
+        config.headers.retain(|name, _| !replaced(name));
+        config
+            .header_values
+            .retain(|header| !replaced(&header.name));
+        config.headers.extend(step.headers.clone());
+        config
+            .header_values
+            .extend(step.header_values.iter().cloned());
+        config.body = step.body.clone();
+        config.multipart = None;
+        config.checks.extend(step.checks.iter().cloned());
+        config.extractions.extend(step.extractions.iter().cloned());
+        config
+    }
+
+    /// The configuration for sending a request a script asked for
+    fn for_script(&self, request: &ScriptRequest) -> Result<HttpConfig, String> {
+        let mut config = self.clone();
+        config.script = None;
+        config.url_list = None;
+        config.replay = None;
+        config.method = HttpMethod::from_str(&request.method)?;
+        config.url = match request.url.strip_prefix('/') {
+            Some(path) => format!("{}/{}", self.url.trim_end_matches('/'), path),
+            None => request.url.clone(),
+        };
+        config.headers.extend(request.headers.iter().cloned());
+        config.body = request.body.clone();
+        config.multipart = None;
+        Ok(config)
+    }
+
+    /// Spread requests over a list of URLs, with the first one as `url`
+    pub fn with_url_list(mut self, list: UrlList) -> Self {
+        self.url = list.urls[0].clone();
+        self.url_list = Some(list);
+        self
+    }
+
+    pub fn with_dns_spread(mut self, spread: bool, weights: Vec<DnsWeight>) -> Self {
+        self.spread_dns = spread || !weights.is_empty();
+        self.dns_weights = weights;
+        self
+    }
+}
+
+/// A client, optionally pinned to one of the addresses the host resolves to
+struct Backend {
+    client: Client,
+    /// The pinned address, reported in per-backend stats
+    address: Option<String>,
+    /// Connection slots when `max_connections` is set
+    pool: Option<Arc<Semaphore>>,
+    resolver: Resolver,
+}
+
+/// Looks hosts up for the clients, timing each lookup as [`DNS_LOOKUP`] and
+/// reusing the addresses as long as the [`DnsCache`] mode allows
+#[derive(Clone)]
+struct Resolver {
+    mode: DnsCache,
+    metrics: CustomMetrics,
+    /// Requests sent so far, for [`DnsCache::Every`]
+    requests: Arc<AtomicU64>,
+    /// Held during a lookup so concurrent connections wait for it rather
+    /// than all looking the host up
+    cache: Arc<tokio::sync::Mutex<HashMap<String, CachedLookup>>>,
+    /// The `--sni` name requests are sent to and the host it stands for,
+    /// which is looked up in its place
+    sni: Option<(String, String)>,
 }
 
-/// Fire a single HTTP request and return the result
-pub async fn fire_single_request(client: &Client, config: &HttpConfig) -> RequestResult {
+struct CachedLookup {
+    addrs: Vec<SocketAddr>,
+    /// Requests sent when the host was looked up
+    at: u64,
+}
+
+impl Resolver {
+    fn new(mode: DnsCache, metrics: &CustomMetrics) -> Self {
+        Self {
+            mode,
+            metrics: metrics.clone(),
+            requests: Arc::new(AtomicU64::new(0)),
+            cache: Arc::default(),
+            sni: None,
+        }
+    }
+
+    /// Count a request towards [`DnsCache::Every`]
+    fn sent(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    async fn lookup(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+        let start = Instant::now();
+        let addrs = tokio::net::lookup_host((host, 0)).await?.collect();
+        self.metrics
+            .time(DNS_LOOKUP, start.elapsed().as_secs_f64() * 1000.0);
+        Ok(addrs)
+    }
+}
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let host = match &resolver.sni {
+                Some((sni, host)) if sni == name.as_str() => host.as_str(),
+                _ => name.as_str(),
+            };
+            let addrs = if resolver.mode == DnsCache::Off {
+                resolver.lookup(host).await?
+            } else {
+                let mut cache = resolver.cache.lock().await;
+                let sent = resolver.requests.load(Ordering::Relaxed);
+                let fresh = |cached: &&CachedLookup| match resolver.mode {
+                    DnsCache::Every(requests) => sent - cached.at < requests,
+                    _ => true,
+                };
+                match cache.get(host).filter(fresh) {
+                    Some(cached) => cached.addrs.clone(),
+                    None => {
+                        let addrs = resolver.lookup(host).await?;
+                        let cached = CachedLookup {
+                            addrs: addrs.clone(),
+                            at: sent,
+                        };
+                        cache.insert(host.to_string(), cached);
+                        addrs
+                    }
+                }
+            };
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+fn build_client(
+    config: &HttpConfig,
+    timeout_secs: u64,
+    pin: Option<(&str, &[SocketAddr])>,
+    local_addr: Option<IpAddr>,
+    resolver: &Resolver,
+    roots: &[Certificate],
+    metrics: &CustomMetrics,
+) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT))
+        .timeout(std::time::Duration::from_secs(timeout_secs))
+        .local_address(local_addr)
+        .dns_resolver(Arc::new(resolver.clone()));
+    builder = match config.http2 {
+        None => builder.http1_only(),
+        Some(Http2Mode::Alpn) => builder,
+        Some(Http2Mode::PriorKnowledge) => builder.http2_prior_knowledge(),
+    };
+    for root in roots {
+        builder = builder.add_root_certificate(root.clone());
+    }
+    #[cfg(unix)]
+    if let Some(socket) = &config.unix_socket {
+        builder = builder.unix_socket(socket.as_path());
+    }
+    if let Some((host, addrs)) = pin {
+        // Requests go to the --sni name in place of the host
+        builder = builder.resolve_to_addrs(config.sni.as_deref().unwrap_or(host), addrs);
+    }
+    if !config.keepalive {
+        // Nothing is kept idle, so every request opens a fresh connection
+        builder = builder.pool_max_idle_per_host(0);
+    } else if let Some(max_connections) = config.max_connections {
+        builder = builder.pool_max_idle_per_host(max_connections);
+    }
+    if let Some(proxy) = config.proxy.as_deref() {
+        builder = builder
+            .proxy(Proxy::all(proxy).with_context(|| format!("Invalid proxy URL {}", proxy))?);
+    }
+    let metrics = metrics.clone();
+    let proxied = config.proxy.is_some();
+    builder = builder.connector_layer(tower_layer::layer_fn(move |inner| TimedConnect {
+        inner,
+        metrics: metrics.clone(),
+        proxied,
+    }));
+    builder.build().context("Failed to build HTTP client")
+}
+
+/// Connector wrapper that counts new connections and, through a proxy, records
+/// how long each took to set up
+///
+/// Through a proxy this covers the TCP connect to the proxy and, for HTTPS
+/// targets, the CONNECT tunnel and TLS handshake. Pooled connections are
+/// reused, so only the requests that opened a connection pay this cost.
+#[derive(Clone)]
+struct TimedConnect<S> {
+    inner: S,
+    metrics: CustomMetrics,
+    proxied: bool,
+}
+
+impl<S, R> tower_service::Service<R> for TimedConnect<S>
+where
+    S: tower_service::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let start = Instant::now();
+        let connecting = self.inner.call(request);
+        let metrics = self.metrics.clone();
+        let proxied = self.proxied;
+        Box::pin(async move {
+            let connection = connecting.await?;
+            metrics.increment(CONNECTIONS_OPENED, 1);
+            if proxied {
+                metrics.time(PROXY_CONNECT, start.elapsed().as_secs_f64() * 1000.0);
+            }
+            Ok(connection)
+        })
+    }
+}
+
+fn connection_pool(config: &HttpConfig) -> Option<Arc<Semaphore>> {
+    config
+        .max_connections
+        .map(|max_connections| Arc::new(Semaphore::new(max_connections)))
+}
+
+/// Read every certificate in a PEM file, to be trusted alongside the system roots
+fn load_ca_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("No PEM certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+/// The addresses `--resolve` overrides pin the target host to
+///
+/// Like curl, an override only applies to its host and port, but since one that
+/// matches nothing would silently test the wrong node, that is an error here.
+fn resolved_addrs(config: &HttpConfig, url: &reqwest::Url) -> Result<Vec<SocketAddr>> {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    let port = url.port_or_known_default().unwrap_or_default();
+    let mut addrs = Vec::new();
+    for entry in &config.resolve {
+        if entry.host != host || entry.port != port {
+            bail!(
+                "Resolve override {} doesn't match the target {}:{}",
+                entry,
+                host,
+                port
+            );
+        }
+        addrs.push(SocketAddr::new(entry.addr, port));
+    }
+    Ok(addrs)
+}
+
+/// Build the clients to send requests through, plus the order to use them in
+///
+/// Without DNS spreading this is a single client using the OS resolver, or the
+/// `--resolve` addresses. With it, the host is resolved up front and one client
+/// is pinned to each address, so every backend behind DNS round-robin gets
+/// traffic and its own stats.
+///
+/// With source addresses, each of those clients is built once per address,
+/// and consecutive requests take the addresses in turn.
+async fn build_backends(
+    config: &HttpConfig,
+    timeout_secs: u64,
+    metrics: &CustomMetrics,
+) -> Result<(Vec<Backend>, Vec<usize>)> {
+    let roots = match &config.ca_cert {
+        Some(path) => load_ca_certs(path)?,
+        None => Vec::new(),
+    };
+    if config.unix_socket.is_some() {
+        if cfg!(not(unix)) {
+            bail!("Unix domain sockets aren't supported on this platform");
+        }
+        if config.spread_dns || config.proxy.is_some() || !config.resolve.is_empty() {
+            bail!("A unix:// target can't be combined with DNS spreading, --resolve or a proxy");
+        }
+        if !config.local_addrs.is_empty() {
+            bail!("A unix:// target can't be combined with --local-addr");
+        }
+    }
+    check_local_addrs(&config.local_addrs)?;
+    let local_addrs: Vec<Option<IpAddr>> = if config.local_addrs.is_empty() {
+        vec![None]
+    } else {
+        config.local_addrs.iter().copied().map(Some).collect()
+    };
+    let url = reqwest::Url::parse(&config.url).context("Invalid URL")?;
+    let mut resolver = Resolver::new(config.dns_cache, metrics);
+    if let Some(sni) = &config.sni {
+        if url.scheme() != "https" {
+            bail!("--sni only applies to https:// targets");
+        }
+        if config.proxy.is_some() {
+            bail!("--sni can't be combined with a proxy, since the proxy connects to the host");
+        }
+        if config.http2.is_some() {
+            bail!("--sni can't be combined with --http2, since HTTP/2 would send the SNI name as the request's authority");
+        }
+        if sni.parse::<IpAddr>().is_ok() {
+            bail!(
+                "Invalid SNI name {}: expected a hostname, not an address",
+                sni
+            );
+        }
+        let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+        resolver.sni = Some((sni.to_lowercase(), host.to_string()));
+    }
+    // One backend per source address for each client
+    let backends_for = |pin: Option<(&str, &[SocketAddr])>, address: Option<String>| {
+        let pool = connection_pool(config);
+        local_addrs
+            .iter()
+            .map(|&local_addr| {
+                Ok(Backend {
+                    client: build_client(
+                        config,
+                        timeout_secs,
+                        pin,
+                        local_addr,
+                        &resolver,
+                        &roots,
+                        metrics,
+                    )?,
+                    address: address.clone(),
+                    pool: pool.clone(),
+                    resolver: resolver.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+
+    if let Some(list) = config
+        .url_list
+        .as_ref()
+        .filter(|_| config.spread_dns || !config.resolve.is_empty() || config.sni.is_some())
+    {
+        // Backends are pinned to the addresses of a single host
+        let origin = |url: &reqwest::Url| {
+            (
+                url.host_str().map(str::to_string),
+                url.port_or_known_default(),
+            )
+        };
+        for other in &list.urls {
+            let other = reqwest::Url::parse(other)
+                .with_context(|| format!("Invalid URL in {}: {}", list.path.display(), other))?;
+            if origin(&other) != origin(&url) {
+                bail!("With DNS spreading, --resolve or --sni, every URL in the URL file must use the same host and port");
+            }
+        }
+    }
+    let resolved = resolved_addrs(config, &url)?;
+    if !resolved.is_empty() {
+        if config.spread_dns {
+            bail!("DNS spreading can't be combined with --resolve overrides");
+        }
+        if config.proxy.is_some() {
+            bail!("--resolve can't be combined with a proxy, since the proxy resolves the host");
+        }
+    }
+
+    let single = || -> Result<(Vec<Backend>, Vec<usize>)> {
+        let pin = url
+            .host_str()
+            .filter(|_| !resolved.is_empty())
+            .map(|host| (host, resolved.as_slice()));
+        let backends = backends_for(pin, None)?;
+        let schedule = (0..backends.len()).collect();
+        Ok((backends, schedule))
+    };
+
+    if !config.spread_dns {
+        return single();
+    }
+    if config.proxy.is_some() {
+        bail!("DNS spreading can't be combined with a proxy, since the proxy resolves the host");
+    }
+
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return single();
+    };
+    if host.parse::<IpAddr>().is_ok() || host.starts_with('[') {
+        // An address literal has nothing to spread across
+        return single();
+    }
+
+    let mut ips: Vec<IpAddr> = Vec::new();
+    for addr in tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("Failed to resolve {}", host))?
+    {
+        if !ips.contains(&addr.ip()) {
+            ips.push(addr.ip());
+        }
+    }
+
+    for weight in &config.dns_weights {
+        if !ips.contains(&weight.ip) {
+            bail!(
+                "{} does not resolve to {} (resolved: {:?})",
+                host,
+                weight.ip,
+                ips
+            );
+        }
+    }
+
+    let mut backends = Vec::new();
+    let mut weights = Vec::new();
+    for ip in ips {
+        let weight = config
+            .dns_weights
+            .iter()
+            .find(|w| w.ip == ip)
+            .map(|w| w.weight)
+            .unwrap_or(1);
+        if weight == 0 {
+            continue;
+        }
+        backends.extend(backends_for(
+            Some((host, &[SocketAddr::new(ip, port)])),
+            Some(ip.to_string()),
+        )?);
+        weights.push(weight);
+    }
+
+    if backends.is_empty() {
+        bail!("Every address of {} has a weight of 0", host);
+    }
+
+    Ok((
+        backends,
+        rotate_sources(&weighted_schedule(&weights), local_addrs.len()),
+    ))
+}
+
+/// Turn a schedule of resolved addresses into one of backends, when each
+/// address has a backend per source address
+///
+/// The schedule is repeated once per source address, so the addresses keep
+/// their order and share: with two sources, `[0, 1]` becomes `[0, 2, 1, 3]`.
+fn rotate_sources(schedule: &[usize], sources: usize) -> Vec<usize> {
+    (0..schedule.len() * sources)
+        .map(|i| schedule[i % schedule.len()] * sources + i / schedule.len())
+        .collect()
+}
+
+/// Interleave indices by weight (smooth weighted round-robin)
+///
+/// Weights 3 and 1 give `[0, 0, 1, 0]` rather than `[0, 0, 0, 1]`, so no backend
+/// gets a burst of consecutive requests. Weights are divided by their greatest
+/// common divisor first, so 3000 and 1000 give the same four-entry schedule.
+fn weighted_schedule(weights: &[u32]) -> Vec<usize> {
+    let divisor = weights.iter().fold(0, |a, &b| gcd(a, b)).max(1);
+    let weights: Vec<u32> = weights.iter().map(|w| w / divisor).collect();
+    let total: i64 = weights.iter().map(|w| i64::from(*w)).sum();
+    let mut current = vec![0i64; weights.len()];
+    let mut schedule = Vec::with_capacity(total as usize);
+
+    for _ in 0..total {
+        for (c, w) in current.iter_mut().zip(&weights) {
+            *c += i64::from(*w);
+        }
+        let (best, _) = current
+            .iter()
+            .enumerate()
+            .max_by(|(i, a), (j, b)| a.cmp(b).then(j.cmp(i)))
+            .expect("at least one weight");
+        current[best] -= total;
+        schedule.push(best);
+    }
+
+    schedule
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// A response's headers and body, kept for a script to inspect
+struct Captured {
+    headers: HeaderMap,
+    body: String,
+}
+
+/// Point an HTTPS request at the `--sni` name, so it is the TLS server name,
+/// while the Host header keeps naming the original host (the resolver maps
+/// the name back to that host's addresses). HTTP/2 would take the rewritten
+/// host as the request's `:authority`, so `--sni` is HTTP/1.1 only.
+fn send_to_sni(request: &mut reqwest::Request, sni: &str) {
+    let url = request.url_mut();
+    if url.scheme() != "https" {
+        return;
+    }
+    let Some(host) = url.host_str() else {
+        return;
+    };
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    if url.set_host(Some(sni)).is_err() || request.headers().contains_key(HOST) {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&authority) {
+        request.headers_mut().insert(HOST, value);
+    }
+}
+
+/// Fire a single HTTP request and return the result, along with the response's
+/// headers and body when `capture` is set and the body could be read
+async fn fire_single_request(
+    client: &Client,
+    config: &HttpConfig,
+    variables: &Variables,
+    context: RequestContext<'_>,
+    capture: bool,
+) -> (RequestResult, Option<Captured>) {
     let start = Instant::now();
+    let context = RequestContext {
+        row: config.data.as_ref().map(|data| data.row(context.seq)),
+        ..context
+    };
 
     // Build the request
-    let mut request_builder = client.request(config.method.to_reqwest_method(), &config.url);
+    let entry = config.replay.as_ref().map(|log| log.entry(context.seq));
+    let replayed;
+    let url = match (&config.url_list, entry) {
+        (_, Some(entry)) => {
+            replayed = format!("{}{}", config.url.trim_end_matches('/'), entry.path);
+            &replayed
+        }
+        (Some(list), None) => list.url(context.seq),
+        (None, None) => &config.url,
+    };
+    let method = entry.map_or(&config.method, |entry| &entry.method);
+    let url = variables.substitute(url, &context);
+    let mut request_builder = client.request(method.to_reqwest_method(), url.as_ref());
+
+    // A User-Agent header set explicitly wins over the list
+    if let Some(list) = config.user_agents.as_ref().filter(|_| {
+        !config
+            .headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(USER_AGENT.as_str()))
+    }) {
+        request_builder = request_builder.header(USER_AGENT, list.pick());
+    }
 
     // Add custom headers
     for (key, value) in &config.headers {
-        request_builder = request_builder.header(key, value);
+        request_builder =
+            request_builder.header(key, variables.substitute(value, &context).as_ref());
+    }
+    for header in &config.header_values {
+        // A header a step or script sets replaces the file's values
+        if config
+            .headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(&header.name))
+        {
+            continue;
+        }
+        request_builder = request_builder.header(
+            &header.name,
+            variables
+                .substitute(header.value(context.seq), &context)
+                .as_ref(),
+        );
+    }
+    if let Some(accept_encoding) = &config.accept_encoding {
+        request_builder = request_builder.header(ACCEPT_ENCODING, accept_encoding);
+    }
+    if let Some(credentials) = &config.basic_auth {
+        let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+        request_builder = request_builder.basic_auth(user, Some(password));
     }
 
     // Add body if present
-    if let Some(body_content) = &config.body {
-        request_builder = request_builder.body(body_content.clone());
+    if let Some(form) = &config.multipart {
+        request_builder = request_builder
+            .header(CONTENT_TYPE, &form.content_type)
+            .body(form.body.clone());
+    } else if let Some(body_content) = &config.body {
+        request_builder =
+            request_builder.body(variables.substitute(body_content, &context).into_owned());
     }
 
-    // Send the request
-    match request_builder.send().await {
+    // Send the request, once the hooks have had their say
+    let request = request_builder
+        .build()
+        .map_err(|e| error_kind(&e))
+        .and_then(|mut request| match &config.hooks {
+            Some(hooks) => before_request(hooks.as_ref(), context.seq, &mut request)
+                .map(|()| request)
+                .map_err(|e| ErrorKind::Other(format!("before_request hook: {}", e))),
+            None => Ok(request),
+        })
+        .map(|mut request| {
+            if let Some(sni) = &config.sni {
+                send_to_sni(&mut request, sni);
+            }
+            request
+        });
+    let sent = match request {
+        Ok(request) => client.execute(request).await.map_err(|e| error_kind(&e)),
+        Err(error) => Err(error),
+    };
+    match sent {
         Ok(response) => {
-            let duration = start.elapsed().as_millis();
             let status = response.status().as_u16();
-            let success = response.status().is_success();
+            // Status checks and --expect-status replace the default rule that
+            // only 2xx succeeds
+            let status_ok = checks::covers_status(&config.checks)
+                || match &config.expect_status {
+                    Some(expected) => expected.contains(status),
+                    None => response.status().is_success(),
+                };
+            let http_version = Some(version_name(response.version()));
+            let keep_body = capture
+                || config.checks.iter().any(Check::needs_body)
+                || config.extractions.iter().any(Extraction::needs_body);
+            let headers =
+                (capture || !config.extractions.is_empty()).then(|| response.headers().clone());
 
-            RequestResult {
-                duration,
+            // The body is read to the end so the connection can be reused and
+            // latency includes the transfer
+            let mut captured = None;
+            let (body, error) = match read_body(response, keep_body).await {
+                Ok((body, content)) => {
+                    let content = String::from_utf8_lossy(&content);
+                    let failed = status_ok
+                        .then(|| checks::first_failure(&config.checks, status, &content))
+                        .flatten();
+                    if let (true, None, Some(headers)) = (status_ok, &failed, &headers) {
+                        variables.extract(&config.extractions, headers, &content);
+                    }
+                    if let (true, Some(headers)) = (capture, headers) {
+                        captured = Some(Captured {
+                            headers,
+                            body: content.into_owned(),
+                        });
+                    }
+                    (
+                        Some(body),
+                        failed.map(|check| ErrorKind::AssertionFailed(check.to_string())),
+                    )
+                }
+                Err(e) => (None, Some(error_kind(&e))),
+            };
+
+            let result = RequestResult {
+                duration: start.elapsed().as_millis(),
                 status,
-                success,
-                error: None,
-            }
+                success: status_ok && error.is_none(),
+                error,
+                queue_time: 0,
+                operation: Some(method.as_str()),
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version,
+                body,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                // Form uploads are reported like S3 PUTs
+                sent: config.multipart.as_ref().map(|form| form.body.len() as u64),
+                worker: None,
+            };
+            (result, captured)
         }
-        Err(e) => {
+        Err(error) => {
             let duration = start.elapsed().as_millis();
-            RequestResult {
+            let result = RequestResult {
                 duration,
                 status: 0,
                 success: false,
-                error: Some(e.to_string()),
+                error: Some(error),
+                queue_time: 0,
+                operation: Some(method.as_str()),
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
+            };
+            (result, None)
+        }
+    }
+}
+
+/// Let the hooks change a request's method, URL and headers before it's sent
+pub(super) fn before_request(
+    hooks: &dyn Hooks,
+    seq: u64,
+    request: &mut reqwest::Request,
+) -> Result<(), String> {
+    let mut hook_request = HookRequest {
+        seq,
+        method: request.method().to_string(),
+        url: request.url().to_string(),
+        headers: request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.to_string(), value)
+            })
+            .collect(),
+    };
+    hooks.before_request(&mut hook_request)?;
+
+    *request.method_mut() = Method::from_bytes(hook_request.method.to_uppercase().as_bytes())
+        .map_err(|_| format!("invalid method '{}'", hook_request.method))?;
+    *request.url_mut() = reqwest::Url::parse(&hook_request.url)
+        .map_err(|e| format!("invalid URL '{}': {}", hook_request.url, e))?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &hook_request.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value for header {}", name))?;
+        headers.append(name, value);
+    }
+    *request.headers_mut() = headers;
+    Ok(())
+}
+
+/// Content encodings `--compression` can ask for
+const ENCODINGS: [&str; 4] = ["gzip", "br", "zstd", "deflate"];
+
+/// Parse a `--compression` list such as `gzip,br` into an `Accept-Encoding`
+/// value, with `none` asking for uncompressed responses
+pub fn parse_compression(s: &str) -> Result<String, String> {
+    if s.trim().eq_ignore_ascii_case("none") {
+        return Ok("identity".to_string());
+    }
+    let encodings = s
+        .split(',')
+        .map(|encoding| {
+            let encoding = encoding.trim().to_lowercase();
+            if ENCODINGS.contains(&encoding.as_str()) {
+                Ok(encoding)
+            } else {
+                Err(format!(
+                    "Unsupported compression '{}': expected none or a list of {}",
+                    encoding,
+                    ENCODINGS.join(", ")
+                ))
             }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(encodings.join(", "))
+}
+
+/// Name a response's content encoding, as counted in the transfer stats
+fn encoding_name(header: Option<&reqwest::header::HeaderValue>) -> &'static str {
+    let value = header
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match value.as_str() {
+        "" | "identity" => "identity",
+        "gzip" | "x-gzip" => "gzip",
+        "br" => "br",
+        "zstd" => "zstd",
+        "deflate" => "deflate",
+        _ => "other",
+    }
+}
+
+/// The size of a body once decoded, if it can be told without a decoder
+/// for the encoding
+fn decoded_size(encoding: &str, bytes: &[u8]) -> Option<u64> {
+    match encoding {
+        // The gzip trailer ends with the uncompressed size (mod 2^32)
+        "gzip" if bytes.len() >= 18 && bytes.starts_with(&[0x1f, 0x8b]) => {
+            let trailer: [u8; 4] = bytes[bytes.len() - 4..].try_into().ok()?;
+            Some(u32::from_le_bytes(trailer) as u64)
         }
+        "zstd" => zstd::stream::decode_all(bytes)
+            .ok()
+            .map(|decoded| decoded.len() as u64),
+        _ => None,
+    }
+}
+
+/// Read a response body to the end, keeping it only when checks need it or its
+/// decoded size has to be worked out from the bytes
+async fn read_body(
+    mut response: reqwest::Response,
+    keep_body: bool,
+) -> reqwest::Result<(BodySize, Vec<u8>)> {
+    let encoding = encoding_name(response.headers().get(CONTENT_ENCODING));
+    let keep = keep_body || matches!(encoding, "gzip" | "zstd");
+    let mut received = 0;
+    let mut kept = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        received += chunk.len() as u64;
+        if keep {
+            kept.extend_from_slice(&chunk);
+        }
+    }
+
+    let decoded = match encoding {
+        "identity" => Some(received),
+        _ => decoded_size(encoding, &kept),
+    };
+    Ok((
+        BodySize {
+            received,
+            decoded,
+            encoding,
+        },
+        kept,
+    ))
+}
+
+/// Classify a failed request, telling connect timeouts from response timeouts
+pub(super) fn error_kind(error: &reqwest::Error) -> ErrorKind {
+    if error.is_timeout() {
+        if error.is_connect() {
+            ErrorKind::ConnectTimeout
+        } else {
+            ErrorKind::ResponseTimeout
+        }
+    } else {
+        ErrorKind::from_error(error)
+    }
+}
+
+/// Send one request through a backend, first waiting for a connection slot
+/// when the pool is capped
+async fn send_on(
+    backend: &Backend,
+    config: &HttpConfig,
+    variables: &Variables,
+    context: RequestContext<'_>,
+    capture: bool,
+) -> (RequestResult, Option<Captured>) {
+    // With a capped pool, time spent waiting for a free connection is
+    // client-side queueing rather than server latency
+    let queued = Instant::now();
+    let _connection = match &backend.pool {
+        Some(pool) => Some(pool.acquire().await.unwrap()),
+        None => None,
+    };
+    let queue_time = queued.elapsed().as_micros();
+    backend.resolver.sent();
+
+    let (mut result, captured) =
+        fire_single_request(&backend.client, config, variables, context, capture).await;
+    result.backend = backend.address.clone();
+    if backend.pool.is_some() {
+        result.queue_time = queue_time;
     }
+    (result, captured)
+}
+
+/// Send a script's requests from its blocking thread, recording each result
+fn script_sender(
+    backends: Arc<Vec<Backend>>,
+    schedule: Vec<usize>,
+    base: HttpConfig,
+    variables: Variables,
+    live: LiveResults,
+) -> Arc<Sender> {
+    let runtime = tokio::runtime::Handle::current();
+    let next = AtomicUsize::new(0);
+    Arc::new(move |request: ScriptRequest| {
+        let config = base.for_script(&request)?;
+        let backend = &backends[schedule[next.fetch_add(1, Ordering::Relaxed) % schedule.len()]];
+        let (mut result, captured) = runtime.block_on(send_on(
+            backend,
+            &config,
+            &variables,
+            RequestContext::default(),
+            true,
+        ));
+        result.step = request.name.as_deref().map(Arc::from);
+        if let Some(hooks) = &base.hooks {
+            hooks.after_response(&result);
+        }
+
+        let (headers, body) = match captured {
+            Some(captured) => (
+                captured
+                    .headers
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                        (name.to_string(), value)
+                    })
+                    .collect(),
+                captured.body,
+            ),
+            None => (Vec::new(), String::new()),
+        };
+        let response = ScriptResponse {
+            status: result.status,
+            ok: result.success,
+            body,
+            headers,
+            duration: result.duration,
+            error: result.error.as_ref().map(ToString::to_string),
+        };
+        live.record(result);
+        Ok(response)
+    })
+}
+
+/// The request or scenario steps of an iteration, labelled with the step's
+/// name and tag
+type Labels = (Option<Arc<str>>, Option<Arc<str>>);
+
+/// Sends HTTP iterations: a single request, every step of a scenario, or a
+/// run of the script
+struct HttpExecutor {
+    backends: Arc<Vec<Backend>>,
+    schedule: Vec<usize>,
+    requests: Vec<(Labels, HttpConfig)>,
+    scenario: bool,
+    variables: Variables,
+    replay: Option<Arc<AccessLog>>,
+    runner: Option<Arc<ScriptRunner>>,
+    metrics: CustomMetrics,
+    script_error_shown: AtomicBool,
+}
+
+impl HttpExecutor {
+    /// Run the script once on a blocking thread
+    async fn run_script(&self, runner: &Arc<ScriptRunner>, iteration: &Iteration) {
+        let runner = Arc::clone(runner);
+        let (seq, worker_id, count) = (iteration.seq, iteration.worker_id, iteration.iteration);
+        let outcome =
+            tokio::task::spawn_blocking(move || runner.run_iteration(seq, worker_id, count)).await;
+        if let Ok(Err(error)) = outcome {
+            self.metrics.increment(SCRIPT_ERRORS, 1);
+            if !self.script_error_shown.swap(true, Ordering::Relaxed) {
+                iteration
+                    .recorder
+                    .warn(&format!("script error in iteration {}: {}", seq, error));
+            }
+        }
+        iteration.recorder.advance(1);
+    }
+}
+
+impl RequestExecutor for HttpExecutor {
+    fn progress_message(&self) -> String {
+        "Sending HTTP requests...".to_string()
+    }
+
+    fn requests_per_iteration(&self) -> u64 {
+        self.requests.len() as u64
+    }
+
+    fn due(&self, seq: u64) -> Option<Duration> {
+        // A replay keeping the logged timing holds each request until it's due
+        self.replay.as_ref().and_then(|log| log.due(seq))
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        if let Some(runner) = &self.runner {
+            return self.run_script(runner, &iteration).await;
+        }
+
+        let backend = &self.backends[self.schedule[iteration.seq as usize % self.schedule.len()]];
+        let context = RequestContext {
+            seq: iteration.seq,
+            worker_id: iteration.worker_id,
+            iteration: iteration.iteration,
+            row: None,
+        };
+        // Each scenario iteration acts as its own virtual user, so values
+        // one step extracts only reach the later steps of that iteration
+        let scenario_variables;
+        let variables = if self.scenario {
+            scenario_variables = Variables::default();
+            &scenario_variables
+        } else {
+            &self.variables
+        };
+
+        for (position, ((step, tag), config)) in self.requests.iter().enumerate() {
+            let (mut result, _) = send_on(backend, config, variables, context, false).await;
+            result.step = step.clone();
+            result.tag = tag.clone();
+            let success = result.success;
+            iteration.recorder.record(result);
+
+            // Later steps build on this one, so the iteration ends here
+            if !success {
+                iteration
+                    .recorder
+                    .advance((self.requests.len() - position - 1) as u64);
+                break;
+            }
+        }
+    }
+}
+
+/// Send the first request of the test once, outside of any run, returning
+/// its result and the response body
+pub async fn preview(
+    http_config: &HttpConfig,
+    timeout_secs: u64,
+) -> Result<(RequestResult, Option<String>)> {
+    let mut config = match http_config.steps.first() {
+        Some(step) => http_config.for_step(step),
+        None => http_config.clone(),
+    };
+    config.hooks = None;
+    if let Some((socket, url)) = unix_target(&config.url) {
+        config.unix_socket = Some(socket);
+        config.url = url;
+    }
+
+    let (backends, _) = build_backends(&config, timeout_secs, &CustomMetrics::default()).await?;
+    let (result, captured) = send_on(
+        &backends[0],
+        &config,
+        &Variables::default(),
+        RequestContext::default(),
+        true,
+    )
+    .await;
+    Ok((result, captured.map(|captured| captured.body)))
 }
 
 /// Run an HTTP load test with the given configuration
 pub async fn run_load_test(
     http_config: &HttpConfig,
-    num_requests: u64,
-    concurrency: u64,
+    stages: &[Stage],
+    startup: Startup,
     timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
 ) -> Result<LoadTestStats> {
-    let client = Client::builder()
-        .user_agent("rustyload/0.2")
-        .timeout(std::time::Duration::from_secs(timeout_secs))
-        .build()
-        .context("Failed to build HTTP client")?;
-
-    let client = Arc::new(client);
-    let semaphore = Arc::new(Semaphore::new(concurrency as usize));
-    let config = Arc::new(http_config.clone());
-
-    // Create progress bar
-    let pb = ProgressBar::new(num_requests);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
-            .unwrap()
-            .progress_chars("█▓▒░  "),
-    );
-    pb.set_message("Sending HTTP requests...");
-
-    let overall_start = Instant::now();
-
-    // Spawn all tasks
-    let mut handles = Vec::with_capacity(num_requests as usize);
-
-    for _ in 0..num_requests {
-        let client = Arc::clone(&client);
-        let semaphore = Arc::clone(&semaphore);
-        let config = Arc::clone(&config);
-        let pb = pb.clone();
-
-        let handle = tokio::spawn(async move {
-            let _permit = semaphore.acquire().await.unwrap();
-            let result = fire_single_request(&client, &config).await;
-            pb.inc(1);
-            result
-        });
-
-        handles.push(handle);
+    let mut http_config = http_config.clone();
+    http_config.hooks = hooks.clone();
+    if let Some((socket, url)) = unix_target(&http_config.url) {
+        http_config.unix_socket = Some(socket);
+        http_config.url = url;
     }
 
-    // Collect results
-    let mut results = Vec::with_capacity(num_requests as usize);
-    for handle in handles {
-        if let Ok(result) = handle.await {
-            results.push(result);
-        }
-    }
+    let metrics = live.metrics().clone();
+    let (backends, schedule) = build_backends(&http_config, timeout_secs, &metrics).await?;
 
-    let total_duration = overall_start.elapsed().as_millis();
+    let scenario = !http_config.steps.is_empty();
+    let replay = http_config.replay.clone();
+    let script = http_config.script.clone();
+    let requests: Vec<(Labels, HttpConfig)> = if http_config.steps.is_empty() {
+        vec![((None, None), http_config)]
+    } else {
+        http_config
+            .steps
+            .iter()
+            .map(|step| {
+                let mut config = http_config.for_step(step);
+                if let Some((_, url)) = unix_target(&config.url) {
+                    config.url = url;
+                }
+                let labels = (
+                    Some(Arc::from(step.name.as_str())),
+                    step.tag.as_deref().map(Arc::from),
+                );
+                (labels, config)
+            })
+            .collect()
+    };
+    let step_order: Vec<Option<Arc<str>>> =
+        requests.iter().map(|((step, _), _)| step.clone()).collect();
 
-    pb.finish_with_message("Complete!");
+    let backends = Arc::new(backends);
+    let variables = Variables::default();
 
-    // Calculate statistics
-    let stats = calculate_stats(&results, total_duration);
+    // A script replaces the configured request, sending through the same backends
+    let runner = script.map(|script| {
+        let sender = script_sender(
+            Arc::clone(&backends),
+            schedule.clone(),
+            requests[0].1.clone(),
+            variables.clone(),
+            live.clone(),
+        );
+        Arc::new(ScriptRunner::new(&script, sender, metrics.clone()))
+    });
+
+    let executor = HttpExecutor {
+        backends,
+        schedule,
+        requests,
+        scenario,
+        variables,
+        replay,
+        runner,
+        metrics: metrics.clone(),
+        script_error_shown: AtomicBool::new(false),
+    };
+    let mut stats = engine::run(executor, stages, startup, quiet, live, hooks).await;
+    // Steps in scenario order rather than by name
+    stats.by_step.sort_by_key(|group| {
+        step_order
+            .iter()
+            .position(|step| step.as_deref() == Some(group.name.as_str()))
+    });
 
     Ok(stats)
 }
@@ -208,6 +1917,316 @@ mod tests {
         assert!(HttpMethod::from_str("INVALID").is_err());
     }
 
+    #[test]
+    fn test_http_method_as_str_roundtrip() {
+        for method in ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD"] {
+            assert_eq!(HttpMethod::from_str(method).unwrap().as_str(), method);
+        }
+    }
+
+    #[test]
+    fn test_dns_weight_parse() {
+        let weight = DnsWeight::parse("10.0.0.1=3").unwrap();
+        assert_eq!(weight.ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(weight.weight, 3);
+        assert!(DnsWeight::parse("::1=0").is_ok());
+        assert!(DnsWeight::parse("example.com=1").is_err());
+        assert!(DnsWeight::parse("10.0.0.1").is_err());
+        assert!(DnsWeight::parse("10.0.0.1=10000").is_ok());
+        assert!(DnsWeight::parse("10.0.0.1=4000000000").is_err());
+    }
+
+    #[test]
+    fn test_http2_mode_from_str() {
+        assert_eq!(Http2Mode::from_str("alpn").unwrap(), Http2Mode::Alpn);
+        assert_eq!(
+            Http2Mode::from_str("Prior-Knowledge").unwrap(),
+            Http2Mode::PriorKnowledge
+        );
+        assert!(Http2Mode::from_str("h2c").is_err());
+        assert_eq!(version_name(reqwest::Version::HTTP_2), "HTTP/2");
+    }
+
+    #[test]
+    fn test_dns_cache_from_str() {
+        assert_eq!(DnsCache::from_str("once").unwrap(), DnsCache::Once);
+        assert_eq!(DnsCache::from_str("None").unwrap(), DnsCache::Off);
+        assert_eq!(DnsCache::from_str("500").unwrap(), DnsCache::Every(500));
+        assert_eq!(DnsCache::Every(500).to_string(), "500");
+        assert!(DnsCache::from_str("0").is_err());
+        assert!(DnsCache::from_str("always").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolver() {
+        use reqwest::dns::Resolve;
+
+        let lookups = |resolver: &Resolver| {
+            resolver
+                .metrics
+                .summarize()
+                .into_iter()
+                .find_map(|m| match m {
+                    crate::metrics::CustomMetricSummary::Timer { count, .. } => Some(count),
+                    _ => None,
+                })
+                .unwrap_or(0)
+        };
+        let resolve = |resolver: Resolver| async move {
+            let name = reqwest::dns::Name::from_str("localhost").unwrap();
+            let addrs: Vec<SocketAddr> = resolver.resolve(name).await.unwrap().collect();
+            assert!(addrs.iter().all(|a| a.ip().is_loopback()));
+        };
+
+        let off = Resolver::new(DnsCache::Off, &CustomMetrics::default());
+        for _ in 0..3 {
+            resolve(off.clone()).await;
+        }
+        assert_eq!(lookups(&off), 3);
+
+        let once = Resolver::new(DnsCache::Once, &CustomMetrics::default());
+        for _ in 0..3 {
+            resolve(once.clone()).await;
+            once.sent();
+        }
+        assert_eq!(lookups(&once), 1);
+
+        let every = Resolver::new(DnsCache::Every(2), &CustomMetrics::default());
+        for _ in 0..5 {
+            resolve(every.clone()).await;
+            every.sent();
+        }
+        assert_eq!(lookups(&every), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sni() {
+        use reqwest::dns::Resolve;
+
+        let client = Client::new();
+        let mut request = client.get("https://10.0.0.5:8443/health").build().unwrap();
+        send_to_sni(&mut request, "tenant.example.com");
+        assert_eq!(
+            request.url().as_str(),
+            "https://tenant.example.com:8443/health"
+        );
+        assert_eq!(request.headers()[HOST], "10.0.0.5:8443");
+
+        let mut named = client
+            .get("https://lb.internal/")
+            .header(HOST, "api.example.com")
+            .build()
+            .unwrap();
+        send_to_sni(&mut named, "tenant.example.com");
+        assert_eq!(named.url().host_str(), Some("tenant.example.com"));
+        assert_eq!(named.headers()[HOST], "api.example.com");
+
+        let mut plain = client.get("http://10.0.0.5/").build().unwrap();
+        send_to_sni(&mut plain, "tenant.example.com");
+        assert_eq!(plain.url().host_str(), Some("10.0.0.5"));
+
+        // HTTP/2 takes the authority from the rewritten URL, not the Host header
+        for http2 in [Http2Mode::Alpn, Http2Mode::PriorKnowledge] {
+            let config = HttpConfig::new("https://10.0.0.5:8443/health".to_string())
+                .with_sni(Some("tenant.example.com".to_string()))
+                .with_http2(Some(http2));
+            let error = build_backends(&config, 5, &CustomMetrics::default())
+                .await
+                .err()
+                .unwrap();
+            assert!(error.to_string().contains("--http2"), "{}", error);
+        }
+
+        // The name is looked up as the host it stands for
+        let mut resolver = Resolver::new(DnsCache::Off, &CustomMetrics::default());
+        resolver.sni = Some(("tenant.example.com".to_string(), "127.0.0.1".to_string()));
+        let name = reqwest::dns::Name::from_str("tenant.example.com").unwrap();
+        let addrs: Vec<SocketAddr> = resolver.resolve(name).await.unwrap().collect();
+        assert_eq!(addrs, vec!["127.0.0.1:0".parse::<SocketAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_resolve_override() {
+        let entry = ResolveOverride::parse("Example.com:443:10.0.0.5").unwrap();
+        assert_eq!(entry.host, "example.com");
+        assert_eq!(entry.port, 443);
+        assert_eq!(entry.to_string(), "example.com:443:10.0.0.5");
+        let v6 = ResolveOverride::parse("example.com:8443:[::1]").unwrap();
+        assert_eq!(v6.to_string(), "example.com:8443:[::1]");
+        assert!(ResolveOverride::parse("example.com:10.0.0.5").is_err());
+        assert!(ResolveOverride::parse("example.com:https:10.0.0.5").is_err());
+
+        let config =
+            HttpConfig::new("https://example.com/health".to_string()).with_resolve(vec![entry]);
+        let url = reqwest::Url::parse(&config.url).unwrap();
+        assert_eq!(
+            resolved_addrs(&config, &url).unwrap(),
+            vec!["10.0.0.5:443".parse::<SocketAddr>().unwrap()]
+        );
+        let other_port = reqwest::Url::parse("http://example.com/").unwrap();
+        assert!(resolved_addrs(&config, &other_port).is_err());
+    }
+
+    #[test]
+    fn test_unix_target() {
+        assert_eq!(
+            unix_target("unix:///var/run/app.sock"),
+            Some((
+                PathBuf::from("/var/run/app.sock"),
+                "http://localhost/".to_string()
+            ))
+        );
+        assert_eq!(
+            unix_target("unix:///var/run/app.sock:/api/health?x=1"),
+            Some((
+                PathBuf::from("/var/run/app.sock"),
+                "http://localhost/api/health?x=1".to_string()
+            ))
+        );
+        assert_eq!(unix_target("http://localhost/"), None);
+        assert!(is_http_target("unix:///tmp/app.sock"));
+        assert!(!is_http_target("localhost:6379"));
+    }
+
+    #[test]
+    fn test_compression() {
+        assert_eq!(parse_compression("gzip, BR").unwrap(), "gzip, br");
+        assert_eq!(parse_compression("none").unwrap(), "identity");
+        assert!(parse_compression("lzma").is_err());
+
+        // "hello" gzipped: the trailer records the 5 uncompressed bytes
+        let gzip = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x07, 0x00, 0x86, 0xa6, 0x10, 0x36, 0x05, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(decoded_size("gzip", &gzip), Some(5));
+        let zstd = zstd::stream::encode_all(&b"hello hello hello"[..], 3).unwrap();
+        assert_eq!(decoded_size("zstd", &zstd), Some(17));
+        assert_eq!(decoded_size("br", b"\x0b\x02\x80hello\x03"), None);
+    }
+
+    #[test]
+    fn test_expected_status() {
+        let expected = ExpectedStatus::parse("200, 201,4XX").unwrap();
+        assert!(expected.contains(201));
+        assert!(expected.contains(404));
+        assert!(!expected.contains(204));
+        assert!(!expected.contains(500));
+        assert_eq!(expected.to_string(), "200,201,4xx");
+
+        assert!(ExpectedStatus::parse("ok").is_err());
+        assert!(ExpectedStatus::parse("200,").is_err());
+        assert!(ExpectedStatus::parse("99").is_err());
+        assert!(ExpectedStatus::parse("7xx").is_err());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("Authorization: Bearer a:b").unwrap(),
+            ("Authorization".to_string(), "Bearer a:b".to_string())
+        );
+        assert_eq!(
+            parse_header("X-Empty:").unwrap(),
+            ("X-Empty".to_string(), String::new())
+        );
+        assert!(parse_header("no-colon").is_err());
+        assert!(parse_header(": value").is_err());
+    }
+
+    #[test]
+    fn test_load_ca_certs_rejects_non_pem() {
+        let path = std::env::temp_dir().join(format!("rustyload-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        assert!(load_ca_certs(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_ca_certs(&path).is_err());
+    }
+
+    #[test]
+    fn test_url_list() {
+        let path = std::env::temp_dir().join(format!("rustyload-urls-{}.txt", std::process::id()));
+        std::fs::write(&path, "# from access logs\nhttp://a/1\n\n  https://a/2  \n").unwrap();
+        let list = UrlList::load(&path, DataOrder::RoundRobin).unwrap();
+        assert_eq!(list.urls, ["http://a/1", "https://a/2"]);
+        assert_eq!(list.url(0), "http://a/1");
+        assert_eq!(list.url(3), "https://a/2");
+
+        let config = HttpConfig::new(String::new()).with_url_list(list);
+        assert_eq!(config.url, "http://a/1");
+
+        std::fs::write(&path, "http://a/1\nunix:///tmp/app.sock\n").unwrap();
+        assert!(UrlList::load(&path, DataOrder::Random).is_err());
+        std::fs::write(&path, "# nothing\n").unwrap();
+        assert!(UrlList::load(&path, DataOrder::Random).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_user_agent_list() {
+        let path = std::env::temp_dir().join(format!("rustyload-uas-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "# desktop\nMozilla/5.0 (X11; Linux x86_64)\n\n  curl/8.5.0  \n",
+        )
+        .unwrap();
+        let list = UserAgentList::load(&path).unwrap();
+        assert_eq!(
+            list.agents,
+            ["Mozilla/5.0 (X11; Linux x86_64)", "curl/8.5.0"]
+        );
+        let picked = list.pick();
+        assert!(list.agents.iter().any(|agent| agent == picked));
+
+        std::fs::write(&path, "curl/8.5.0\nbad\x7fagent\n").unwrap();
+        assert!(UserAgentList::load(&path).is_err());
+        std::fs::write(&path, "# nothing\n").unwrap();
+        assert!(UserAgentList::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_header_values() {
+        let path =
+            std::env::temp_dir().join(format!("rustyload-tenants-{}.txt", std::process::id()));
+        std::fs::write(&path, "acme\n# paused: initech\nglobex\n").unwrap();
+        let header = HeaderValues::load("X-Tenant", &path).unwrap();
+        let values: Vec<&str> = (0..3).map(|seq| header.value(seq)).collect();
+        assert_eq!(values, ["acme", "globex", "acme"]);
+
+        std::fs::write(&path, "acme\nbad\nvalue\x00\n").unwrap();
+        let error = HeaderValues::load("X-Tenant", &path).unwrap_err();
+        assert!(
+            error.to_string().contains("X-Tenant header value"),
+            "{}",
+            error
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(HeaderValues::load("X-Tenant", &path).is_err());
+    }
+
+    #[test]
+    fn test_rotate_sources() {
+        assert_eq!(rotate_sources(&[0, 1], 1), vec![0, 1]);
+        assert_eq!(rotate_sources(&[0, 1], 2), vec![0, 2, 1, 3]);
+        assert_eq!(
+            rotate_sources(&[0, 0, 1, 0], 2),
+            vec![0, 0, 2, 0, 1, 1, 3, 1]
+        );
+    }
+
+    #[test]
+    fn test_weighted_schedule() {
+        assert_eq!(weighted_schedule(&[1, 1, 1]), vec![0, 1, 2]);
+        assert_eq!(weighted_schedule(&[3, 1]), vec![0, 0, 1, 0]);
+        assert_eq!(weighted_schedule(&[5]), vec![0]);
+        assert_eq!(weighted_schedule(&[6000, 2000]), vec![0, 0, 1, 0]);
+        assert_eq!(
+            weighted_schedule(&[MAX_DNS_WEIGHT, MAX_DNS_WEIGHT - 1]).len(),
+            2 * MAX_DNS_WEIGHT as usize - 1
+        );
+    }
+
     #[test]
     fn test_http_config_builder() {
         let config = HttpConfig::new("https://example.com".to_string())
@@ -1,39 +1,119 @@
 //! Protocol abstraction module for supporting multiple load testing targets
 //!
-//! This module provides a common interface for different protocols (HTTP, FlashKV, etc.)
+//! This module provides a common interface for different protocols (HTTP, gRPC, FlashKV, etc.)
 
+pub mod dns;
+pub mod engine;
 pub mod flashkv;
+pub mod grpc;
 pub mod http;
+pub mod mysql;
+pub mod nats;
+pub mod ping;
+pub mod resp;
+pub mod s3;
+pub mod sse;
+pub mod udp;
 
-use std::collections::HashMap;
+use crate::hooks::Hooks;
+use crate::metrics::{CustomMetricSummary, CustomMetrics};
+use crate::resources::{self, ClientStats};
+use crate::sla::{self, SlaStats};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
 
 /// Supported protocols for load testing
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum Protocol {
     #[default]
     Http,
+    Grpc,
+    Sse,
+    Dns,
+    Udp,
+    Nats,
+    /// MySQL or MariaDB
+    Mysql,
+    /// S3-compatible object storage (AWS, MinIO, Ceph RGW)
+    S3,
+    /// ICMP echo, or TCP connect where ICMP isn't allowed
+    Ping,
     FlashKV,
+    /// Redis or Valkey, with the FlashKV commands sent over RESP
+    Redis,
 }
 
 impl Protocol {
-    pub fn from_str(s: &str) -> Result<Self, String> {
-        match s.to_lowercase().as_str() {
-            "http" | "https" => Ok(Protocol::Http),
-            "flashkv" | "kv" | "tcp" => Ok(Protocol::FlashKV),
-            _ => Err(format!("Unsupported protocol: {}", s)),
+    /// The names `--protocol` accepts, besides aliases such as https
+    pub const NAMES: [&'static str; 11] = [
+        "http", "grpc", "sse", "dns", "udp", "nats", "mysql", "s3", "ping", "flashkv", "redis",
+    ];
+
+    /// The name `--protocol` and config files use
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Http => "http",
+            Protocol::Grpc => "grpc",
+            Protocol::Sse => "sse",
+            Protocol::Dns => "dns",
+            Protocol::Udp => "udp",
+            Protocol::Nats => "nats",
+            Protocol::Mysql => "mysql",
+            Protocol::S3 => "s3",
+            Protocol::Ping => "ping",
+            Protocol::FlashKV => "flashkv",
+            Protocol::Redis => "redis",
         }
     }
 
     pub fn display_name(&self) -> &'static str {
         match self {
             Protocol::Http => "HTTP/HTTPS",
+            Protocol::Grpc => "gRPC",
+            Protocol::Sse => "SSE",
+            Protocol::Dns => "DNS",
+            Protocol::Udp => "UDP",
+            Protocol::Nats => "NATS",
+            Protocol::Mysql => "MySQL",
+            Protocol::S3 => "S3",
+            Protocol::Ping => "Ping",
             Protocol::FlashKV => "FlashKV (TCP)",
+            Protocol::Redis => "Redis (RESP)",
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "http" | "https" => Ok(Protocol::Http),
+            "grpc" => Ok(Protocol::Grpc),
+            "sse" => Ok(Protocol::Sse),
+            "dns" => Ok(Protocol::Dns),
+            "udp" => Ok(Protocol::Udp),
+            "nats" => Ok(Protocol::Nats),
+            "mysql" | "mariadb" => Ok(Protocol::Mysql),
+            "s3" => Ok(Protocol::S3),
+            "ping" => Ok(Protocol::Ping),
+            "flashkv" | "kv" | "tcp" => Ok(Protocol::FlashKV),
+            "redis" | "valkey" => Ok(Protocol::Redis),
+            _ => Err(format!("Unsupported protocol: {}", s)),
         }
     }
 }
 
 /// Common result structure for any protocol request
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
 pub struct RequestResult {
     /// Duration of the request in milliseconds
@@ -42,12 +122,197 @@ pub struct RequestResult {
     pub status: u16,
     /// Whether the request was successful
     pub success: bool,
-    /// Error message if the request failed
-    pub error: Option<String>,
+    /// Why the request failed, when it failed before a reply was classified
+    /// (a non-2xx HTTP response is a failure without an error)
+    pub error: Option<ErrorKind>,
+    /// Time spent queued in the client before being written, in microseconds
+    /// (only measured for pipelined protocols)
+    pub queue_time: u128,
+    /// The operation performed (HTTP method), used for per-operation breakdowns
+    pub operation: Option<&'static str>,
+    /// The backend address that served the request, when requests are spread
+    /// across several resolved addresses
+    pub backend: Option<String>,
+    /// When the request completed, stamped when it is recorded in [`LiveResults`]
+    pub completed_at: Option<Instant>,
+    /// Whether a key lookup (GET, EXISTS) found its key, `None` for other requests
+    pub cache_hit: Option<bool>,
+    /// The HTTP version that served the response, e.g. `HTTP/2`
+    pub http_version: Option<&'static str>,
+    /// Size of the response body, for responses whose body was read
+    pub body: Option<BodySize>,
+    /// The scenario step the request belongs to, for per-step breakdowns
+    pub step: Option<Arc<str>>,
+    /// The tag of the request's step, for per-tag breakdowns
+    pub tag: Option<Arc<str>>,
+    /// The gRPC status name the call ended with, e.g. `UNAVAILABLE`
+    pub grpc_status: Option<&'static str>,
+    /// What a long-lived event stream received, for SSE subscribers
+    pub stream: Option<StreamResult>,
+    /// The response code a DNS query was answered with, e.g. `NXDOMAIN`
+    pub dns_rcode: Option<&'static str>,
+    /// Whether a UDP datagram's reply never arrived, for datagrams that wait
+    /// for one
+    pub lost: Option<bool>,
+    /// Bytes uploaded in the request body, for protocols that report uploads
+    pub sent: Option<u64>,
+    /// The worker (connection, for protocols with one per worker) that sent
+    /// the request, stamped by the [`engine::Recorder`]
+    pub worker: Option<u64>,
+}
+
+/// One event-stream subscription, from connecting until it ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamResult {
+    pub events: u64,
+    /// How long the subscriber was connected, in milliseconds
+    pub connected_ms: u128,
+    /// Whether the stream ended before the subscriber was done with it
+    pub dropped: bool,
+}
+
+/// Size of one response body, as received and after decoding
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodySize {
+    /// Bytes as received, still in the response's content encoding
+    pub received: u64,
+    /// Bytes after decoding, when the size could be determined
+    pub decoded: Option<u64>,
+    /// The response's content encoding, `identity` when it had none
+    pub encoding: &'static str,
+}
+
+/// Why a request failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// The hostname could not be resolved
+    Dns,
+    /// The server refused the connection
+    ConnectRefused,
+    /// The connection could not be established in time
+    ConnectTimeout,
+    /// The TLS handshake failed (certificate, protocol version, ...)
+    Tls,
+    /// The reply did not arrive in time
+    ResponseTimeout,
+    /// The connection was reset or closed by the server
+    Reset,
+    /// The reply could not be parsed
+    ProtocolParse,
+    /// The reply arrived but failed a check, with the check that failed
+    AssertionFailed(String),
+    /// Anything else, with the full error message
+    Other(String),
+}
+
+impl ErrorKind {
+    /// Names of all kinds, as used in stats and `errors.<kind>` thresholds
+    pub const NAMES: [&'static str; 9] = [
+        "dns",
+        "connect_refused",
+        "connect_timeout",
+        "tls",
+        "response_timeout",
+        "reset",
+        "protocol_parse",
+        "assertion_failed",
+        "other",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Dns => "dns",
+            ErrorKind::ConnectRefused => "connect_refused",
+            ErrorKind::ConnectTimeout => "connect_timeout",
+            ErrorKind::Tls => "tls",
+            ErrorKind::ResponseTimeout => "response_timeout",
+            ErrorKind::Reset => "reset",
+            ErrorKind::ProtocolParse => "protocol_parse",
+            ErrorKind::AssertionFailed(_) => "assertion_failed",
+            ErrorKind::Other(_) => "other",
+        }
+    }
+
+    /// Classify an error by looking through its chain of causes
+    pub fn from_error(error: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(error);
+        while let Some(cause) = source {
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                match io.kind() {
+                    std::io::ErrorKind::ConnectionRefused => return ErrorKind::ConnectRefused,
+                    std::io::ErrorKind::TimedOut => return ErrorKind::ConnectTimeout,
+                    std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof => return ErrorKind::Reset,
+                    std::io::ErrorKind::InvalidData => return ErrorKind::ProtocolParse,
+                    _ => {}
+                }
+            }
+            source = cause.source();
+        }
+
+        // Resolver and TLS errors don't have typed causes, so fall back to the message
+        let message = error_chain(error);
+        let lower = message.to_lowercase();
+        if lower.contains("dns error") || lower.contains("failed to lookup address") {
+            ErrorKind::Dns
+        } else if lower.contains("connection refused") {
+            ErrorKind::ConnectRefused
+        } else if [
+            "certificate",
+            "tls",
+            "ssl",
+            "wrong version number",
+            "handshake",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+        {
+            ErrorKind::Tls
+        } else if lower.contains("connection reset") || lower.contains("connection closed") {
+            ErrorKind::Reset
+        } else {
+            ErrorKind::Other(message)
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Dns => write!(f, "DNS resolution failed"),
+            ErrorKind::ConnectRefused => write!(f, "connection refused"),
+            ErrorKind::ConnectTimeout => write!(f, "connect timed out"),
+            ErrorKind::Tls => write!(f, "TLS handshake failed"),
+            ErrorKind::ResponseTimeout => write!(f, "response timed out"),
+            ErrorKind::Reset => write!(f, "connection reset"),
+            ErrorKind::ProtocolParse => write!(f, "invalid reply"),
+            ErrorKind::AssertionFailed(check) => write!(f, "check failed: {}", check),
+            ErrorKind::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Describe an error including its causes, since the top-level message rarely
+/// says what actually went wrong (e.g. "error sending request for url (...)")
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let cause = cause.to_string();
+        if !message.contains(&cause) {
+            message.push_str(": ");
+            message.push_str(&cause);
+        }
+        source = source.and_then(|s| s.source());
+    }
+    message
 }
 
 /// Statistics from a load test run
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoadTestStats {
     pub total_requests: u64,
     pub successful_requests: u64,
@@ -59,7 +324,551 @@ pub struct LoadTestStats {
     pub p50: u128,
     pub p95: u128,
     pub p99: u128,
+    /// Successful requests per whole-millisecond latency, so runs can be
+    /// merged without losing their percentiles
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub latency_histogram: BTreeMap<u128, u64>,
     pub requests_per_second: f64,
+    /// Completions in the busiest whole second of the run
+    pub peak_rps: f64,
+    /// Completions in the 5th percentile second, i.e. only 5% of seconds were slower
+    pub p5_rps: f64,
+    /// Requests completed in each whole second of the run, in order (for the
+    /// terminal summary only)
+    #[serde(skip)]
+    pub rps_series: Vec<u128>,
+    /// Average time requests spent in the client-side send queue (ms)
+    pub avg_queue_time: f64,
+    /// Longest time a request spent in the client-side send queue (ms)
+    pub max_queue_time: f64,
+    /// Custom metrics recorded during the run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub custom_metrics: Vec<CustomMetricSummary>,
+    /// Per-operation breakdown, present when more than one operation was used
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_operation: Vec<GroupStats>,
+    /// Per-backend breakdown, present when requests were spread across addresses
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_backend: Vec<GroupStats>,
+    /// Per-HTTP-version breakdown, present when responses came over more than one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_http_version: Vec<GroupStats>,
+    /// Per-tag breakdown, present when requests carried more than one tag
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_tag: Vec<GroupStats>,
+    /// Per-gRPC-status breakdown, present when calls ended with more than one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_grpc_status: Vec<GroupStats>,
+    /// Requests and latency per worker, present when the run had more than one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workers: Option<WorkerStats>,
+    /// Per-step breakdown of a multi-step scenario
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_step: Vec<GroupStats>,
+    /// Responses per HTTP version that served them
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub http_versions: BTreeMap<String, u64>,
+    /// Most frequent errors, most common first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorCount>,
+    /// Hit ratio of key lookups, present when the run included any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyspace: Option<KeyspaceStats>,
+    /// Response body sizes and transfer rate, present when bodies were read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferStats>,
+    /// Events received by SSE subscribers, present for event-stream runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<StreamStats>,
+    /// Response codes of DNS queries, present for DNS runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsStats>,
+    /// Replies to UDP datagrams, present for UDP runs that wait for them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datagrams: Option<DatagramStats>,
+    /// Failed requests per error kind (see [`ErrorKind::NAMES`] and [`STATUS_ERROR`])
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors_by_kind: BTreeMap<String, u64>,
+    /// p50/p95/p99 per [`LATENCY_WINDOW_SECS`] window, skipping windows without requests
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub latency_over_time: Vec<LatencyWindow>,
+    /// The load generator's own CPU, memory and sockets, where they can be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<ClientStats>,
+    /// Apdex score and the share of requests under latency targets, added
+    /// to the stats of a finished run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sla: Option<SlaStats>,
+}
+
+/// Maximum number of distinct errors kept in [`LoadTestStats::errors`]
+pub(crate) const MAX_ERRORS: usize = 10;
+
+/// Error kind of failed requests that got a reply with a failing status
+pub const STATUS_ERROR: &str = "status";
+
+/// How often one error occurred during a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorCount {
+    /// The [`ErrorKind`] name, or [`STATUS_ERROR`]
+    #[serde(default)]
+    pub kind: String,
+    pub message: String,
+    pub count: u64,
+}
+
+/// Latency and error summary for a subset of the requests in a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroupStats {
+    pub name: String,
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub avg_latency: f64,
+    pub p50: u128,
+    pub p95: u128,
+    pub p99: u128,
+    /// Latency (ms) → number of successful requests, for merging the groups
+    /// of several runs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub latency_histogram: BTreeMap<u128, u64>,
+}
+
+impl GroupStats {
+    /// Percentage of requests in the group that failed
+    pub fn error_rate(&self) -> f64 {
+        if self.total_requests > 0 {
+            self.failed_requests as f64 / self.total_requests as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A worker's p99 at least this many times the median worker's marks it as slow
+pub const SLOW_WORKER_FACTOR: f64 = 2.0;
+
+/// How far above the median worker's p99 a slow worker's must also be, so
+/// 2 ms against 1 ms doesn't count
+const SLOW_WORKER_MARGIN_MS: u128 = 10;
+
+/// Successful requests a worker needs for its p99 to be compared
+const MIN_WORKER_REQUESTS: u64 = 20;
+
+/// Requests and latency of each worker over a run
+///
+/// When only a few workers are slow, the server is usually fine and the cause
+/// is on the client side (a stalled connection, a starved task) or a single
+/// backend those workers' connections ended up on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerStats {
+    /// One entry per worker, named by its id, in id order
+    pub workers: Vec<GroupStats>,
+    /// Median of the workers' p99, over those with enough requests to compare
+    pub median_p99: u128,
+    /// Workers whose p99 is far above the median, slowest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub slow: Vec<String>,
+    /// The run's p99 without the slow workers' requests, present when any were slow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p99_without_slow: Option<u128>,
+}
+
+/// Response body bytes over a run, as received and after decoding
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferStats {
+    /// Body bytes as received, i.e. what went over the network
+    pub received_bytes: u64,
+    /// Body bytes after decoding, for responses whose decoded size is known
+    pub decoded_bytes: u64,
+    /// Responses whose decoded size couldn't be determined (e.g. `br`),
+    /// left out of `decoded_bytes`
+    pub undecoded_responses: u64,
+    /// Responses per content encoding
+    pub by_encoding: BTreeMap<String, u64>,
+    /// Average body size as received
+    pub avg_response_bytes: f64,
+    /// Body bytes received per second of the run
+    pub bytes_per_second: f64,
+    /// Request body bytes uploaded, for protocols that report uploads
+    pub sent_bytes: u64,
+    /// Request body bytes uploaded per second of the run
+    pub sent_bytes_per_second: f64,
+}
+
+impl TransferStats {
+    /// How many times smaller the bodies were on the wire, when every
+    /// decoded size is known
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.undecoded_responses == 0 && self.received_bytes > 0)
+            .then(|| self.decoded_bytes as f64 / self.received_bytes as f64)
+    }
+}
+
+/// Check that each source address is assigned to this machine, since
+/// connections bound to one that isn't would all fail
+pub fn check_local_addrs(addrs: &[std::net::IpAddr]) -> anyhow::Result<()> {
+    for addr in addrs {
+        std::net::UdpSocket::bind((*addr, 0)).map_err(|e| {
+            anyhow::anyhow!(
+                "Can't bind to local address {} ({}): it must be assigned to this machine",
+                addr,
+                e
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Format a byte count with a binary unit, e.g. `1.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Events received by the subscribers of an event-stream run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamStats {
+    /// Subscriptions that got a stream
+    pub subscribers: u64,
+    pub events: u64,
+    /// Events received per second across all subscribers
+    pub events_per_second: f64,
+    /// Average events per second each subscriber received while connected
+    pub events_per_subscriber_second: f64,
+    /// Streams that ended before the subscriber was done with them
+    pub dropped: u64,
+    /// Average time subscribers were connected, in seconds
+    pub avg_connected_secs: f64,
+}
+
+/// Response codes DNS queries were answered with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsStats {
+    /// Queries that got a response
+    pub answered: u64,
+    /// Responses per response code
+    pub rcodes: BTreeMap<String, u64>,
+    /// Percentage of responses that were NXDOMAIN
+    pub nxdomain_rate: f64,
+    /// Percentage of responses that were SERVFAIL
+    pub servfail_rate: f64,
+}
+
+/// UDP datagrams that got a reply and those that were lost
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatagramStats {
+    pub sent: u64,
+    pub replies: u64,
+    /// Datagrams without a reply within the packet timeout
+    pub lost: u64,
+    /// Percentage of datagrams that were lost
+    pub loss_rate: f64,
+}
+
+/// Hits and misses of key lookups over a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyspaceStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Percentage of lookups that found their key
+    pub hit_ratio: f64,
+    /// Length of each interval in seconds
+    pub interval_secs: u64,
+    /// Hit ratio per interval, skipping intervals without lookups
+    pub intervals: Vec<KeyspaceInterval>,
+}
+
+/// Hits and misses of key lookups completed in one interval of a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyspaceInterval {
+    /// Seconds from the start of the run to the start of the interval
+    pub start_secs: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_ratio: f64,
+}
+
+/// Latency percentiles of the requests completed in one window of a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyWindow {
+    /// Seconds from the start of the run to the start of the window
+    pub start_secs: u64,
+    pub requests: u64,
+    pub failed: u64,
+    /// Percentiles of the successful requests, 0 when none succeeded
+    pub p50: u128,
+    pub p95: u128,
+    pub p99: u128,
+    /// Latency (ms) → number of successful requests, for merging the windows
+    /// of several runs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub latency_histogram: BTreeMap<u128, u64>,
+}
+
+/// Length of the windows latency percentiles are tracked over, in seconds
+pub const LATENCY_WINDOW_SECS: u64 = 5;
+
+/// Runs are split into at most this many keyspace intervals
+const MAX_KEYSPACE_INTERVALS: u64 = 20;
+
+/// Results recorded so far in a run
+///
+/// Workers record each result as it completes, so the run can be reported on
+/// (e.g. by periodic snapshots) while it is still in progress.
+#[derive(Debug, Clone, Default)]
+pub struct LiveResults {
+    results: Arc<Mutex<Vec<RequestResult>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<RequestResult>>>>,
+    stop: StopHandle,
+    metrics: CustomMetrics,
+}
+
+impl LiveResults {
+    pub fn record(&self, mut result: RequestResult) {
+        result.completed_at.get_or_insert_with(Instant::now);
+        self.publish(std::slice::from_ref(&result));
+        self.results.lock().unwrap().push(result);
+    }
+
+    pub fn extend(&self, mut results: Vec<RequestResult>) {
+        let now = Instant::now();
+        for result in &mut results {
+            result.completed_at.get_or_insert(now);
+        }
+        self.publish(&results);
+        self.results.lock().unwrap().extend(results);
+    }
+
+    /// Receive every result recorded from now on; the channel closes once
+    /// the run is over
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<RequestResult> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    fn publish(&self, results: &[RequestResult]) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // Receivers that were dropped are forgotten
+        subscribers.retain(|sender| {
+            results
+                .iter()
+                .all(|result| sender.send(result.clone()).is_ok())
+        });
+    }
+
+    /// A handle that stops the run these results are recorded for
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// The run's custom metrics
+    pub fn metrics(&self) -> &CustomMetrics {
+        &self.metrics
+    }
+
+    /// Stats over everything recorded so far
+    pub fn stats(&self, elapsed: u128) -> LoadTestStats {
+        // Copy out so workers aren't blocked while the stats are computed
+        let results = self.results.lock().unwrap().clone();
+        calculate_stats(&results, elapsed)
+    }
+
+    /// Number of results recorded so far
+    pub fn completed(&self) -> u64 {
+        self.results.lock().unwrap().len() as u64
+    }
+
+    /// Take all recorded results, leaving the collection empty
+    pub fn take(&self) -> Vec<RequestResult> {
+        std::mem::take(&mut *self.results.lock().unwrap())
+    }
+}
+
+/// Stops a run early: requests in flight complete and are recorded, the rest
+/// are not sent
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the run is stopped
+    pub async fn stopped(&self) {
+        loop {
+            // Registered before checking, so a stop in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_stopped() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// A load test running in the background, see [`LoadTestConfig::start`]
+pub struct RunningTest {
+    /// Every result as its request completes; closes when the test is over
+    pub results: mpsc::UnboundedReceiver<RequestResult>,
+    stop: StopHandle,
+    task: JoinHandle<anyhow::Result<LoadTestStats>>,
+}
+
+impl RunningTest {
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Wait for the test to end, returning the stats over all its requests
+    pub async fn finish(self) -> anyhow::Result<LoadTestStats> {
+        self.task.await?
+    }
+}
+
+/// One phase of a run: a number of requests sent at a fixed concurrency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub requests: u64,
+    pub concurrency: u64,
+}
+
+/// How concurrent workers are released at the start of each stage, so they
+/// don't all fire their first request in the same instant
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Startup {
+    /// Random extra delay of up to this long per worker
+    pub jitter: Duration,
+    /// Release workers evenly over this long instead of all at once
+    pub soft_start: Duration,
+}
+
+impl Startup {
+    /// How long worker `index` of `workers` waits before its first request
+    pub fn delay(&self, index: u64, workers: u64) -> Duration {
+        let ramp = self
+            .soft_start
+            .mul_f64(index as f64 / workers.max(1) as f64);
+        let jitter = match self.jitter.as_nanos() as u64 {
+            0 => Duration::ZERO,
+            max => Duration::from_nanos(rand::rng().random_range(0..max)),
+        };
+        ramp + jitter
+    }
+
+    /// A pool of `workers` workers, each released once its startup delay has
+    /// passed
+    pub fn worker_pool(&self, workers: u64) -> Arc<WorkerPool> {
+        let pool = Arc::new(WorkerPool {
+            slots: Arc::new(Semaphore::new(0)),
+            // Reversed so the lowest ids are handed out first
+            idle: Mutex::new((0..workers).rev().collect()),
+            iterations: (0..workers).map(|_| AtomicU64::new(0)).collect(),
+        });
+        if *self == Startup::default() {
+            pool.slots.add_permits(workers as usize);
+            return pool;
+        }
+        for index in 0..workers {
+            let slots = Arc::clone(&pool.slots);
+            let delay = self.delay(index, workers);
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                slots.add_permits(1);
+            });
+        }
+        pool
+    }
+}
+
+/// The concurrent workers of a stage: each request runs on one of them, so at
+/// most `workers` requests are in flight
+pub struct WorkerPool {
+    slots: Arc<Semaphore>,
+    idle: Mutex<Vec<u64>>,
+    /// Requests started per worker
+    iterations: Vec<AtomicU64>,
+}
+
+impl WorkerPool {
+    /// Wait for a free worker
+    pub async fn acquire(self: &Arc<Self>) -> Worker {
+        let permit = Arc::clone(&self.slots).acquire_owned().await.unwrap();
+        // Holding a permit guarantees an idle worker
+        let id = self.idle.lock().unwrap().pop().unwrap_or_default();
+        Worker {
+            id,
+            iteration: self.iterations[id as usize].fetch_add(1, Ordering::Relaxed),
+            pool: Arc::clone(self),
+            _permit: permit,
+        }
+    }
+}
+
+/// A worker busy with one request, handed back to its pool when dropped
+pub struct Worker {
+    /// Which worker this is, from 0 up to the concurrency
+    pub id: u64,
+    /// How many requests this worker started before this one
+    pub iteration: u64,
+    pool: Arc<WorkerPool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.pool.idle.lock().unwrap().push(self.id);
+    }
+}
+
+impl fmt::Display for Startup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.soft_start.is_zero() {
+            parts.push(format!("soft start {:?}", self.soft_start));
+        }
+        if !self.jitter.is_zero() {
+            parts.push(format!("jitter {:?}", self.jitter));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// One request sent ahead of a run, see [`LoadTestConfig::preview`]
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub result: RequestResult,
+    /// The response body or server reply, for protocols that read one
+    pub snippet: Option<String>,
 }
 
 /// Unified configuration for load testing any protocol
@@ -75,12 +884,52 @@ pub struct LoadTestConfig {
     pub timeout_secs: u64,
     /// HTTP-specific configuration
     pub http_config: Option<http::HttpConfig>,
-    /// FlashKV-specific configuration
+    /// gRPC-specific configuration
+    pub grpc_config: Option<grpc::GrpcConfig>,
+    /// SSE-specific configuration
+    pub sse_config: Option<sse::SseConfig>,
+    /// DNS-specific configuration
+    pub dns_config: Option<dns::DnsConfig>,
+    /// UDP-specific configuration
+    pub udp_config: Option<udp::UdpConfig>,
+    /// NATS-specific configuration
+    pub nats_config: Option<nats::NatsConfig>,
+    /// MySQL-specific configuration
+    pub mysql_config: Option<mysql::MysqlConfig>,
+    /// S3-specific configuration
+    pub s3_config: Option<s3::S3Config>,
+    /// Ping-specific configuration
+    pub ping_config: Option<ping::PingConfig>,
+    /// FlashKV-specific configuration, also used for Redis
     pub flashkv_config: Option<flashkv::FlashKVConfig>,
+    /// Stages run back to back (empty for a single stage of `num_requests`
+    /// at `concurrency`)
+    pub stages: Vec<Stage>,
+    /// How workers are released at the start of each stage
+    pub startup: Startup,
+    /// Callbacks around the run and every request
+    pub hooks: Option<Arc<dyn Hooks>>,
+    /// Latency the Apdex score of the results is computed for
+    pub apdex_threshold: Option<Duration>,
+    /// The target as written, when it differs from the expanded one
+    pub shown_target: Option<String>,
 }
 
 #[allow(dead_code)]
 impl LoadTestConfig {
+    /// Whether requests can wait in a client-side queue: behind each other
+    /// on a pipelined FlashKV/Redis connection, or for a slot in a capped
+    /// HTTP connection pool
+    pub fn queues_requests(&self) -> bool {
+        self.flashkv_config
+            .as_ref()
+            .is_some_and(|config| config.pipeline_depth > 1)
+            || self
+                .http_config
+                .as_ref()
+                .is_some_and(|config| config.max_connections.is_some())
+    }
+
     pub fn new_http(url: String, num_requests: u64, concurrency: u64) -> Self {
         Self {
             protocol: Protocol::Http,
@@ -92,8 +941,48 @@ impl LoadTestConfig {
                 method: http::HttpMethod::GET,
                 headers: HashMap::new(),
                 body: None,
+                multipart: None,
+                basic_auth: None,
+                spread_dns: false,
+                dns_weights: Vec::new(),
+                proxy: None,
+                ca_cert: None,
+                sni: None,
+                http2: None,
+                keepalive: true,
+                max_connections: None,
+                resolve: Vec::new(),
+                local_addrs: Vec::new(),
+                dns_cache: http::DnsCache::Off,
+                unix_socket: None,
+                accept_encoding: None,
+                expect_status: None,
+                checks: Vec::new(),
+                extractions: Vec::new(),
+                data: None,
+                url_list: None,
+                user_agent: None,
+                user_agents: None,
+                header_values: Vec::new(),
+                replay: None,
+                script: None,
+                steps: Vec::new(),
+                hooks: None,
             }),
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
             flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
         }
     }
 
@@ -110,6 +999,14 @@ impl LoadTestConfig {
             concurrency,
             timeout_secs: 30,
             http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
             flashkv_config: Some(flashkv::FlashKVConfig {
                 host,
                 port,
@@ -117,7 +1014,220 @@ impl LoadTestConfig {
                 use_random_keys: false,
                 key_prefix: "key".to_string(),
                 key_range: 1000,
+                pipeline_depth: 1,
+                wire: flashkv::WireFormat::Inline,
+                keepalive: true,
+                username: None,
+                password: None,
+                preload: 0,
+                preload_value_size: flashkv::DEFAULT_PRELOAD_VALUE_SIZE,
+                value_size: None,
+                nodes: Vec::new(),
+                placement: flashkv::NodePlacement::KeyHash,
+                socket: None,
+                reconnect_backoff: flashkv::DEFAULT_RECONNECT_BACKOFF,
+                reconnect_max_backoff: flashkv::DEFAULT_RECONNECT_MAX_BACKOFF,
+                binary_values: false,
+                replay: None,
+                local_addrs: Vec::new(),
             }),
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_grpc(config: grpc::GrpcConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Grpc,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: Some(config),
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_sse(config: sse::SseConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Sse,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: Some(config),
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_dns(config: dns::DnsConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Dns,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: Some(config),
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_udp(config: udp::UdpConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Udp,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: Some(config),
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_nats(config: nats::NatsConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Nats,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: Some(config),
+            mysql_config: None,
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_mysql(config: mysql::MysqlConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Mysql,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: Some(config),
+            s3_config: None,
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_s3(config: s3::S3Config, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::S3,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: Some(config),
+            ping_config: None,
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
+        }
+    }
+
+    pub fn new_ping(config: ping::PingConfig, num_requests: u64, concurrency: u64) -> Self {
+        Self {
+            protocol: Protocol::Ping,
+            num_requests,
+            concurrency,
+            timeout_secs: 30,
+            http_config: None,
+            grpc_config: None,
+            sse_config: None,
+            dns_config: None,
+            udp_config: None,
+            nats_config: None,
+            mysql_config: None,
+            s3_config: None,
+            ping_config: Some(config),
+            flashkv_config: None,
+            stages: Vec::new(),
+            startup: Startup::default(),
+            hooks: None,
+            apdex_threshold: None,
+            shown_target: None,
         }
     }
 
@@ -126,21 +1236,455 @@ impl LoadTestConfig {
         self
     }
 
+    pub fn with_hooks(mut self, hooks: Arc<dyn Hooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    pub fn with_apdex_threshold(mut self, threshold: Duration) -> Self {
+        self.apdex_threshold = Some(threshold);
+        self
+    }
+
+    /// How `stats` measure up against the latency targets, including this
+    /// test's Apdex threshold
+    pub fn sla(&self, stats: &LoadTestStats) -> SlaStats {
+        let apdex_threshold = self.apdex_threshold.map(|t| t.as_millis().max(1));
+        sla::summarize(stats, apdex_threshold)
+    }
+
+    /// The stages to run, in order
+    pub fn stages(&self) -> Vec<Stage> {
+        if self.stages.is_empty() {
+            vec![Stage {
+                requests: self.num_requests,
+                concurrency: self.concurrency,
+            }]
+        } else {
+            self.stages.clone()
+        }
+    }
+
+    /// Run the test to completion, recording each result in `live` as it
+    /// completes
+    pub async fn run(&self, quiet: bool, live: &LiveResults) -> anyhow::Result<LoadTestStats> {
+        let concurrency = self.stages().iter().map(|s| s.concurrency).max();
+        resources::ensure_fd_limit(concurrency.unwrap_or(0))?;
+        if let Some(hooks) = &self.hooks {
+            hooks.on_start(self, live.metrics());
+        }
+        let mut stats = match self.protocol {
+            Protocol::Http => {
+                let http_config = self
+                    .http_config
+                    .as_ref()
+                    .expect("HTTP config required for HTTP protocol");
+                http::run_load_test(
+                    http_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Grpc => {
+                let grpc_config = self
+                    .grpc_config
+                    .as_ref()
+                    .expect("gRPC config required for gRPC protocol");
+                grpc::run_load_test(
+                    grpc_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Sse => {
+                let sse_config = self
+                    .sse_config
+                    .as_ref()
+                    .expect("SSE config required for SSE protocol");
+                sse::run_load_test(
+                    sse_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Dns => {
+                let dns_config = self
+                    .dns_config
+                    .as_ref()
+                    .expect("DNS config required for DNS protocol");
+                dns::run_load_test(
+                    dns_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Udp => {
+                let udp_config = self
+                    .udp_config
+                    .as_ref()
+                    .expect("UDP config required for UDP protocol");
+                udp::run_load_test(
+                    udp_config,
+                    &self.stages(),
+                    self.startup,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Nats => {
+                let nats_config = self
+                    .nats_config
+                    .as_ref()
+                    .expect("NATS config required for NATS protocol");
+                nats::run_load_test(
+                    nats_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Mysql => {
+                let mysql_config = self
+                    .mysql_config
+                    .as_ref()
+                    .expect("MySQL config required for MySQL protocol");
+                mysql::run_load_test(
+                    mysql_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::S3 => {
+                let s3_config = self
+                    .s3_config
+                    .as_ref()
+                    .expect("S3 config required for S3 protocol");
+                s3::run_load_test(
+                    s3_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::Ping => {
+                let ping_config = self
+                    .ping_config
+                    .as_ref()
+                    .expect("Ping config required for ping protocol");
+                ping::run_load_test(
+                    ping_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+            Protocol::FlashKV | Protocol::Redis => {
+                let flashkv_config = self
+                    .flashkv_config
+                    .as_ref()
+                    .expect("FlashKV config required for FlashKV and Redis protocols");
+                flashkv::run_load_test(
+                    flashkv_config,
+                    &self.stages(),
+                    self.startup,
+                    self.timeout_secs,
+                    quiet,
+                    live,
+                    self.hooks.clone(),
+                )
+                .await?
+            }
+        };
+        stats.sla = Some(self.sla(&stats));
+        if let Some(hooks) = &self.hooks {
+            hooks.on_finish(&stats);
+        }
+        Ok(stats)
+    }
+
+    /// Send one request (the first of the test) before the run, to catch a
+    /// wrong target or command before sending many of them
+    pub async fn preview(&self) -> anyhow::Result<Preview> {
+        let (result, snippet) = match self.protocol {
+            Protocol::Http => {
+                let http_config = self
+                    .http_config
+                    .as_ref()
+                    .expect("HTTP config required for HTTP protocol");
+                if http_config.script.is_some() {
+                    anyhow::bail!("A preview isn't available for scripts");
+                }
+                http::preview(http_config, self.timeout_secs).await?
+            }
+            Protocol::FlashKV | Protocol::Redis => {
+                let flashkv_config = self
+                    .flashkv_config
+                    .as_ref()
+                    .expect("FlashKV config required for FlashKV protocol");
+                flashkv::preview(flashkv_config, self.timeout_secs).await
+            }
+            // Subscribers stay connected for as long as they're configured to
+            Protocol::Sse => anyhow::bail!("A preview isn't available for SSE"),
+            // The rest have no reply worth showing, so one request is run as
+            // a test of its own
+            _ => {
+                let mut config = self.clone();
+                config.num_requests = 1;
+                config.concurrency = 1;
+                config.stages = Vec::new();
+                config.hooks = None;
+                let live = LiveResults::default();
+                let mut results = live.subscribe();
+                config.run(true, &live).await?;
+                let result = results
+                    .try_recv()
+                    .map_err(|_| anyhow::anyhow!("The preview request wasn't sent"))?;
+                (result, None)
+            }
+        };
+        Ok(Preview { result, snippet })
+    }
+
+    /// Start the test in the background, without a progress bar, to follow
+    /// its results as they arrive or stop it early
+    pub fn start(self) -> RunningTest {
+        let live = LiveResults::default();
+        let results = live.subscribe();
+        let stop = live.stop_handle();
+        let task = tokio::spawn(async move { self.run(true, &live).await });
+        RunningTest {
+            results,
+            stop,
+            task,
+        }
+    }
+
     /// Get display URL/address for the config
     pub fn display_target(&self) -> String {
+        if let Some(target) = &self.shown_target {
+            return target.clone();
+        }
         match self.protocol {
-            Protocol::Http => self
-                .http_config
+            Protocol::Http => match &self.http_config {
+                Some(http::HttpConfig {
+                    url_list: Some(list),
+                    ..
+                }) => format!("{} ({} URLs)", list.path.display(), list.urls.len()),
+                Some(c) => c.url.clone(),
+                None => "unknown".to_string(),
+            },
+            Protocol::Grpc => self
+                .grpc_config
+                .as_ref()
+                .map(|c| format!("{}/{}", c.url.trim_end_matches('/'), c.method))
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::Sse => self
+                .sse_config
                 .as_ref()
                 .map(|c| c.url.clone())
                 .unwrap_or_else(|| "unknown".to_string()),
-            Protocol::FlashKV => self
+            Protocol::Dns => self
+                .dns_config
+                .as_ref()
+                .map(|c| {
+                    format!(
+                        "{} ({} {})",
+                        c.server,
+                        c.name,
+                        dns::record_type_name(c.record_type)
+                    )
+                })
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::Udp => self
+                .udp_config
+                .as_ref()
+                .map(|c| c.target.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::Nats => self
+                .nats_config
+                .as_ref()
+                .map(|c| format!("nats://{} ({})", c.address(), c.subject))
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::Mysql => self
+                .mysql_config
+                .as_ref()
+                .map(|c| match &c.database {
+                    Some(database) => format!("mysql://{}/{}", c.address(), database),
+                    None => format!("mysql://{}", c.address()),
+                })
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::S3 => self
+                .s3_config
+                .as_ref()
+                .map(|c| format!("{}/{}", c.endpoint, c.bucket))
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::Ping => self
+                .ping_config
+                .as_ref()
+                .map(|c| format!("{} ({})", c.target, c.mode.as_str()))
+                .unwrap_or_else(|| "unknown".to_string()),
+            Protocol::FlashKV | Protocol::Redis => self
                 .flashkv_config
                 .as_ref()
-                .map(|c| format!("{}:{}", c.host, c.port))
+                .map(|c| match c.nodes.len() {
+                    0 => c.address(),
+                    more => format!(
+                        "{} (+{} nodes, {})",
+                        c.address(),
+                        more,
+                        c.placement.as_str()
+                    ),
+                })
                 .unwrap_or_else(|| "unknown".to_string()),
         }
     }
+
+    /// The address requests go to, in the form `retarget` takes
+    pub fn target(&self) -> String {
+        if let Some(c) = &self.http_config {
+            c.url.clone()
+        } else if let Some(c) = &self.grpc_config {
+            c.url.clone()
+        } else if let Some(c) = &self.sse_config {
+            c.url.clone()
+        } else if let Some(c) = &self.dns_config {
+            c.server.clone()
+        } else if let Some(c) = &self.udp_config {
+            c.target.clone()
+        } else if let Some(c) = &self.nats_config {
+            c.address()
+        } else if let Some(c) = &self.mysql_config {
+            c.address()
+        } else if let Some(c) = &self.s3_config {
+            c.endpoint.clone()
+        } else if let Some(c) = &self.ping_config {
+            c.target.clone()
+        } else if let Some(c) = &self.flashkv_config {
+            c.address()
+        } else {
+            String::new()
+        }
+    }
+
+    /// Send the same requests to another address: a URL for HTTP, gRPC, SSE
+    /// and S3, else host:port (credentials, databases and subjects are kept)
+    pub fn retarget(&mut self, target: &str) -> anyhow::Result<()> {
+        let target = target.trim();
+        let url = || {
+            if http::is_http_target(target) {
+                Ok(target.to_string())
+            } else {
+                Err(anyhow::anyhow!(
+                    "{} isn't a URL: it must start with http:// or https://",
+                    target
+                ))
+            }
+        };
+        // The port stays the same when only a host is given
+        let host_and_port = |scheme: &str, port: u16| -> anyhow::Result<(String, u16)> {
+            let address = target.strip_prefix(scheme).unwrap_or(target);
+            Ok(match address.rsplit_once(':') {
+                Some((host, port)) => (
+                    host.to_string(),
+                    port.parse()
+                        .map_err(|_| anyhow::anyhow!("Invalid port in {}", target))?,
+                ),
+                None => (address.to_string(), port),
+            })
+        };
+        if target.is_empty() {
+            anyhow::bail!("The target can't be empty");
+        }
+
+        if let Some(c) = &mut self.http_config {
+            if c.url_list.is_some()
+                || !c.steps.is_empty()
+                || c.replay.is_some()
+                || c.script.is_some()
+            {
+                anyhow::bail!(
+                    "The URLs of this test come from a file, steps, a replay or a script"
+                );
+            }
+            c.url = url()?;
+        } else if let Some(c) = &mut self.grpc_config {
+            c.url = url()?;
+        } else if let Some(c) = &mut self.sse_config {
+            c.url = url()?;
+        } else if let Some(c) = &mut self.dns_config {
+            c.server = target.to_string();
+        } else if let Some(c) = &mut self.udp_config {
+            c.target = target.to_string();
+        } else if let Some(c) = &mut self.nats_config {
+            (c.host, c.port) = host_and_port("nats://", c.port)?;
+        } else if let Some(c) = &mut self.mysql_config {
+            (c.host, c.port) = host_and_port("mysql://", c.port)?;
+        } else if let Some(c) = &mut self.s3_config {
+            c.endpoint = s3::S3Config::new(target.to_string(), c.bucket.clone())?.endpoint;
+        } else if let Some(c) = &mut self.ping_config {
+            c.target = target.to_string();
+        } else if let Some(c) = &mut self.flashkv_config {
+            if !c.nodes.is_empty() {
+                anyhow::bail!("A test spread over several nodes can't be pointed at another one");
+            }
+            match target.strip_prefix(flashkv::UNIX_SCHEME) {
+                Some(path) => {
+                    c.socket = Some(std::path::PathBuf::from(path));
+                    (c.host, c.port) = (target.to_string(), 0);
+                }
+                None => {
+                    // A socket had no port to keep
+                    let port = if c.socket.take().is_some() {
+                        6379
+                    } else {
+                        c.port
+                    };
+                    (c.host, c.port) = host_and_port("redis://", port)?;
+                }
+            }
+        }
+        self.shown_target = None;
+        Ok(())
+    }
 }
 
 /// Calculate statistics from request results
@@ -174,13 +1718,36 @@ pub fn calculate_stats(results: &[RequestResult], total_duration: u128) -> LoadT
         (min, max, avg, p50, p95, p99)
     };
 
+    let latency_histogram = histogram(&latencies);
+
+    // Client-side queue times, reported separately from the server round trip
+    let queue_times: Vec<u128> = results
+        .iter()
+        .filter(|r| r.success)
+        .map(|r| r.queue_time)
+        .collect();
+    let (avg_queue_time, max_queue_time) = if queue_times.is_empty() {
+        (0.0, 0.0)
+    } else {
+        let sum: u128 = queue_times.iter().sum();
+        let avg = sum as f64 / queue_times.len() as f64 / 1000.0;
+        let max = *queue_times.iter().max().unwrap() as f64 / 1000.0;
+        (avg, max)
+    };
+
     let requests_per_second = if total_duration > 0 {
         (total_requests as f64 / total_duration as f64) * 1000.0
     } else {
         0.0
     };
 
-    LoadTestStats {
+    let rps_series = completions_per_second(results);
+    let mut per_second = rps_series.clone();
+    per_second.sort_unstable();
+    let peak_rps = per_second.last().copied().unwrap_or(0) as f64;
+    let p5_rps = percentile(&per_second, 5.0) as f64;
+
+    let mut stats = LoadTestStats {
         total_requests,
         successful_requests,
         failed_requests,
@@ -191,11 +1758,445 @@ pub fn calculate_stats(results: &[RequestResult], total_duration: u128) -> LoadT
         p50,
         p95,
         p99,
+        latency_histogram,
         requests_per_second,
+        peak_rps,
+        p5_rps,
+        rps_series,
+        avg_queue_time,
+        max_queue_time,
+        custom_metrics: Vec::new(),
+        by_operation: group_stats(results, |r| r.operation),
+        by_backend: group_stats(results, |r| r.backend.as_deref()),
+        by_http_version: group_stats(results, |r| r.http_version),
+        by_grpc_status: group_stats(results, |r| r.grpc_status),
+        workers: worker_stats(results),
+        by_step: group_stats(results, |r| r.step.as_deref()),
+        by_tag: group_stats(results, |r| r.tag.as_deref()),
+        http_versions: count_http_versions(results),
+        keyspace: keyspace_stats(results),
+        transfer: transfer_stats(results, total_duration),
+        stream: stream_stats(results, total_duration),
+        dns: dns_stats(results),
+        datagrams: datagram_stats(results),
+        errors: count_errors(results),
+        errors_by_kind: count_error_kinds(results),
+        latency_over_time: latency_over_time(results),
+        client: None,
+        sla: None,
+    };
+    stats.sla = Some(sla::summarize(&stats, None));
+    stats
+}
+
+/// When the earliest request of a run was sent
+fn run_start(results: &[RequestResult]) -> Option<Instant> {
+    results
+        .iter()
+        .filter_map(|r| {
+            let completed_at = r.completed_at?;
+            Some(
+                completed_at
+                    .checked_sub(Duration::from_millis(r.duration as u64))
+                    .unwrap_or(completed_at),
+            )
+        })
+        .min()
+}
+
+/// Number of requests completed in each whole second of the run
+///
+/// Seconds are counted from the start of the earliest request. The trailing
+/// partial second is left out, since it would always look like the slowest.
+fn completions_per_second(results: &[RequestResult]) -> Vec<u128> {
+    let (Some(origin), Some(end)) = (
+        run_start(results),
+        results.iter().filter_map(|r| r.completed_at).max(),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut counts = vec![0; (end - origin).as_secs() as usize];
+    for completed_at in results.iter().filter_map(|r| r.completed_at) {
+        let second = (completed_at - origin).as_secs() as usize;
+        if let Some(count) = counts.get_mut(second) {
+            *count += 1;
+        }
+    }
+    counts
+}
+
+/// Latency percentiles per window of the run, so a latency that degrades
+/// over a long run shows rather than being averaged away
+fn latency_over_time(results: &[RequestResult]) -> Vec<LatencyWindow> {
+    let Some(origin) = run_start(results) else {
+        return Vec::new();
+    };
+
+    let mut windows: BTreeMap<u64, (u64, Vec<u128>)> = BTreeMap::new();
+    for result in results {
+        let Some(completed_at) = result.completed_at else {
+            continue;
+        };
+        let index = (completed_at - origin).as_secs() / LATENCY_WINDOW_SECS;
+        let (requests, latencies) = windows.entry(index).or_default();
+        *requests += 1;
+        if result.success {
+            latencies.push(result.duration);
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(index, (requests, mut latencies))| {
+            latencies.sort_unstable();
+            LatencyWindow {
+                start_secs: index * LATENCY_WINDOW_SECS,
+                requests,
+                failed: requests - latencies.len() as u64,
+                p50: percentile(&latencies, 50.0),
+                p95: percentile(&latencies, 95.0),
+                p99: percentile(&latencies, 99.0),
+                latency_histogram: histogram(&latencies),
+            }
+        })
+        .collect()
+}
+
+/// Total body bytes received and decoded, per content encoding, and bytes
+/// uploaded
+fn transfer_stats(results: &[RequestResult], total_duration: u128) -> Option<TransferStats> {
+    let mut transfer: Option<TransferStats> = None;
+    let mut responses = 0;
+    for sent in results.iter().filter_map(|r| r.sent) {
+        transfer
+            .get_or_insert_with(TransferStats::default)
+            .sent_bytes += sent;
+    }
+    for body in results.iter().filter_map(|r| r.body.as_ref()) {
+        responses += 1;
+        let transfer = transfer.get_or_insert_with(TransferStats::default);
+        transfer.received_bytes += body.received;
+        match body.decoded {
+            Some(decoded) => transfer.decoded_bytes += decoded,
+            None => transfer.undecoded_responses += 1,
+        }
+        *transfer
+            .by_encoding
+            .entry(body.encoding.to_string())
+            .or_default() += 1;
+    }
+
+    if let Some(transfer) = &mut transfer {
+        if responses > 0 {
+            transfer.avg_response_bytes = transfer.received_bytes as f64 / responses as f64;
+        }
+        if total_duration > 0 {
+            transfer.bytes_per_second =
+                transfer.received_bytes as f64 / total_duration as f64 * 1000.0;
+            transfer.sent_bytes_per_second =
+                transfer.sent_bytes as f64 / total_duration as f64 * 1000.0;
+        }
+    }
+    transfer
+}
+
+/// Events received over an event-stream run
+fn stream_stats(results: &[RequestResult], total_duration: u128) -> Option<StreamStats> {
+    let streams: Vec<&StreamResult> = results.iter().filter_map(|r| r.stream.as_ref()).collect();
+    if streams.is_empty() {
+        return None;
+    }
+
+    let subscribers = streams.len() as u64;
+    let events = streams.iter().map(|s| s.events).sum();
+    let connected_secs: Vec<f64> = streams
+        .iter()
+        .map(|s| s.connected_ms as f64 / 1000.0)
+        .collect();
+    let rates: f64 = streams
+        .iter()
+        .zip(&connected_secs)
+        .filter(|(_, secs)| **secs > 0.0)
+        .map(|(s, secs)| s.events as f64 / secs)
+        .sum();
+    Some(StreamStats {
+        subscribers,
+        events,
+        events_per_second: if total_duration > 0 {
+            events as f64 / total_duration as f64 * 1000.0
+        } else {
+            0.0
+        },
+        events_per_subscriber_second: rates / subscribers as f64,
+        dropped: streams.iter().filter(|s| s.dropped).count() as u64,
+        avg_connected_secs: connected_secs.iter().sum::<f64>() / subscribers as f64,
+    })
+}
+
+/// Responses per DNS response code
+fn dns_stats(results: &[RequestResult]) -> Option<DnsStats> {
+    let mut rcodes: BTreeMap<String, u64> = BTreeMap::new();
+    for rcode in results.iter().filter_map(|r| r.dns_rcode) {
+        *rcodes.entry(rcode.to_string()).or_default() += 1;
+    }
+    let answered: u64 = rcodes.values().sum();
+    if answered == 0 {
+        return None;
+    }
+
+    let rate = |rcode: &str| {
+        rcodes.get(rcode).copied().unwrap_or_default() as f64 / answered as f64 * 100.0
+    };
+    Some(DnsStats {
+        answered,
+        nxdomain_rate: rate("NXDOMAIN"),
+        servfail_rate: rate("SERVFAIL"),
+        rcodes,
+    })
+}
+
+/// Replies and losses of UDP datagrams that waited for a reply
+fn datagram_stats(results: &[RequestResult]) -> Option<DatagramStats> {
+    let sent = results.iter().filter(|r| r.lost.is_some()).count() as u64;
+    if sent == 0 {
+        return None;
+    }
+
+    let lost = results.iter().filter(|r| r.lost == Some(true)).count() as u64;
+    Some(DatagramStats {
+        sent,
+        replies: sent - lost,
+        lost,
+        loss_rate: lost as f64 / sent as f64 * 100.0,
+    })
+}
+
+/// Per-worker breakdown, with the workers whose p99 stands out
+fn worker_stats(results: &[RequestResult]) -> Option<WorkerStats> {
+    compare_workers(group_stats(results, |r| r.worker))
+}
+
+/// Compare the workers' p99s, marking those far above the median as slow
+pub fn compare_workers(workers: Vec<GroupStats>) -> Option<WorkerStats> {
+    if workers.is_empty() {
+        return None;
+    }
+
+    let compared: Vec<&GroupStats> = workers
+        .iter()
+        .filter(|w| w.successful_requests >= MIN_WORKER_REQUESTS)
+        .collect();
+    let mut p99s: Vec<u128> = compared.iter().map(|w| w.p99).collect();
+    p99s.sort_unstable();
+    let median_p99 = percentile(&p99s, 50.0);
+
+    let mut slow: Vec<&GroupStats> = if compared.len() < 3 {
+        // Two workers have no majority to stand out from
+        Vec::new()
+    } else {
+        compared
+            .into_iter()
+            .filter(|w| {
+                w.p99 as f64 >= median_p99 as f64 * SLOW_WORKER_FACTOR
+                    && w.p99 >= median_p99 + SLOW_WORKER_MARGIN_MS
+            })
+            .collect()
+    };
+    slow.sort_by_key(|w| std::cmp::Reverse(w.p99));
+    let slow: Vec<String> = slow.into_iter().map(|w| w.name.clone()).collect();
+
+    let p99_without_slow = (!slow.is_empty()).then(|| {
+        let mut latencies = BTreeMap::new();
+        for worker in workers.iter().filter(|w| !slow.contains(&w.name)) {
+            for (latency, count) in &worker.latency_histogram {
+                *latencies.entry(*latency).or_default() += count;
+            }
+        }
+        histogram_percentile(&latencies, 99.0)
+    });
+
+    Some(WorkerStats {
+        workers,
+        median_p99,
+        slow,
+        p99_without_slow,
+    })
+}
+
+/// Hit ratio of key lookups, overall and per interval
+fn keyspace_stats(results: &[RequestResult]) -> Option<KeyspaceStats> {
+    let lookups: Vec<&RequestResult> = results.iter().filter(|r| r.cache_hit.is_some()).collect();
+    if lookups.is_empty() {
+        return None;
+    }
+
+    let ratio = |hits: u64, misses: u64| hits as f64 / (hits + misses) as f64 * 100.0;
+    let hits = lookups.iter().filter(|r| r.cache_hit == Some(true)).count() as u64;
+    let misses = lookups.len() as u64 - hits;
+
+    let mut intervals = Vec::new();
+    let mut interval_secs = 0;
+    let end = lookups.iter().filter_map(|r| r.completed_at).max();
+    if let (Some(origin), Some(end)) = (run_start(results), end) {
+        // Whole seconds, and few enough intervals to read at a glance
+        interval_secs = (end - origin)
+            .as_secs()
+            .div_ceil(MAX_KEYSPACE_INTERVALS)
+            .max(1);
+
+        let mut counts: BTreeMap<u64, (u64, u64)> = BTreeMap::new();
+        for lookup in &lookups {
+            let Some(completed_at) = lookup.completed_at else {
+                continue;
+            };
+            let index = (completed_at - origin).as_secs() / interval_secs;
+            let (hits, misses) = counts.entry(index).or_default();
+            if lookup.cache_hit == Some(true) {
+                *hits += 1;
+            } else {
+                *misses += 1;
+            }
+        }
+
+        intervals = counts
+            .into_iter()
+            .map(|(index, (hits, misses))| KeyspaceInterval {
+                start_secs: index * interval_secs,
+                hits,
+                misses,
+                hit_ratio: ratio(hits, misses),
+            })
+            .collect();
+    }
+
+    Some(KeyspaceStats {
+        hits,
+        misses,
+        hit_ratio: ratio(hits, misses),
+        interval_secs,
+        intervals,
+    })
+}
+
+/// Kind and message of a failed request (the status when there is no error)
+fn describe_error(result: &RequestResult) -> (&'static str, String) {
+    match &result.error {
+        Some(error) => (error.name(), error.to_string()),
+        None => match (result.grpc_status, result.dns_rcode) {
+            (Some(status), _) => (STATUS_ERROR, format!("gRPC status {}", status)),
+            (None, Some(rcode)) => (STATUS_ERROR, format!("DNS {}", rcode)),
+            (None, None) => (STATUS_ERROR, format!("status {}", result.status)),
+        },
+    }
+}
+
+/// Count failed requests by error message
+fn count_errors(results: &[RequestResult]) -> Vec<ErrorCount> {
+    let mut counts: HashMap<(&'static str, String), u64> = HashMap::new();
+    for result in results.iter().filter(|r| !r.success) {
+        *counts.entry(describe_error(result)).or_default() += 1;
+    }
+
+    let mut errors: Vec<ErrorCount> = counts
+        .into_iter()
+        .map(|((kind, message), count)| ErrorCount {
+            kind: kind.to_string(),
+            message,
+            count,
+        })
+        .collect();
+    errors.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.message.cmp(&b.message))
+    });
+    errors.truncate(MAX_ERRORS);
+    errors
+}
+
+/// Count failed requests by error kind
+fn count_error_kinds(results: &[RequestResult]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for result in results.iter().filter(|r| !r.success) {
+        let kind = result.error.as_ref().map_or(STATUS_ERROR, ErrorKind::name);
+        *counts.entry(kind.to_string()).or_default() += 1;
+    }
+    counts
+}
+
+/// Count responses by the HTTP version that served them
+fn count_http_versions(results: &[RequestResult]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for version in results.iter().filter_map(|r| r.http_version) {
+        *counts.entry(version.to_string()).or_default() += 1;
+    }
+    counts
+}
+
+/// Summarize results grouped by a key, skipping results without one
+///
+/// Returns an empty list unless at least two distinct groups are present, since
+/// a breakdown with a single group would just repeat the overall stats.
+pub fn group_stats<'a, K, F>(results: &'a [RequestResult], key: F) -> Vec<GroupStats>
+where
+    K: Ord + ToString,
+    F: Fn(&'a RequestResult) -> Option<K>,
+{
+    let mut groups: BTreeMap<K, Vec<&RequestResult>> = BTreeMap::new();
+    for result in results {
+        if let Some(name) = key(result) {
+            groups.entry(name).or_default().push(result);
+        }
+    }
+
+    if groups.len() < 2 {
+        return Vec::new();
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, group)| {
+            let total_requests = group.len() as u64;
+            let mut latencies: Vec<u128> = group
+                .iter()
+                .filter(|r| r.success)
+                .map(|r| r.duration)
+                .collect();
+            latencies.sort_unstable();
+
+            let successful_requests = latencies.len() as u64;
+            let avg_latency = if latencies.is_empty() {
+                0.0
+            } else {
+                latencies.iter().sum::<u128>() as f64 / latencies.len() as f64
+            };
+
+            GroupStats {
+                name: name.to_string(),
+                total_requests,
+                successful_requests,
+                failed_requests: total_requests - successful_requests,
+                avg_latency,
+                p50: percentile(&latencies, 50.0),
+                p95: percentile(&latencies, 95.0),
+                p99: percentile(&latencies, 99.0),
+                latency_histogram: histogram(&latencies),
+            }
+        })
+        .collect()
+}
+
+/// Latency (ms) → number of requests that took it
+fn histogram(latencies: &[u128]) -> BTreeMap<u128, u64> {
+    let mut histogram = BTreeMap::new();
+    for latency in latencies {
+        *histogram.entry(*latency).or_default() += 1;
     }
+    histogram
 }
 
-fn percentile(sorted_data: &[u128], pct: f64) -> u128 {
+pub(crate) fn percentile(sorted_data: &[u128], pct: f64) -> u128 {
     if sorted_data.is_empty() {
         return 0;
     }
@@ -215,6 +2216,39 @@ fn percentile(sorted_data: &[u128], pct: f64) -> u128 {
     }
 }
 
+/// The same percentile as [`percentile`] over the latencies a histogram counts
+pub fn histogram_percentile(histogram: &BTreeMap<u128, u64>, pct: f64) -> u128 {
+    let len: u64 = histogram.values().sum();
+    if len == 0 {
+        return 0;
+    }
+
+    // The value at an index of the sorted latencies
+    let value_at = |index: u64| {
+        let mut seen = 0;
+        for (&value, &count) in histogram {
+            seen += count;
+            if index < seen {
+                return value;
+            }
+        }
+        *histogram.keys().next_back().unwrap()
+    };
+
+    let rank = (pct / 100.0) * (len - 1) as f64;
+    let lower = rank.floor() as u64;
+    let upper = rank.ceil() as u64;
+
+    if lower == upper || upper >= len {
+        value_at(lower.min(len - 1))
+    } else {
+        let weight = rank - lower as f64;
+        let lower_val = value_at(lower) as f64;
+        let upper_val = value_at(upper) as f64;
+        (lower_val + weight * (upper_val - lower_val)) as u128
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,9 +2261,37 @@ mod tests {
         assert_eq!(Protocol::from_str("flashkv").unwrap(), Protocol::FlashKV);
         assert_eq!(Protocol::from_str("kv").unwrap(), Protocol::FlashKV);
         assert_eq!(Protocol::from_str("tcp").unwrap(), Protocol::FlashKV);
+        assert_eq!(Protocol::from_str("Valkey").unwrap(), Protocol::Redis);
+        assert_eq!(Protocol::from_str("gRPC").unwrap(), Protocol::Grpc);
+        assert_eq!(Protocol::from_str("sse").unwrap(), Protocol::Sse);
+        assert_eq!(Protocol::from_str("DNS").unwrap(), Protocol::Dns);
+        assert_eq!(Protocol::from_str("udp").unwrap(), Protocol::Udp);
+        assert_eq!(Protocol::from_str("NATS").unwrap(), Protocol::Nats);
+        assert_eq!(Protocol::from_str("MariaDB").unwrap(), Protocol::Mysql);
+        assert_eq!(Protocol::from_str("S3").unwrap(), Protocol::S3);
+        assert_eq!(Protocol::from_str("ping").unwrap(), Protocol::Ping);
         assert!(Protocol::from_str("invalid").is_err());
+        for name in Protocol::NAMES {
+            assert_eq!(Protocol::from_str(name).unwrap().as_str(), name);
+        }
     }
 
+    #[test]
+    fn test_retarget() {
+        let mut config = LoadTestConfig::new_http("https://a.example.com".to_string(), 10, 1);
+        config.retarget("http://b.example.com/health").unwrap();
+        assert_eq!(config.target(), "http://b.example.com/health");
+        assert!(config.retarget("b.example.com").is_err());
+
+        let mut config = LoadTestConfig::new_flashkv("localhost".to_string(), 7000, vec![], 10, 1);
+        config.retarget("10.0.0.2").unwrap();
+        assert_eq!(config.target(), "10.0.0.2:7000");
+        config.retarget("unix:///tmp/kv.sock").unwrap();
+        assert_eq!(config.target(), "unix:///tmp/kv.sock");
+        config.retarget("redis://kv:6380").unwrap();
+        assert_eq!(config.target(), "kv:6380");
+        assert!(config.retarget("kv:port").is_err());
+    }
     #[test]
     fn test_calculate_stats_empty() {
         let results: Vec<RequestResult> = vec![];
@@ -247,18 +2309,63 @@ mod tests {
                 status: 200,
                 success: true,
                 error: None,
+                queue_time: 0,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
             },
             RequestResult {
                 duration: 200,
                 status: 200,
                 success: true,
                 error: None,
+                queue_time: 0,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
             },
             RequestResult {
                 duration: 50,
                 status: 0,
                 success: false,
-                error: Some("timeout".to_string()),
+                error: Some(ErrorKind::ResponseTimeout),
+                queue_time: 0,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
             },
         ];
         let stats = calculate_stats(&results, 1000);
@@ -267,5 +2374,503 @@ mod tests {
         assert_eq!(stats.failed_requests, 1);
         assert_eq!(stats.min_latency, 100);
         assert_eq!(stats.max_latency, 200);
+        assert_eq!(stats.avg_queue_time, 0.0);
+        assert_eq!(
+            stats.latency_histogram,
+            BTreeMap::from([(100, 1), (200, 1)])
+        );
+        let sla = stats.sla.as_ref().unwrap();
+        assert!(sla.apdex.is_none());
+        assert_eq!(sla.under[0].ms, 100);
+
+        let config = LoadTestConfig::new_http("http://localhost".to_string(), 3, 1)
+            .with_apdex_threshold(Duration::from_millis(150));
+        let apdex = config.sla(&stats).apdex.unwrap();
+        assert_eq!(
+            (apdex.satisfied, apdex.tolerating, apdex.frustrated),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_histogram_percentile() {
+        let latencies = vec![3, 5, 5, 5, 8, 13, 13, 40, 41, 90];
+        let mut histogram = BTreeMap::new();
+        for latency in &latencies {
+            *histogram.entry(*latency).or_default() += 1;
+        }
+        for pct in [0.0, 5.0, 50.0, 95.0, 99.0, 100.0] {
+            assert_eq!(
+                histogram_percentile(&histogram, pct),
+                percentile(&latencies, pct)
+            );
+        }
+        assert_eq!(histogram_percentile(&BTreeMap::new(), 50.0), 0);
+    }
+
+    #[test]
+    fn test_live_results() {
+        let live = LiveResults::default();
+        live.clone().record(RequestResult {
+            duration: 10,
+            status: 200,
+            success: true,
+            ..Default::default()
+        });
+        live.extend(vec![RequestResult::default(); 3]);
+
+        let stats = live.stats(1000);
+        assert_eq!(stats.total_requests, 4);
+        assert_eq!(stats.failed_requests, 3);
+
+        assert_eq!(live.take().len(), 4);
+        assert_eq!(live.stats(1000).total_requests, 0);
+    }
+
+    #[test]
+    fn test_calculate_stats_queue_time() {
+        let results = vec![
+            RequestResult {
+                duration: 1,
+                status: 200,
+                success: true,
+                error: None,
+                queue_time: 500,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
+            },
+            RequestResult {
+                duration: 1,
+                status: 200,
+                success: true,
+                error: None,
+                queue_time: 1500,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
+            },
+        ];
+        let stats = calculate_stats(&results, 10);
+        assert_eq!(stats.avg_queue_time, 1.0);
+        assert_eq!(stats.max_queue_time, 1.5);
+    }
+
+    #[test]
+    fn test_breakdown_by_operation() {
+        let result = |operation, duration, success| RequestResult {
+            duration,
+            status: if success { 200 } else { 500 },
+            success,
+            operation: Some(operation),
+            ..Default::default()
+        };
+        let results = vec![
+            result("GET", 10, true),
+            result("GET", 20, true),
+            result("POST", 100, true),
+            result("POST", 300, false),
+        ];
+
+        let stats = calculate_stats(&results, 1000);
+        assert_eq!(stats.by_operation.len(), 2);
+
+        let get = &stats.by_operation[0];
+        assert_eq!(get.name, "GET");
+        assert_eq!(get.total_requests, 2);
+        assert_eq!(get.avg_latency, 15.0);
+
+        let post = &stats.by_operation[1];
+        assert_eq!(post.name, "POST");
+        assert_eq!(post.failed_requests, 1);
+        assert_eq!(post.error_rate(), 50.0);
+        assert_eq!(post.p99, 100);
+    }
+
+    #[test]
+    fn test_no_breakdown_for_single_operation() {
+        let results = vec![RequestResult {
+            duration: 10,
+            success: true,
+            operation: Some("GET"),
+            ..Default::default()
+        }];
+        assert!(calculate_stats(&results, 100).by_operation.is_empty());
+    }
+
+    #[test]
+    fn test_count_errors() {
+        let failed = |error: Option<ErrorKind>, status| RequestResult {
+            status,
+            error,
+            ..Default::default()
+        };
+        let results = vec![
+            failed(Some(ErrorKind::ConnectRefused), 0),
+            failed(None, 503),
+            failed(Some(ErrorKind::ConnectRefused), 0),
+            failed(Some(ErrorKind::Other("boom".to_string())), 0),
+            RequestResult {
+                success: true,
+                ..Default::default()
+            },
+        ];
+
+        let stats = calculate_stats(&results, 100);
+        assert_eq!(
+            stats.errors,
+            vec![
+                ErrorCount {
+                    kind: "connect_refused".to_string(),
+                    message: "connection refused".to_string(),
+                    count: 2
+                },
+                ErrorCount {
+                    kind: "other".to_string(),
+                    message: "boom".to_string(),
+                    count: 1
+                },
+                ErrorCount {
+                    kind: STATUS_ERROR.to_string(),
+                    message: "status 503".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(stats.errors_by_kind["connect_refused"], 2);
+        assert_eq!(stats.errors_by_kind[STATUS_ERROR], 1);
+        assert!(!stats.errors_by_kind.contains_key("dns"));
+    }
+
+    #[test]
+    fn test_peak_and_p5_rps() {
+        let origin = Instant::now();
+        let completed = |millis: u64| RequestResult {
+            success: true,
+            completed_at: Some(origin + Duration::from_millis(millis)),
+            ..Default::default()
+        };
+
+        // 30 requests in the first second, 10 in each of the next two, and a
+        // trailing partial second that is not counted
+        let mut results: Vec<RequestResult> = (0..30).map(|i| completed(i * 30)).collect();
+        results.extend((0..10).map(|i| completed(1000 + i * 90)));
+        results.extend((0..10).map(|i| completed(2000 + i * 90)));
+        results.push(completed(3500));
+
+        let stats = calculate_stats(&results, 3500);
+        assert_eq!(stats.peak_rps, 30.0);
+        assert_eq!(stats.p5_rps, 10.0);
+
+        // Runs shorter than a second have no whole second to report on
+        let stats = calculate_stats(&results[..5], 200);
+        assert_eq!(stats.peak_rps, 0.0);
+    }
+
+    #[test]
+    fn test_keyspace_stats() {
+        let origin = Instant::now();
+        let lookup = |secs: u64, hit| RequestResult {
+            success: true,
+            completed_at: Some(origin + Duration::from_secs(secs)),
+            cache_hit: Some(hit),
+            ..Default::default()
+        };
+
+        // Hits dry up over time, e.g. under eviction pressure
+        let results = vec![
+            lookup(0, true),
+            lookup(0, true),
+            lookup(1, true),
+            lookup(1, false),
+            lookup(2, false),
+            RequestResult {
+                success: true,
+                completed_at: Some(origin + Duration::from_secs(2)),
+                ..Default::default()
+            },
+        ];
+
+        let keyspace = calculate_stats(&results, 2000).keyspace.unwrap();
+        assert_eq!((keyspace.hits, keyspace.misses), (3, 2));
+        assert_eq!(keyspace.hit_ratio, 60.0);
+        assert_eq!(keyspace.interval_secs, 1);
+        let ratios: Vec<f64> = keyspace.intervals.iter().map(|i| i.hit_ratio).collect();
+        assert_eq!(ratios, vec![100.0, 50.0, 0.0]);
+
+        assert!(calculate_stats(&results[5..], 2000).keyspace.is_none());
+    }
+
+    #[test]
+    fn test_latency_over_time() {
+        let origin = Instant::now();
+        let request = |secs: u64, duration, success| RequestResult {
+            duration,
+            success,
+            completed_at: Some(origin + Duration::from_secs(secs)),
+            ..Default::default()
+        };
+
+        // Latency creeps up, with a quiet stretch in the middle
+        let mut results: Vec<RequestResult> =
+            (0..100).map(|i| request(0, 10 + i % 5, true)).collect();
+        results.extend((0..100).map(|i| request(12, 40 + i % 20, true)));
+        results.push(request(13, 0, false));
+
+        let windows = calculate_stats(&results, 13000).latency_over_time;
+        let starts: Vec<u64> = windows.iter().map(|w| w.start_secs).collect();
+        assert_eq!(starts, vec![0, 10]);
+        assert_eq!((windows[0].requests, windows[0].failed), (100, 0));
+        assert_eq!((windows[0].p50, windows[0].p99), (12, 14));
+        assert_eq!((windows[1].requests, windows[1].failed), (101, 1));
+        assert_eq!((windows[1].p50, windows[1].p99), (49, 59));
+
+        assert!(calculate_stats(&[RequestResult::default()], 0)
+            .latency_over_time
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dns_stats() {
+        let answer = |rcode, success| RequestResult {
+            success,
+            dns_rcode: Some(rcode),
+            ..Default::default()
+        };
+        let results = vec![
+            answer("NOERROR", true),
+            answer("NOERROR", true),
+            answer("NXDOMAIN", true),
+            answer("SERVFAIL", false),
+            RequestResult {
+                error: Some(ErrorKind::ResponseTimeout),
+                ..Default::default()
+            },
+        ];
+
+        let stats = calculate_stats(&results, 1000);
+        let dns = stats.dns.unwrap();
+        assert_eq!(dns.answered, 4);
+        assert_eq!(dns.rcodes["NOERROR"], 2);
+        assert_eq!((dns.nxdomain_rate, dns.servfail_rate), (25.0, 25.0));
+        assert!(stats.errors.iter().any(|e| e.message == "DNS SERVFAIL"));
+        assert!(calculate_stats(&results[4..], 1000).dns.is_none());
+    }
+
+    #[test]
+    fn test_datagram_stats() {
+        let datagram = |lost: bool| RequestResult {
+            success: !lost,
+            lost: Some(lost),
+            ..Default::default()
+        };
+        let results = vec![
+            datagram(false),
+            datagram(false),
+            datagram(false),
+            datagram(true),
+        ];
+
+        let datagrams = calculate_stats(&results, 1000).datagrams.unwrap();
+        assert_eq!(
+            (datagrams.sent, datagrams.replies, datagrams.lost),
+            (4, 3, 1)
+        );
+        assert_eq!(datagrams.loss_rate, 25.0);
+        // Fire-and-forget datagrams don't wait for replies
+        let unanswered = vec![RequestResult {
+            success: true,
+            ..Default::default()
+        }];
+        assert!(calculate_stats(&unanswered, 1000).datagrams.is_none());
+    }
+
+    #[test]
+    fn test_transfer_stats() {
+        let response = |received, decoded, encoding| RequestResult {
+            success: true,
+            body: Some(BodySize {
+                received,
+                decoded,
+                encoding,
+            }),
+            ..Default::default()
+        };
+
+        let results = vec![
+            response(1000, Some(1000), "identity"),
+            response(200, Some(800), "gzip"),
+        ];
+        let transfer = calculate_stats(&results, 2000).transfer.unwrap();
+        assert_eq!(transfer.received_bytes, 1200);
+        assert_eq!(transfer.avg_response_bytes, 600.0);
+        assert_eq!(transfer.bytes_per_second, 600.0);
+        assert_eq!(transfer.compression_ratio(), Some(1800.0 / 1200.0));
+
+        let mut results = results;
+        results.push(response(100, None, "br"));
+        let transfer = calculate_stats(&results, 2000).transfer.unwrap();
+        assert_eq!(transfer.undecoded_responses, 1);
+        assert_eq!(transfer.compression_ratio(), None);
+        assert!(calculate_stats(&[], 2000).transfer.is_none());
+
+        // Uploads alone, without response bodies
+        let upload = RequestResult {
+            success: true,
+            sent: Some(4096),
+            ..Default::default()
+        };
+        let transfer = calculate_stats(&[upload.clone(), upload], 1000)
+            .transfer
+            .unwrap();
+        assert_eq!(transfer.sent_bytes, 8192);
+        assert_eq!(transfer.sent_bytes_per_second, 8192.0);
+        assert_eq!(transfer.avg_response_bytes, 0.0);
+    }
+
+    #[test]
+    fn test_error_kind_from_error() {
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert_eq!(ErrorKind::from_error(&refused), ErrorKind::ConnectRefused);
+
+        // Typed causes are found behind context layers
+        let wrapped = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+            .context("Failed to read response");
+        assert_eq!(ErrorKind::from_error(wrapped.as_ref()), ErrorKind::Reset);
+
+        let dns = anyhow::anyhow!("dns error: failed to lookup address information");
+        assert_eq!(ErrorKind::from_error(dns.as_ref()), ErrorKind::Dns);
+
+        let other = anyhow::anyhow!("Too many open files").context("tcp connect error");
+        assert_eq!(
+            ErrorKind::from_error(other.as_ref()),
+            ErrorKind::Other("tcp connect error: Too many open files".to_string())
+        );
+    }
+
+    #[test]
+    fn test_breakdown_by_backend() {
+        let result = |backend: &str, success| RequestResult {
+            duration: 10,
+            success,
+            backend: Some(backend.to_string()),
+            ..Default::default()
+        };
+        let results = vec![
+            result("10.0.0.1", true),
+            result("10.0.0.2", false),
+            result("10.0.0.2", false),
+        ];
+
+        let stats = calculate_stats(&results, 1000);
+        assert!(stats.by_operation.is_empty());
+        assert_eq!(stats.by_backend.len(), 2);
+        assert_eq!(stats.by_backend[0].error_rate(), 0.0);
+        assert_eq!(stats.by_backend[1].name, "10.0.0.2");
+        assert_eq!(stats.by_backend[1].error_rate(), 100.0);
+    }
+
+    #[test]
+    fn test_worker_stats() {
+        let result = |worker, duration| RequestResult {
+            duration,
+            success: true,
+            worker: Some(worker),
+            ..Default::default()
+        };
+        let mut results = Vec::new();
+        for i in 0..30 {
+            for worker in [0, 2, 10] {
+                results.push(result(worker, 10 + i % 3));
+            }
+            // Worker 1 has a stalled connection every tenth request
+            results.push(result(1, if i % 10 == 0 { 500 } else { 11 }));
+        }
+
+        let workers = calculate_stats(&results, 1000).workers.unwrap();
+        let names: Vec<&str> = workers.workers.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, ["0", "1", "2", "10"]);
+        assert_eq!(workers.median_p99, 12);
+        assert_eq!(workers.slow, ["1"]);
+        assert_eq!(workers.p99_without_slow, Some(12));
+
+        // Without the stalls no worker stands out
+        results.retain(|r| r.duration < 500);
+        let workers = calculate_stats(&results, 1000).workers.unwrap();
+        assert!(workers.slow.is_empty());
+        assert_eq!(workers.p99_without_slow, None);
+
+        // A single worker has nothing to compare against
+        results.retain(|r| r.worker == Some(0));
+        assert!(calculate_stats(&results, 1000).workers.is_none());
+    }
+
+    #[test]
+    fn test_breakdown_by_tag() {
+        let result = |step: &str, tag: Option<&str>| RequestResult {
+            duration: 10,
+            success: true,
+            step: Some(Arc::from(step)),
+            tag: tag.map(Arc::from),
+            ..Default::default()
+        };
+        let results = vec![
+            result("login", Some("auth")),
+            result("refresh", Some("auth")),
+            result("browse", Some("read")),
+            result("logout", None),
+        ];
+
+        let stats = calculate_stats(&results, 1000);
+        assert_eq!(stats.by_step.len(), 4);
+        assert_eq!(stats.by_tag.len(), 2);
+        assert_eq!(stats.by_tag[0].name, "auth");
+        assert_eq!(stats.by_tag[0].total_requests, 2);
+        assert_eq!(stats.by_tag[1].total_requests, 1);
+    }
+
+    #[test]
+    fn test_startup_delay() {
+        let soft_start = Startup {
+            soft_start: Duration::from_secs(4),
+            ..Default::default()
+        };
+        assert_eq!(soft_start.delay(0, 4), Duration::ZERO);
+        assert_eq!(soft_start.delay(2, 4), Duration::from_secs(2));
+        assert_eq!(soft_start.to_string(), "soft start 4s");
+
+        let jitter = Startup {
+            jitter: Duration::from_millis(100),
+            ..soft_start
+        };
+        for _ in 0..100 {
+            let delay = jitter.delay(1, 4);
+            assert!(delay >= Duration::from_secs(1));
+            assert!(delay < Duration::from_millis(1100));
+        }
+        assert_eq!(jitter.to_string(), "soft start 4s, jitter 100ms");
+        assert_eq!(Startup::default().delay(3, 4), Duration::ZERO);
     }
 }
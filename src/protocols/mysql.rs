@@ -0,0 +1,664 @@
+//! MySQL protocol implementation for load testing
+//!
+//! Speaks the MySQL client/server protocol over plain TCP, so MariaDB works
+//! too. Each concurrent worker keeps its own connection, taken from a pool
+//! for every request. A request executes one parameterized statement: by
+//! default it is prepared once per connection and executed again and again;
+//! without statement reuse it is prepared, executed and closed every time.
+
+use crate::hooks::Hooks;
+use crate::metrics::{CustomMetrics, CONNECTIONS_OPENED};
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::{ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup};
+use crate::template::{self, RequestContext};
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::rsa::{Padding, Rsa};
+use openssl::sha::{sha1, sha256};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// Port used when the server URL has none
+pub const DEFAULT_MYSQL_PORT: u16 = 3306;
+
+// Capability flags sent in the handshake response
+const CLIENT_LONG_PASSWORD: u32 = 0x1;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x8;
+const CLIENT_PROTOCOL_41: u32 = 0x200;
+const CLIENT_TRANSACTIONS: u32 = 0x2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+const CLIENT_MULTI_RESULTS: u32 = 0x20000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x80000;
+
+// Commands
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_STMT_EXECUTE: u8 = 0x17;
+const COM_STMT_CLOSE: u8 = 0x19;
+
+/// Column type that parameters are bound as; the server converts as needed
+const MYSQL_TYPE_VAR_STRING: u8 = 0xfd;
+
+/// Configuration for MySQL load testing
+#[derive(Debug, Clone)]
+pub struct MysqlConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Option<String>,
+    pub database: Option<String>,
+    /// The statement to execute, with `?` placeholders
+    pub sql: String,
+    /// Values bound to the placeholders, with `{{placeholders}}` expanded per request
+    pub params: Vec<String>,
+    /// Prepare the statement once per connection instead of for every request
+    pub reuse_statements: bool,
+}
+
+impl MysqlConfig {
+    /// Parse a `mysql://[user[:password]@]host[:port][/database]` URL (the
+    /// scheme is optional)
+    pub fn new(url: &str, sql: String) -> Result<Self> {
+        let rest = url
+            .strip_prefix("mysql://")
+            .or_else(|| url.strip_prefix("mariadb://"))
+            .unwrap_or(url);
+        let (credentials, rest) = match rest.rsplit_once('@') {
+            Some((credentials, rest)) => (Some(credentials), rest),
+            None => (None, rest),
+        };
+        let (address, database) = match rest.split_once('/') {
+            Some((address, database)) => (address, Some(database).filter(|d| !d.is_empty())),
+            None => (rest, None),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .with_context(|| format!("Invalid MySQL port in {}", url))?,
+            ),
+            None => (address, DEFAULT_MYSQL_PORT),
+        };
+        if host.is_empty() {
+            bail!("Missing MySQL server host in {}", url);
+        }
+        if sql.trim().is_empty() {
+            bail!("The SQL statement is empty");
+        }
+
+        let (user, password) = match credentials.map(|c| c.split_once(':')) {
+            Some(Some((user, password))) => (user, Some(password.to_string())),
+            Some(None) => (credentials.unwrap_or_default(), None),
+            None => ("root", None),
+        };
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            user: user.to_string(),
+            password,
+            database: database.map(str::to_string),
+            sql,
+            params: Vec::new(),
+            reuse_statements: true,
+        })
+    }
+
+    pub fn with_params(mut self, params: Vec<String>) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_statement_reuse(mut self, reuse_statements: bool) -> Self {
+        self.reuse_statements = reuse_statements;
+        self
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// A protocol error from the server's side, classified as a parse error
+fn invalid(message: impl Into<String>) -> anyhow::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into()).into()
+}
+
+/// Read a length-encoded integer at `pos`, moving past it
+fn read_lenenc(packet: &[u8], pos: &mut usize) -> Result<u64> {
+    let first = *packet
+        .get(*pos)
+        .ok_or_else(|| invalid("Truncated packet"))?;
+    let size = match first {
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => {
+            *pos += 1;
+            return Ok(first as u64);
+        }
+    };
+    let bytes = packet
+        .get(*pos + 1..*pos + 1 + size)
+        .ok_or_else(|| invalid("Truncated packet"))?;
+    *pos += 1 + size;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as u64))
+}
+
+/// Append `bytes` prefixed with their length-encoded length
+fn write_lenenc_str(buf: &mut Vec<u8>, bytes: &[u8]) {
+    let len = bytes.len() as u64;
+    match len {
+        0..=250 => buf.push(len as u8),
+        251..=0xffff => {
+            buf.push(0xfc);
+            buf.extend(&len.to_le_bytes()[..2]);
+        }
+        0x10000..=0xff_ffff => {
+            buf.push(0xfd);
+            buf.extend(&len.to_le_bytes()[..3]);
+        }
+        _ => {
+            buf.push(0xfe);
+            buf.extend(len.to_le_bytes());
+        }
+    }
+    buf.extend(bytes);
+}
+
+/// The error in an ERR packet, e.g. "MySQL 1146 (42S02): Table 'shop.x' doesn't exist"
+fn server_error(packet: &[u8]) -> anyhow::Error {
+    if packet.len() < 3 {
+        return invalid("Truncated error packet");
+    }
+    let code = u16::from_le_bytes([packet[1], packet[2]]);
+    let message = match packet.get(3) {
+        // Protocol 4.1 adds a SQL state marker and the state
+        Some(b'#') if packet.len() >= 9 => format!(
+            "MySQL {} ({}): {}",
+            code,
+            String::from_utf8_lossy(&packet[4..9]),
+            String::from_utf8_lossy(&packet[9..])
+        ),
+        _ => format!(
+            "MySQL {}: {}",
+            code,
+            String::from_utf8_lossy(packet.get(3..).unwrap_or_default())
+        ),
+    };
+    anyhow!(message)
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+/// The scrambled password an authentication plugin expects for `nonce`
+fn scramble(plugin: &str, password: &str, nonce: &[u8]) -> Result<Vec<u8>> {
+    if password.is_empty() {
+        return Ok(Vec::new());
+    }
+    match plugin {
+        // SHA1(password) XOR SHA1(nonce + SHA1(SHA1(password)))
+        "mysql_native_password" => {
+            let hash = sha1(password.as_bytes());
+            let mut salted = nonce.to_vec();
+            salted.extend(sha1(&hash));
+            Ok(xor(&hash, &sha1(&salted)))
+        }
+        // SHA256(password) XOR SHA256(SHA256(SHA256(password)) + nonce)
+        "caching_sha2_password" => {
+            let hash = sha256(password.as_bytes());
+            let mut salted = sha256(&hash).to_vec();
+            salted.extend(nonce);
+            Ok(xor(&hash, &sha256(&salted)))
+        }
+        _ => bail!("Unsupported MySQL authentication plugin: {}", plugin),
+    }
+}
+
+/// A statement prepared on a connection
+#[derive(Debug, Clone, Copy)]
+struct Statement {
+    id: u32,
+    params: usize,
+}
+
+/// One client connection, authenticated
+struct Connection {
+    stream: BufReader<TcpStream>,
+    /// Sequence number of the next packet
+    seq: u8,
+    /// The statement prepared for reuse, if any
+    statement: Option<Statement>,
+}
+
+impl Connection {
+    async fn read_packet(&mut self) -> Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        loop {
+            let mut header = [0; 4];
+            self.stream.read_exact(&mut header).await?;
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            self.seq = header[3].wrapping_add(1);
+            let start = payload.len();
+            payload.resize(start + len, 0);
+            self.stream.read_exact(&mut payload[start..]).await?;
+            // A full-size packet continues in the next one
+            if len < 0xff_ffff {
+                return Ok(payload);
+            }
+        }
+    }
+
+    async fn write_packet(&mut self, payload: &[u8]) -> Result<()> {
+        if payload.len() >= 0xff_ffff {
+            bail!("MySQL packets over 16 MB aren't supported");
+        }
+        let mut packet = (payload.len() as u32).to_le_bytes();
+        packet[3] = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+        let mut message = packet.to_vec();
+        message.extend(payload);
+        self.stream.write_all(&message).await?;
+        Ok(())
+    }
+
+    /// Start a new command, which resets the sequence
+    async fn command(&mut self, payload: &[u8]) -> Result<()> {
+        self.seq = 0;
+        self.write_packet(payload).await
+    }
+
+    /// Read packets up to and including the EOF packet ending a list
+    async fn skip_until_eof(&mut self) -> Result<()> {
+        loop {
+            let packet = self.read_packet().await?;
+            match packet.first() {
+                Some(0xfe) if packet.len() < 9 => return Ok(()),
+                Some(0xff) => return Err(server_error(&packet)),
+                _ => {}
+            }
+        }
+    }
+
+    async fn open(config: &MysqlConfig, metrics: &CustomMetrics) -> Result<Self> {
+        let stream = TcpStream::connect(config.address())
+            .await
+            .context("Failed to connect to MySQL server")?;
+        metrics.increment(CONNECTIONS_OPENED, 1);
+        let mut connection = Self {
+            stream: BufReader::new(stream),
+            seq: 0,
+            statement: None,
+        };
+
+        // Initial handshake: protocol version, server version, connection id,
+        // then the nonce in two parts around the capabilities
+        let greeting = connection.read_packet().await?;
+        if greeting.first() == Some(&0xff) {
+            return Err(server_error(&greeting));
+        }
+        if greeting.first() != Some(&10) {
+            return Err(invalid("Unsupported MySQL handshake protocol"));
+        }
+        let parse = || -> Option<(Vec<u8>, String)> {
+            let version_end = greeting.iter().skip(1).position(|&b| b == 0)? + 1;
+            let mut pos = version_end + 1 + 4;
+            let mut nonce = greeting.get(pos..pos + 8)?.to_vec();
+            // Filler, capabilities, charset, status, upper capabilities,
+            // nonce length and 10 reserved bytes
+            pos += 8 + 1 + 2 + 1 + 2 + 2;
+            let nonce_len = *greeting.get(pos)? as usize;
+            pos += 1 + 10;
+            let rest_len = nonce_len.saturating_sub(8).max(13);
+            let rest = greeting.get(pos..pos + rest_len)?;
+            nonce.extend(rest.strip_suffix(&[0]).unwrap_or(rest));
+            pos += rest_len;
+            let plugin = greeting
+                .get(pos..)
+                .map(|name| {
+                    String::from_utf8_lossy(name)
+                        .trim_end_matches('\0')
+                        .to_string()
+                })
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "mysql_native_password".to_string());
+            Some((nonce, plugin))
+        };
+        let (mut nonce, mut plugin) =
+            parse().ok_or_else(|| invalid("Malformed MySQL handshake"))?;
+
+        let password = config.password.as_deref().unwrap_or_default();
+        let mut capabilities = CLIENT_LONG_PASSWORD
+            | CLIENT_PROTOCOL_41
+            | CLIENT_TRANSACTIONS
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_MULTI_RESULTS
+            | CLIENT_PLUGIN_AUTH;
+        if config.database.is_some() {
+            capabilities |= CLIENT_CONNECT_WITH_DB;
+        }
+        let mut response = capabilities.to_le_bytes().to_vec();
+        response.extend(16_777_215u32.to_le_bytes());
+        // utf8mb4, then reserved bytes
+        response.push(45);
+        response.extend([0; 23]);
+        response.extend(config.user.as_bytes());
+        response.push(0);
+        let auth = scramble(&plugin, password, &nonce)?;
+        response.push(auth.len() as u8);
+        response.extend(&auth);
+        if let Some(database) = &config.database {
+            response.extend(database.as_bytes());
+            response.push(0);
+        }
+        response.extend(plugin.as_bytes());
+        response.push(0);
+        connection.write_packet(&response).await?;
+
+        loop {
+            let packet = connection.read_packet().await?;
+            match packet.first() {
+                Some(0x00) => return Ok(connection),
+                Some(0xff) => return Err(server_error(&packet)),
+                // The server asks for another authentication plugin
+                Some(0xfe) => {
+                    let name_end = packet.iter().skip(1).position(|&b| b == 0).map(|p| p + 1);
+                    let name_end = name_end.ok_or_else(|| invalid("Malformed auth switch"))?;
+                    plugin = String::from_utf8_lossy(&packet[1..name_end]).to_string();
+                    let data = &packet[name_end + 1..];
+                    nonce = data.strip_suffix(&[0]).unwrap_or(data).to_vec();
+                    let auth = scramble(&plugin, password, &nonce)?;
+                    connection.write_packet(&auth).await?;
+                }
+                // caching_sha2_password: 3 is a cache hit, 4 asks for the
+                // full password, sent encrypted with the server's public key
+                Some(0x01) if packet.get(1) == Some(&3) => {}
+                Some(0x01) if packet.get(1) == Some(&4) => {
+                    connection.write_packet(&[2]).await?;
+                    let key = connection.read_packet().await?;
+                    let rsa = Rsa::public_key_from_pem(key.get(1..).unwrap_or_default())
+                        .context("Invalid public key from the MySQL server")?;
+                    let mut secret = password.as_bytes().to_vec();
+                    secret.push(0);
+                    let secret: Vec<u8> = secret
+                        .iter()
+                        .zip(nonce.iter().cycle())
+                        .map(|(a, b)| a ^ b)
+                        .collect();
+                    let mut encrypted = vec![0; rsa.size() as usize];
+                    let len = rsa.public_encrypt(&secret, &mut encrypted, Padding::PKCS1_OAEP)?;
+                    connection.write_packet(&encrypted[..len]).await?;
+                }
+                _ => return Err(invalid("Unexpected packet during MySQL authentication")),
+            }
+        }
+    }
+
+    async fn prepare(&mut self, sql: &str) -> Result<Statement> {
+        let mut payload = vec![COM_STMT_PREPARE];
+        payload.extend(sql.as_bytes());
+        self.command(&payload).await?;
+
+        let packet = self.read_packet().await?;
+        match packet.first() {
+            Some(0x00) if packet.len() >= 9 => {}
+            Some(0xff) => return Err(server_error(&packet)),
+            _ => return Err(invalid("Malformed prepare response")),
+        }
+        let id = u32::from_le_bytes([packet[1], packet[2], packet[3], packet[4]]);
+        let columns = u16::from_le_bytes([packet[5], packet[6]]);
+        let params = u16::from_le_bytes([packet[7], packet[8]]);
+        // Parameter and column definitions, each list ended by an EOF packet
+        if params > 0 {
+            self.skip_until_eof().await?;
+        }
+        if columns > 0 {
+            self.skip_until_eof().await?;
+        }
+        Ok(Statement {
+            id,
+            params: params as usize,
+        })
+    }
+
+    /// Execute a statement, returning the number of rows it returned or affected
+    async fn execute(&mut self, statement: Statement, params: &[String]) -> Result<u64> {
+        if params.len() != statement.params {
+            bail!(
+                "The statement has {} placeholders but {} parameters were given",
+                statement.params,
+                params.len()
+            );
+        }
+        let mut payload = vec![COM_STMT_EXECUTE];
+        payload.extend(statement.id.to_le_bytes());
+        // No cursor, one iteration
+        payload.push(0);
+        payload.extend(1u32.to_le_bytes());
+        if !params.is_empty() {
+            // No NULLs, then the types, bound anew every time
+            payload.extend(vec![0; params.len().div_ceil(8)]);
+            payload.push(1);
+            for _ in params {
+                payload.extend([MYSQL_TYPE_VAR_STRING, 0]);
+            }
+            for param in params {
+                write_lenenc_str(&mut payload, param.as_bytes());
+            }
+        }
+        self.command(&payload).await?;
+
+        let packet = self.read_packet().await?;
+        match packet.first() {
+            Some(0x00) => return read_lenenc(&packet, &mut 1),
+            Some(0xff) => return Err(server_error(&packet)),
+            _ => {}
+        }
+        // A result set: column definitions, then rows, each ended by an EOF
+        self.skip_until_eof().await?;
+        let mut rows = 0;
+        loop {
+            let packet = self.read_packet().await?;
+            match packet.first() {
+                Some(0xfe) if packet.len() < 9 => return Ok(rows),
+                Some(0xff) => return Err(server_error(&packet)),
+                _ => rows += 1,
+            }
+        }
+    }
+
+    /// Run the configured statement once with `params`
+    async fn query(&mut self, config: &MysqlConfig, params: &[String]) -> Result<u64> {
+        if !config.reuse_statements {
+            let statement = self.prepare(&config.sql).await?;
+            let rows = self.execute(statement, params).await;
+            let mut close = vec![COM_STMT_CLOSE];
+            close.extend(statement.id.to_le_bytes());
+            self.command(&close).await?;
+            return rows;
+        }
+        let statement = match self.statement {
+            Some(statement) => statement,
+            None => {
+                let statement = self.prepare(&config.sql).await?;
+                self.statement = Some(statement);
+                statement
+            }
+        };
+        self.execute(statement, params).await
+    }
+}
+
+/// Executes one statement per iteration over pooled connections
+struct MysqlExecutor {
+    config: MysqlConfig,
+    timeout_secs: u64,
+    metrics: CustomMetrics,
+    /// Idle connections, at most one per concurrent worker
+    pool: Mutex<Vec<Connection>>,
+}
+
+impl MysqlExecutor {
+    async fn query(&self, iteration: &Iteration) -> RequestResult {
+        let request_timeout = Duration::from_secs(self.timeout_secs);
+        let pooled = self.pool.lock().unwrap().pop();
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => {
+                let start = Instant::now();
+                let opened = tokio::time::timeout(
+                    request_timeout,
+                    Connection::open(&self.config, &self.metrics),
+                )
+                .await;
+                match opened {
+                    Ok(Ok(connection)) => connection,
+                    failed => {
+                        let error = match failed {
+                            Ok(Err(e)) => ErrorKind::from_error(e.as_ref()),
+                            _ => ErrorKind::ConnectTimeout,
+                        };
+                        return RequestResult {
+                            duration: start.elapsed().as_millis(),
+                            error: Some(error),
+                            ..Default::default()
+                        };
+                    }
+                }
+            }
+        };
+
+        let context = RequestContext {
+            seq: iteration.seq,
+            worker_id: iteration.worker_id,
+            iteration: iteration.iteration,
+            row: None,
+        };
+        let params: Vec<String> = self
+            .config
+            .params
+            .iter()
+            .map(|param| {
+                template::expand(param, |name| template::generate(name, &context)).into_owned()
+            })
+            .collect();
+
+        let start = Instant::now();
+        let rows =
+            tokio::time::timeout(request_timeout, connection.query(&self.config, &params)).await;
+        let duration = start.elapsed().as_millis();
+
+        let error = match rows {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(ErrorKind::from_error(e.as_ref())),
+            Err(_) => Some(ErrorKind::ResponseTimeout),
+        };
+        // Errors from the server (e.g. a deadlock) leave the connection usable;
+        // after a timeout or broken packet it may be mid-response
+        if matches!(error, None | Some(ErrorKind::Other(_))) {
+            self.pool.lock().unwrap().push(connection);
+        }
+
+        RequestResult {
+            duration,
+            success: error.is_none(),
+            error,
+            ..Default::default()
+        }
+    }
+}
+
+impl RequestExecutor for MysqlExecutor {
+    fn progress_message(&self) -> String {
+        let sql: String = self.config.sql.chars().take(40).collect();
+        format!("Executing {} on {}...", sql, self.config.address())
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "yellow"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = self.query(&iteration).await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run a MySQL load test with the given configuration
+pub async fn run_load_test(
+    config: &MysqlConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    let executor = MysqlExecutor {
+        config: config.clone(),
+        timeout_secs,
+        metrics: live.metrics().clone(),
+        pool: Mutex::new(Vec::new()),
+    };
+    let stats = engine::run(executor, stages, startup, quiet, live, hooks).await;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_url() {
+        let sql = || "SELECT 1".to_string();
+        let config = MysqlConfig::new("mysql://app:s3cr@t@db.internal/shop", sql()).unwrap();
+        assert_eq!(config.address(), "db.internal:3306");
+        assert_eq!(config.user, "app");
+        assert_eq!(config.password.as_deref(), Some("s3cr@t"));
+        assert_eq!(config.database.as_deref(), Some("shop"));
+        assert!(config.reuse_statements);
+
+        let config = MysqlConfig::new("localhost:3307", sql()).unwrap();
+        assert_eq!((config.user.as_str(), config.port), ("root", 3307));
+        assert_eq!((config.password, config.database), (None, None));
+
+        assert!(MysqlConfig::new("mysql://localhost", " ".to_string()).is_err());
+        assert!(MysqlConfig::new("mysql://localhost:db", sql()).is_err());
+    }
+
+    #[test]
+    fn test_wire_encoding() {
+        let packet = [0xfc, 0x34, 0x12, 0x05];
+        let mut pos = 0;
+        assert_eq!(read_lenenc(&packet, &mut pos).unwrap(), 0x1234);
+        assert_eq!(read_lenenc(&packet, &mut pos).unwrap(), 5);
+        assert!(read_lenenc(&packet, &mut pos).is_err());
+
+        let mut buf = Vec::new();
+        write_lenenc_str(&mut buf, &[b'x'; 300]);
+        assert_eq!(&buf[..3], &[0xfc, 0x2c, 0x01]);
+
+        let error = server_error(b"\xff\x7a\x04#42S02Table 'shop.x' doesn't exist");
+        assert_eq!(
+            error.to_string(),
+            "MySQL 1146 (42S02): Table 'shop.x' doesn't exist"
+        );
+
+        assert_eq!(
+            scramble("mysql_native_password", "", b"nonce").unwrap(),
+            b""
+        );
+        assert_eq!(
+            scramble("caching_sha2_password", "pw", &[0; 20])
+                .unwrap()
+                .len(),
+            32
+        );
+        assert!(scramble("dialog", "pw", &[0; 20]).is_err());
+    }
+}
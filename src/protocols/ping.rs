@@ -0,0 +1,340 @@
+//! Ping latency mode
+//!
+//! Measures the network round trip to a host, without any application on
+//! top, to compare against the latency of the other protocols. Every request
+//! is one ICMP echo from a fresh socket: a raw socket when privileged, else an
+//! unprivileged ICMP datagram socket (Linux, within `ping_group_range`). Where
+//! neither can be opened, a TCP ping times connecting to a port instead.
+
+use crate::hooks::Hooks;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::{ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup};
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use socket2::{Domain, Protocol as SocketProtocol, SockAddr, Socket, Type};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// Port a TCP ping connects to when the target has none
+pub const DEFAULT_TCP_PING_PORT: u16 = 80;
+
+/// Echo payload size, as `ping` sends by default
+const PAYLOAD_SIZE: usize = 56;
+
+/// How round trips are measured
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PingMode {
+    /// ICMP when a socket can be opened, else TCP
+    #[default]
+    Auto,
+    Icmp,
+    Tcp,
+}
+
+impl FromStr for PingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(PingMode::Auto),
+            "icmp" => Ok(PingMode::Icmp),
+            "tcp" => Ok(PingMode::Tcp),
+            _ => Err(format!(
+                "Unsupported ping mode: {} (use auto, icmp or tcp)",
+                s
+            )),
+        }
+    }
+}
+
+impl PingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PingMode::Auto => "auto",
+            PingMode::Icmp => "icmp",
+            PingMode::Tcp => "tcp",
+        }
+    }
+}
+
+/// Configuration for ping load testing
+#[derive(Debug, Clone)]
+pub struct PingConfig {
+    /// Target as `host`, or `host:port` for TCP pings
+    pub target: String,
+    pub mode: PingMode,
+}
+
+impl PingConfig {
+    pub fn new(target: String) -> Self {
+        Self {
+            target,
+            mode: PingMode::Auto,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: PingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The host and the port for TCP pings
+    fn host_and_port(&self) -> Result<(&str, u16)> {
+        let target = self.target.trim_start_matches("ping://");
+        let (host, port) = match target.rsplit_once(':') {
+            // A bare IPv6 address has colons but no port
+            Some((host, port)) if !host.contains(':') || host.ends_with(']') => (
+                host,
+                port.parse()
+                    .with_context(|| format!("Invalid port in ping target {}", self.target))?,
+            ),
+            _ => (target, DEFAULT_TCP_PING_PORT),
+        };
+        Ok((host.trim_start_matches('[').trim_end_matches(']'), port))
+    }
+}
+
+/// The kind of socket ICMP echoes are sent from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IcmpSocket {
+    /// Needs privileges; replies include the IPv4 header
+    Raw,
+    /// Unprivileged; the kernel sets the echo identifier itself
+    Datagram,
+}
+
+impl IcmpSocket {
+    fn open(self, ip: IpAddr) -> io::Result<Socket> {
+        let (domain, protocol) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, SocketProtocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, SocketProtocol::ICMPV6),
+        };
+        let kind = match self {
+            IcmpSocket::Raw => Type::RAW,
+            IcmpSocket::Datagram => Type::DGRAM,
+        };
+        Socket::new(domain, kind, Some(protocol))
+    }
+
+    /// The first kind of socket that can be opened
+    fn detect(ip: IpAddr) -> Option<Self> {
+        [IcmpSocket::Raw, IcmpSocket::Datagram]
+            .into_iter()
+            .find(|kind| kind.open(ip).is_ok())
+    }
+}
+
+/// Internet checksum over `data`
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// An echo request; the kernel fills in the checksum for ICMPv6
+fn echo_request(ipv6: bool, id: u16, seq: u16) -> Vec<u8> {
+    let mut packet = vec![if ipv6 { 128 } else { 8 }, 0, 0, 0];
+    packet.extend(id.to_be_bytes());
+    packet.extend(seq.to_be_bytes());
+    packet.extend((0..PAYLOAD_SIZE).map(|i| i as u8));
+    if !ipv6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// Whether `packet` is the reply to our echo, `id` being `None` when the
+/// kernel picks the identifier
+fn is_echo_reply(packet: &[u8], ipv6: bool, id: Option<u16>, seq: u16) -> bool {
+    let reply_type = if ipv6 { 129 } else { 0 };
+    packet.len() >= 8
+        && packet[0] == reply_type
+        && packet[6..8] == seq.to_be_bytes()
+        && id.is_none_or(|id| packet[4..6] == id.to_be_bytes())
+}
+
+/// Send one echo request and wait for its reply
+async fn icmp_exchange(kind: IcmpSocket, ip: IpAddr, id: u16, seq: u16) -> io::Result<()> {
+    let socket = kind.open(ip)?;
+    socket.set_nonblocking(true)?;
+    socket.connect(&SockAddr::from(SocketAddr::new(ip, 0)))?;
+    // Datagram-like enough for tokio to send and receive on
+    let socket = UdpSocket::from_std(socket.into())?;
+
+    let ipv6 = ip.is_ipv6();
+    socket.send(&echo_request(ipv6, id, seq)).await?;
+    let mut buf = vec![0; 1500];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        let mut packet = &buf[..len];
+        // Raw IPv4 sockets receive the IP header too
+        if kind == IcmpSocket::Raw && !ipv6 {
+            let header_len = (packet.first().copied().unwrap_or_default() & 0x0f) as usize * 4;
+            packet = packet.get(header_len..).unwrap_or_default();
+        }
+        let id = (kind == IcmpSocket::Raw).then_some(id);
+        if is_echo_reply(packet, ipv6, id, seq) {
+            return Ok(());
+        }
+    }
+}
+
+/// How each ping is sent, decided once before the run
+#[derive(Debug, Clone, Copy)]
+enum Probe {
+    Icmp(IcmpSocket),
+    Tcp,
+}
+
+/// Sends one ping per iteration
+struct PingExecutor {
+    target: SocketAddr,
+    probe: Probe,
+    /// Echo identifier for raw sockets, to tell our replies from other pings'
+    id: u16,
+    timeout: Duration,
+    /// Why ICMP isn't used in auto mode, if it isn't
+    fallback: Option<&'static str>,
+}
+
+impl PingExecutor {
+    async fn ping(&self, seq: u64) -> RequestResult {
+        let start = Instant::now();
+        let reply = match self.probe {
+            Probe::Icmp(kind) => {
+                let exchange = icmp_exchange(kind, self.target.ip(), self.id, seq as u16);
+                tokio::time::timeout(self.timeout, exchange).await
+            }
+            Probe::Tcp => {
+                let connect = TcpStream::connect(self.target);
+                tokio::time::timeout(self.timeout, async { connect.await.map(drop) }).await
+            }
+        };
+        let duration = start.elapsed().as_millis();
+
+        let icmp = matches!(self.probe, Probe::Icmp(_));
+        match reply {
+            Ok(Ok(())) => RequestResult {
+                duration,
+                success: true,
+                lost: icmp.then_some(false),
+                ..Default::default()
+            },
+            Ok(Err(e)) => RequestResult {
+                duration,
+                error: Some(ErrorKind::from_error(&e)),
+                ..Default::default()
+            },
+            Err(_) => RequestResult {
+                duration,
+                error: Some(if icmp {
+                    ErrorKind::ResponseTimeout
+                } else {
+                    ErrorKind::ConnectTimeout
+                }),
+                lost: icmp.then_some(true),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl RequestExecutor for PingExecutor {
+    fn progress_message(&self) -> String {
+        match (self.probe, self.fallback) {
+            (Probe::Icmp(_), _) => format!("Pinging {} over ICMP...", self.target.ip()),
+            (Probe::Tcp, Some(reason)) => {
+                format!("Pinging {} over TCP ({})...", self.target, reason)
+            }
+            (Probe::Tcp, None) => format!("Pinging {} over TCP...", self.target),
+        }
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "white"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = self.ping(iteration.seq).await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run a ping load test with the given configuration
+pub async fn run_load_test(
+    config: &PingConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    let (host, port) = config.host_and_port()?;
+    let target = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("Failed to resolve ping target {}", host))?
+        .next()
+        .with_context(|| format!("No address found for ping target {}", host))?;
+
+    let (probe, fallback) = match (config.mode, IcmpSocket::detect(target.ip())) {
+        (PingMode::Tcp, _) => (Probe::Tcp, None),
+        (_, Some(kind)) => (Probe::Icmp(kind), None),
+        (PingMode::Auto, None) => (Probe::Tcp, Some("ICMP needs privileges")),
+        (PingMode::Icmp, None) => bail!(
+            "Can't open an ICMP socket: run as root, grant CAP_NET_RAW, allow your group in \
+             net.ipv4.ping_group_range, or use --ping-mode tcp"
+        ),
+    };
+    let executor = PingExecutor {
+        target,
+        probe,
+        id: rand::rng().random(),
+        timeout: Duration::from_secs(timeout_secs),
+        fallback,
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_packets() {
+        let request = echo_request(false, 0x1234, 7);
+        assert_eq!(&request[..8], &[8, 0, 0xee, 0xb1, 0x12, 0x34, 0, 7]);
+        // A packet with its checksum filled in sums to zero
+        assert_eq!(checksum(&request), 0);
+
+        let mut reply = request.clone();
+        reply[0] = 0;
+        assert!(is_echo_reply(&reply, false, Some(0x1234), 7));
+        assert!(is_echo_reply(&reply, false, None, 7));
+        assert!(!is_echo_reply(&reply, false, Some(0x4321), 7));
+        assert!(!is_echo_reply(&reply, false, None, 8));
+        assert!(!is_echo_reply(&request, false, None, 7));
+
+        let target = |target: &str| {
+            let config = PingConfig::new(target.to_string());
+            config
+                .host_and_port()
+                .map(|(host, port)| (host.to_string(), port))
+                .unwrap()
+        };
+        assert_eq!(target("example.com"), ("example.com".to_string(), 80));
+        assert_eq!(target("ping://10.0.0.1:443"), ("10.0.0.1".to_string(), 443));
+        assert_eq!(target("::1"), ("::1".to_string(), 80));
+        assert_eq!(target("[::1]:22"), ("::1".to_string(), 22));
+    }
+}
@@ -0,0 +1,227 @@
+//! RESP (REdis Serialization Protocol) encoding and decoding
+//!
+//! Commands are sent as arrays of bulk strings, which every RESP server
+//! accepts. Replies are decoded in full, including the RESP3 types a server
+//! sends after `HELLO 3`, so multi-line replies never leave the connection out
+//! of sync.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// A decoded reply
+///
+/// RESP3 aggregates (maps, sets and pushes) are flattened into arrays and
+/// big numbers are kept as text, since replies are only classified, not used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Array(Vec<Reply>),
+    /// A nil bulk string or array (RESP2), or a null (RESP3)
+    Null,
+    Boolean(bool),
+    Double(String),
+}
+
+impl Reply {
+    pub fn is_error(&self) -> bool {
+        matches!(self, Reply::Error(_))
+    }
+}
+
+impl fmt::Display for Reply {
+    /// Formatted like `redis-cli` does
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Simple(s) | Reply::Double(s) => write!(f, "{}", s),
+            Reply::Error(e) => write!(f, "(error) {}", e),
+            Reply::Integer(n) => write!(f, "(integer) {}", n),
+            Reply::Bulk(bytes) => write!(f, "{:?}", String::from_utf8_lossy(bytes)),
+            Reply::Array(items) if items.is_empty() => write!(f, "(empty array)"),
+            Reply::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}) {}", i + 1, item)?;
+                }
+                Ok(())
+            }
+            Reply::Null => write!(f, "(nil)"),
+            Reply::Boolean(b) => write!(f, "({})", b),
+        }
+    }
+}
+
+/// Encode a command as an array of bulk strings
+pub fn encode<A: AsRef<[u8]>>(args: &[A]) -> Vec<u8> {
+    let mut encoded = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        let arg = arg.as_ref();
+        encoded.extend(format!("${}\r\n", arg.len()).as_bytes());
+        encoded.extend(arg);
+        encoded.extend(b"\r\n");
+    }
+    encoded
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read one line without its CRLF, failing if the connection closed
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Connection closed by server",
+        ));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Read exactly `len` bytes followed by a CRLF
+async fn read_blob<R: AsyncBufRead + Unpin>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut blob = vec![0; len + 2];
+    reader.read_exact(&mut blob).await?;
+    if !blob.ends_with(b"\r\n") {
+        return Err(invalid("Bulk string not terminated by CRLF".to_string()));
+    }
+    blob.truncate(len);
+    Ok(blob)
+}
+
+/// Read the next reply
+pub fn read_reply<'a, R: AsyncBufRead + Unpin + Send>(
+    reader: &'a mut R,
+) -> Pin<Box<dyn Future<Output = io::Result<Reply>> + Send + 'a>> {
+    Box::pin(async move {
+        let line = read_line(reader).await?;
+        let Some(kind) = line.chars().next() else {
+            return Err(invalid("Empty reply line".to_string()));
+        };
+        let rest = &line[kind.len_utf8()..];
+        // The length of a bulk string or aggregate, -1 for RESP2's nil
+        let len = || -> io::Result<i64> {
+            rest.parse()
+                .map_err(|_| invalid(format!("Invalid length in {:?}", line)))
+        };
+
+        Ok(match kind {
+            '+' => Reply::Simple(rest.to_string()),
+            '-' => Reply::Error(rest.to_string()),
+            ':' => Reply::Integer(len()?),
+            ',' | '(' => Reply::Double(rest.to_string()),
+            '#' => Reply::Boolean(rest == "t"),
+            '_' => Reply::Null,
+            '$' | '=' | '!' => match len()? {
+                -1 => Reply::Null,
+                len if len < 0 => return Err(invalid(format!("Invalid length in {:?}", line))),
+                len => {
+                    let blob = read_blob(reader, len as usize).await?;
+                    match kind {
+                        '!' => Reply::Error(String::from_utf8_lossy(&blob).into_owned()),
+                        // Verbatim strings start with their format, e.g. "txt:"
+                        '=' => Reply::Bulk(blob.get(4..).unwrap_or_default().to_vec()),
+                        _ => Reply::Bulk(blob),
+                    }
+                }
+            },
+            '*' | '~' | '>' | '%' | '|' => {
+                let len = len()?;
+                if len == -1 {
+                    return Ok(Reply::Null);
+                }
+                // A map's entries are key/value pairs
+                let count = if matches!(kind, '%' | '|') {
+                    len * 2
+                } else {
+                    len
+                };
+                let mut items = Vec::with_capacity(count.clamp(0, 1024) as usize);
+                for _ in 0..count {
+                    items.push(read_reply(reader).await?);
+                }
+                if kind == '|' {
+                    // Attributes annotate the reply that follows them
+                    return read_reply(reader).await;
+                }
+                Reply::Array(items)
+            }
+            _ => return Err(invalid(format!("Unknown reply type in {:?}", line))),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+
+    async fn decode(bytes: &[u8]) -> Vec<Reply> {
+        let mut reader = BufReader::new(bytes);
+        let mut replies = Vec::new();
+        while !reader.fill_buf().await.unwrap().is_empty() {
+            replies.push(read_reply(&mut reader).await.unwrap());
+        }
+        replies
+    }
+
+    #[test]
+    fn test_encode() {
+        let args = ["SET", "greeting", "hello world"].map(String::from);
+        assert_eq!(
+            encode(&args),
+            b"*3\r\n$3\r\nSET\r\n$8\r\ngreeting\r\n$11\r\nhello world\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_reply() {
+        let replies = decode(
+            b"+OK\r\n-ERR wrong type\r\n:42\r\n$5\r\nhe\r\no\r\n$-1\r\n*-1\r\n\
+              *2\r\n$1\r\na\r\n*1\r\n:1\r\n",
+        )
+        .await;
+        assert_eq!(
+            replies,
+            vec![
+                Reply::Simple("OK".to_string()),
+                Reply::Error("ERR wrong type".to_string()),
+                Reply::Integer(42),
+                Reply::Bulk(b"he\r\no".to_vec()),
+                Reply::Null,
+                Reply::Null,
+                Reply::Array(vec![
+                    Reply::Bulk(b"a".to_vec()),
+                    Reply::Array(vec![Reply::Integer(1)])
+                ]),
+            ]
+        );
+
+        // RESP3 types, with an attribute ahead of the reply it annotates
+        let replies =
+            decode(b"_\r\n#t\r\n,3.14\r\n%1\r\n+k\r\n:1\r\n|1\r\n+ttl\r\n:5\r\n=7\r\ntxt:abc\r\n")
+                .await;
+        assert_eq!(
+            replies,
+            vec![
+                Reply::Null,
+                Reply::Boolean(true),
+                Reply::Double("3.14".to_string()),
+                Reply::Array(vec![Reply::Simple("k".to_string()), Reply::Integer(1)]),
+                Reply::Bulk(b"abc".to_vec()),
+            ]
+        );
+
+        let mut reader = BufReader::new(&b"$3\r\nab"[..]);
+        assert!(read_reply(&mut reader).await.is_err());
+        assert_eq!(Reply::Null.to_string(), "(nil)");
+    }
+}
@@ -0,0 +1,303 @@
+//! Server-Sent Events implementation for load testing
+//!
+//! Every iteration is one subscriber: it opens an `text/event-stream` request,
+//! holds it for a while and counts the events that arrive. The latency of a
+//! subscriber is its time to first event; a stream the server closes (or that
+//! fails) before the subscriber is done counts as a dropped connection.
+
+use crate::hooks::Hooks;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::http::{before_request, error_kind, version_name};
+use crate::protocols::{
+    ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup, StreamResult,
+};
+use anyhow::{Context, Result};
+use reqwest::header::{ACCEPT, CACHE_CONTROL};
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a subscriber stays connected by default
+pub const DEFAULT_HOLD: Duration = Duration::from_secs(10);
+
+/// Configuration for SSE load testing
+#[derive(Debug, Clone)]
+pub struct SseConfig {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// How long each subscriber stays connected
+    pub hold: Duration,
+    /// Disconnect once this many events arrived, before `hold` is up
+    pub max_events: Option<u64>,
+}
+
+impl SseConfig {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            headers: Vec::new(),
+            hold: DEFAULT_HOLD,
+            max_events: None,
+        }
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    pub fn with_max_events(mut self, max_events: Option<u64>) -> Self {
+        self.max_events = max_events;
+        self
+    }
+}
+
+/// Counts the events in an event stream as its bytes arrive
+///
+/// An event is dispatched by a blank line after at least one `data` field;
+/// comments (`:` lines) and events without data, such as keep-alives, don't
+/// count.
+#[derive(Debug, Default)]
+struct EventCounter {
+    /// The start of the current line's field name, enough to tell `data`
+    field: Vec<u8>,
+    /// Past the colon ending the field name
+    in_value: bool,
+    line_empty: bool,
+    has_data: bool,
+    /// The last byte was a `\r`, so a following `\n` ends no new line
+    after_cr: bool,
+}
+
+impl EventCounter {
+    fn new() -> Self {
+        Self {
+            line_empty: true,
+            ..Default::default()
+        }
+    }
+
+    /// Feed the next bytes, returning the number of events they completed
+    fn feed(&mut self, bytes: &[u8]) -> u64 {
+        let mut events = 0;
+        for &byte in bytes {
+            let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+            match byte {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    if self.line_empty {
+                        if std::mem::take(&mut self.has_data) {
+                            events += 1;
+                        }
+                    } else if self.field == b"data" {
+                        self.has_data = true;
+                    }
+                    self.field.clear();
+                    self.in_value = false;
+                    self.line_empty = true;
+                }
+                _ => {
+                    // The field name runs up to the first colon
+                    if byte == b':' {
+                        self.in_value = true;
+                    } else if !self.in_value && self.field.len() <= 4 {
+                        self.field.push(byte);
+                    }
+                    self.line_empty = false;
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Open one subscription and hold it until it's done
+async fn subscribe(
+    client: &Client,
+    config: &SseConfig,
+    timeout_secs: u64,
+    iteration: &Iteration,
+    hooks: Option<&dyn Hooks>,
+) -> RequestResult {
+    let start = Instant::now();
+    let mut request_builder = client
+        .get(&config.url)
+        .header(ACCEPT, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache");
+    for (key, value) in &config.headers {
+        request_builder = request_builder.header(key, value);
+    }
+    let request = request_builder
+        .build()
+        .map_err(|e| error_kind(&e))
+        .and_then(|mut request| match hooks {
+            Some(hooks) => before_request(hooks, iteration.seq, &mut request)
+                .map(|()| request)
+                .map_err(|e| ErrorKind::Other(format!("before_request hook: {}", e))),
+            None => Ok(request),
+        });
+    let sent = match request {
+        Ok(request) => {
+            match tokio::time::timeout(Duration::from_secs(timeout_secs), client.execute(request))
+                .await
+            {
+                Ok(sent) => sent.map_err(|e| error_kind(&e)),
+                Err(_) => Err(ErrorKind::ResponseTimeout),
+            }
+        }
+        Err(error) => Err(error),
+    };
+    let mut response = match sent {
+        Ok(response) => response,
+        Err(error) => {
+            return RequestResult {
+                duration: start.elapsed().as_millis(),
+                error: Some(error),
+                ..Default::default()
+            }
+        }
+    };
+
+    let status = response.status().as_u16();
+    let http_version = Some(version_name(response.version()));
+    if !response.status().is_success() {
+        return RequestResult {
+            duration: start.elapsed().as_millis(),
+            status,
+            http_version,
+            ..Default::default()
+        };
+    }
+
+    let deadline = tokio::time::Instant::from(start + config.hold);
+    let mut counter = EventCounter::new();
+    let mut events = 0;
+    let mut first_event = None;
+    let mut error = None;
+    while config.max_events.is_none_or(|max| events < max) {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk,
+            _ = tokio::time::sleep_until(deadline) => break,
+            _ = iteration.recorder.stopped() => break,
+        };
+        match chunk {
+            Ok(Some(bytes)) => {
+                let completed = counter.feed(&bytes);
+                if completed > 0 && first_event.is_none() {
+                    first_event = Some(start.elapsed());
+                }
+                events += completed;
+            }
+            Ok(None) => {
+                error = Some(ErrorKind::Other("stream closed by the server".to_string()));
+                break;
+            }
+            Err(e) => {
+                error = Some(error_kind(&e));
+                break;
+            }
+        }
+    }
+    let connected = start.elapsed();
+    let dropped = error.is_some();
+    if first_event.is_none() && error.is_none() {
+        error = Some(ErrorKind::Other(format!(
+            "no events within {:?}",
+            config.hold
+        )));
+    }
+
+    RequestResult {
+        duration: first_event.unwrap_or(connected).as_millis(),
+        status,
+        success: error.is_none(),
+        error,
+        http_version,
+        stream: Some(StreamResult {
+            events,
+            connected_ms: connected.as_millis(),
+            dropped,
+        }),
+        ..Default::default()
+    }
+}
+
+/// Holds one subscription per iteration
+struct SseExecutor {
+    client: Client,
+    config: SseConfig,
+    timeout_secs: u64,
+    hooks: Option<Arc<dyn Hooks>>,
+}
+
+impl RequestExecutor for SseExecutor {
+    fn progress_message(&self) -> String {
+        format!("Subscribing to {}...", self.config.url)
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "green"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = subscribe(
+            &self.client,
+            &self.config,
+            self.timeout_secs,
+            &iteration,
+            self.hooks.as_deref(),
+        )
+        .await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run an SSE load test with the given configuration
+pub async fn run_load_test(
+    config: &SseConfig,
+    stages: &[Stage],
+    startup: Startup,
+    timeout_secs: u64,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    // No overall timeout, streams are meant to stay open; one HTTP/1.1
+    // connection per subscriber, as browsers have
+    let client = Client::builder()
+        .user_agent("rustyload/0.2")
+        .connect_timeout(Duration::from_secs(timeout_secs))
+        .http1_only()
+        .build()
+        .context("Failed to build HTTP client")?;
+    let executor = SseExecutor {
+        client,
+        config: config.clone(),
+        timeout_secs,
+        hooks: hooks.clone(),
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_counter() {
+        let mut counter = EventCounter::new();
+        // Comments and events without data don't count
+        assert_eq!(counter.feed(b": keep-alive\n\nevent: ping\n\n"), 0);
+        assert_eq!(counter.feed(b"data: one\n\n"), 1);
+        // Split across chunks, with CRLF line endings and a multi-line event
+        assert_eq!(counter.feed(b"id: 2\r\nda"), 0);
+        assert_eq!(counter.feed(b"ta: two\r\ndata: more\r"), 0);
+        assert_eq!(counter.feed(b"\n\r\n"), 1);
+        assert_eq!(counter.feed(b"data\n\ndata:x\n\ndataz: no\n\n"), 2);
+    }
+}
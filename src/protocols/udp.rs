@@ -0,0 +1,221 @@
+//! UDP protocol implementation for load testing
+//!
+//! Every request is one datagram sent from a fresh socket (so a new source
+//! port each time). By default the request waits for a reply until the packet
+//! timeout; a datagram without one counts as lost. With replies disabled,
+//! requests are fire-and-forget and only sending is measured.
+
+use crate::hooks::Hooks;
+use crate::protocols::engine::{self, Iteration, RequestExecutor};
+use crate::protocols::{
+    BodySize, ErrorKind, LiveResults, LoadTestStats, RequestResult, Stage, Startup,
+};
+use anyhow::{bail, Context, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Payload size when neither a body nor a size is given
+pub const DEFAULT_PAYLOAD_SIZE: usize = 64;
+
+/// Largest payload a UDP datagram can carry over IPv4
+pub const MAX_PAYLOAD_SIZE: usize = 65507;
+
+/// How long to wait for a reply by default
+pub const DEFAULT_PACKET_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Configuration for UDP load testing
+#[derive(Debug, Clone)]
+pub struct UdpConfig {
+    /// Target address as `host:port`
+    pub target: String,
+    /// The datagram sent with every request
+    pub payload: Vec<u8>,
+    /// Wait for a reply to every datagram
+    pub expect_reply: bool,
+    /// How long to wait for a reply before the datagram counts as lost
+    pub packet_timeout: Duration,
+}
+
+impl UdpConfig {
+    pub fn new(target: String) -> Self {
+        Self {
+            target,
+            payload: vec![0; DEFAULT_PAYLOAD_SIZE],
+            expect_reply: true,
+            packet_timeout: DEFAULT_PACKET_TIMEOUT,
+        }
+    }
+
+    /// Send `content`, repeated or cut to `size` bytes when one is given
+    /// (zero bytes without content)
+    pub fn with_payload(mut self, content: Option<&[u8]>, size: Option<usize>) -> Result<Self> {
+        self.payload = match (content, size) {
+            (Some(content), None) => content.to_vec(),
+            (Some(content), Some(size)) if !content.is_empty() => {
+                content.iter().copied().cycle().take(size).collect()
+            }
+            (_, size) => vec![0; size.unwrap_or(DEFAULT_PAYLOAD_SIZE)],
+        };
+        if self.payload.len() > MAX_PAYLOAD_SIZE {
+            bail!(
+                "A UDP payload can't be larger than {} bytes, got {}",
+                MAX_PAYLOAD_SIZE,
+                self.payload.len()
+            );
+        }
+        Ok(self)
+    }
+
+    pub fn with_expect_reply(mut self, expect_reply: bool) -> Self {
+        self.expect_reply = expect_reply;
+        self
+    }
+
+    pub fn with_packet_timeout(mut self, packet_timeout: Duration) -> Self {
+        self.packet_timeout = packet_timeout;
+        self
+    }
+}
+
+/// Send one datagram, returning the size of its reply when one is expected
+async fn exchange(
+    server: SocketAddr,
+    payload: &[u8],
+    expect_reply: bool,
+) -> std::io::Result<Option<usize>> {
+    let local: SocketAddr = if server.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(payload).await?;
+    if !expect_reply {
+        return Ok(None);
+    }
+    let mut buf = vec![0; MAX_PAYLOAD_SIZE];
+    Ok(Some(socket.recv(&mut buf).await?))
+}
+
+/// Send one datagram and wait for its reply
+async fn fire_single_request(server: SocketAddr, config: &UdpConfig) -> RequestResult {
+    let start = Instant::now();
+    let sent = tokio::time::timeout(
+        config.packet_timeout,
+        exchange(server, &config.payload, config.expect_reply),
+    )
+    .await;
+    let duration = start.elapsed().as_millis();
+
+    match sent {
+        Ok(Ok(reply)) => RequestResult {
+            duration,
+            success: true,
+            body: reply.map(|len| BodySize {
+                received: len as u64,
+                decoded: Some(len as u64),
+                encoding: "identity",
+            }),
+            lost: config.expect_reply.then_some(false),
+            ..Default::default()
+        },
+        Ok(Err(e)) => RequestResult {
+            duration,
+            error: Some(ErrorKind::from_error(&e)),
+            ..Default::default()
+        },
+        Err(_) => RequestResult {
+            duration,
+            error: Some(ErrorKind::ResponseTimeout),
+            lost: Some(true),
+            ..Default::default()
+        },
+    }
+}
+
+/// Sends one datagram per iteration
+struct UdpExecutor {
+    server: SocketAddr,
+    config: UdpConfig,
+}
+
+impl RequestExecutor for UdpExecutor {
+    fn progress_message(&self) -> String {
+        format!(
+            "Sending {} byte datagrams to {}...",
+            self.config.payload.len(),
+            self.config.target
+        )
+    }
+
+    fn progress_color(&self) -> &'static str {
+        "cyan"
+    }
+
+    async fn execute(&self, iteration: Iteration) {
+        let result = fire_single_request(self.server, &self.config).await;
+        iteration.recorder.record(result);
+    }
+}
+
+/// Run a UDP load test with the given configuration
+pub async fn run_load_test(
+    config: &UdpConfig,
+    stages: &[Stage],
+    startup: Startup,
+    quiet: bool,
+    live: &LiveResults,
+    hooks: Option<Arc<dyn Hooks>>,
+) -> Result<LoadTestStats> {
+    let server = tokio::net::lookup_host(&config.target)
+        .await
+        .with_context(|| format!("Failed to resolve UDP target {}", config.target))?
+        .next()
+        .with_context(|| format!("No address found for UDP target {}", config.target))?;
+    let executor = UdpExecutor {
+        server,
+        config: config.clone(),
+    };
+    Ok(engine::run(executor, stages, startup, quiet, live, hooks).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let config = |content: Option<&[u8]>, size| {
+            UdpConfig::new("localhost:9000".to_string()).with_payload(content, size)
+        };
+        assert_eq!(
+            config(None, None).unwrap().payload.len(),
+            DEFAULT_PAYLOAD_SIZE
+        );
+        assert_eq!(config(None, Some(3)).unwrap().payload, vec![0, 0, 0]);
+        assert_eq!(config(Some(b"ping"), None).unwrap().payload, b"ping");
+        assert_eq!(config(Some(b"ab"), Some(5)).unwrap().payload, b"ababa");
+        assert_eq!(config(Some(b"ping"), Some(2)).unwrap().payload, b"pi");
+        assert!(config(None, Some(MAX_PAYLOAD_SIZE + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lost_datagram() {
+        // A socket that never replies
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = UdpConfig::new(String::new()).with_packet_timeout(Duration::from_millis(50));
+
+        let result = fire_single_request(silent.local_addr().unwrap(), &config).await;
+        assert!(!result.success);
+        assert_eq!(result.lost, Some(true));
+        assert_eq!(result.error, Some(ErrorKind::ResponseTimeout));
+
+        let config = config.with_expect_reply(false);
+        let result = fire_single_request(silent.local_addr().unwrap(), &config).await;
+        assert!(result.success);
+        assert_eq!(result.lost, None);
+    }
+}
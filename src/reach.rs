@@ -0,0 +1,220 @@
+//! Quick reachability checks for a target typed into the wizard
+//!
+//! Every step a request depends on is tried once, in order: resolving the
+//! host, opening a TCP connection and, for `https://` URLs, the TLS handshake.
+//! A typo then shows up as the step it breaks, right after it's typed, rather
+//! than as a run where every request fails.
+
+use openssl::ssl::{SslConnector, SslMethod};
+use reqwest::Url;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long each step may take
+pub const STEP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What to check for a target
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    /// Resolve the host and connect, then complete a TLS handshake when `tls`
+    Tcp { host: String, port: u16, tls: bool },
+    /// Only resolve the host, for targets without connections (UDP, ICMP)
+    Resolve { host: String, port: u16 },
+    /// Check a Unix domain socket exists
+    Socket(PathBuf),
+}
+
+impl Probe {
+    /// The probe for an `http://`, `https://` or `unix://` URL
+    pub fn url(url: &str) -> Result<Self, String> {
+        if let Some(path) = url.strip_prefix("unix://") {
+            // unix:///path/to.sock:/request/path
+            let socket = path.rfind(":/").map_or(path, |index| &path[..index]);
+            return Ok(Probe::Socket(PathBuf::from(socket)));
+        }
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| format!("No host in {}", url))?;
+        Ok(Probe::Tcp {
+            host: host.trim_matches(['[', ']']).to_string(),
+            port: parsed
+                .port_or_known_default()
+                .ok_or_else(|| format!("No port in {}", url))?,
+            tls: parsed.scheme() == "https",
+        })
+    }
+
+    /// The probe for `host:port`, using `default_port` when there's none
+    pub fn address(address: &str, default_port: u16) -> Result<Self, String> {
+        let (host, port) = split_address(address, default_port)?;
+        Ok(Probe::Tcp {
+            host,
+            port,
+            tls: false,
+        })
+    }
+
+    /// The probe for a `host[:port]` that is only resolved
+    pub fn resolve(address: &str, default_port: u16) -> Result<Self, String> {
+        let (host, port) = split_address(address, default_port)?;
+        Ok(Probe::Resolve { host, port })
+    }
+}
+
+/// What a successful check found
+#[derive(Debug)]
+pub struct Reached {
+    /// The address connected to (or resolved), none for a socket
+    pub address: Option<SocketAddr>,
+    pub elapsed: Duration,
+    /// The steps that passed, e.g. "DNS lookup", "TCP connect"
+    pub steps: Vec<&'static str>,
+}
+
+/// The step a check stopped at
+#[derive(Debug)]
+pub struct Failure {
+    pub step: &'static str,
+    pub error: String,
+}
+
+/// Run the checks of `probe`, stopping at the first failing step
+pub fn check(probe: &Probe, timeout: Duration) -> Result<Reached, Failure> {
+    let start = Instant::now();
+    let fail = |step, error: &dyn std::fmt::Display| Failure {
+        step,
+        error: error.to_string(),
+    };
+
+    let (host, port, tls, connect) = match probe {
+        Probe::Socket(path) => {
+            return match std::fs::metadata(path) {
+                Ok(_) => Ok(Reached {
+                    address: None,
+                    elapsed: start.elapsed(),
+                    steps: vec!["socket"],
+                }),
+                Err(e) => Err(fail("socket", &format!("{}: {}", path.display(), e))),
+            };
+        }
+        Probe::Resolve { host, port } => (host, *port, false, false),
+        Probe::Tcp { host, port, tls } => (host, *port, *tls, true),
+    };
+
+    let address = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| fail("DNS lookup", &e))?
+        .next()
+        .ok_or_else(|| fail("DNS lookup", &format!("{} has no addresses", host)))?;
+    let mut steps = vec!["DNS lookup"];
+    if !connect {
+        return Ok(Reached {
+            address: Some(address),
+            elapsed: start.elapsed(),
+            steps,
+        });
+    }
+
+    let stream =
+        TcpStream::connect_timeout(&address, timeout).map_err(|e| fail("TCP connect", &e))?;
+    steps.push("TCP connect");
+
+    if tls {
+        stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)))
+            .map_err(|e| fail("TLS handshake", &e))?;
+        let connector = SslConnector::builder(SslMethod::tls())
+            .map_err(|e| fail("TLS handshake", &e))?
+            .build();
+        connector
+            .connect(host, stream)
+            .map_err(|e| fail("TLS handshake", &e))?;
+        steps.push("TLS handshake");
+    }
+
+    Ok(Reached {
+        address: Some(address),
+        elapsed: start.elapsed(),
+        steps,
+    })
+}
+
+/// Split `host[:port]`, keeping IPv6 addresses in brackets whole
+fn split_address(address: &str, default_port: u16) -> Result<(String, u16), String> {
+    let address = address.trim();
+    let address = address
+        .split_once("://")
+        .map_or(address, |(_, rest)| rest)
+        .trim_end_matches('/');
+    // Credentials aren't part of the address
+    let address = address.rsplit_once('@').map_or(address, |(_, rest)| rest);
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') && !host.ends_with(':') => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid port in {}", address))?,
+        ),
+        _ => (address, default_port),
+    };
+    let host = host.trim_matches(['[', ']']);
+    if host.is_empty() {
+        return Err(format!("No host in {}", address));
+    }
+    Ok((host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_probes() {
+        assert_eq!(
+            Probe::url("https://api.example.com/health").unwrap(),
+            Probe::Tcp {
+                host: "api.example.com".to_string(),
+                port: 443,
+                tls: true
+            }
+        );
+        assert_eq!(
+            Probe::url("unix:///tmp/app.sock:/health").unwrap(),
+            Probe::Socket(PathBuf::from("/tmp/app.sock"))
+        );
+        assert_eq!(
+            Probe::address("nats://user:pw@10.0.0.1", 4222).unwrap(),
+            Probe::Tcp {
+                host: "10.0.0.1".to_string(),
+                port: 4222,
+                tls: false
+            }
+        );
+        assert_eq!(
+            Probe::resolve("[::1]:53", 53).unwrap(),
+            Probe::Resolve {
+                host: "::1".to_string(),
+                port: 53
+            }
+        );
+        assert!(Probe::address("localhost:http", 80).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let probe = Probe::address(&format!("127.0.0.1:{}", port), 0).unwrap();
+        let reached = check(&probe, STEP_TIMEOUT).unwrap();
+        assert_eq!(reached.steps, vec!["DNS lookup", "TCP connect"]);
+
+        drop(listener);
+        assert_eq!(check(&probe, STEP_TIMEOUT).unwrap_err().step, "TCP connect");
+
+        let missing = Probe::Socket(PathBuf::from("/nonexistent/rustyload.sock"));
+        assert_eq!(check(&missing, STEP_TIMEOUT).unwrap_err().step, "socket");
+    }
+}
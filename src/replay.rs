@@ -0,0 +1,373 @@
+//! Replaying the requests of a web server access log, or the commands of a
+//! FlashKV/Redis command log
+//!
+//! Reads logs in the Common or Combined Log Format written by nginx and Apache,
+//! e.g. `10.0.0.1 - - [10/Oct/2024:13:55:36 +0000] "GET /items?page=2 HTTP/1.1" 200 512`,
+//! and sends each logged method and path to the target URL. Command logs are
+//! captured with MONITOR, e.g. `1700000000.123456 [0 10.0.0.1:5000] "SET" "k" "v"`,
+//! or list one command per line. With a speed factor the requests keep their
+//! original spacing, so a burst in production is a burst in the replay too.
+
+use crate::protocols::flashkv::{self, FlashKVCommand};
+use crate::protocols::http::HttpMethod;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One request read from the log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub method: HttpMethod,
+    /// The path and query string, as logged
+    pub path: String,
+    /// When the request was made, relative to the first one in the log
+    pub offset: Duration,
+}
+
+/// The requests of an access log, replayed in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLog {
+    pub path: PathBuf,
+    pub entries: Vec<LogEntry>,
+    /// Lines that weren't requests rustyload can send
+    pub skipped: usize,
+    /// Keep the logged timing, sped up by this factor; `None` sends requests
+    /// as fast as the concurrency allows
+    pub speed: Option<f64>,
+}
+
+impl AccessLog {
+    pub fn load(path: &Path, speed: Option<f64>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read access log {}", path.display()))?;
+        let log = Self::parse(&text, speed)
+            .with_context(|| format!("In access log {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..log
+        })
+    }
+
+    fn parse(text: &str, speed: Option<f64>) -> Result<Self> {
+        check_speed(speed)?;
+
+        let mut lines = Vec::new();
+        let mut skipped = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match parse_line(line) {
+                Some(parsed) => lines.push(parsed),
+                None => skipped += 1,
+            }
+        }
+        let Some(&(start, _, _)) = lines.first() else {
+            bail!("No requests found; expected the Common or Combined Log Format");
+        };
+
+        // Logs only have whole seconds, so requests within the same second
+        // are spread evenly over it
+        let mut entries = Vec::with_capacity(lines.len());
+        for second in lines.chunk_by(|a, b| a.0 == b.0) {
+            for (n, (time, method, path)) in second.iter().enumerate() {
+                let offset = Duration::from_secs((time - start).max(0) as u64)
+                    + Duration::from_secs(1).mul_f64(n as f64 / second.len() as f64);
+                entries.push(LogEntry {
+                    method: method.clone(),
+                    path: path.clone(),
+                    offset,
+                });
+            }
+        }
+
+        Ok(Self {
+            path: PathBuf::new(),
+            entries,
+            skipped,
+            speed,
+        })
+    }
+
+    /// The entry request number `seq` replays, starting over after the last one
+    pub fn entry(&self, seq: u64) -> &LogEntry {
+        &self.entries[(seq % self.entries.len() as u64) as usize]
+    }
+
+    /// How long after the start of the run request number `seq` is due, when
+    /// the logged timing is kept
+    pub fn due(&self, seq: u64) -> Option<Duration> {
+        let span = self.entries[self.entries.len() - 1].offset;
+        let rounds = seq / self.entries.len() as u64;
+        Some(scaled(self.entry(seq).offset, span, rounds, self.speed?))
+    }
+}
+
+/// Connection commands rustyload sends itself, or that would change the state
+/// of the connection for every command after them
+const SKIPPED_COMMANDS: [&str; 5] = ["AUTH", "HELLO", "SELECT", "MONITOR", "QUIT"];
+
+/// One command read from a command log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedCommand {
+    pub command: FlashKVCommand,
+    /// When the command was sent, relative to the first one in the log
+    pub offset: Duration,
+}
+
+/// The commands of a FlashKV/Redis command log, replayed in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandLog {
+    pub path: PathBuf,
+    pub entries: Vec<LoggedCommand>,
+    /// Lines that weren't commands rustyload can send
+    pub skipped: usize,
+    /// Keep the logged timing, sped up by this factor; `None` sends commands
+    /// as fast as the concurrency allows
+    pub speed: Option<f64>,
+}
+
+impl CommandLog {
+    pub fn load(path: &Path, speed: Option<f64>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read command log {}", path.display()))?;
+        let log = Self::parse(&text, speed)
+            .with_context(|| format!("In command log {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..log
+        })
+    }
+
+    fn parse(text: &str, speed: Option<f64>) -> Result<Self> {
+        check_speed(speed)?;
+
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let monitor: Vec<Option<(f64, Vec<Vec<u8>>)>> =
+            lines.iter().map(|line| parse_monitor_line(line)).collect();
+        // A MONITOR capture starts with its OK reply; in a plain list every
+        // line is a command, without timing
+        let timed = monitor.iter().any(Option::is_some);
+        if !timed && speed.is_some() {
+            bail!("Keeping the timing needs a MONITOR log with timestamps");
+        }
+
+        let mut start = None;
+        let mut entries = Vec::with_capacity(lines.len());
+        let mut skipped = 0;
+        for (line, parsed) in lines.iter().zip(monitor) {
+            let (offset, args) = match parsed {
+                Some((time, args)) => {
+                    let start = *start.get_or_insert(time);
+                    (Duration::from_secs_f64((time - start).max(0.0)), Some(args))
+                }
+                None if timed => (Duration::ZERO, None),
+                None => (Duration::ZERO, flashkv::split_args(line).ok()),
+            };
+            match args.as_deref().and_then(logged_command) {
+                Some(command) => entries.push(LoggedCommand { command, offset }),
+                None => skipped += 1,
+            }
+        }
+        if entries.is_empty() {
+            bail!("No commands found; expected MONITOR output or one command per line");
+        }
+
+        Ok(Self {
+            path: PathBuf::new(),
+            entries,
+            skipped,
+            speed,
+        })
+    }
+
+    /// The entry command number `seq` replays, starting over after the last one
+    pub fn entry(&self, seq: u64) -> &LoggedCommand {
+        &self.entries[(seq % self.entries.len() as u64) as usize]
+    }
+
+    /// How long after the start of the run command number `seq` is due, when
+    /// the logged timing is kept
+    pub fn due(&self, seq: u64) -> Option<Duration> {
+        let span = self.entries[self.entries.len() - 1].offset;
+        let rounds = seq / self.entries.len() as u64;
+        Some(scaled(self.entry(seq).offset, span, rounds, self.speed?))
+    }
+}
+
+fn check_speed(speed: Option<f64>) -> Result<()> {
+    if let Some(speed) = speed.filter(|speed| !(*speed > 0.0 && speed.is_finite())) {
+        bail!("The replay speed must be a positive factor, got {}", speed);
+    }
+    Ok(())
+}
+
+/// The logged `offset` of an entry replayed after `rounds` passes over a log
+/// spanning `span`, sped up by `speed`
+fn scaled(offset: Duration, span: Duration, rounds: u64, speed: f64) -> Duration {
+    (span * rounds as u32 + offset).div_f64(speed)
+}
+
+/// The time (in Unix seconds) and arguments of a MONITOR line such as
+/// `1700000000.123456 [0 127.0.0.1:5000] "SET" "k" "v"`
+fn parse_monitor_line(line: &str) -> Option<(f64, Vec<Vec<u8>>)> {
+    let (time, rest) = line.split_once(' ')?;
+    let time: f64 = time.parse().ok().filter(|time: &f64| time.is_finite())?;
+    // The client may be an IPv6 address in brackets, but the arguments are
+    // always quoted
+    let (_, args) = rest.strip_prefix('[')?.split_once("] \"")?;
+    let args = flashkv::split_args(&format!("\"{}", args)).ok()?;
+    Some((time, args))
+}
+
+/// The command of logged arguments, quoted again so that binary values and
+/// spaces survive parsing
+fn logged_command(args: &[Vec<u8>]) -> Option<FlashKVCommand> {
+    let name = args.first()?;
+    if SKIPPED_COMMANDS
+        .iter()
+        .any(|skip| name.eq_ignore_ascii_case(skip.as_bytes()))
+    {
+        return None;
+    }
+    let line = args
+        .iter()
+        .map(|arg| flashkv::quote_inline(arg))
+        .collect::<Vec<_>>()
+        .join(&b' ');
+    String::from_utf8(line).ok()?.parse::<FlashKVCommand>().ok()
+}
+
+/// The time (in Unix seconds), method and path of one log line
+fn parse_line(line: &str) -> Option<(i64, HttpMethod, String)> {
+    let (_, rest) = line.split_once('[')?;
+    let (time, rest) = rest.split_once(']')?;
+    let (_, rest) = rest.split_once('"')?;
+    let (request, _) = rest.split_once('"')?;
+
+    let mut parts = request.split(' ');
+    let method = parts.next()?.parse::<HttpMethod>().ok()?;
+    let path = parts.next().filter(|path| path.starts_with('/'))?;
+    Some((parse_time(time)?, method, path.to_string()))
+}
+
+/// Parse a log timestamp such as `10/Oct/2024:13:55:36 +0200` into Unix seconds
+fn parse_time(s: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (datetime, zone) = s.split_once(' ')?;
+    let mut fields = datetime.split(['/', ':']);
+    let day: i64 = fields.next()?.parse().ok()?;
+    let month = fields.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let year: i64 = fields.next()?.parse().ok()?;
+    let hour: i64 = fields.next()?.parse().ok()?;
+    let minute: i64 = fields.next()?.parse().ok()?;
+    let second: i64 = fields.next()?.parse().ok()?;
+
+    let sign = match zone.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let zone_hours: i64 = zone.get(1..3)?.parse().ok()?;
+    let zone_minutes: i64 = zone.get(3..5)?.parse().ok()?;
+
+    // Days since the Unix epoch of a proleptic Gregorian date
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(
+        days * 86_400 + hour * 3600 + minute * 60 + second
+            - sign * (zone_hours * 3600 + zone_minutes * 60),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("01/Jan/1970:00:00:00 +0000"), Some(0));
+        assert_eq!(parse_time("10/Oct/2000:13:55:36 -0700"), Some(971_211_336));
+        assert_eq!(
+            parse_time("29/Feb/2024:01:00:00 +0100"),
+            Some(1_709_164_800)
+        );
+        assert_eq!(parse_time("10/Foo/2000:13:55:36 -0700"), None);
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let log = AccessLog::parse(
+            r#"10.0.0.1 - - [10/Oct/2024:13:55:36 +0000] "GET /items?page=2 HTTP/1.1" 200 512 "-" "curl/8.0"
+10.0.0.2 - bob [10/Oct/2024:13:55:36 +0000] "POST /orders HTTP/1.1" 201 64
+10.0.0.3 - - [10/Oct/2024:13:55:38 +0000] "-" 400 0
+10.0.0.3 - - [10/Oct/2024:13:55:40 +0000] "DELETE /orders/7 HTTP/2.0" 204 0
+not a log line
+"#,
+            Some(2.0),
+        )
+        .unwrap();
+        assert_eq!(log.entries.len(), 3);
+        assert_eq!(log.skipped, 2);
+        assert_eq!(log.entries[0].path, "/items?page=2");
+        assert_eq!(log.entries[1].method, HttpMethod::POST);
+        assert_eq!(log.entries[1].offset, Duration::from_millis(500));
+        assert_eq!(log.entries[2].offset, Duration::from_secs(4));
+
+        assert_eq!(log.entry(4).path, "/orders");
+        assert_eq!(log.due(2), Some(Duration::from_secs(2)));
+        assert_eq!(log.due(4), Some(Duration::from_millis(2250)));
+
+        assert!(AccessLog::parse("nothing here", None).is_err());
+        assert!(AccessLog::parse("", Some(0.0)).is_err());
+    }
+
+    #[test]
+    fn test_parse_command_log() {
+        let log = CommandLog::parse(
+            r#"OK
+1700000000.000000 [0 10.0.0.1:5000] "AUTH" "secret"
+1700000000.000000 [0 10.0.0.1:5000] "SET" "user:1" "two words\x00"
+1700000000.250000 [0 [::1]:5001] "GET" "user:1"
+1700000001.000000 [0 lua] "INCR" "visits"
+"#,
+            Some(2.0),
+        )
+        .unwrap();
+        assert_eq!(log.entries.len(), 3);
+        assert_eq!(log.skipped, 2);
+        assert_eq!(
+            log.entries[0].command,
+            FlashKVCommand::Set {
+                key: "user:1".to_string(),
+                value: b"two words\0".to_vec(),
+            }
+        );
+        assert_eq!(log.entries[1].offset, Duration::from_millis(250));
+        assert_eq!(log.entry(5).command.display_name(), "INCR");
+        assert_eq!(log.due(1), Some(Duration::from_millis(125)));
+        assert_eq!(log.due(4), Some(Duration::from_millis(625)));
+
+        // A plain list, one command per line and without timing
+        let log =
+            CommandLog::parse("# warm-up\nGET user:1\nSELECT 2\nHGET h \"a b\"\n", None).unwrap();
+        assert_eq!(log.entries.len(), 2);
+        assert_eq!(log.skipped, 1);
+        assert_eq!(log.due(1), None);
+        assert!(CommandLog::parse("GET user:1", Some(1.0)).is_err());
+        assert!(CommandLog::parse("AUTH secret", None).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Run reports and where they are delivered
+//!
+//! The [`Report`] is the machine-readable outcome of a run. It is printed in
+//! quiet mode and can also be written to a JSON file, rendered as HTML or
+//! posted to a webhook, as selected through [`Outputs`].
+
+use crate::baseline::MetricDelta;
+use crate::output;
+use crate::protocols::LoadTestStats;
+use crate::thresholds::ThresholdResult;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Machine-readable outcome of a run
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub target: &'a str,
+    pub protocol: &'a str,
+    #[serde(flatten)]
+    pub stats: &'a LoadTestStats,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub thresholds: &'a [ThresholdResult],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub baseline: &'a [MetricDelta],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub hints: &'a [String],
+}
+
+impl Report<'_> {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize results")
+    }
+
+    /// Write the report as JSON to a file, zstd-compressed if `compress` is
+    /// set, returning the path written
+    pub fn write_json(&self, path: &Path, compress: bool) -> Result<PathBuf> {
+        output::write_file(path, compress, self.to_json()?)
+            .with_context(|| format!("Failed to write JSON results to {}", path.display()))
+    }
+}
+
+/// How the results of a run are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// The boxed summary, for a terminal
+    Table,
+    /// The [`Report`] as JSON
+    Json,
+    /// A summary laid out like wrk's, for scripts that scrape it
+    Wrk,
+}
+
+impl OutputFormat {
+    /// The names `--output` accepts
+    pub const NAMES: [&'static str; 3] = ["table", "json", "wrk"];
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "wrk" => Ok(OutputFormat::Wrk),
+            _ => Err(format!(
+                "Invalid output format '{}': expected table, json or wrk",
+                s
+            )),
+        }
+    }
+}
+
+/// Where results go in addition to the terminal
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outputs {
+    /// JSON results file
+    pub json: Option<PathBuf>,
+    /// Self-contained HTML report
+    pub html: Option<PathBuf>,
+    /// URL the JSON results are POSTed to
+    pub webhook: Option<String>,
+    /// Record the run in the history database
+    pub history: bool,
+}
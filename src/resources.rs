@@ -0,0 +1,314 @@
+//! The load generator's own resource usage during a run
+//!
+//! A client that runs out of CPU sends its requests late and measures its own
+//! scheduling delays as latency, so its numbers say more about the machine it
+//! ran on than about the server. The process's CPU time, memory and open
+//! sockets are sampled while a run is in progress so the report can tell.
+//! They are read from `/proc`, so only Linux runs report them.
+//!
+//! Before a run, the open file limit is checked against its concurrency, so a
+//! low `ulimit -n` fails the run up front rather than turning into thousands
+//! of "too many open files" errors halfway through.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often usage is sampled during a run
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// CPU use of the busiest interval at or above this percentage of every core
+/// marks the client as saturated
+pub const SATURATED_CPU_PERCENT: f64 = 90.0;
+
+/// File descriptors a run needs besides one per concurrent request: stdio,
+/// the runtime, the history database, data and script files
+const FD_HEADROOM: u64 = 64;
+
+/// Make sure the process may open enough files for `concurrency` requests at
+/// once, raising the soft limit up to the hard limit if it is too low
+#[cfg(unix)]
+pub fn ensure_fd_limit(concurrency: u64) -> Result<()> {
+    use rustix::process::{getrlimit, setrlimit, Resource};
+
+    let needed = concurrency + FD_HEADROOM;
+    let mut limit = getrlimit(Resource::Nofile);
+    // No value means unlimited
+    let soft = limit.current.unwrap_or(u64::MAX);
+    if soft >= needed {
+        return Ok(());
+    }
+    let advice = format!(
+        "{} concurrent requests need about {} open files, but the limit is {}",
+        concurrency, needed, soft
+    );
+    match limit.maximum {
+        Some(hard) if hard < needed => anyhow::bail!(
+            "{} (hard limit {}): raise it with `ulimit -n {}` or lower --concurrency",
+            advice,
+            hard,
+            needed
+        ),
+        _ => {
+            limit.current = Some(needed);
+            setrlimit(Resource::Nofile, limit).map_err(|e| {
+                anyhow::anyhow!(
+                    "{} and couldn't be raised ({}): raise it with `ulimit -n {}` or lower --concurrency",
+                    advice,
+                    e,
+                    needed
+                )
+            })
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn ensure_fd_limit(_concurrency: u64) -> Result<()> {
+    Ok(())
+}
+
+/// The load generator's resource usage over a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientStats {
+    /// CPU cores available to the process
+    pub cores: u64,
+    /// Average CPU use over the run, as a percentage of one core
+    pub avg_cpu_percent: f64,
+    /// CPU use of the busiest sampling interval, as a percentage of one core
+    pub peak_cpu_percent: f64,
+    /// Largest resident memory of the process, in bytes
+    pub peak_memory_bytes: u64,
+    /// Most sockets the process had open at once
+    pub peak_sockets: u64,
+    /// Whether the busiest interval used nearly every core, so the client
+    /// rather than the server may have set the pace
+    pub saturated: bool,
+}
+
+impl ClientStats {
+    /// CPU use of the busiest interval as a percentage of every core
+    pub fn peak_cpu_share(&self) -> f64 {
+        self.peak_cpu_percent / self.cores.max(1) as f64
+    }
+}
+
+/// One reading of the process's usage
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    at: Instant,
+    cpu_secs: f64,
+    memory_bytes: u64,
+    sockets: u64,
+}
+
+/// The peaks of the readings so far
+#[derive(Debug, Default)]
+struct Peaks {
+    /// The reading the next interval's CPU use is measured from
+    last: Option<Usage>,
+    cpu_percent: f64,
+    memory_bytes: u64,
+    sockets: u64,
+}
+
+impl Peaks {
+    fn add(&mut self, usage: Usage) {
+        self.memory_bytes = self.memory_bytes.max(usage.memory_bytes);
+        self.sockets = self.sockets.max(usage.sockets);
+        let Some(last) = self.last else {
+            self.last = Some(usage);
+            return;
+        };
+        // CPU time only moves in whole clock ticks, so over a short interval
+        // one tick more or less would make a large difference
+        let secs = (usage.at - last.at).as_secs_f64();
+        if secs >= SAMPLE_INTERVAL.as_secs_f64() / 2.0 {
+            let cpu_percent = (usage.cpu_secs - last.cpu_secs) / secs * 100.0;
+            self.cpu_percent = self.cpu_percent.max(cpu_percent);
+            self.last = Some(usage);
+        }
+    }
+}
+
+/// Samples the process's usage in the background until it is finished
+pub struct Sampler {
+    first: Option<Usage>,
+    peaks: Arc<Mutex<Peaks>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    pub fn start() -> Self {
+        let first = read_usage();
+        let peaks = Arc::new(Mutex::new(Peaks::default()));
+        let task = first.map(|usage| {
+            peaks.lock().unwrap().add(usage);
+            let peaks = Arc::clone(&peaks);
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(SAMPLE_INTERVAL);
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    match read_usage() {
+                        Some(usage) => peaks.lock().unwrap().add(usage),
+                        None => break,
+                    }
+                }
+            })
+        });
+        Self { first, peaks, task }
+    }
+
+    /// Stop sampling and summarize, `None` where usage can't be read
+    pub fn finish(self) -> Option<ClientStats> {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+        let first = self.first?;
+        let last = read_usage()?;
+        let mut peaks = self.peaks.lock().unwrap();
+        peaks.add(last);
+
+        let secs = (last.at - first.at).as_secs_f64();
+        let avg_cpu_percent = if secs > 0.0 {
+            (last.cpu_secs - first.cpu_secs) / secs * 100.0
+        } else {
+            0.0
+        };
+        // Runs shorter than an interval only have their average
+        let peak_cpu_percent = peaks.cpu_percent.max(avg_cpu_percent);
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get() as u64);
+        Some(ClientStats {
+            cores,
+            avg_cpu_percent,
+            peak_cpu_percent,
+            peak_memory_bytes: peaks.memory_bytes,
+            peak_sockets: peaks.sockets,
+            saturated: peak_cpu_percent >= SATURATED_CPU_PERCENT * cores as f64,
+        })
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_usage() -> Option<Usage> {
+    let at = Instant::now();
+    let ticks = cpu_ticks(&std::fs::read_to_string("/proc/self/stat").ok()?)?;
+    let memory_bytes = resident_bytes(&std::fs::read_to_string("/proc/self/status").ok()?)?;
+    let sockets = std::fs::read_dir("/proc/self/fd")
+        .ok()?
+        .filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok())
+        .filter(|target| target.to_string_lossy().starts_with("socket:"))
+        .count() as u64;
+    Some(Usage {
+        at,
+        cpu_secs: ticks as f64 / CLOCK_TICKS,
+        memory_bytes,
+        sockets,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_usage() -> Option<Usage> {
+    None
+}
+
+/// Clock ticks per second of the CPU times in `/proc`, which is 100 on every
+/// mainstream architecture
+#[cfg(target_os = "linux")]
+const CLOCK_TICKS: f64 = 100.0;
+
+/// User plus system CPU time in `/proc/<pid>/stat`, in clock ticks
+#[cfg(target_os = "linux")]
+fn cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name in parentheses may contain spaces, the fields after it
+    // can't; utime and stime are the 14th and 15th fields of the line
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Resident memory in `/proc/<pid>/status`, in bytes
+#[cfg(target_os = "linux")]
+fn resident_bytes(status: &str) -> Option<u64> {
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim();
+    Some(kb.parse::<u64>().ok()? * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peaks() {
+        let start = Instant::now();
+        let usage = |ms, cpu_secs, sockets| Usage {
+            at: start + Duration::from_millis(ms),
+            cpu_secs,
+            memory_bytes: 1024,
+            sockets,
+        };
+        let mut peaks = Peaks::default();
+        peaks.add(usage(0, 0.0, 2));
+        peaks.add(usage(500, 0.4, 50));
+        // Too short after the last reading to measure the CPU over
+        peaks.add(usage(510, 0.43, 10));
+        peaks.add(usage(1000, 0.6, 10));
+        assert!((peaks.cpu_percent - 80.0).abs() < 1e-9);
+        assert_eq!(peaks.sockets, 50);
+        assert_eq!(peaks.memory_bytes, 1024);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_proc() {
+        let stat = "4242 (tokio runtime) S 1 4242 4242 0 -1 4194560 2370 0 0 0 125 31 0 0 20 0 9";
+        assert_eq!(cpu_ticks(stat), Some(156));
+        let status = "Name:\trustyload\nVmPeak:\t  200000 kB\nVmRSS:\t   15360 kB\nThreads:\t9\n";
+        assert_eq!(resident_bytes(status), Some(15360 * 1024));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_fd_limit() {
+        use rustix::process::{getrlimit, Resource};
+
+        ensure_fd_limit(10).unwrap();
+        let limit = getrlimit(Resource::Nofile);
+        assert!(limit.current.unwrap_or(u64::MAX) >= 10 + FD_HEADROOM);
+
+        if let Some(hard) = limit.maximum {
+            let error = ensure_fd_limit(hard).unwrap_err().to_string();
+            assert!(error.contains("ulimit -n"), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sampler() {
+        let stats = Sampler::start().finish();
+        if cfg!(target_os = "linux") {
+            let stats = stats.unwrap();
+            assert!(stats.cores >= 1);
+            assert!(stats.peak_memory_bytes > 0);
+        } else {
+            assert!(stats.is_none());
+        }
+    }
+}
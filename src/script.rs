@@ -0,0 +1,533 @@
+//! Per-iteration request logic written in Rhai
+//!
+//! With `--script flow.rhai` every iteration runs the script instead of
+//! sending the configured request. Scripts see `seq`, `worker_id` and
+//! `iteration` as constants and can call:
+//!
+//! - `request(method, url)` or `request(method, url, #{ headers: #{..}, body: "..", name: ".." })`:
+//!   send a request (a URL starting with `/` is appended to `--url`) and get
+//!   back `#{ status, ok, body, headers, duration, error }`; `name` groups the
+//!   request in the per-step stats
+//! - `counter(name)`, `counter(name, by)`, `gauge(name, value)` and
+//!   `timer(name, ms)`: record custom metrics
+//! - `sleep(ms)`: pause, e.g. for think time between requests
+//!
+//! Requests are recorded like any other, and a script that throws ends its
+//! iteration and is counted in the `script_errors` metric.
+//!
+//! With `--hooks hooks.rhai` the functions a script defines are called around
+//! the run instead, see [`ScriptHooks`].
+
+use crate::hooks::{HookRequest, Hooks};
+use crate::metrics::CustomMetrics;
+use crate::protocols::{LoadTestConfig, LoadTestStats, RequestResult};
+use anyhow::{anyhow, bail, Context, Result};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Counter of iterations that ended with a script error
+pub const SCRIPT_ERRORS: &str = "script_errors";
+
+/// A script file, checked for syntax errors when loaded
+#[derive(Clone)]
+pub struct Script {
+    pub path: PathBuf,
+    ast: AST,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").field("path", &self.path).finish()
+    }
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+        let ast = Engine::new()
+            .compile(&source)
+            .map_err(|e| anyhow!("{}", e))
+            .with_context(|| format!("In script {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ast,
+        })
+    }
+}
+
+/// A request a script asked for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    /// Groups the request in the per-step stats
+    pub name: Option<String>,
+}
+
+/// What a script gets back for a request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptResponse {
+    /// 0 when no response arrived
+    pub status: u16,
+    /// Whether the request counted as a success
+    pub ok: bool,
+    pub body: String,
+    /// Header names in lowercase
+    pub headers: Vec<(String, String)>,
+    /// Milliseconds until the body was read
+    pub duration: u128,
+    pub error: Option<String>,
+}
+
+/// Sends a script's request and waits for the response, blocking the thread
+pub type Sender = dyn Fn(ScriptRequest) -> Result<ScriptResponse, String> + Send + Sync;
+
+/// A script ready to run iterations, with its functions bound to one run
+pub struct ScriptRunner {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptRunner {
+    pub fn new(script: &Script, sender: Arc<Sender>, metrics: CustomMetrics) -> Self {
+        let mut engine = new_engine(Arc::new(Mutex::new(metrics)));
+
+        let send = Arc::clone(&sender);
+        engine.register_fn(
+            "request",
+            move |method: &str, url: &str| -> Result<Map, Box<EvalAltResult>> {
+                let request = ScriptRequest {
+                    method: method.to_string(),
+                    url: url.to_string(),
+                    ..Default::default()
+                };
+                send(request).map(response_map).map_err(Into::into)
+            },
+        );
+        let send = sender;
+        engine.register_fn(
+            "request",
+            move |method: &str, url: &str, options: Map| -> Result<Map, Box<EvalAltResult>> {
+                let request = request_with_options(method, url, options)?;
+                send(request).map(response_map).map_err(Into::into)
+            },
+        );
+
+        engine.register_fn("sleep", |ms: INT| {
+            std::thread::sleep(Duration::from_millis(ms.max(0) as u64))
+        });
+
+        Self {
+            engine,
+            ast: script.ast.clone(),
+        }
+    }
+
+    /// Run the script once, blocking the thread until it finishes
+    pub fn run_iteration(&self, seq: u64, worker_id: u64, iteration: u64) -> Result<(), String> {
+        let mut scope = Scope::new();
+        scope.push_constant("seq", seq as INT);
+        scope.push_constant("worker_id", worker_id as INT);
+        scope.push_constant("iteration", iteration as INT);
+        self.engine
+            .run_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// An engine with `counter`, `gauge` and `timer` recording into whichever
+/// registry `metrics` holds when they are called, and `print` and `debug`
+/// writing to stderr so they don't mix with results on stdout
+fn new_engine(metrics: Arc<Mutex<CustomMetrics>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| eprintln!("{}", text));
+    engine.on_debug(|text, _, _| eprintln!("{}", text));
+
+    let m = Arc::clone(&metrics);
+    engine.register_fn("counter", move |name: &str| {
+        m.lock().unwrap().increment(name, 1)
+    });
+    let m = Arc::clone(&metrics);
+    engine.register_fn("counter", move |name: &str, by: INT| {
+        m.lock().unwrap().increment(name, by.max(0) as u64)
+    });
+    let m = Arc::clone(&metrics);
+    engine.register_fn("gauge", move |name: &str, value: f64| {
+        m.lock().unwrap().gauge(name, value)
+    });
+    let m = Arc::clone(&metrics);
+    engine.register_fn("gauge", move |name: &str, value: INT| {
+        m.lock().unwrap().gauge(name, value as f64)
+    });
+    let m = Arc::clone(&metrics);
+    engine.register_fn("timer", move |name: &str, ms: f64| {
+        m.lock().unwrap().time(name, ms)
+    });
+    let m = metrics;
+    engine.register_fn("timer", move |name: &str, ms: INT| {
+        m.lock().unwrap().time(name, ms as f64)
+    });
+    engine
+}
+
+/// The functions `--hooks` looks for, with their number of parameters
+const HOOKS: [(&str, usize); 4] = [
+    ("on_start", 0),
+    ("before_request", 1),
+    ("after_response", 1),
+    ("on_finish", 1),
+];
+
+/// Hooks defined as functions of a script:
+///
+/// - `on_start()`: before the first request
+/// - `before_request(request)`: gets `#{ seq, method, url, headers }` and may
+///   return it changed, e.g. with an extra header; throwing fails the request
+/// - `after_response(result)`: gets `#{ status, ok, duration, error, method, step, tag }`
+/// - `on_finish(stats)`: gets the stats as in the JSON output
+///
+/// Hooks can record custom metrics with `counter`, `gauge` and `timer`, and
+/// a hook that throws (other than `before_request`) is counted in the
+/// `script_errors` metric.
+pub struct ScriptHooks {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    /// Hooks the script defines
+    defined: Vec<&'static str>,
+    metrics: Arc<Mutex<CustomMetrics>>,
+    error_shown: AtomicBool,
+}
+
+impl ScriptHooks {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::new(Script::load(path)?)
+    }
+
+    fn new(script: Script) -> Result<Self> {
+        let defined: Vec<_> = HOOKS
+            .iter()
+            .filter(|(name, params)| {
+                script
+                    .ast
+                    .iter_functions()
+                    .any(|f| f.name == *name && f.params.len() == *params)
+            })
+            .map(|(name, _)| *name)
+            .collect();
+        if defined.is_empty() {
+            bail!(
+                "Hooks script {} defines none of on_start(), before_request(request), after_response(result) or on_finish(stats)",
+                script.path.display()
+            );
+        }
+
+        let metrics = Arc::new(Mutex::new(CustomMetrics::default()));
+        Ok(Self {
+            path: script.path,
+            engine: new_engine(Arc::clone(&metrics)),
+            ast: script.ast,
+            defined,
+            metrics,
+            error_shown: AtomicBool::new(false),
+        })
+    }
+
+    /// Call a hook the script defines, without running its top-level code
+    fn call(&self, name: &str, args: impl FuncArgs) -> Option<Result<Dynamic, String>> {
+        if !self.defined.contains(&name) {
+            return None;
+        }
+        let options = CallFnOptions::new().eval_ast(false);
+        Some(
+            self.engine
+                .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, args)
+                .map_err(|e| e.to_string()),
+        )
+    }
+
+    /// Call a hook whose result doesn't matter, counting errors
+    fn notify(&self, name: &str, args: impl FuncArgs) {
+        if let Some(Err(error)) = self.call(name, args) {
+            self.metrics.lock().unwrap().increment(SCRIPT_ERRORS, 1);
+            if !self.error_shown.swap(true, Ordering::Relaxed) {
+                eprintln!("Warning: {} hook failed: {}", name, error);
+            }
+        }
+    }
+}
+
+impl Hooks for ScriptHooks {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn on_start(&self, _config: &LoadTestConfig, metrics: &CustomMetrics) {
+        *self.metrics.lock().unwrap() = metrics.clone();
+        self.notify("on_start", ());
+    }
+
+    fn before_request(&self, request: &mut HookRequest) -> Result<(), String> {
+        let headers: Map = request
+            .headers
+            .iter()
+            .map(|(name, value)| (name.into(), Dynamic::from(value.clone())))
+            .collect();
+        let mut map = Map::new();
+        map.insert("seq".into(), Dynamic::from(request.seq as INT));
+        map.insert("method".into(), Dynamic::from(request.method.clone()));
+        map.insert("url".into(), Dynamic::from(request.url.clone()));
+        map.insert("headers".into(), Dynamic::from(headers));
+
+        let Some(returned) = self.call("before_request", (map,)).transpose()? else {
+            return Ok(());
+        };
+        if returned.is_unit() {
+            return Ok(());
+        }
+        let map = returned
+            .try_cast::<Map>()
+            .ok_or("before_request must return the request map or nothing")?;
+        if let Some(method) = map.get("method") {
+            request.method = method.to_string();
+        }
+        if let Some(url) = map.get("url") {
+            request.url = url.to_string();
+        }
+        if let Some(headers) = map.get("headers") {
+            let headers = headers
+                .clone()
+                .try_cast::<Map>()
+                .ok_or("request headers must be a map")?;
+            request.headers = headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn after_response(&self, result: &RequestResult) {
+        let text =
+            |value: Option<&str>| value.map_or(Dynamic::UNIT, |v| Dynamic::from(v.to_string()));
+        let mut map = Map::new();
+        map.insert("status".into(), Dynamic::from(result.status as INT));
+        map.insert("ok".into(), Dynamic::from(result.success));
+        map.insert("duration".into(), Dynamic::from(result.duration as INT));
+        map.insert(
+            "error".into(),
+            text(result.error.as_ref().map(ToString::to_string).as_deref()),
+        );
+        map.insert("method".into(), text(result.operation));
+        map.insert("step".into(), text(result.step.as_deref()));
+        map.insert("tag".into(), text(result.tag.as_deref()));
+        self.notify("after_response", (map,));
+    }
+
+    fn on_finish(&self, stats: &LoadTestStats) {
+        let stats = serde_json::to_string(stats)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                self.engine
+                    .parse_json(json, true)
+                    .map_err(|e| e.to_string())
+            });
+        match stats {
+            Ok(stats) => self.notify("on_finish", (stats,)),
+            Err(error) => eprintln!("Warning: on_finish hook failed: {}", error),
+        }
+    }
+}
+
+fn request_with_options(
+    method: &str,
+    url: &str,
+    options: Map,
+) -> Result<ScriptRequest, Box<EvalAltResult>> {
+    let mut request = ScriptRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        ..Default::default()
+    };
+    for (key, value) in options {
+        match key.as_str() {
+            "headers" => {
+                let headers = value
+                    .try_cast::<Map>()
+                    .ok_or("request headers must be a map, e.g. #{ \"Accept\": \"text/plain\" }")?;
+                request.headers = headers
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect();
+            }
+            "body" => request.body = Some(value.to_string()),
+            "name" => request.name = Some(value.to_string()),
+            other => return Err(format!("Unknown request option '{}'", other).into()),
+        }
+    }
+    Ok(request)
+}
+
+fn response_map(response: ScriptResponse) -> Map {
+    let headers: Map = response
+        .headers
+        .into_iter()
+        .map(|(name, value)| (name.into(), Dynamic::from(value)))
+        .collect();
+    let mut map = Map::new();
+    map.insert("status".into(), Dynamic::from(response.status as INT));
+    map.insert("ok".into(), Dynamic::from(response.ok));
+    map.insert("body".into(), Dynamic::from(response.body));
+    map.insert("headers".into(), Dynamic::from(headers));
+    map.insert("duration".into(), Dynamic::from(response.duration as INT));
+    map.insert(
+        "error".into(),
+        response.error.map_or(Dynamic::UNIT, Dynamic::from),
+    );
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::CustomMetricSummary;
+    use std::sync::Mutex;
+
+    fn script(source: &str) -> Script {
+        Script {
+            path: PathBuf::from("test.rhai"),
+            ast: Engine::new().compile(source).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_run_iteration() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&sent);
+        let sender: Arc<Sender> = Arc::new(move |request: ScriptRequest| {
+            log.lock().unwrap().push(request.clone());
+            Ok(ScriptResponse {
+                status: 201,
+                ok: true,
+                body: r#"{"id": 7}"#.to_string(),
+                headers: vec![("location".to_string(), "/orders/7".to_string())],
+                duration: 12,
+                error: None,
+            })
+        });
+        let metrics = CustomMetrics::default();
+        let runner = ScriptRunner::new(
+            &script(
+                r#"
+                let created = request("POST", "/orders", #{
+                    headers: #{ "Content-Type": "application/json" },
+                    body: `{"worker": ${worker_id}}`,
+                    name: "create",
+                });
+                if created.status != 201 { throw "not created"; }
+                let id = parse_json(created.body).id;
+                request("GET", created.headers.location + "?seq=" + seq);
+                counter("orders");
+                timer("order_time", created.duration);
+                "#,
+            ),
+            sender,
+            metrics.clone(),
+        );
+
+        runner.run_iteration(5, 2, 0).unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].name.as_deref(), Some("create"));
+        assert_eq!(sent[0].body.as_deref(), Some(r#"{"worker": 2}"#));
+        assert_eq!(
+            sent[0].headers,
+            vec![("Content-Type".to_string(), "application/json".to_string())]
+        );
+        assert_eq!(sent[1].url, "/orders/7?seq=5");
+        assert_eq!(
+            metrics.summarize()[1],
+            CustomMetricSummary::Counter {
+                name: "orders".to_string(),
+                total: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let sender: Arc<Sender> = Arc::new(|_| Err("connection refused".to_string()));
+        let runner = ScriptRunner::new(
+            &script(r#"request("GET", "/");"#),
+            sender,
+            CustomMetrics::default(),
+        );
+        assert!(runner
+            .run_iteration(0, 0, 0)
+            .unwrap_err()
+            .contains("connection refused"));
+
+        let runner = ScriptRunner::new(
+            &script(r#"request("GET", "/", #{ bogus: 1 });"#),
+            Arc::new(|_| Ok(ScriptResponse::default())),
+            CustomMetrics::default(),
+        );
+        assert!(runner.run_iteration(0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_script_hooks() {
+        let hooks = ScriptHooks::new(script(
+            r#"
+            fn on_start() { gauge("started", 1); }
+            fn before_request(request) {
+                request.headers["X-Seq"] = `${request.seq}`;
+                request.method = "POST";
+                request
+            }
+            fn after_response(result) {
+                if !result.ok { counter("failed"); }
+                if result.step == () { throw "no step"; }
+            }
+            fn on_finish(stats) { counter("total", stats.total_requests); }
+            "#,
+        ))
+        .unwrap();
+        let metrics = CustomMetrics::default();
+        hooks.on_start(
+            &LoadTestConfig::new_http("http://x".to_string(), 1, 1),
+            &metrics,
+        );
+
+        let mut request = HookRequest {
+            seq: 3,
+            method: "GET".to_string(),
+            url: "http://x/".to_string(),
+            headers: vec![("accept".to_string(), "*/*".to_string())],
+        };
+        hooks.before_request(&mut request).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.headers.len(), 2);
+        assert!(request
+            .headers
+            .contains(&("X-Seq".to_string(), "3".to_string())));
+
+        hooks.after_response(&RequestResult::default());
+        hooks.on_finish(&crate::protocols::calculate_stats(
+            &[RequestResult::default()],
+            10,
+        ));
+        let summary = metrics.summarize();
+        let names: Vec<&str> = summary.iter().map(CustomMetricSummary::name).collect();
+        assert_eq!(names, ["failed", "script_errors", "started", "total"]);
+
+        assert!(ScriptHooks::new(script("fn helper(x) { x }")).is_err());
+    }
+}
@@ -0,0 +1,748 @@
+//! Running load tests on request over a small HTTP API
+//!
+//! `rustyload serve` accepts tests as config files (the same format as
+//! `--config`) and runs them one at a time in the order they were submitted,
+//! so tests triggered from CI or a portal don't compete for the machine.
+//!
+//! | Request                    | Does                                       |
+//! |----------------------------|--------------------------------------------|
+//! | `POST /tests`              | Queue a test, answering with its status    |
+//! | `GET /tests`               | Every test, newest first                   |
+//! | `GET /tests/{id}`          | Status and progress of a test              |
+//! | `GET /tests/{id}/results`  | The JSON results once the test has ended   |
+//! | `GET /tests/{id}/timeline` | Per-second points of a test's run          |
+//! | `DELETE /tests/{id}`       | Cancel a queued or running test            |
+//! | `GET /`                    | The live [dashboard](crate::dashboard)     |
+//!
+//! Runs started with `--web` serve the same API read-only, for the one test
+//! they run.
+
+use crate::config::TestFile;
+use crate::dashboard::{self, Timeline};
+use crate::env::Env;
+use crate::protocols::{LiveResults, LoadTestConfig};
+use crate::thresholds::{self, Threshold};
+use crate::{hints, history, report};
+use anyhow::{bail, Context, Result};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+/// Largest test config accepted
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Where a submitted test is at
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Queued,
+    Running,
+    Finished,
+    /// The test couldn't run, see [`TestStatus::error`]
+    Failed,
+    /// Cancelled before it finished; results of a running test are kept
+    Cancelled,
+}
+
+/// What `GET /tests/{id}` answers with
+#[derive(Debug, Clone, Serialize)]
+pub struct TestStatus {
+    pub id: u64,
+    pub status: Status,
+    pub target: String,
+    pub protocol: &'static str,
+    /// Requests the test plans to send
+    pub requests: u64,
+    /// Requests completed so far
+    pub completed: u64,
+    /// Time since the test started running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_secs: Option<f64>,
+    /// Whether every threshold of the test passed, once it has results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passed: Option<bool>,
+    /// Id of the run in the history database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A submitted test
+struct Test {
+    status: TestStatus,
+    /// Taken by the runner when the test starts
+    config: Option<(LoadTestConfig, Vec<Threshold>)>,
+    live: LiveResults,
+    /// Started along with the run
+    timeline: Option<Timeline>,
+    started: Option<Instant>,
+    /// The JSON report, once the test has ended
+    results: Option<String>,
+}
+
+impl Test {
+    fn status(&self) -> TestStatus {
+        let mut status = self.status.clone();
+        if status.status == Status::Running {
+            status.completed = self.live.completed();
+            status.elapsed_secs = self.started.map(|s| s.elapsed().as_secs_f64());
+        }
+        status
+    }
+}
+
+/// What a test's run ended with
+#[derive(Debug)]
+pub struct Finished {
+    /// The JSON report
+    pub results: String,
+    pub completed: u64,
+    /// Whether every threshold passed
+    pub passed: bool,
+    pub history_id: Option<i64>,
+}
+
+/// The tests a daemon knows about and the queue of those still to run
+pub struct Server {
+    tests: Mutex<Vec<Test>>,
+    /// None when the server only shows runs started elsewhere
+    queue: Option<mpsc::UnboundedSender<u64>>,
+    /// Bearer token every request must carry, if any
+    token: Option<String>,
+    /// Whether submitted tests may read files on this machine
+    allow_local_files: bool,
+    /// Whether submitted tests may read this machine's environment variables
+    allow_env: bool,
+}
+
+impl Server {
+    /// Start the runner for a new daemon, recording runs in the history
+    /// database at `history` when given
+    ///
+    /// Unless `allow_local_files` is set, tests that read files on this
+    /// machine (a `data` CSV, `@path` headers and form parts, …) are
+    /// rejected, so API clients can't send the server's files to a target.
+    /// Likewise, unless `allow_env` is set their `${VAR}` references and
+    /// credential fallbacks (`AWS_ACCESS_KEY_ID`, `REDISCLI_AUTH`, …) can't
+    /// read the server's environment variables.
+    pub fn start(
+        token: Option<String>,
+        history: Option<PathBuf>,
+        allow_local_files: bool,
+        allow_env: bool,
+    ) -> Arc<Self> {
+        let (queue, mut queued) = mpsc::unbounded_channel();
+        let server = Arc::new(Self {
+            tests: Mutex::new(Vec::new()),
+            queue: Some(queue),
+            token,
+            allow_local_files,
+            allow_env,
+        });
+
+        let runner = server.clone();
+        tokio::spawn(async move {
+            while let Some(id) = queued.recv().await {
+                runner.run(id, history.as_deref()).await;
+            }
+        });
+        server
+    }
+
+    /// A read-only server for runs registered with [`Server::watch`]
+    pub fn read_only() -> Arc<Self> {
+        Arc::new(Self {
+            tests: Mutex::new(Vec::new()),
+            queue: None,
+            token: None,
+            allow_local_files: false,
+            allow_env: false,
+        })
+    }
+
+    /// Show a run started elsewhere, returning its id for [`Server::finish`]
+    pub fn watch(&self, config: &LoadTestConfig, live: &LiveResults) -> u64 {
+        let mut tests = self.tests.lock().unwrap();
+        let test = add(&mut tests, config, Status::Running);
+        test.live = live.clone();
+        test.timeline = Some(Timeline::start(live));
+        test.started = Some(Instant::now());
+        test.status.id
+    }
+
+    /// Record how a test's run ended
+    pub fn finish(&self, id: u64, outcome: Result<Finished>) {
+        let mut tests = self.tests.lock().unwrap();
+        let Some(test) = tests.iter_mut().find(|t| t.status.id == id) else {
+            return;
+        };
+        let status = &mut test.status;
+        status.elapsed_secs = test.started.map(|s| s.elapsed().as_secs_f64());
+        match outcome {
+            Ok(finished) => {
+                status.status = if test.live.stop_handle().is_stopped() {
+                    Status::Cancelled
+                } else {
+                    Status::Finished
+                };
+                status.completed = finished.completed;
+                status.passed = Some(finished.passed);
+                status.history_id = finished.history_id;
+                test.results = Some(finished.results);
+            }
+            Err(e) => {
+                status.status = Status::Failed;
+                status.completed = test.live.completed();
+                status.error = Some(format!("{:#}", e));
+            }
+        }
+        // Letting go of the results ends the timeline
+        test.live = LiveResults::default();
+    }
+
+    /// Run a queued test to the end, unless it was cancelled first
+    async fn run(&self, id: u64, history_path: Option<&std::path::Path>) {
+        let (config, thresholds, live) = {
+            let mut tests = self.tests.lock().unwrap();
+            let Some(test) = tests.iter_mut().find(|t| t.status.id == id) else {
+                return;
+            };
+            let Some((config, thresholds)) = test.config.take() else {
+                return;
+            };
+            test.status.status = Status::Running;
+            test.timeline = Some(Timeline::start(&test.live));
+            test.started = Some(Instant::now());
+            (config, thresholds, test.live.clone())
+        };
+
+        let outcome = config.run(true, &live).await.and_then(|stats| {
+            let threshold_results = thresholds::evaluate_all(&thresholds, &stats);
+            let hints = hints::analyze(&stats, &config);
+            let target = config.display_target();
+            let json = report::Report {
+                target: &target,
+                protocol: config.protocol.display_name(),
+                stats: &stats,
+                thresholds: &threshold_results,
+                baseline: &[],
+                hints: &hints,
+            }
+            .to_json()?;
+
+            // A broken history database should never fail the run itself
+            let history_id = history_path.and_then(|path| {
+                history::History::open(path)
+                    .and_then(|h| h.record(&config, &stats, None))
+                    .map_err(|e| eprintln!("Warning: {:#}", e))
+                    .ok()
+            });
+            Ok(Finished {
+                results: json,
+                completed: stats.total_requests,
+                passed: threshold_results.iter().all(|r| r.passed),
+                history_id,
+            })
+        });
+        self.finish(id, outcome);
+    }
+
+    /// Queue a test from a config file's content
+    fn submit(
+        &self,
+        queue: &mpsc::UnboundedSender<u64>,
+        content: &str,
+        toml: bool,
+    ) -> Result<TestStatus> {
+        let test: TestFile = if toml {
+            toml::from_str(content).context("Failed to parse the test as TOML")?
+        } else {
+            // JSON is YAML too
+            serde_yaml::from_str(content).context("Failed to parse the test as JSON or YAML")?
+        };
+        let local_files = test.local_files();
+        if !self.allow_local_files && !local_files.is_empty() {
+            bail!(
+                "The test reads files on the server ({}), which needs rustyload serve --allow-local-files",
+                local_files.join(", ")
+            );
+        }
+        let env = if self.allow_env {
+            Env::process()
+        } else {
+            Env::denied()
+        };
+        let (config, thresholds) = test.into_config_with(&env)?;
+
+        let mut tests = self.tests.lock().unwrap();
+        let test = add(&mut tests, &config, Status::Queued);
+        test.config = Some((config, thresholds));
+        let status = test.status();
+        queue
+            .send(status.id)
+            .context("The test runner has stopped")?;
+        Ok(status)
+    }
+
+    /// Cancel a test; a running one stops sending and keeps its results
+    fn cancel(&self, id: u64) -> Option<Result<TestStatus, TestStatus>> {
+        let mut tests = self.tests.lock().unwrap();
+        let test = tests.iter_mut().find(|t| t.status.id == id)?;
+        match test.status.status {
+            Status::Queued => {
+                test.config = None;
+                test.status.status = Status::Cancelled;
+            }
+            Status::Running => test.live.stop_handle().stop(),
+            Status::Finished | Status::Failed | Status::Cancelled => {
+                return Some(Err(test.status()));
+            }
+        }
+        Some(Ok(test.status()))
+    }
+
+    /// Answer one API request
+    async fn handle(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if let Some(token) = &self.token {
+            // Browsers opening the dashboard can't set a header, so the
+            // token may also be given as `?token=`
+            let header = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            let query = request
+                .uri()
+                .query()
+                .and_then(|query| query.split('&').find_map(|p| p.strip_prefix("token=")));
+            if header.or(query) != Some(token) {
+                return error(StatusCode::UNAUTHORIZED, "Missing or wrong bearer token");
+            }
+        }
+
+        let method = request.method().clone();
+        let path = request.uri().path().trim_end_matches('/').to_string();
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+        let id = segments.get(1).map(|id| id.parse::<u64>());
+
+        match (&method, segments.as_slice(), id) {
+            (&Method::GET, [], None) => {
+                let mut response = respond(StatusCode::OK, dashboard::PAGE.to_string());
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+                response
+            }
+            (&Method::POST, ["tests"], None) => {
+                let Some(queue) = &self.queue else {
+                    return read_only();
+                };
+                let toml = request
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.contains("toml"));
+                let body = match Limited::new(request.into_body(), MAX_BODY_BYTES)
+                    .collect()
+                    .await
+                {
+                    Ok(body) => body.to_bytes(),
+                    Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+                };
+                let Ok(content) = std::str::from_utf8(&body) else {
+                    return error(StatusCode::BAD_REQUEST, "The test isn't valid UTF-8");
+                };
+                match self.submit(queue, content, toml) {
+                    Ok(status) => json(StatusCode::ACCEPTED, &status),
+                    Err(e) => error(StatusCode::BAD_REQUEST, &format!("{:#}", e)),
+                }
+            }
+            (&Method::GET, ["tests"], None) => {
+                let tests = self.tests.lock().unwrap();
+                let statuses: Vec<TestStatus> = tests.iter().rev().map(Test::status).collect();
+                json(StatusCode::OK, &statuses)
+            }
+            (_, ["tests", ..], Some(Err(_))) => error(StatusCode::NOT_FOUND, "No such test"),
+            (&Method::GET, ["tests", _], Some(Ok(id))) => {
+                let tests = self.tests.lock().unwrap();
+                match tests.iter().find(|t| t.status.id == id) {
+                    Some(test) => json(StatusCode::OK, &test.status()),
+                    None => error(StatusCode::NOT_FOUND, "No such test"),
+                }
+            }
+            (&Method::GET, ["tests", _, "results"], Some(Ok(id))) => {
+                let tests = self.tests.lock().unwrap();
+                match tests.iter().find(|t| t.status.id == id) {
+                    Some(Test {
+                        results: Some(results),
+                        ..
+                    }) => respond(StatusCode::OK, results.clone()),
+                    Some(test) => {
+                        let message = match (&test.status.status, &test.status.error) {
+                            (_, Some(e)) => format!("Test {} failed: {}", id, e),
+                            (Status::Cancelled, None) => {
+                                format!("Test {} was cancelled before it started", id)
+                            }
+                            _ => format!("Test {} hasn't ended yet", id),
+                        };
+                        error(StatusCode::CONFLICT, &message)
+                    }
+                    None => error(StatusCode::NOT_FOUND, "No such test"),
+                }
+            }
+            (&Method::GET, ["tests", _, "timeline"], Some(Ok(id))) => {
+                let tests = self.tests.lock().unwrap();
+                match tests.iter().find(|t| t.status.id == id) {
+                    Some(test) => {
+                        let points = test.timeline.as_ref().map(Timeline::points);
+                        json(StatusCode::OK, &points.unwrap_or_default())
+                    }
+                    None => error(StatusCode::NOT_FOUND, "No such test"),
+                }
+            }
+            (&Method::DELETE, ["tests", _], Some(Ok(id))) => {
+                if self.queue.is_none() {
+                    return read_only();
+                }
+                match self.cancel(id) {
+                    Some(Ok(status)) => json(StatusCode::OK, &status),
+                    Some(Err(status)) => json(StatusCode::CONFLICT, &status),
+                    None => error(StatusCode::NOT_FOUND, "No such test"),
+                }
+            }
+            (_, [] | ["tests"] | ["tests", _] | ["tests", _, "results" | "timeline"], _) => {
+                error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+            }
+            _ => error(StatusCode::NOT_FOUND, "Not found"),
+        }
+    }
+}
+
+/// Add a test of `config` with the next id
+fn add<'a>(tests: &'a mut Vec<Test>, config: &LoadTestConfig, status: Status) -> &'a mut Test {
+    let id = tests.last().map_or(1, |t| t.status.id + 1);
+    tests.push(Test {
+        status: TestStatus {
+            id,
+            status,
+            target: config.display_target(),
+            protocol: config.protocol.display_name(),
+            requests: config.stages().iter().map(|s| s.requests).sum(),
+            completed: 0,
+            elapsed_secs: None,
+            passed: None,
+            history_id: None,
+            error: None,
+        },
+        config: None,
+        live: LiveResults::default(),
+        timeline: None,
+        started: None,
+        results: None,
+    });
+    tests.last_mut().unwrap()
+}
+
+/// Serve the API on `listener` until the process ends
+pub async fn serve(listener: TcpListener, server: Arc<Server>) -> Result<()> {
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Failed to accept a connection")?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            let service = service_fn(|request| {
+                let server = server.clone();
+                async move { Ok::<_, Infallible>(server.handle(request).await) }
+            });
+            // A client hanging up mid-request only ends its own connection
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+fn respond(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Response<Full<Bytes>> {
+    match serde_json::to_string_pretty(value) {
+        Ok(body) => respond(status, body),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    respond(status, serde_json::json!({ "error": message }).to_string())
+}
+
+/// The answer to changes requested from a `--web` dashboard
+fn read_only() -> Response<Full<Bytes>> {
+    error(
+        StatusCode::METHOD_NOT_ALLOWED,
+        "This dashboard only shows the current run; use rustyload serve to run tests on request",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/tests", listener.local_addr().unwrap());
+        let server = Server::start(Some("secret".to_string()), None, false, false);
+        tokio::spawn(serve(listener, server));
+
+        let client = reqwest::Client::new();
+        let response = client.get(&base).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        // The daemon load tests its own API
+        let test = format!(r#"{{"url": "{}", "requests": 5, "concurrency": 2}}"#, base);
+        let submitted: serde_json::Value = client
+            .post(&base)
+            .bearer_auth("secret")
+            .body(test)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(submitted["id"], 1);
+        assert_eq!(submitted["requests"], 5);
+
+        let mut status = serde_json::Value::Null;
+        for _ in 0..100 {
+            status = client
+                .get(format!("{}/1", base))
+                .bearer_auth("secret")
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            if status["status"] == "finished" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(status["status"], "finished");
+
+        let results: serde_json::Value = client
+            .get(format!("{}/1/results", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(results["total_requests"], 5);
+
+        let timeline: Vec<serde_json::Value> = client
+            .get(format!("{}/1/timeline", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(!timeline.is_empty());
+
+        let page = client
+            .get(base.replace("/tests", "/?token=secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(page.status(), 200);
+        assert!(page.text().await.unwrap().contains("RustyLoad dashboard"));
+
+        let response = client
+            .delete(format!("{}/1", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 409);
+
+        let response = client
+            .post(&base)
+            .bearer_auth("secret")
+            .body("requests: [")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Tests can't read the server's files without --allow-local-files
+        let response = client
+            .post(&base)
+            .bearer_auth("secret")
+            .body(format!(
+                r#"{{"url": "{}", "form": ["file=@/etc/passwd"]}}"#,
+                base
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(response.text().await.unwrap().contains("form"));
+
+        let response = client
+            .post(&base)
+            .bearer_auth("secret")
+            .body(format!(
+                r#"{{"url": "{}", "steps": [{{"headers": {{"X-Key": "@/etc/passwd"}}}}]}}"#,
+                base
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(response.text().await.unwrap().contains("steps headers"));
+
+        let response = client
+            .get(format!("{}/7", base))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[test]
+    fn test_submit_env() {
+        std::env::set_var("RUSTYLOAD_TEST_SERVE_KEY", "s3cret");
+        std::env::set_var("AWS_ACCESS_KEY_ID", "AKIATEST");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        std::env::set_var("REDISCLI_AUTH", "s3cret");
+        let server = |allow_env| Server {
+            tests: Mutex::new(Vec::new()),
+            queue: None,
+            token: None,
+            allow_local_files: false,
+            allow_env,
+        };
+        let (queue, _queued) = mpsc::unbounded_channel();
+        let header =
+            r#"{"url": "http://localhost/", "headers": {"X-Key": "${RUSTYLOAD_TEST_SERVE_KEY}"}}"#;
+        let sse =
+            r#"{"protocol": "sse", "url": "http://localhost/?key=${RUSTYLOAD_TEST_SERVE_KEY}"}"#;
+        let s3 = r#"{"url": "http://localhost:9000", "s3_bucket": "b"}"#;
+        let redis = r#"{"url": "redis://localhost:6379"}"#;
+
+        // Tests can't read the server's environment without --allow-env
+        let denied = server(false);
+        for test in [header, sse] {
+            let error = denied.submit(&queue, test, false).unwrap_err();
+            assert!(format!("{:#}", error).contains("are off"), "{:#}", error);
+        }
+        let error = denied.submit(&queue, s3, false).unwrap_err();
+        assert!(
+            error.to_string().contains("S3 needs credentials"),
+            "{}",
+            error
+        );
+        denied.submit(&queue, redis, false).unwrap();
+        let tests = denied.tests.lock().unwrap();
+        let (config, _) = tests[0].config.as_ref().unwrap();
+        assert_eq!(config.flashkv_config.as_ref().unwrap().password, None);
+        drop(tests);
+
+        let allowed = server(true);
+        for test in [header, s3, redis] {
+            allowed.submit(&queue, test, false).unwrap();
+        }
+        let tests = allowed.tests.lock().unwrap();
+        let config = |index: usize| &tests[index].config.as_ref().unwrap().0;
+        assert_eq!(
+            config(0).http_config.as_ref().unwrap().headers["X-Key"],
+            "s3cret"
+        );
+        assert_eq!(config(1).s3_config.as_ref().unwrap().access_key, "AKIATEST");
+        assert_eq!(
+            config(2)
+                .flashkv_config
+                .as_ref()
+                .unwrap()
+                .password
+                .as_deref(),
+            Some("s3cret")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/tests", listener.local_addr().unwrap());
+        let server = Server::read_only();
+        tokio::spawn(serve(listener, server.clone()));
+
+        let config = LoadTestConfig::new_http(base.clone(), 10, 1);
+        let live = LiveResults::default();
+        let id = server.watch(&config, &live);
+
+        let client = reqwest::Client::new();
+        let tests: serde_json::Value = client
+            .get(&base)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(tests[0]["status"], "running");
+        let response = client
+            .delete(format!("{}/{}", base, id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 405);
+        let response = client.post(&base).body("{}").send().await.unwrap();
+        assert_eq!(response.status(), 405);
+
+        let finished = Finished {
+            results: "{}".to_string(),
+            completed: 10,
+            passed: false,
+            history_id: None,
+        };
+        server.finish(id, Ok(finished));
+        let status: serde_json::Value = client
+            .get(format!("{}/{}", base, id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["status"], "finished");
+        assert_eq!(status["passed"], false);
+    }
+}
@@ -0,0 +1,42 @@
+//! Byte sizes
+//!
+//! Object sizes, value sizes and generated form files are all given as a
+//! number with an optional binary unit, parsed the same way wherever they
+//! appear on the command line or in a config file.
+
+/// Parse a byte size such as `512`, `64KiB`, `4k` or `1.5MB` (units are
+/// binary: K, M and G mean 1024, 1024² and 1024³)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Invalid size: {}", s))?;
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1u64,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "Invalid size unit in {} (use B, KiB, MiB or GiB)",
+                s
+            ))
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64KiB"), Ok(65536));
+        assert_eq!(parse_size("1.5m"), Ok(1572864));
+        assert!(parse_size("10 parsecs").is_err());
+    }
+}
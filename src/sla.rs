@@ -0,0 +1,156 @@
+//! Apdex score and the share of requests under latency targets
+//!
+//! SRE teams often report against a latency target rather than percentiles:
+//! the share of requests answered within 100 ms, 250 ms and so on, and the
+//! Apdex score for a target T, which counts requests within T as satisfied,
+//! within 4T as tolerating and the rest (including failures) as frustrated.
+//! Both are computed from the latency histogram of the successful requests.
+
+use crate::protocols::LoadTestStats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Latency targets the share of requests under is always reported for, in ms
+pub const TARGETS_MS: [u128; 4] = [100, 250, 500, 1000];
+
+/// Requests within this many times the Apdex threshold are tolerating
+const TOLERATING_FACTOR: u128 = 4;
+
+/// How the run measured up against latency targets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlaStats {
+    /// Present when an Apdex threshold was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apdex: Option<Apdex>,
+    /// The share of all requests that succeeded within each target
+    pub under: Vec<LatencyTarget>,
+}
+
+/// The Apdex score for a latency threshold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Apdex {
+    pub threshold_ms: u128,
+    /// From 0 (every request frustrated) to 1 (every request satisfied)
+    pub score: f64,
+    pub satisfied: u64,
+    pub tolerating: u64,
+    pub frustrated: u64,
+}
+
+impl Apdex {
+    /// The usual rating of the score, from Excellent down to Unacceptable
+    pub fn rating(&self) -> &'static str {
+        match self.score {
+            s if s >= 0.94 => "Excellent",
+            s if s >= 0.85 => "Good",
+            s if s >= 0.7 => "Fair",
+            s if s >= 0.5 => "Poor",
+            _ => "Unacceptable",
+        }
+    }
+}
+
+/// One latency target
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyTarget {
+    pub ms: u128,
+    /// Percentage of all requests that succeeded within the target
+    pub percent: f64,
+}
+
+/// Measure `stats` against the standard targets and, if given, the Apdex
+/// threshold, which is added to the targets
+pub fn summarize(stats: &LoadTestStats, apdex_threshold_ms: Option<u128>) -> SlaStats {
+    let histogram = &stats.latency_histogram;
+    let total = stats.total_requests;
+
+    let mut targets = TARGETS_MS.to_vec();
+    targets.extend(apdex_threshold_ms);
+    targets.sort_unstable();
+    targets.dedup();
+
+    let under = targets
+        .into_iter()
+        .map(|ms| LatencyTarget {
+            ms,
+            percent: percent(within(histogram, ms), total),
+        })
+        .collect();
+
+    let apdex = apdex_threshold_ms
+        .filter(|_| total > 0)
+        .map(|threshold_ms| {
+            let satisfied = within(histogram, threshold_ms);
+            let tolerating = within(histogram, threshold_ms * TOLERATING_FACTOR) - satisfied;
+            Apdex {
+                threshold_ms,
+                score: (satisfied as f64 + tolerating as f64 / 2.0) / total as f64,
+                satisfied,
+                tolerating,
+                frustrated: total - satisfied - tolerating,
+            }
+        });
+
+    SlaStats { apdex, under }
+}
+
+/// Successful requests that took at most `ms`
+fn within(histogram: &BTreeMap<u128, u64>, ms: u128) -> u64 {
+    histogram.range(..=ms).map(|(_, count)| count).sum()
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    count as f64 / total as f64 * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> LoadTestStats {
+        LoadTestStats {
+            total_requests: 100,
+            successful_requests: 95,
+            failed_requests: 5,
+            latency_histogram: [(50, 60), (200, 20), (900, 10), (1500, 5)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_under_targets() {
+        let sla = summarize(&stats(), None);
+        assert!(sla.apdex.is_none());
+        let under: Vec<(u128, f64)> = sla.under.iter().map(|t| (t.ms, t.percent)).collect();
+        assert_eq!(
+            under,
+            vec![(100, 60.0), (250, 80.0), (500, 80.0), (1000, 90.0)]
+        );
+    }
+
+    #[test]
+    fn test_apdex() {
+        let sla = summarize(&stats(), Some(300));
+        let apdex = sla.apdex.unwrap();
+        // 80 within 300 ms, 10 within 1200 ms, 5 slower and 5 failed
+        assert_eq!(
+            (apdex.satisfied, apdex.tolerating, apdex.frustrated),
+            (80, 10, 10)
+        );
+        assert!((apdex.score - 0.85).abs() < 1e-9);
+        assert_eq!(apdex.rating(), "Good");
+        assert!(sla.under.iter().any(|t| t.ms == 300));
+
+        let empty = summarize(&LoadTestStats::default(), Some(300));
+        assert!(empty.apdex.is_none());
+        assert!(empty.under.iter().all(|t| t.percent == 0.0));
+    }
+}
@@ -0,0 +1,141 @@
+//! Periodic snapshots of a run in progress
+//!
+//! During long soak tests a summary of everything recorded so far is written
+//! to the snapshot directory at a fixed interval, so a crash of the generator
+//! (or the machine) still leaves usable data up to the last snapshot.
+
+use crate::protocols::{LiveResults, LoadTestStats};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// Contents of a snapshot file
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    sequence: u64,
+    /// Unix timestamp (seconds) when the snapshot was taken
+    timestamp: u64,
+    /// Seconds since the run started
+    elapsed_secs: f64,
+    /// Whether this is the last snapshot of a completed run
+    #[serde(rename = "final")]
+    is_final: bool,
+    #[serde(flatten)]
+    stats: &'a LoadTestStats,
+}
+
+/// Writes snapshots of a running test at a fixed interval
+pub struct Snapshotter {
+    dir: PathBuf,
+    started: Instant,
+    sequence: Arc<AtomicU64>,
+    handle: JoinHandle<()>,
+}
+
+impl Snapshotter {
+    /// Create the snapshot directory and start writing snapshots in the background
+    pub fn start(dir: PathBuf, every: Duration, live: LiveResults) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create snapshot directory {}", dir.display()))?;
+
+        let started = Instant::now();
+        let sequence = Arc::new(AtomicU64::new(0));
+        let handle = tokio::spawn({
+            let dir = dir.clone();
+            let sequence = Arc::clone(&sequence);
+            async move {
+                let mut ticker = tokio::time::interval_at((started + every).into(), every);
+                loop {
+                    ticker.tick().await;
+                    let elapsed = started.elapsed();
+                    let n = sequence.fetch_add(1, Ordering::Relaxed) + 1;
+                    // Summarizing every result so far and writing the file
+                    // would stall a runtime worker the load generators share
+                    let (dir, live) = (dir.clone(), live.clone());
+                    let written = tokio::task::spawn_blocking(move || {
+                        let stats = live.stats(elapsed.as_millis());
+                        write_snapshot(&dir, n, elapsed, false, &stats)
+                    })
+                    .await;
+                    match written {
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => eprintln!("Warning: {:#}", e),
+                        Err(e) => eprintln!("Warning: snapshot {} failed: {}", n, e),
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            dir,
+            started,
+            sequence,
+            handle,
+        })
+    }
+
+    /// Stop the periodic snapshots and write a final one with the completed stats
+    pub fn finish(self, stats: &LoadTestStats) -> Result<PathBuf> {
+        self.handle.abort();
+        let n = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        write_snapshot(&self.dir, n, self.started.elapsed(), true, stats)
+    }
+}
+
+/// Write one snapshot file, atomically so a crash never leaves a partial file
+fn write_snapshot(
+    dir: &Path,
+    sequence: u64,
+    elapsed: Duration,
+    is_final: bool,
+    stats: &LoadTestStats,
+) -> Result<PathBuf> {
+    let snapshot = Snapshot {
+        sequence,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        elapsed_secs: elapsed.as_secs_f64(),
+        is_final,
+        stats,
+    };
+
+    let path = dir.join(format!("snapshot-{:04}.json", sequence));
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&snapshot)?)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .with_context(|| format!("Failed to write snapshot {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_snapshot() {
+        let dir = std::env::temp_dir().join(format!("rustyload-snap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stats = LoadTestStats {
+            total_requests: 42,
+            ..Default::default()
+        };
+
+        let path = write_snapshot(&dir, 3, Duration::from_secs(90), false, &stats).unwrap();
+        assert_eq!(path.file_name().unwrap(), "snapshot-0003.json");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["sequence"], 3);
+        assert_eq!(json["elapsed_secs"], 90.0);
+        assert_eq!(json["final"], false);
+        assert_eq!(json["total_requests"], 42);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+//! Running one FlashKV/Redis workload at increasing pipeline depths
+//!
+//! Pipelining trades latency for throughput: every doubling of the depth
+//! keeps more commands in flight per connection, until the server (or the
+//! network) saturates and only latency keeps growing. A sweep runs the same
+//! workload at depths 1, 2, 4, … up to a maximum so the knee is visible in
+//! one table.
+
+use crate::protocols::flashkv::FlashKVConfig;
+use crate::protocols::{LiveResults, LoadTestConfig, LoadTestStats};
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// The results of the workload at one pipeline depth
+#[derive(Debug, Serialize)]
+pub struct SweepPoint {
+    pub depth: usize,
+    pub stats: LoadTestStats,
+}
+
+impl SweepPoint {
+    /// Percentage of commands that failed
+    pub fn error_rate(&self) -> f64 {
+        if self.stats.total_requests > 0 {
+            self.stats.failed_requests as f64 / self.stats.total_requests as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Powers of two below `max`, then `max` itself
+pub fn pipeline_depths(max: usize) -> Vec<usize> {
+    let mut depths: Vec<usize> =
+        std::iter::successors(Some(1), |depth: &usize| depth.checked_mul(2))
+            .take_while(|depth| *depth < max)
+            .collect();
+    depths.push(max.max(1));
+    depths
+}
+
+/// The FlashKV/Redis workload of `config`, if it can be swept
+pub fn check(config: &LoadTestConfig) -> Result<&FlashKVConfig> {
+    let Some(flashkv) = &config.flashkv_config else {
+        bail!("A pipeline sweep needs a FlashKV or Redis target");
+    };
+    if !flashkv.keepalive {
+        bail!("A pipeline sweep needs keepalive: pipelined commands share a persistent connection");
+    }
+    Ok(flashkv)
+}
+
+/// Run `config` once per pipeline depth up to `max`, calling `on_depth`
+/// before each run
+pub async fn pipeline(
+    config: &LoadTestConfig,
+    max: usize,
+    quiet: bool,
+    mut on_depth: impl FnMut(usize),
+) -> Result<Vec<SweepPoint>> {
+    let flashkv = check(config)?;
+    let mut points = Vec::new();
+    for depth in pipeline_depths(max) {
+        on_depth(depth);
+        let mut config = config.clone();
+        config.flashkv_config = Some(flashkv.clone().with_pipeline_depth(depth));
+        let stats = config.run(quiet, &LiveResults::default()).await?;
+        points.push(SweepPoint { depth, stats });
+    }
+    Ok(points)
+}
+
+/// The depth with the highest throughput among those without errors, or
+/// among all of them when every depth had some
+pub fn best(points: &[SweepPoint]) -> Option<usize> {
+    let clean: Vec<&SweepPoint> = points.iter().filter(|p| p.error_rate() == 0.0).collect();
+    let candidates = if clean.is_empty() {
+        points.iter().collect()
+    } else {
+        clean
+    };
+    candidates
+        .into_iter()
+        .max_by(|a, b| {
+            a.stats
+                .requests_per_second
+                .total_cmp(&b.stats.requests_per_second)
+        })
+        .map(|point| point.depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_depths() {
+        assert_eq!(pipeline_depths(1), vec![1]);
+        assert_eq!(pipeline_depths(16), vec![1, 2, 4, 8, 16]);
+        assert_eq!(pipeline_depths(12), vec![1, 2, 4, 8, 12]);
+        assert_eq!(pipeline_depths(0), vec![1]);
+    }
+
+    #[test]
+    fn test_best_depth() {
+        let point = |depth, rps, failed| SweepPoint {
+            depth,
+            stats: LoadTestStats {
+                total_requests: 100,
+                failed_requests: failed,
+                requests_per_second: rps,
+                ..Default::default()
+            },
+        };
+        let points = vec![point(1, 100.0, 0), point(2, 180.0, 0), point(4, 250.0, 3)];
+        assert_eq!(best(&points), Some(2));
+        assert_eq!(best(&points[2..]), Some(4));
+        assert_eq!(best(&[]), None);
+    }
+}
@@ -0,0 +1,180 @@
+//! `{{name}}` placeholders in request URLs, header values and bodies
+//!
+//! Placeholders are expanded for every request, so each one can target a
+//! distinct resource. Names are looked up in the request's `--data` row, then
+//! in variables saved with `--extract`, and then in these built-in generators:
+//!
+//! - `{{seq}}`: the request's number in the run, counting from 0
+//! - `{{worker_id}}`: the concurrent worker sending it, from 0 up to the concurrency
+//! - `{{iteration}}`: how many requests that worker sent before it in the stage
+//! - `{{uuid}}`: a random version 4 UUID
+//! - `{{random_int}}` or `{{random_int(1, 100)}}`: a random integer (inclusive range)
+//! - `{{random_string}}` or `{{random_string(16)}}`: random letters and digits
+//! - `{{timestamp}}` and `{{timestamp_ms}}`: the Unix time in seconds or milliseconds
+//! - `{{first_name}}`, `{{last_name}}`, `{{name}}`, `{{email}}` and
+//!   `{{address}}`: fake personal details
+//! - `{{lorem}}` or `{{lorem(1024)}}`: filler text of exactly that many bytes
+
+use crate::data::DataRow;
+use crate::fake;
+use rand::distr::{Alphanumeric, SampleString};
+use rand::Rng;
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_RANDOM_INT_MAX: i64 = 1_000_000;
+const DEFAULT_RANDOM_STRING_LEN: usize = 8;
+
+/// What one request's placeholders expand from, besides saved variables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestContext<'a> {
+    /// The request's number in the run
+    pub seq: u64,
+    /// The worker sending the request
+    pub worker_id: u64,
+    /// Requests the worker sent before this one
+    pub iteration: u64,
+    /// The request's `--data` row
+    pub row: Option<DataRow<'a>>,
+}
+
+/// Replace every `{{name}}` in `s` with what `resolve` returns for it, leaving
+/// names it doesn't know untouched
+pub fn expand<'a>(s: &'a str, resolve: impl Fn(&str) -> Option<String>) -> Cow<'a, str> {
+    if !s.contains("{{") {
+        return Cow::Borrowed(s);
+    }
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + 4 + end];
+        expanded.push_str(&rest[..start]);
+        match resolve(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(placeholder),
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+    expanded.push_str(rest);
+    Cow::Owned(expanded)
+}
+
+/// The value of a built-in generator for a request, or `None` if `name` isn't one
+pub fn generate(name: &str, context: &RequestContext) -> Option<String> {
+    let (function, args) = match name.split_once('(') {
+        Some((function, args)) => (function.trim(), Some(args.strip_suffix(')')?)),
+        None => (name, None),
+    };
+    let args: Vec<&str> = args
+        .map(|args| args.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    let now = || {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    };
+
+    match (function, args.as_slice()) {
+        ("seq", []) => Some(context.seq.to_string()),
+        ("worker_id", []) => Some(context.worker_id.to_string()),
+        ("iteration", []) => Some(context.iteration.to_string()),
+        ("uuid", []) => Some(uuid_v4()),
+        ("timestamp", []) => Some(now().as_secs().to_string()),
+        ("timestamp_ms", []) => Some(now().as_millis().to_string()),
+        ("random_int", []) => Some(
+            rand::rng()
+                .random_range(0..=DEFAULT_RANDOM_INT_MAX)
+                .to_string(),
+        ),
+        ("random_int", [min, max]) => {
+            let (min, max): (i64, i64) = (min.parse().ok()?, max.parse().ok()?);
+            (min <= max).then(|| rand::rng().random_range(min..=max).to_string())
+        }
+        ("random_string", []) => Some(random_string(DEFAULT_RANDOM_STRING_LEN)),
+        ("random_string", [len]) => Some(random_string(len.parse().ok()?)),
+        ("first_name", []) => Some(fake::first_name()),
+        ("last_name", []) => Some(fake::last_name()),
+        ("name", []) => Some(fake::name()),
+        ("email", []) => Some(fake::email()),
+        ("address", []) => Some(fake::address()),
+        ("lorem", []) => Some(fake::lorem(fake::DEFAULT_LOREM_BYTES)),
+        ("lorem", [bytes]) => Some(fake::lorem(bytes.parse().ok()?)),
+        _ => None,
+    }
+}
+
+fn random_string(len: usize) -> String {
+    Alphanumeric.sample_string(&mut rand::rng(), len)
+}
+
+/// A random (version 4) UUID in its hyphenated form
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::rng().random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let resolve = |name: &str| (name == "id").then(|| "7".to_string());
+        assert_eq!(expand("/users/{{id}}", resolve), "/users/7");
+        assert_eq!(
+            expand("/users/{{ id }}/{{other}}", resolve),
+            "/users/7/{{other}}"
+        );
+        assert_eq!(expand("unclosed {{id", resolve), "unclosed {{id");
+        assert!(matches!(expand("/plain", resolve), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_generate() {
+        let context = RequestContext {
+            seq: 41,
+            worker_id: 3,
+            iteration: 7,
+            row: None,
+        };
+        assert_eq!(generate("seq", &context).unwrap(), "41");
+        assert_eq!(generate("worker_id", &context).unwrap(), "3");
+        assert_eq!(generate("iteration", &context).unwrap(), "7");
+        assert!(generate("nope", &context).is_none());
+        assert!(generate("seq(1)", &context).is_none());
+
+        let uuid = generate("uuid", &context).unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(uuid, generate("uuid", &context).unwrap());
+
+        for _ in 0..100 {
+            let n: i64 = generate("random_int(-2, 2)", &context)
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((-2..=2).contains(&n));
+        }
+        assert!(generate("random_int(5, 1)", &context).is_none());
+        assert_eq!(generate("random_string(12)", &context).unwrap().len(), 12);
+        assert_eq!(generate("random_string", &context).unwrap().len(), 8);
+        assert_eq!(generate("lorem(300)", &context).unwrap().len(), 300);
+        assert!(generate("email", &context).unwrap().contains('@'));
+
+        let timestamp: u64 = generate("timestamp", &context).unwrap().parse().unwrap();
+        assert!(timestamp > 1_600_000_000);
+    }
+}
@@ -0,0 +1,433 @@
+//! Pass/fail thresholds for CI gating
+//!
+//! A threshold is a condition such as `p99>500ms` or `error_rate>1%`. When the
+//! condition holds after a run, the threshold is violated and rustyload exits
+//! with a non-zero status.
+
+use crate::protocols::{ErrorKind, LoadTestStats, STATUS_ERROR};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Metrics that thresholds can be declared on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    P50,
+    P95,
+    P99,
+    MinLatency,
+    MaxLatency,
+    AvgLatency,
+    ErrorRate,
+    SuccessRate,
+    RequestsPerSecond,
+    FailedRequests,
+    /// Share of key lookups that found their key (FlashKV/Redis GET and EXISTS)
+    HitRatio,
+    /// Failed requests of one error kind, e.g. `errors.dns`
+    ErrorsOfKind(&'static str),
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_lowercase();
+        if let Some(kind) = s.strip_prefix("errors.") {
+            return ErrorKind::NAMES
+                .iter()
+                .chain([&STATUS_ERROR])
+                .find(|name| **name == kind)
+                .map(|name| Metric::ErrorsOfKind(name))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown error kind: {} (expected one of {}, {})",
+                        kind,
+                        ErrorKind::NAMES.join(", "),
+                        STATUS_ERROR
+                    )
+                });
+        }
+
+        match s.as_str() {
+            "p50" | "median" => Ok(Metric::P50),
+            "p95" => Ok(Metric::P95),
+            "p99" => Ok(Metric::P99),
+            "min" => Ok(Metric::MinLatency),
+            "max" => Ok(Metric::MaxLatency),
+            "avg" | "mean" => Ok(Metric::AvgLatency),
+            "error_rate" | "errors" => Ok(Metric::ErrorRate),
+            "success_rate" => Ok(Metric::SuccessRate),
+            "rps" => Ok(Metric::RequestsPerSecond),
+            "failed" => Ok(Metric::FailedRequests),
+            "hit_ratio" => Ok(Metric::HitRatio),
+            _ => Err(format!("Unknown threshold metric: {}", s)),
+        }
+    }
+}
+
+impl Metric {
+    pub fn name(&self) -> Cow<'static, str> {
+        let name = match self {
+            Metric::P50 => "p50",
+            Metric::P95 => "p95",
+            Metric::P99 => "p99",
+            Metric::MinLatency => "min",
+            Metric::MaxLatency => "max",
+            Metric::AvgLatency => "avg",
+            Metric::ErrorRate => "error_rate",
+            Metric::SuccessRate => "success_rate",
+            Metric::RequestsPerSecond => "rps",
+            Metric::FailedRequests => "failed",
+            Metric::HitRatio => "hit_ratio",
+            Metric::ErrorsOfKind(kind) => return format!("errors.{}", kind).into(),
+        };
+        name.into()
+    }
+
+    pub fn is_latency(&self) -> bool {
+        matches!(
+            self,
+            Metric::P50
+                | Metric::P95
+                | Metric::P99
+                | Metric::MinLatency
+                | Metric::MaxLatency
+                | Metric::AvgLatency
+        )
+    }
+
+    pub fn is_rate(&self) -> bool {
+        matches!(
+            self,
+            Metric::ErrorRate | Metric::SuccessRate | Metric::HitRatio
+        )
+    }
+
+    /// Read the metric value from the stats (latencies in ms, rates in percent)
+    pub fn value(&self, stats: &LoadTestStats) -> f64 {
+        let error_rate = if stats.total_requests > 0 {
+            stats.failed_requests as f64 / stats.total_requests as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        match self {
+            Metric::P50 => stats.p50 as f64,
+            Metric::P95 => stats.p95 as f64,
+            Metric::P99 => stats.p99 as f64,
+            Metric::MinLatency => stats.min_latency as f64,
+            Metric::MaxLatency => stats.max_latency as f64,
+            Metric::AvgLatency => stats.avg_latency,
+            Metric::ErrorRate => error_rate,
+            Metric::SuccessRate => {
+                if stats.total_requests > 0 {
+                    100.0 - error_rate
+                } else {
+                    0.0
+                }
+            }
+            Metric::RequestsPerSecond => stats.requests_per_second,
+            Metric::FailedRequests => stats.failed_requests as f64,
+            // A run without lookups found nothing
+            Metric::HitRatio => stats.keyspace.as_ref().map_or(0.0, |k| k.hit_ratio),
+            Metric::ErrorsOfKind(kind) => {
+                stats.errors_by_kind.get(*kind).copied().unwrap_or(0) as f64
+            }
+        }
+    }
+
+    /// Unit suffix used when displaying values of this metric
+    pub fn unit(&self) -> &'static str {
+        if self.is_latency() {
+            "ms"
+        } else if self.is_rate() {
+            "%"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Comparison operators supported in threshold expressions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+        }
+    }
+
+    fn holds(&self, actual: f64, limit: f64) -> bool {
+        match self {
+            Comparison::Greater => actual > limit,
+            Comparison::GreaterOrEqual => actual >= limit,
+            Comparison::Less => actual < limit,
+            Comparison::LessOrEqual => actual <= limit,
+            Comparison::Equal => (actual - limit).abs() < f64::EPSILON,
+        }
+    }
+}
+
+/// A failure condition, e.g. `p99>500ms`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    /// Limit in the metric's unit (ms for latencies, percent for rates)
+    pub limit: f64,
+}
+
+impl Threshold {
+    /// Parse a threshold expression such as `p99>500ms`, `error_rate>1%` or `rps<100`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        // Two-character operators must be tried before their one-character prefixes
+        const OPERATORS: [(&str, Comparison); 5] = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+        ];
+
+        let (metric, comparison, value) = OPERATORS
+            .iter()
+            .find_map(|(symbol, comparison)| {
+                s.split_once(symbol)
+                    .map(|(metric, value)| (metric, *comparison, value))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "Invalid threshold '{}': expected <metric><op><value>, e.g. p99>500ms",
+                    s
+                )
+            })?;
+
+        let metric = Metric::from_str(metric)?;
+        let limit = parse_limit(metric, value.trim())?;
+
+        Ok(Self {
+            metric,
+            comparison,
+            limit,
+        })
+    }
+
+    /// Evaluate the threshold against the stats of a finished run
+    pub fn evaluate(&self, stats: &LoadTestStats) -> ThresholdResult {
+        let actual = self.metric.value(stats);
+        ThresholdResult {
+            threshold: self.to_string(),
+            actual,
+            passed: !self.comparison.holds(actual, self.limit),
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            self.metric.name(),
+            self.comparison.symbol(),
+            self.limit,
+            self.metric.unit()
+        )
+    }
+}
+
+/// Parse a threshold limit, converting units to the metric's base unit
+fn parse_limit(metric: Metric, value: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid threshold value '{}' for {}", value, metric.name());
+
+    let (number, multiplier) = if metric.is_latency() {
+        if let Some(number) = value.strip_suffix("ms") {
+            (number, 1.0)
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, 1000.0)
+        } else {
+            (value, 1.0)
+        }
+    } else if metric.is_rate() {
+        (value.strip_suffix('%').unwrap_or(value), 1.0)
+    } else {
+        (value, 1.0)
+    };
+
+    number
+        .trim()
+        .parse::<f64>()
+        .map(|n| n * multiplier)
+        .map_err(|_| invalid())
+}
+
+/// Outcome of evaluating one threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdResult {
+    /// The threshold expression, normalized
+    pub threshold: String,
+    /// The measured value of the metric
+    pub actual: f64,
+    /// Whether the run stayed within the threshold
+    pub passed: bool,
+}
+
+/// Evaluate every threshold against the stats
+pub fn evaluate_all(thresholds: &[Threshold], stats: &LoadTestStats) -> Vec<ThresholdResult> {
+    thresholds.iter().map(|t| t.evaluate(stats)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{calculate_stats, ErrorKind, KeyspaceStats, RequestResult};
+
+    fn sample_stats() -> LoadTestStats {
+        let mut results: Vec<RequestResult> = (1..=100)
+            .map(|i| RequestResult {
+                duration: i,
+                status: 200,
+                success: true,
+                error: None,
+                queue_time: 0,
+                operation: None,
+                backend: None,
+                completed_at: None,
+                cache_hit: None,
+                http_version: None,
+                body: None,
+                step: None,
+                tag: None,
+                grpc_status: None,
+                stream: None,
+                dns_rcode: None,
+                lost: None,
+                sent: None,
+                worker: None,
+            })
+            .collect();
+        results.push(RequestResult {
+            duration: 5,
+            status: 500,
+            success: false,
+            error: Some(ErrorKind::Other("boom".to_string())),
+            queue_time: 0,
+            operation: None,
+            backend: None,
+            completed_at: None,
+            cache_hit: None,
+            http_version: None,
+            body: None,
+            step: None,
+            tag: None,
+            grpc_status: None,
+            stream: None,
+            dns_rcode: None,
+            lost: None,
+            sent: None,
+            worker: None,
+        });
+        calculate_stats(&results, 1000)
+    }
+
+    #[test]
+    fn test_parse_threshold() {
+        let t = Threshold::parse("p99>500ms").unwrap();
+        assert_eq!(t.metric, Metric::P99);
+        assert_eq!(t.comparison, Comparison::Greater);
+        assert_eq!(t.limit, 500.0);
+
+        let t = Threshold::parse("p95 >= 1.5s").unwrap();
+        assert_eq!(t.comparison, Comparison::GreaterOrEqual);
+        assert_eq!(t.limit, 1500.0);
+
+        let t = Threshold::parse("error_rate>1%").unwrap();
+        assert_eq!(t.metric, Metric::ErrorRate);
+        assert_eq!(t.limit, 1.0);
+
+        let t = Threshold::parse("rps<100").unwrap();
+        assert_eq!(t.comparison, Comparison::Less);
+    }
+
+    #[test]
+    fn test_parse_threshold_invalid() {
+        assert!(Threshold::parse("p99").is_err());
+        assert!(Threshold::parse("p42>10ms").is_err());
+        assert!(Threshold::parse("p99>fast").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_threshold() {
+        let stats = sample_stats();
+
+        assert!(
+            Threshold::parse("p99>500ms")
+                .unwrap()
+                .evaluate(&stats)
+                .passed
+        );
+        assert!(
+            !Threshold::parse("p50>10ms")
+                .unwrap()
+                .evaluate(&stats)
+                .passed
+        );
+
+        // 1 failure out of 101 requests is just under 1%
+        let result = Threshold::parse("error_rate>1%").unwrap().evaluate(&stats);
+        assert!(result.passed);
+        assert!(
+            !Threshold::parse("error_rate>0.5%")
+                .unwrap()
+                .evaluate(&stats)
+                .passed
+        );
+    }
+
+    #[test]
+    fn test_error_kind_threshold() {
+        let stats = sample_stats();
+
+        let threshold = Threshold::parse("errors.other>0").unwrap();
+        assert_eq!(threshold.metric, Metric::ErrorsOfKind("other"));
+        assert_eq!(threshold.to_string(), "errors.other>0");
+        assert!(!threshold.evaluate(&stats).passed);
+        assert!(
+            Threshold::parse("errors.dns>0")
+                .unwrap()
+                .evaluate(&stats)
+                .passed
+        );
+        assert!(Threshold::parse("errors.bogus>0").is_err());
+    }
+
+    #[test]
+    fn test_hit_ratio_threshold() {
+        let mut stats = sample_stats();
+        let threshold = Threshold::parse("hit_ratio<90%").unwrap();
+        assert_eq!(threshold.metric, Metric::HitRatio);
+        assert!(!threshold.evaluate(&stats).passed);
+
+        stats.keyspace = Some(KeyspaceStats {
+            hits: 95,
+            misses: 5,
+            hit_ratio: 95.0,
+            ..Default::default()
+        });
+        assert!(threshold.evaluate(&stats).passed);
+    }
+}
@@ -0,0 +1,34 @@
+//! Deliver results to a webhook
+//!
+//! The JSON report is POSTed as-is, so anything that accepts JSON (a Slack
+//! relay, a CI bot, a metrics collector) can consume it.
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use std::time::Duration;
+
+/// How long to wait for the webhook to accept the results
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POST a JSON body to the webhook URL
+pub async fn send(url: &str, json: String) -> Result<()> {
+    let client = Client::builder()
+        .user_agent("rustyload/0.2")
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+
+    let response = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(json)
+        .send()
+        .await
+        .with_context(|| format!("Failed to send results to webhook {}", url))?;
+
+    if !response.status().is_success() {
+        bail!("Webhook {} responded with {}", url, response.status());
+    }
+    Ok(())
+}
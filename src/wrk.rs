@@ -0,0 +1,341 @@
+//! wrk-compatible summary
+//!
+//! Dashboards and scripts written against wrk scrape its summary text, so
+//! `--output wrk` prints the results in the same layout and units: thread
+//! stats for latency and requests/sec, the latency distribution, the request
+//! and byte counts, socket errors and the overall rates. rustyload has no
+//! threads in wrk's sense, so the run is reported as a single thread whose
+//! Req/Sec samples are the requests completed in each second.
+
+use crate::protocols::{histogram_percentile, LoadTestStats, STATUS_ERROR};
+use crate::report::Report;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// Latency percentiles listed in the distribution, as in `wrk --latency`
+const PERCENTILES: [f64; 4] = [50.0, 75.0, 90.0, 99.0];
+
+/// A unit system: values are scaled down while they reach 85% of the next
+/// unit, but never into the last one (which is how wrk formats them)
+struct Units {
+    scale: f64,
+    base: &'static str,
+    units: &'static [&'static str],
+}
+
+const TIME_US: Units = Units {
+    scale: 1000.0,
+    base: "us",
+    units: &["ms", "s"],
+};
+
+const TIME_S: Units = Units {
+    scale: 60.0,
+    base: "s",
+    units: &["m", "h"],
+};
+
+const BINARY: Units = Units {
+    scale: 1024.0,
+    base: "",
+    units: &["K", "M", "G", "T", "P"],
+};
+
+const METRIC: Units = Units {
+    scale: 1000.0,
+    base: "",
+    units: &["k", "M", "G", "T", "P"],
+};
+
+impl Units {
+    fn format(&self, n: f64, precision: usize) -> String {
+        let mut amount = n;
+        let mut unit = self.base;
+        for (i, next) in self.units.iter().enumerate() {
+            if i + 1 == self.units.len() || amount < self.scale * 0.85 {
+                break;
+            }
+            amount /= self.scale;
+            unit = next;
+        }
+        format!("{:.*}{}", precision, amount, unit)
+    }
+}
+
+fn format_time_us(us: f64) -> String {
+    if us >= 1_000_000.0 {
+        TIME_S.format(us / 1_000_000.0, 2)
+    } else {
+        TIME_US.format(us, 2)
+    }
+}
+
+/// A value right-aligned in `width` columns, with the number aligned whatever
+/// its unit
+fn units(value: String, width: usize) -> String {
+    let mut chars = value.chars().rev();
+    let pad = [chars.next(), chars.next()]
+        .iter()
+        .filter(|c| !c.is_some_and(|c| c.is_alphabetic()))
+        .count();
+    let width = width - pad;
+    let value: String = value.chars().take(width).collect();
+    format!("{:>width$}{}", value, " ".repeat(pad))
+}
+
+/// Mean, sample standard deviation, maximum and the percentage of samples
+/// within one standard deviation of the mean
+struct Summary {
+    mean: f64,
+    stdev: f64,
+    max: f64,
+    within_stdev: f64,
+}
+
+impl Summary {
+    /// Summarize samples given as value and count pairs
+    fn new(samples: &BTreeMap<u128, u64>) -> Self {
+        let count: u64 = samples.values().sum();
+        if count == 0 {
+            return Self {
+                mean: 0.0,
+                stdev: 0.0,
+                max: 0.0,
+                within_stdev: 0.0,
+            };
+        }
+        let mean = samples
+            .iter()
+            .map(|(&value, &n)| value as f64 * n as f64)
+            .sum::<f64>()
+            / count as f64;
+        let stdev = if count < 2 {
+            0.0
+        } else {
+            let squares: f64 = samples
+                .iter()
+                .map(|(&value, &n)| (value as f64 - mean).powi(2) * n as f64)
+                .sum();
+            (squares / (count - 1) as f64).sqrt()
+        };
+        let within: u64 = samples
+            .iter()
+            .filter(|(&value, _)| (value as f64 - mean).abs() <= stdev)
+            .map(|(_, &n)| n)
+            .sum();
+        Self {
+            mean,
+            stdev,
+            max: samples.keys().next_back().copied().unwrap_or(0) as f64,
+            within_stdev: within as f64 / count as f64 * 100.0,
+        }
+    }
+
+    fn line(&self, name: &str, format: fn(f64) -> String) -> String {
+        format!(
+            "    {:<10}{}{}{}{:8.2}%",
+            name,
+            units(format(self.mean), 8),
+            units(format(self.stdev), 10),
+            units(format(self.max), 9),
+            self.within_stdev
+        )
+    }
+}
+
+/// The summary wrk would print for this run, against `connections`
+/// concurrent connections
+pub fn render(report: &Report, connections: u64) -> String {
+    let stats = report.stats;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "Running {} test @ {}",
+        TIME_S.format(stats.total_duration as f64 / 1000.0, 0),
+        report.target
+    );
+    let _ = writeln!(out, "  1 threads and {} connections", connections);
+
+    // Latencies are recorded in whole milliseconds, wrk reports microseconds
+    let latency_us: BTreeMap<u128, u64> = stats
+        .latency_histogram
+        .iter()
+        .map(|(&ms, &count)| (ms * 1000, count))
+        .collect();
+    let mut per_second = BTreeMap::new();
+    for &requests in &stats.rps_series {
+        *per_second.entry(requests).or_insert(0) += 1;
+    }
+    if per_second.is_empty() && stats.total_requests > 0 {
+        // Runs shorter than a second have no whole seconds to sample
+        per_second.insert(stats.requests_per_second.round() as u128, 1);
+    }
+
+    let _ = writeln!(
+        out,
+        "  Thread Stats{:>6}{:>11}{:>8}{:>12}",
+        "Avg", "Stdev", "Max", "+/- Stdev"
+    );
+    let _ = writeln!(
+        out,
+        "{}",
+        Summary::new(&latency_us).line("Latency", format_time_us)
+    );
+    let _ = writeln!(
+        out,
+        "{}",
+        Summary::new(&per_second).line("Req/Sec", |n| METRIC.format(n, 2))
+    );
+
+    let _ = writeln!(out, "  Latency Distribution");
+    for pct in PERCENTILES {
+        let us = histogram_percentile(&latency_us, pct) as f64;
+        let _ = writeln!(out, "{:7.0}%{}", pct, units(format_time_us(us), 10));
+    }
+
+    let received = stats.transfer.as_ref().map_or(0, |t| t.received_bytes);
+    let _ = writeln!(
+        out,
+        "  {} requests in {}, {}B read",
+        stats.total_requests,
+        format_time_us(stats.total_duration as f64 * 1000.0),
+        BINARY.format(received as f64, 2)
+    );
+
+    let errors = SocketErrors::new(stats);
+    if errors.connect + errors.read + errors.timeout > 0 {
+        let _ = writeln!(
+            out,
+            "  Socket errors: connect {}, read {}, write 0, timeout {}",
+            errors.connect, errors.read, errors.timeout
+        );
+    }
+    if errors.status > 0 {
+        let _ = writeln!(out, "  Non-2xx or 3xx responses: {}", errors.status);
+    }
+
+    let secs = stats.total_duration as f64 / 1000.0;
+    let bytes_per_second = if secs > 0.0 {
+        received as f64 / secs
+    } else {
+        0.0
+    };
+    let _ = writeln!(out, "Requests/sec: {:9.2}", stats.requests_per_second);
+    let _ = write!(
+        out,
+        "Transfer/sec: {:>10}B",
+        BINARY.format(bytes_per_second, 2)
+    );
+    out
+}
+
+/// Failed requests in wrk's error categories
+struct SocketErrors {
+    connect: u64,
+    read: u64,
+    timeout: u64,
+    status: u64,
+}
+
+impl SocketErrors {
+    fn new(stats: &LoadTestStats) -> Self {
+        let mut errors = Self {
+            connect: 0,
+            read: 0,
+            timeout: 0,
+            status: 0,
+        };
+        for (kind, &count) in &stats.errors_by_kind {
+            match kind.as_str() {
+                "dns" | "connect_refused" | "connect_timeout" => errors.connect += count,
+                "response_timeout" => errors.timeout += count,
+                STATUS_ERROR => errors.status += count,
+                // Failed checks got a response, wrk has no category for them
+                "assertion_failed" => {}
+                _ => errors.read += count,
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::TransferStats;
+
+    #[test]
+    fn test_units() {
+        assert_eq!(format_time_us(635.91), "635.91us");
+        assert_eq!(format_time_us(890.0), "0.89ms");
+        assert_eq!(format_time_us(12_920.0), "12.92ms");
+        assert_eq!(format_time_us(30_000_000.0), "30.00s");
+        assert_eq!(METRIC.format(56_200.0, 2), "56.20k");
+        assert_eq!(BINARY.format(17.76 * 1024.0 * 1024.0 * 1024.0, 2), "17.76G");
+        assert_eq!(TIME_S.format(30.0, 0), "30s");
+
+        assert_eq!(units("635.91us".to_string(), 8), "635.91us");
+        assert_eq!(units("0.89ms".to_string(), 10), "    0.89ms");
+        assert_eq!(units("8.07k".to_string(), 10), "    8.07k ");
+        assert_eq!(units("86.00".to_string(), 9), "  86.00  ");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut errors_by_kind = BTreeMap::new();
+        errors_by_kind.insert("connect_refused".to_string(), 2);
+        errors_by_kind.insert("response_timeout".to_string(), 1);
+        errors_by_kind.insert(STATUS_ERROR.to_string(), 7);
+        let stats = LoadTestStats {
+            total_requests: 110,
+            successful_requests: 100,
+            failed_requests: 10,
+            total_duration: 2000,
+            requests_per_second: 55.0,
+            latency_histogram: [(1, 50), (2, 40), (10, 10)].into_iter().collect(),
+            rps_series: vec![50, 60],
+            transfer: Some(TransferStats {
+                received_bytes: 2048,
+                ..Default::default()
+            }),
+            errors_by_kind,
+            ..Default::default()
+        };
+        let report = Report {
+            target: "http://localhost:8080/",
+            protocol: "HTTP/HTTPS",
+            stats: &stats,
+            thresholds: &[],
+            baseline: &[],
+            hints: &[],
+        };
+
+        let text = render(&report, 10);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Running 2s test @ http://localhost:8080/");
+        assert_eq!(lines[1], "  1 threads and 10 connections");
+        assert_eq!(
+            lines[2],
+            "  Thread Stats   Avg      Stdev     Max   +/- Stdev"
+        );
+        assert_eq!(
+            lines[3],
+            "    Latency     2.30ms    2.62ms  10.00ms   90.00%"
+        );
+        assert_eq!(
+            lines[4],
+            "    Req/Sec    55.00      7.07    60.00    100.00%"
+        );
+        assert_eq!(lines[6], "     50%    1.50ms");
+        assert_eq!(lines[9], "     99%   10.00ms");
+        assert_eq!(lines[10], "  110 requests in 2.00s, 2.00KB read");
+        assert_eq!(
+            lines[11],
+            "  Socket errors: connect 2, read 0, write 0, timeout 1"
+        );
+        assert_eq!(lines[12], "  Non-2xx or 3xx responses: 7");
+        assert_eq!(lines[13], "Requests/sec:     55.00");
+        assert_eq!(lines[14], "Transfer/sec:      1.00KB");
+    }
+}